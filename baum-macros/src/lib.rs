@@ -0,0 +1,249 @@
+//! Derive macros for `baum::ToBaum` and `baum::FromBaum`, the `baum!`
+//! literal macro and `baum_match!`. Use them through the `derive` feature of
+//! the `baum` crate.
+//!
+//! Structs map to inner nodes with one child per field. Enum variants map to
+//! inner nodes whose first child is the variant's tag (a `u32` leaf),
+//! followed by the variant's fields. The tag defaults to the variant's index
+//! and can be set using `#[baum(tag = 42)]`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use proc_macro2::{Delimiter, TokenTree};
+mod pattern;
+
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Ident, Lit, LitInt, Variant};
+
+#[proc_macro_derive(ToBaum, attributes(baum))]
+pub fn derive_to_baum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_to_baum(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(FromBaum, attributes(baum))]
+pub fn derive_from_baum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_from_baum(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Builds a `baum::Node` from the text syntax, e.g.
+/// `baum!((0x01_02 ("abc" 0x03)))`. Leaves are hex literals, string literals
+/// (UTF-8) or byte string literals. Since `0x` isn't a valid Rust token,
+/// empty leaves are written as `""`.
+#[proc_macro]
+pub fn baum(input: TokenStream) -> TokenStream {
+    let mut tokens = TokenStream2::from(input).into_iter();
+    let res = match (tokens.next(), tokens.next()) {
+        (Some(token), None) => expand_node(token),
+        (None, _) => Err(syn::Error::new(proc_macro2::Span::call_site(), "expected a node")),
+        (Some(_), Some(extra)) => Err(syn::Error::new(extra.span(), "unexpected tokens after node")),
+    };
+    res.unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Matches a `baum::Node` against patterns in text syntax (see
+/// `baum::Pattern`), binding the nodes captured by placeholders to variables
+/// of the same name:
+///
+/// ```ignore
+/// baum_match!(node,
+///     "(0x01 $payload)" => handle(payload),
+///     "(0x02 $a $b ...)" => merge(a, b),
+///     _ => reject(),
+/// )
+/// ```
+///
+/// The first matching arm is evaluated. Like `if` without `else`, the
+/// fallback arm can be left out if the arms evaluate to `()`.
+#[proc_macro]
+pub fn baum_match(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as pattern::Input);
+    pattern::expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand_node(token: TokenTree) -> syn::Result<TokenStream2> {
+    match token {
+        TokenTree::Group(group) if group.delimiter() == Delimiter::Parenthesis => {
+            let children = group.stream().into_iter().map(expand_node).collect::<syn::Result<Vec<_>>>()?;
+            Ok(quote!(::baum::Node::Inner(::std::vec![#(#children),*])))
+        }
+        TokenTree::Literal(lit) => {
+            let bytes = match Lit::new(lit) {
+                Lit::Int(lit) => hex_bytes(&lit)?,
+                Lit::Str(lit) => lit.value().into_bytes(),
+                Lit::ByteStr(lit) => lit.value(),
+                lit => return Err(syn::Error::new(lit.span(), "expected a hex or string literal")),
+            };
+            Ok(quote!(::baum::Node::Leaf(::std::vec![#(#bytes),*])))
+        }
+        token => Err(syn::Error::new(token.span(), "expected a leaf or a parenthesized node")),
+    }
+}
+
+/// Decodes a hex literal like the parser does: underscores are ignored and an
+/// odd number of digits makes the first digit a byte of its own.
+fn hex_bytes(lit: &LitInt) -> syn::Result<Vec<u8>> {
+    let repr = lit.to_string();
+    let digits = match repr.strip_prefix("0x") {
+        Some(digits) if lit.suffix().is_empty() => digits.replace('_', ""),
+        _ => return Err(syn::Error::new(lit.span(), "expected a hex literal like 0x01_02")),
+    };
+    let digits = digits.chars().map(|c| c.to_digit(16).map(|d| d as u8)).collect::<Option<Vec<u8>>>()
+        .ok_or_else(|| syn::Error::new(lit.span(), "invalid hex digit"))?;
+    let (first, rest) = digits.split_at(digits.len() % 2);
+    Ok(first.iter().copied().chain(rest.chunks(2).map(|pair| pair[0] * 0x10 + pair[1])).collect())
+}
+
+fn add_bounds(mut generics: Generics, bound: syn::TypeParamBound) -> Generics {
+    for param in generics.type_params_mut() {
+        param.bounds.push(bound.clone());
+    }
+    generics
+}
+
+/// Returns the tags of all variants, rejecting duplicates.
+fn tags<'a>(variants: impl Iterator<Item = &'a Variant>) -> syn::Result<Vec<u32>> {
+    let mut tags: Vec<u32> = vec!();
+    for (idx, variant) in variants.enumerate() {
+        let mut tag = idx as u32;
+        for attr in variant.attrs.iter().filter(|a| a.path().is_ident("baum")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    tag = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                    Ok(())
+                } else {
+                    Err(meta.error("unsupported baum attribute"))
+                }
+            })?;
+        }
+        if tags.contains(&tag) {
+            return Err(syn::Error::new_spanned(&variant.ident, format!("duplicate tag {}", tag)));
+        }
+        tags.push(tag);
+    }
+    Ok(tags)
+}
+
+/// Bindings for the fields, used to destructure a struct or variant.
+fn bindings(fields: &Fields) -> Vec<Ident> {
+    (0..fields.len()).map(|idx| quote::format_ident!("field{}", idx)).collect()
+}
+
+fn pattern(path: TokenStream2, fields: &Fields, bindings: &[Ident]) -> TokenStream2 {
+    match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|f| &f.ident);
+            quote!(#path { #(#idents: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// Constructs a struct or variant from `children`, starting at `offset`.
+fn construct(path: TokenStream2, fields: &Fields, offset: usize) -> TokenStream2 {
+    let values = (offset..offset + fields.len()).map(|idx| quote!(::baum::__private::child(children, #idx)?));
+    match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|f| &f.ident);
+            quote!(#path { #(#idents: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#values),*)),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+fn expand_to_baum(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let bindings = bindings(&data.fields);
+            let pattern = pattern(quote!(Self), &data.fields, &bindings);
+            quote! {
+                let #pattern = self;
+                ::baum::Node::Inner(::std::vec![#(::baum::ToBaum::to_node(#bindings)),*])
+            }
+        }
+        Data::Enum(data) => {
+            let tags = tags(data.variants.iter())?;
+            let arms = data.variants.iter().zip(tags).map(|(variant, tag)| {
+                let ident = &variant.ident;
+                let bindings = bindings(&variant.fields);
+                let pattern = pattern(quote!(Self::#ident), &variant.fields, &bindings);
+                quote! {
+                    #pattern => ::baum::Node::Inner(::std::vec![
+                        ::baum::ToBaum::to_node(&#tag),
+                        #(::baum::ToBaum::to_node(#bindings)),*
+                    ])
+                }
+            });
+            if data.variants.is_empty() {
+                quote!(match *self {})
+            } else {
+                quote! {
+                    match self {
+                        #(#arms,)*
+                    }
+                }
+            }
+        }
+        Data::Union(_) => return Err(syn::Error::new_spanned(&input.ident, "unions are not supported")),
+    };
+
+    let name = &input.ident;
+    let generics = add_bounds(input.generics.clone(), parse_quote!(::baum::ToBaum));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::baum::ToBaum for #name #ty_generics #where_clause {
+            fn to_node(&self) -> ::baum::Node {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_from_baum(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let len = data.fields.len();
+            let value = construct(quote!(Self), &data.fields, 0);
+            quote! {
+                let children = ::baum::__private::children(node, #len)?;
+                ::std::result::Result::Ok(#value)
+            }
+        }
+        Data::Enum(data) => {
+            let tags = tags(data.variants.iter())?;
+            let arms = data.variants.iter().zip(tags).map(|(variant, tag)| {
+                let ident = &variant.ident;
+                let len = variant.fields.len() + 1;
+                let value = construct(quote!(Self::#ident), &variant.fields, 1);
+                quote! {
+                    #tag => {
+                        let children = ::baum::__private::children(node, #len)?;
+                        ::std::result::Result::Ok(#value)
+                    }
+                }
+            });
+            quote! {
+                match ::baum::__private::tag(node)? {
+                    #(#arms)*
+                    tag => ::std::result::Result::Err(::baum::__private::unknown_variant(tag)),
+                }
+            }
+        }
+        Data::Union(_) => return Err(syn::Error::new_spanned(&input.ident, "unions are not supported")),
+    };
+
+    let name = &input.ident;
+    let generics = add_bounds(input.generics.clone(), parse_quote!(::baum::FromBaum));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::baum::FromBaum for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn from_node(node: &::baum::Node) -> ::std::result::Result<Self, ::baum::FromNodeError> {
+                #body
+            }
+        }
+    })
+}
@@ -0,0 +1,137 @@
+//! Implementation of `baum_match!`. Patterns are parsed by `baum::Pattern`
+//! at runtime, but checked here so that errors are reported at compile time.
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{Expr, LitStr, Token};
+
+pub struct Input {
+    node: Expr,
+    arms: Vec<(LitStr, Expr)>,
+    fallback: Option<Expr>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let node = input.parse()?;
+        let mut arms = vec!();
+        let mut fallback = None;
+        while !input.is_empty() && fallback.is_none() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            if input.peek(Token![_]) {
+                input.parse::<Token![_]>()?;
+                input.parse::<Token![=>]>()?;
+                fallback = Some(input.parse()?);
+            } else {
+                let pattern = input.parse()?;
+                input.parse::<Token![=>]>()?;
+                arms.push((pattern, input.parse()?));
+            }
+        }
+        input.parse::<Option<Token![,]>>()?;
+        if arms.is_empty() {
+            return Err(input.error("expected at least one pattern"));
+        }
+        Ok(Input { node, arms, fallback })
+    }
+}
+
+/// Checks the syntax of `pattern` and returns the names of its placeholders
+/// in order of first occurrence, like `baum::Pattern::placeholders`.
+fn placeholders(pattern: &str) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = vec!();
+    let mut depth = 0usize;
+    let mut nodes = 0;
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if depth == 0 && nodes == 1 && !c.is_ascii_whitespace() {
+            return Err("unexpected characters after node".to_string());
+        }
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1).ok_or("unexpected ')'")?,
+            '0' => {
+                if chars.next() != Some('x') {
+                    return Err("expected 'x'".to_string());
+                }
+                while chars.next_if(|c| c.is_ascii_hexdigit() || *c == '_').is_some() {}
+            }
+            '"' => loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('"' | '\\' | 'n' | 'r' | 't' | '0') => {},
+                        Some('x') => {
+                            if !(chars.next().is_some_and(|c| c.is_ascii_hexdigit()) && chars.next().is_some_and(|c| c.is_ascii_hexdigit())) {
+                                return Err("expected two hex digits after '\\x'".to_string());
+                            }
+                        }
+                        _ => return Err("invalid escape sequence".to_string()),
+                    },
+                    Some(_) => {},
+                    None => return Err("unterminated string".to_string()),
+                }
+            },
+            '$' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                if syn::parse_str::<syn::Ident>(&name).is_err() {
+                    return Err(format!("placeholder '${}' isn't a valid identifier", name));
+                }
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            '_' => {},
+            '.' => {
+                if chars.next() != Some('.') || chars.next() != Some('.') {
+                    return Err("expected '...'".to_string());
+                }
+                if depth == 0 {
+                    return Err("'...' is only allowed among children".to_string());
+                }
+            }
+            c if c.is_ascii_whitespace() => continue,
+            _ => return Err(format!("unexpected character '{}'", c)),
+        }
+        if depth == 0 {
+            nodes += 1;
+        }
+    }
+    match (depth, nodes) {
+        (0, 1) => Ok(names),
+        _ => Err("unexpected end of pattern".to_string()),
+    }
+}
+
+pub fn expand(input: Input) -> syn::Result<TokenStream2> {
+    let node = &input.node;
+    let mut arms = vec!();
+    for (pattern, body) in &input.arms {
+        let names = placeholders(&pattern.value()).map_err(|e| syn::Error::new(pattern.span(), e))?;
+        let names = names.iter().map(|name| format_ident!("{}", name, span = pattern.span()));
+        let indices = 0..names.len();
+        arms.push(quote! {
+            if let ::std::option::Option::Some(__baum_captures) = {
+                static PATTERN: ::std::sync::OnceLock<::baum::Pattern> = ::std::sync::OnceLock::new();
+                PATTERN.get_or_init(|| ::baum::Pattern::parse(#pattern).unwrap()).captures(__baum_node)
+            } {
+                #(let #names: &::baum::Node = __baum_captures[#indices];)*
+                #body
+            }
+        });
+    }
+    let fallback = input.fallback.iter();
+    Ok(quote! {
+        {
+            let __baum_node: &::baum::Node = ::baum::__private::node(&(#node));
+            #(#arms)else* #(else { #fallback })*
+        }
+    })
+}
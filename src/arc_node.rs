@@ -0,0 +1,108 @@
+use crate::Node;
+
+use std::sync::Arc;
+
+/// Persistent tree: clones are cheap and edits return a new tree that shares
+/// all unchanged subtrees with the old one.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ArcNode {
+    Leaf(Arc<[u8]>),
+    Inner(Arc<[ArcNode]>),
+}
+
+impl ArcNode {
+    /// Returns the leaf's bytes, or `None` for inner nodes.
+    pub fn leaf(&self) -> Option<&[u8]> {
+        match self {
+            ArcNode::Leaf(bytes) => Some(bytes),
+            ArcNode::Inner(_) => None,
+        }
+    }
+
+    /// Returns the node's children; leaves have none.
+    pub fn children(&self) -> &[ArcNode] {
+        match self {
+            ArcNode::Leaf(_) => &[],
+            ArcNode::Inner(nodes) => nodes,
+        }
+    }
+
+    /// Returns the node at `path`, if there is one.
+    pub fn get(&self, path: &[usize]) -> Option<&ArcNode> {
+        path.iter().try_fold(self, |node, &idx| node.children().get(idx))
+    }
+
+    /// Returns a new tree with the node at `path` replaced by `node`, or
+    /// `None` if there's no node at `path`. Only the nodes on `path` are
+    /// copied.
+    pub fn replace(&self, path: &[usize], node: ArcNode) -> Option<ArcNode> {
+        match path.split_first() {
+            None => Some(node),
+            Some((&idx, rest)) => {
+                let child = self.children().get(idx)?.replace(rest, node)?;
+                let mut children = self.children().to_vec();
+                children[idx] = child;
+                Some(ArcNode::Inner(children.into()))
+            }
+        }
+    }
+
+    /// Returns `true` if both nodes share the same allocation, which implies
+    /// that they're equal.
+    pub fn ptr_eq(&self, other: &ArcNode) -> bool {
+        match (self, other) {
+            (ArcNode::Leaf(a), ArcNode::Leaf(b)) => Arc::ptr_eq(a, b),
+            (ArcNode::Inner(a), ArcNode::Inner(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    pub fn to_node(&self) -> Node {
+        match self {
+            ArcNode::Leaf(bytes) => Node::Leaf(bytes.to_vec()),
+            ArcNode::Inner(nodes) => Node::Inner(nodes.iter().map(ArcNode::to_node).collect()),
+        }
+    }
+}
+
+impl From<&Node> for ArcNode {
+    fn from(node: &Node) -> Self {
+        match node {
+            Node::Leaf(bytes) => ArcNode::Leaf(bytes.as_slice().into()),
+            Node::Inner(nodes) => ArcNode::Inner(nodes.iter().map(ArcNode::from).collect()),
+        }
+    }
+}
+
+impl From<&ArcNode> for Node {
+    fn from(node: &ArcNode) -> Self {
+        node.to_node()
+    }
+}
+
+
+#[test]
+fn arc_node() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(Node::Leaf(vec!(2)), Node::Leaf(vec!(3)))),
+    ));
+    let v1 = ArcNode::from(&node);
+    assert_eq!(v1.get(&[1, 0]).and_then(ArcNode::leaf), Some(&[2][..]));
+    assert_eq!(v1.get(&[0, 0]), None);
+
+    let v2 = v1.replace(&[1, 1], ArcNode::Leaf(vec!(4).into())).unwrap();
+    assert_eq!(v2.to_node(), Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(Node::Leaf(vec!(2)), Node::Leaf(vec!(4)))),
+    )));
+    assert_eq!(Node::from(&v1), node);
+    // unchanged subtrees are shared
+    assert!(v1.get(&[0]).unwrap().ptr_eq(v2.get(&[0]).unwrap()));
+    assert!(v1.get(&[1, 0]).unwrap().ptr_eq(v2.get(&[1, 0]).unwrap()));
+    assert!(!v1.get(&[1]).unwrap().ptr_eq(v2.get(&[1]).unwrap()));
+    assert!(v1.clone().ptr_eq(&v1));
+
+    assert_eq!(v1.replace(&[2], ArcNode::Inner(vec!().into())), None);
+    assert_eq!(v1.replace(&[], v2.clone()), Some(v2));
+}
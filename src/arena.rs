@@ -0,0 +1,143 @@
+use crate::Node;
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// Handle of a node in a `Tree`.
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// Position of the node in breadth-first order.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum NodeData {
+    /// Range of the leaf's bytes in the tree's byte buffer.
+    Leaf(Range<usize>),
+    /// Range of the IDs of the node's children.
+    Inner(Range<usize>),
+}
+
+/// Tree stored in a flat arena, as an alternative to `Node` for large
+/// documents. Nodes are stored in breadth-first order, so the children of
+/// every node are contiguous; the bytes of all leaves share one buffer.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Tree {
+    nodes: Vec<NodeData>,
+    bytes: Vec<u8>,
+}
+
+impl Tree {
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    /// Number of nodes in the tree, which is at least one.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the IDs of all nodes in breadth-first order.
+    pub fn ids(&self) -> impl ExactSizeIterator<Item = NodeId> {
+        (0..self.nodes.len()).map(NodeId)
+    }
+
+    /// Panics if `id` doesn't belong to this tree.
+    pub fn data(&self, id: NodeId) -> &NodeData {
+        &self.nodes[id.0]
+    }
+
+    pub fn is_leaf(&self, id: NodeId) -> bool {
+        matches!(self.data(id), NodeData::Leaf(_))
+    }
+
+    /// Returns the bytes of a leaf, or `None` for inner nodes.
+    pub fn leaf(&self, id: NodeId) -> Option<&[u8]> {
+        match self.data(id) {
+            NodeData::Leaf(range) => Some(&self.bytes[range.clone()]),
+            NodeData::Inner(_) => None,
+        }
+    }
+
+    /// Returns the children of a node; leaves have none.
+    pub fn children(&self, id: NodeId) -> impl ExactSizeIterator<Item = NodeId> {
+        match self.data(id) {
+            NodeData::Leaf(_) => 0..0,
+            NodeData::Inner(range) => range.clone(),
+        }.map(NodeId)
+    }
+
+    pub fn child(&self, id: NodeId, idx: usize) -> Option<NodeId> {
+        self.children(id).nth(idx)
+    }
+
+    pub fn to_node(&self) -> Node {
+        // children have higher IDs than their parents, so building the nodes
+        // in reverse order makes them available when their parent is built
+        let mut nodes: Vec<Option<Node>> = vec!(None; self.nodes.len());
+        for idx in (0..self.nodes.len()).rev() {
+            nodes[idx] = Some(match &self.nodes[idx] {
+                NodeData::Leaf(range) => Node::Leaf(self.bytes[range.clone()].to_vec()),
+                NodeData::Inner(range) => Node::Inner(nodes[range.clone()].iter_mut().map(|n| n.take().unwrap()).collect()),
+            });
+        }
+        nodes[0].take().unwrap()
+    }
+}
+
+impl From<&Node> for Tree {
+    fn from(node: &Node) -> Self {
+        let mut tree = Tree { nodes: vec!(), bytes: vec!() };
+        let mut queue = VecDeque::new();
+        queue.push_back(node);
+        // IDs are assigned in the order nodes are queued
+        let mut next_id = 1;
+        while let Some(node) = queue.pop_front() {
+            tree.nodes.push(match node {
+                Node::Leaf(bytes) => {
+                    tree.bytes.extend_from_slice(bytes);
+                    NodeData::Leaf(tree.bytes.len() - bytes.len()..tree.bytes.len())
+                }
+                Node::Inner(nodes) => {
+                    queue.extend(nodes);
+                    next_id += nodes.len();
+                    NodeData::Inner(next_id - nodes.len()..next_id)
+                }
+            });
+        }
+        tree
+    }
+}
+
+impl From<&Tree> for Node {
+    fn from(tree: &Tree) -> Self {
+        tree.to_node()
+    }
+}
+
+
+#[test]
+fn arena_tree() {
+    let node = crate::parse("(0x01 (0x02_03 () (0x04)) 0x)");
+    let tree = Tree::from(&node);
+    assert_eq!(tree.node_count(), 8);
+    let root = tree.root();
+    assert_eq!(tree.children(root).len(), 3);
+    let inner = tree.child(root, 1).unwrap();
+    assert!(!tree.is_leaf(inner));
+    assert_eq!(tree.leaf(tree.child(inner, 0).unwrap()), Some(&[2, 3][..]));
+    let nested = tree.child(inner, 2).unwrap();
+    assert_eq!(tree.leaf(tree.child(nested, 0).unwrap()), Some(&[4][..]));
+    assert_eq!(tree.child(nested, 1), None);
+    assert_eq!(tree.children(tree.child(root, 0).unwrap()).len(), 0);
+    let leaves: Vec<_> = tree.ids().filter_map(|id| tree.leaf(id)).collect();
+    assert_eq!(leaves, vec!(&[1][..], &[][..], &[2, 3][..], &[4][..]));
+    assert_eq!(Node::from(&tree), node);
+
+    let leaf = Node::Leaf(vec!(5));
+    assert_eq!(Tree::from(&leaf).to_node(), leaf);
+}
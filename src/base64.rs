@@ -0,0 +1,73 @@
+// Standard base64 alphabet with padding (RFC 4648).
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn encode(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                s.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// Decodes padded base64. Returns `None` if `s` isn't valid base64.
+pub fn decode(s: &str) -> Option<Vec<u8>> {
+    let s = s.as_bytes();
+    if s.len() % 4 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(s.len() / 4 * 3);
+    for (idx, chunk) in s.chunks(4).enumerate() {
+        let last = idx + 1 == s.len() / 4;
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut n = 0u32;
+        for c in &chunk[..4 - padding] {
+            let v = ALPHABET.iter().position(|a| a == c)? as u32;
+            n = (n << 6) | v;
+        }
+        n <<= 6 * padding as u32;
+        let b = [(n >> 16) as u8, (n >> 8) as u8, n as u8];
+        // reject non-canonical encodings with bits set in the padding
+        if b[3 - padding..].iter().any(|x| *x != 0) {
+            return None;
+        }
+        bytes.extend_from_slice(&b[..3 - padding]);
+    }
+    Some(bytes)
+}
+
+
+#[test]
+fn base64_encode() {
+    assert_eq!(encode(b""), "");
+    assert_eq!(encode(b"f"), "Zg==");
+    assert_eq!(encode(b"fo"), "Zm8=");
+    assert_eq!(encode(b"foo"), "Zm9v");
+    assert_eq!(encode(b"foob"), "Zm9vYg==");
+    assert_eq!(encode(b"fooba"), "Zm9vYmE=");
+    assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+    assert_eq!(encode(&[0xff, 0xfe, 0xfd]), "//79");
+}
+
+#[test]
+fn base64_decode() {
+    for s in [&b""[..], b"f", b"fo", b"foo", b"foob", b"fooba", b"foobar", &[0xff, 0xfe, 0xfd]] {
+        assert_eq!(decode(&encode(s)).as_deref(), Some(s));
+    }
+    assert_eq!(decode("Zg="), None);
+    assert_eq!(decode("Z==="), None);
+    assert_eq!(decode("Zg==Zg=="), None);
+    assert_eq!(decode("Zh=="), None);
+    assert_eq!(decode("Zm9*"), None);
+}
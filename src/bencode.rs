@@ -0,0 +1,105 @@
+//! Conversion between `Node`s and bencode.
+//!
+//! Inner nodes map to lists, leaves to byte strings, e.g. `l3:foo4:barle`.
+//! Integers and dictionaries have no equivalent in `Node` and are rejected
+//! when decoding.
+
+use crate::Node;
+use crate::wire::{read_atom, write_atom};
+
+pub use crate::wire::Error;
+
+pub fn encode(node: &Node) -> Vec<u8> {
+    let mut res = vec!();
+    encode_into(node, &mut res);
+    res
+}
+
+fn encode_into(node: &Node, w: &mut Vec<u8>) {
+    match node {
+        Node::Leaf(bytes) => write_atom(bytes, w),
+        Node::Inner(nodes) => {
+            w.push(b'l');
+            for node in nodes {
+                encode_into(node, w);
+            }
+            w.push(b'e');
+        }
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Node, Error> {
+    // children of the lists that are currently open
+    let mut stack: Vec<Vec<Node>> = vec!();
+    let mut pos = 0;
+
+    loop {
+        let node = match bytes.get(pos) {
+            None => return Err(Error::UnexpectedEnd),
+            Some(b'l') => {
+                pos += 1;
+                stack.push(vec!());
+                continue;
+            }
+            Some(b'e') => {
+                pos += 1;
+                match stack.pop() {
+                    Some(children) => Node::Inner(children),
+                    None => return Err(Error::UnexpectedByte(b'e', pos - 1)),
+                }
+            }
+            Some(b'0'..=b'9') => Node::Leaf(read_atom(bytes, &mut pos)?.to_vec()),
+            Some(b) => return Err(Error::UnexpectedByte(*b, pos)),
+        };
+
+        match stack.last_mut() {
+            Some(children) => children.push(node),
+            None => {
+                if pos != bytes.len() {
+                    return Err(Error::AdditionalBytes);
+                }
+                return Ok(node);
+            }
+        }
+    }
+}
+
+impl Node {
+    pub fn to_bencode(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn from_bencode(bytes: &[u8]) -> Result<Node, Error> {
+        decode(bytes)
+    }
+}
+
+
+#[test]
+fn bencode_roundtrip() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"foo".to_vec()),
+        Node::Inner(vec!(
+            Node::Leaf(vec!()),
+            Node::Inner(vec!()),
+        )),
+        Node::Leaf(b"0123456789lie".to_vec()),
+    ));
+    let exp = b"l3:fool0:lee13:0123456789liee";
+    assert_eq!(node.to_bencode(), exp.to_vec());
+    assert_eq!(Node::from_bencode(exp), Ok(node));
+    assert_eq!(Node::from_bencode(b"3:abc"), Ok(Node::Leaf(b"abc".to_vec())));
+}
+
+#[test]
+fn bencode_errors() {
+    assert_eq!(Node::from_bencode(b""), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_bencode(b"l3:ab"), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_bencode(b"l1:a"), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_bencode(b"01:a"), Err(Error::InvalidLength(0)));
+    assert_eq!(Node::from_bencode(b"l1:aele"), Err(Error::AdditionalBytes));
+    assert_eq!(Node::from_bencode(b"e"), Err(Error::UnexpectedByte(b'e', 0)));
+    assert_eq!(Node::from_bencode(b"li42ee"), Err(Error::UnexpectedByte(b'i', 1)));
+    assert_eq!(Node::from_bencode(b"d3:foo3:bare"), Err(Error::UnexpectedByte(b'd', 0)));
+    assert_eq!(Node::from_bencode(b"99999999999999999999999:a"), Err(Error::InvalidLength(0)));
+}
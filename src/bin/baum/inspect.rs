@@ -0,0 +1,341 @@
+//! `baum inspect`: terminal UI for exploring a document.
+
+use baum::{Node, ParseResult, Path};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::{cursor, queue, terminal};
+
+use std::collections::HashSet;
+use std::io::Write;
+
+const HELP: &str = "j/k: move  l/h: expand/collapse  /: search  n: next match  q: quit";
+
+/// Maximum number of lines of the hex view.
+const HEX_LINES: usize = 8;
+
+pub fn run(root: &Node) -> Result<(), String> {
+    let mut inspector = Inspector::new(root);
+    let mut out = std::io::stdout();
+    terminal::enable_raw_mode().map_err(|e| e.to_string())?;
+    let res = queue!(out, terminal::EnterAlternateScreen, cursor::Hide)
+        .and_then(|_| event_loop(&mut inspector, &mut out));
+    // restore the terminal even if drawing failed
+    let _ = queue!(out, cursor::Show, terminal::LeaveAlternateScreen);
+    let _ = out.flush();
+    let _ = terminal::disable_raw_mode();
+    res.map_err(|e| e.to_string())
+}
+
+fn event_loop<W: Write>(inspector: &mut Inspector, out: &mut W) -> std::io::Result<()> {
+    loop {
+        let (width, height) = terminal::size()?;
+        inspector.draw(out, width as usize, height as usize)?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Release && !inspector.handle(key.code) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Visible line of the tree view.
+struct Row<'a> {
+    path: Path,
+    node: &'a Node,
+}
+
+struct Inspector<'a> {
+    root: &'a Node,
+    /// Paths of the expanded inner nodes.
+    expanded: HashSet<Path>,
+    rows: Vec<Row<'a>>,
+    cursor: usize,
+    /// Index of the first visible row.
+    scroll: usize,
+    /// Number of rows shown by the last `draw`, used for paging.
+    page: usize,
+    /// Search query being typed after pressing `/`.
+    input: Option<String>,
+    query: Option<Vec<u8>>,
+    status: String,
+}
+
+impl<'a> Inspector<'a> {
+    fn new(root: &'a Node) -> Self {
+        let mut inspector = Inspector {
+            root,
+            expanded: std::iter::once(vec!()).collect(),
+            rows: vec!(),
+            cursor: 0,
+            scroll: 0,
+            page: 10,
+            input: None,
+            query: None,
+            status: HELP.to_string(),
+        };
+        inspector.rebuild();
+        inspector
+    }
+
+    /// Recomputes the visible rows, keeping the cursor on the same node if
+    /// it's still visible.
+    fn rebuild(&mut self) {
+        let current = self.rows.get(self.cursor).map(|row| row.path.clone());
+        self.rows.clear();
+        let mut stack = vec!((vec!(), self.root));
+        while let Some((path, node)) = stack.pop() {
+            if let (Node::Inner(nodes), true) = (node, self.expanded.contains(&path)) {
+                for (idx, child) in nodes.iter().enumerate().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(idx);
+                    stack.push((child_path, child));
+                }
+            }
+            self.rows.push(Row { path, node });
+        }
+        if let Some(current) = current {
+            self.select(&current);
+        }
+    }
+
+    /// Moves the cursor to the row of `path` or its closest visible ancestor.
+    fn select(&mut self, path: &[usize]) {
+        for len in (0..=path.len()).rev() {
+            if let Some(idx) = self.rows.iter().position(|row| row.path == path[..len]) {
+                self.cursor = idx;
+                return;
+            }
+        }
+    }
+
+    fn current(&self) -> &Row<'a> {
+        &self.rows[self.cursor]
+    }
+
+    /// Handles a key press, returning `false` to quit.
+    fn handle(&mut self, code: KeyCode) -> bool {
+        if let Some(input) = &mut self.input {
+            match code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => { input.pop(); }
+                KeyCode::Enter => {
+                    self.query = Some(parse_query(input));
+                    self.input = None;
+                    self.next_match();
+                }
+                KeyCode::Esc => {
+                    self.input = None;
+                    self.status = HELP.to_string();
+                }
+                _ => {}
+            }
+            return true;
+        }
+        let last = self.rows.len() - 1;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.cursor = (self.cursor + 1).min(last),
+            KeyCode::PageUp => self.cursor = self.cursor.saturating_sub(self.page),
+            KeyCode::PageDown => self.cursor = (self.cursor + self.page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.cursor = 0,
+            KeyCode::End | KeyCode::Char('G') => self.cursor = last,
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter => {
+                if let Node::Inner(_) = self.current().node {
+                    self.expanded.insert(self.current().path.clone());
+                    self.rebuild();
+                }
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                let path = self.current().path.clone();
+                if !self.expanded.remove(&path) {
+                    // already collapsed, go to the parent instead
+                    self.select(&path[..path.len().saturating_sub(1)]);
+                }
+                self.rebuild();
+            }
+            KeyCode::Char('/') => {
+                self.input = Some(String::new());
+            }
+            KeyCode::Char('n') => self.next_match(),
+            _ => {}
+        }
+        true
+    }
+
+    /// Moves the cursor to the next leaf after the current row containing
+    /// the query, expanding its ancestors.
+    fn next_match(&mut self) {
+        let query = match &self.query {
+            Some(query) => query,
+            None => return,
+        };
+        let mut matches = vec!();
+        find(self.root, query, &mut vec!(), &mut matches);
+        // paths compare in depth-first order
+        let current = &self.current().path;
+        let next = matches.iter().find(|path| *path > current).or_else(|| matches.first()).cloned();
+        self.status = match next {
+            Some(path) => {
+                for len in 0..path.len() {
+                    self.expanded.insert(path[..len].to_vec());
+                }
+                self.rebuild();
+                self.select(&path);
+                format!("{} matches", matches.len())
+            }
+            None => "No matches".to_string(),
+        };
+    }
+
+    fn draw<W: Write>(&mut self, out: &mut W, width: usize, height: usize) -> std::io::Result<()> {
+        let hex = match self.current().node {
+            Node::Leaf(bytes) => hex_lines(bytes, HEX_LINES),
+            Node::Inner(_) => vec!(),
+        };
+        // tree, separator and hex view, status line
+        let tree_height = height.saturating_sub(hex.len() + 2).max(1);
+        self.page = tree_height;
+        if self.cursor < self.scroll {
+            self.scroll = self.cursor;
+        } else if self.cursor >= self.scroll + tree_height {
+            self.scroll = self.cursor + 1 - tree_height;
+        }
+
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        for (line, (idx, row)) in self.rows.iter().enumerate().skip(self.scroll).take(tree_height).enumerate() {
+            queue!(out, cursor::MoveTo(0, line as u16))?;
+            if idx == self.cursor {
+                queue!(out, SetAttribute(Attribute::Reverse))?;
+            }
+            queue!(out, Print(truncate(&self.label(row), width)), SetAttribute(Attribute::Reset))?;
+        }
+        queue!(out, cursor::MoveTo(0, tree_height as u16), Print("─".repeat(width)))?;
+        for (line, text) in (tree_height + 1..).zip(&hex) {
+            queue!(out, cursor::MoveTo(0, line as u16), Print(truncate(text, width)))?;
+        }
+        let status = match &self.input {
+            Some(input) => format!("/{}", input),
+            None => format!("{:?}  {}", self.current().path, self.status),
+        };
+        queue!(out, cursor::MoveTo(0, height.saturating_sub(1) as u16), Print(truncate(&status, width)))?;
+        out.flush()
+    }
+
+    fn label(&self, row: &Row) -> String {
+        let indent = "  ".repeat(row.path.len());
+        match row.node {
+            Node::Inner(nodes) => {
+                let marker = if self.expanded.contains(&row.path) { '▾' } else { '▸' };
+                format!("{}{} ({} children)", indent, marker, nodes.len())
+            }
+            Node::Leaf(bytes) if bytes.len() > 64 => {
+                format!("{}  {}… ({} bytes)", indent, Node::Leaf(bytes[..64].to_vec()), bytes.len())
+            }
+            Node::Leaf(_) => format!("{}  {}", indent, row.node),
+        }
+    }
+}
+
+/// Interprets the query as a leaf in the text format (e.g. `0x01_02`), or
+/// as UTF-8 text otherwise.
+fn parse_query(query: &str) -> Vec<u8> {
+    match Node::parse(query) {
+        ParseResult::Ok(Node::Leaf(bytes)) => bytes,
+        _ => query.as_bytes().to_vec(),
+    }
+}
+
+/// Collects the paths of all leaves containing `query`.
+fn find(node: &Node, query: &[u8], path: &mut Path, res: &mut Vec<Path>) {
+    match node {
+        Node::Leaf(bytes) => {
+            if query.is_empty() || bytes.windows(query.len()).any(|w| w == query) {
+                res.push(path.clone());
+            }
+        }
+        Node::Inner(nodes) => {
+            for (idx, child) in nodes.iter().enumerate() {
+                path.push(idx);
+                find(child, query, path, res);
+                path.pop();
+            }
+        }
+    }
+}
+
+/// Formats up to `max_lines` lines of a hex dump of `bytes`, 16 per line.
+fn hex_lines(bytes: &[u8], max_lines: usize) -> Vec<String> {
+    let mut res: Vec<String> = bytes.chunks(16).take(max_lines).enumerate().map(|(idx, chunk)| {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+        format!("{:08x}  {:<47}  |{}|", idx * 16, hex.join(" "), ascii)
+    }).collect();
+    if bytes.len() > max_lines * 16 {
+        res.pop();
+        res.push(format!("… ({} bytes)", bytes.len()));
+    }
+    res
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    match s.char_indices().nth(width) {
+        Some((idx, _)) => s[..idx].to_string(),
+        None => s.to_string(),
+    }
+}
+
+
+#[test]
+fn inspector() {
+    let node = match Node::parse("(0x01 (0x02 \"abc\") (0x03 (0x61_62)))") {
+        ParseResult::Ok(node) => node,
+        r => panic!("{}", r.err_message()),
+    };
+    let mut inspector = Inspector::new(&node);
+    let paths = |i: &Inspector| i.rows.iter().map(|row| row.path.clone()).collect::<Vec<_>>();
+    assert_eq!(paths(&inspector), vec!(vec!(), vec!(0), vec!(1), vec!(2)));
+
+    inspector.handle(KeyCode::Down);
+    inspector.handle(KeyCode::Down);
+    inspector.handle(KeyCode::Right);
+    assert_eq!(paths(&inspector), vec!(vec!(), vec!(0), vec!(1), vec!(1, 0), vec!(1, 1), vec!(2)));
+    assert_eq!(inspector.label(&inspector.rows[2]), "  ▾ (2 children)");
+    assert_eq!(inspector.label(&inspector.rows[4]), "      0x61_62_63");
+
+    // collapsing a collapsed node moves to its parent
+    inspector.handle(KeyCode::Down);
+    inspector.handle(KeyCode::Left);
+    assert_eq!(inspector.current().path, vec!(1));
+    inspector.handle(KeyCode::Left);
+    assert_eq!((paths(&inspector).len(), inspector.current().path.clone()), (4, vec!(1)));
+
+    // search expands the ancestors of matches and wraps around
+    for c in "/ab\n".chars() {
+        inspector.handle(if c == '\n' { KeyCode::Enter } else { KeyCode::Char(c) });
+    }
+    assert_eq!(inspector.current().path, vec!(1, 1));
+    inspector.handle(KeyCode::Char('n'));
+    assert_eq!(inspector.current().path, vec!(2, 1, 0));
+    inspector.handle(KeyCode::Char('n'));
+    assert_eq!(inspector.current().path, vec!(1, 1));
+    assert_eq!(inspector.status, "2 matches");
+    inspector.query = Some(parse_query("0x04"));
+    inspector.next_match();
+    assert_eq!((inspector.current().path.clone(), inspector.status.as_str()), (vec!(1, 1), "No matches"));
+
+    let mut screen = vec!();
+    inspector.draw(&mut screen, 80, 10).unwrap();
+    assert!(String::from_utf8(screen).unwrap().contains("|abc|"));
+    assert!(!inspector.handle(KeyCode::Char('q')));
+}
+
+#[test]
+fn hex_view() {
+    assert_eq!(hex_lines(b"ab\x00", 2), vec!(format!("00000000  61 62 00{}  |ab.|", " ".repeat(39))));
+    let lines = hex_lines(&[0; 40], 2);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[1], "… (40 bytes)");
+    assert_eq!(truncate("▾ abc", 3), "▾ a");
+}
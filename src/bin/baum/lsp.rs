@@ -0,0 +1,333 @@
+//! `baum lsp`: language server for the text format, speaking LSP on stdio.
+//!
+//! Offers diagnostics, formatting (which keeps leaves as written), folding of
+//! inner nodes spanning multiple lines and go-to-definition on a parenthesis,
+//! which jumps to its match.
+
+use baum::{Node, ParseResult, Path, PrettyConfig};
+
+use lsp_server::{Connection, Message, Notification, Request, Response};
+use lsp_types::notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _, PublishDiagnostics};
+use lsp_types::request::{FoldingRangeRequest, Formatting, GotoDefinition, Request as _};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, FoldingRange, FoldingRangeProviderCapability, GotoDefinitionResponse, Location,
+    OneOf, Position, PublishDiagnosticsParams, Range, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, TextEdit, Url,
+};
+
+use std::collections::HashMap;
+
+/// Line width used for formatting.
+const MAX_WIDTH: usize = 80;
+
+pub fn run() -> Result<(), String> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    let capabilities = serde_json::to_value(capabilities).map_err(|e| e.to_string())?;
+    connection.initialize(capabilities).map_err(|e| e.to_string())?;
+    serve(&connection).map_err(|e| e.to_string())?;
+    drop(connection);
+    io_threads.join().map_err(|e| e.to_string())
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+fn serve(connection: &Connection) -> Result<(), BoxError> {
+    let mut docs: HashMap<Url, String> = HashMap::new();
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    return Ok(());
+                }
+                connection.sender.send(Message::Response(respond(&docs, req)?))?;
+            }
+            Message::Notification(not) => {
+                let uri = match not.method.as_str() {
+                    DidOpenTextDocument::METHOD => {
+                        let params: lsp_types::DidOpenTextDocumentParams = serde_json::from_value(not.params)?;
+                        docs.insert(params.text_document.uri.clone(), params.text_document.text);
+                        params.text_document.uri
+                    }
+                    DidChangeTextDocument::METHOD => {
+                        let mut params: lsp_types::DidChangeTextDocumentParams = serde_json::from_value(not.params)?;
+                        // full sync, so the last change holds the whole text
+                        if let Some(change) = params.content_changes.pop() {
+                            docs.insert(params.text_document.uri.clone(), change.text);
+                        }
+                        params.text_document.uri
+                    }
+                    DidCloseTextDocument::METHOD => {
+                        let params: lsp_types::DidCloseTextDocumentParams = serde_json::from_value(not.params)?;
+                        docs.remove(&params.text_document.uri);
+                        continue;
+                    }
+                    _ => continue,
+                };
+                let text = docs.get(&uri).map_or("", String::as_str);
+                let params = PublishDiagnosticsParams { uri, diagnostics: diagnostics(text), version: None };
+                let not = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+                connection.sender.send(Message::Notification(not))?;
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+fn respond(docs: &HashMap<Url, String>, req: Request) -> Result<Response, BoxError> {
+    let text = |uri: &Url| docs.get(uri).map_or("", String::as_str);
+    Ok(match req.method.as_str() {
+        Formatting::METHOD => {
+            let params: lsp_types::DocumentFormattingParams = serde_json::from_value(req.params)?;
+            Response::new_ok(req.id, format(text(&params.text_document.uri)))
+        }
+        FoldingRangeRequest::METHOD => {
+            let params: lsp_types::FoldingRangeParams = serde_json::from_value(req.params)?;
+            Response::new_ok(req.id, folding_ranges(text(&params.text_document.uri)))
+        }
+        GotoDefinition::METHOD => {
+            let params: lsp_types::GotoDefinitionParams = serde_json::from_value(req.params)?;
+            let pos = params.text_document_position_params;
+            let res = matching_paren(text(&pos.text_document.uri), pos.position).map(|range| {
+                GotoDefinitionResponse::Scalar(Location { uri: pos.text_document.uri, range })
+            });
+            Response::new_ok(req.id, res)
+        }
+        _ => Response::new_err(req.id, lsp_server::ErrorCode::MethodNotFound as i32, format!("Unsupported request {}.", req.method)),
+    })
+}
+
+/// Parentheses of a document, found while skipping string literals.
+struct Parens {
+    /// Byte offsets of matching parentheses.
+    pairs: Vec<(usize, usize)>,
+    /// Byte offsets of parentheses without a match.
+    unmatched: Vec<usize>,
+}
+
+fn scan(text: &str) -> Parens {
+    let mut res = Parens { pairs: vec!(), unmatched: vec!() };
+    let mut open = vec!();
+    let mut chars = text.char_indices();
+    while let Some((pos, c)) = chars.next() {
+        match c {
+            '(' => open.push(pos),
+            ')' => match open.pop() {
+                Some(start) => res.pairs.push((start, pos)),
+                None => res.unmatched.push(pos),
+            },
+            '"' => {
+                // skip to the closing quote, ignoring escaped characters
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => { chars.next(); }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    res.unmatched.extend(open);
+    res.unmatched.sort_unstable();
+    res
+}
+
+fn diagnostics(text: &str) -> Vec<Diagnostic> {
+    let message = match Node::parse(text) {
+        ParseResult::Ok(_) => return vec!(),
+        r => r.err_message().to_string(),
+    };
+    // point at unbalanced parentheses if there are any, since the parser
+    // doesn't report positions
+    let unmatched = scan(text).unmatched;
+    let errors: Vec<(Range, String)> = match unmatched.is_empty() {
+        true => vec!((Range::new(position(text, 0), position(text, text.len())), message)),
+        false => unmatched.into_iter().map(|pos| {
+            let message = if text[pos..].starts_with('(') { "Unclosed '('." } else { "Unexpected ')'." };
+            (Range::new(position(text, pos), position(text, pos + 1)), message.to_string())
+        }).collect(),
+    };
+    errors.into_iter().map(|(range, message)| {
+        Diagnostic { range, severity: Some(DiagnosticSeverity::ERROR), source: Some("baum".to_string()), message, ..Diagnostic::default() }
+    }).collect()
+}
+
+/// Replaces the whole document by its pretty-printed form, unless it can't
+/// be parsed. Leaves are printed as written in the document.
+fn format(text: &str) -> Option<Vec<TextEdit>> {
+    let node = match Node::parse(text) {
+        ParseResult::Ok(node) => node,
+        _ => return None,
+    };
+    let leaves = leaf_sources(text);
+    let renderer = |path: &[usize], _: &[u8]| leaves.get(path).map(|source| source.to_string());
+    let config = PrettyConfig { max_width: MAX_WIDTH, trailing_newline: true, ..PrettyConfig::default() };
+    let range = Range::new(position(text, 0), position(text, text.len()));
+    Some(vec!(TextEdit { range, new_text: node.pretty_print_rendered(&config, &renderer) }))
+}
+
+/// Returns the source text of the leaves of a valid document by their paths.
+/// Leaves containing line breaks are left out, since they can't be printed
+/// as written.
+fn leaf_sources(text: &str) -> HashMap<Path, &str> {
+    let mut res = HashMap::new();
+    // path of the current inner node and the index of its next child
+    let (mut path, mut next): (Path, Vec<usize>) = (vec!(), vec!());
+    let leaf_path = |path: &Path, next: &mut Vec<usize>| {
+        let mut res = path.clone();
+        if let Some(idx) = next.last_mut() {
+            res.push(*idx);
+            *idx += 1;
+        }
+        res
+    };
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '(' => {
+                if !next.is_empty() {
+                    path = leaf_path(&path, &mut next);
+                }
+                next.push(0);
+            }
+            ')' => {
+                next.pop();
+                path.pop();
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut end = text.len();
+                let in_string = c == '"';
+                while let Some(&(pos, c)) = chars.peek() {
+                    if in_string {
+                        chars.next();
+                        match c {
+                            '\\' => { chars.next(); }
+                            '"' => {
+                                end = pos + 1;
+                                break;
+                            }
+                            _ => {}
+                        }
+                    } else if c.is_whitespace() || c == '(' || c == ')' || c == '"' {
+                        end = pos;
+                        break;
+                    } else {
+                        chars.next();
+                    }
+                }
+                let source = &text[start..end];
+                let leaf = leaf_path(&path, &mut next);
+                if !source.contains('\n') {
+                    res.insert(leaf, source);
+                }
+            }
+        }
+    }
+    res
+}
+
+fn folding_ranges(text: &str) -> Vec<FoldingRange> {
+    let mut res: Vec<FoldingRange> = scan(text).pairs.into_iter().filter_map(|(start, end)| {
+        let (start, end) = (position(text, start), position(text, end));
+        match start.line < end.line {
+            true => Some(FoldingRange { start_line: start.line, end_line: end.line, ..FoldingRange::default() }),
+            false => None,
+        }
+    }).collect();
+    res.sort_by_key(|range| (range.start_line, range.end_line));
+    res
+}
+
+/// Returns the range of the parenthesis matching the one at `pos`.
+fn matching_paren(text: &str, pos: Position) -> Option<Range> {
+    let pos = offset(text, pos);
+    let other = scan(text).pairs.into_iter().find_map(|(start, end)| match pos {
+        _ if pos == start => Some(end),
+        _ if pos == end => Some(start),
+        _ => None,
+    })?;
+    Some(Range::new(position(text, other), position(text, other + 1)))
+}
+
+/// Converts a byte offset into a position, counting UTF-16 code units.
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count() as u32;
+    Position::new(line, before[line_start..].encode_utf16().count() as u32)
+}
+
+/// Converts a position into a byte offset, clamping it to the text.
+fn offset(text: &str, pos: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..pos.line {
+        match text[line_start..].find('\n') {
+            Some(idx) => line_start += idx + 1,
+            None => return text.len(),
+        }
+    }
+    let mut units = 0;
+    for (idx, c) in text[line_start..].char_indices() {
+        if units >= pos.character as usize || c == '\n' {
+            return line_start + idx;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+
+#[test]
+fn positions() {
+    let text = "(\"ä😀\"\n  0x01)";
+    assert_eq!(position(text, 0), Position::new(0, 0));
+    // the emoji takes two UTF-16 code units
+    assert_eq!(position(text, 8), Position::new(0, 5));
+    assert_eq!(position(text, 10), Position::new(1, 0));
+    for offs in [0, 1, 2, 4, 8, 9, 10, 12, 16, 17] {
+        assert_eq!(offset(text, position(text, offs)), offs);
+    }
+    assert_eq!(offset(text, Position::new(0, 99)), 9);
+    assert_eq!(offset(text, Position::new(9, 0)), text.len());
+}
+
+#[test]
+fn language_features() {
+    let text = "(0x01\n  (\")(\" ())\n)";
+    assert!(diagnostics(text).is_empty());
+    assert_eq!(matching_paren(text, Position::new(0, 0)), Some(Range::new(Position::new(2, 0), Position::new(2, 1))));
+    assert_eq!(matching_paren(text, Position::new(1, 2)), Some(Range::new(Position::new(1, 10), Position::new(1, 11))));
+    assert_eq!(matching_paren(text, Position::new(1, 4)), None);
+    let folds: Vec<_> = folding_ranges(text).into_iter().map(|r| (r.start_line, r.end_line)).collect();
+    assert_eq!(folds, vec!((0, 2)));
+
+    let edits = format(text).unwrap();
+    assert_eq!(edits[0].new_text, "(0x01 (\")(\" ()))\n");
+    assert_eq!(edits[0].range.end, Position::new(2, 1));
+    assert!(format("(").is_none());
+
+    let diags = diagnostics("(0x01\n (0x02)");
+    assert_eq!((diags.len(), diags[0].message.as_str()), (1, "Unclosed '('."));
+    assert_eq!(diags[0].range, Range::new(Position::new(0, 0), Position::new(0, 1)));
+    let diags = diagnostics("0x01)");
+    assert_eq!((diags[0].message.as_str(), diags[0].range.start), ("Unexpected ')'.", Position::new(0, 4)));
+    let diags = diagnostics("(0y)");
+    assert_eq!((diags[0].message.as_str(), diags[0].range.end), ("Expected 'x'!", Position::new(0, 4)));
+
+    // leaves keep their notation
+    let text = "( \"a\\\"b\"0x01_02\n(0x0A \"\\x41\") 0x)";
+    let edits = format(text).unwrap();
+    assert_eq!(edits[0].new_text, "(\"a\\\"b\" 0x01_02 (0x0A \"\\x41\") 0x)\n");
+    let sources = leaf_sources("0x01");
+    assert_eq!(sources.get(&vec!()), Some(&"0x01"));
+}
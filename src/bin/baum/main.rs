@@ -0,0 +1,52 @@
+//! Command line tool for baum documents.
+
+#[cfg(feature = "tui")]
+mod inspect;
+#[cfg(feature = "repl")]
+mod repl;
+#[cfg(feature = "lsp")]
+mod lsp;
+
+use baum::{Node, ParseResult};
+
+const USAGE: &str = "Usage: baum <command> [args]
+
+Commands:
+    inspect <file>    Explore a document interactively (feature `tui`)
+    repl [file]       Build or edit a document interactively (feature `repl`)
+    lsp               Run a language server for the text format on stdio (feature `lsp`)";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let res: Result<(), String> = match args.as_slice() {
+        #[cfg(feature = "tui")]
+        ["inspect", file] => load(file).and_then(|node| inspect::run(&node)),
+        #[cfg(feature = "repl")]
+        ["repl"] => repl::run(Node::EMPTY_INNER),
+        #[cfg(feature = "repl")]
+        ["repl", file] => load(file).and_then(repl::run),
+        #[cfg(feature = "lsp")]
+        ["lsp"] => lsp::run(),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(msg) = res {
+        eprintln!("{}", msg);
+        std::process::exit(1);
+    }
+}
+
+/// Loads a document in the binary format, or the text format if the file
+/// doesn't start with a magic number.
+#[allow(dead_code)]
+fn load(file: &str) -> Result<Node, String> {
+    let bytes = std::fs::read(file).map_err(|e| format!("Can't read {}: {}", file, e))?;
+    if bytes.starts_with(b"BAUM") {
+        return Node::deserialize_dedup(&bytes).map_err(|e| format!("Can't decode {}: {}", file, e));
+    }
+    let text = String::from_utf8(bytes).map_err(|_| format!("{} is neither a baum document nor UTF-8 text.", file))?;
+    match Node::parse(&text) {
+        ParseResult::Ok(node) => Ok(node),
+        r => Err(format!("Can't parse {}: {}", file, r.err_message())),
+    }
+}
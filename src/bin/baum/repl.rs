@@ -0,0 +1,168 @@
+//! `baum repl`: interactive shell for building and editing a document.
+
+use baum::{Node, ParseResult, Path};
+
+use std::io::{BufRead, Write};
+
+const HELP: &str = "Commands:
+    cd <path>          Go to a child, e.g. `cd 0/2`, `cd ..` or `cd /1`
+    ls                 List the children of the current node
+    print              Pretty-print the current node
+    set <node>         Replace the current node, e.g. `set 0x01_02`
+    insert [idx] <node>
+                       Insert a child (at the end by default)
+    rm <idx>           Remove a child
+    save <file>        Write the document in the binary format
+    quit               Exit";
+
+pub fn run(root: Node) -> Result<(), String> {
+    let mut repl = Repl { root, path: vec!() };
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("{}> ", repl.pwd());
+        std::io::stdout().flush().map_err(|e| e.to_string())?;
+        let line = match lines.next() {
+            Some(line) => line.map_err(|e| e.to_string())?,
+            None => return Ok(()),
+        };
+        if line.trim() == "quit" {
+            return Ok(());
+        }
+        match repl.exec(&line) {
+            Ok(Some(out)) => println!("{}", out),
+            Ok(None) => {}
+            Err(msg) => println!("Error: {}", msg),
+        }
+    }
+}
+
+struct Repl {
+    root: Node,
+    /// Path of the current node.
+    path: Path,
+}
+
+impl Repl {
+    fn pwd(&self) -> String {
+        let parts: Vec<String> = self.path.iter().map(usize::to_string).collect();
+        format!("/{}", parts.join("/"))
+    }
+
+    fn current(&mut self) -> &mut Node {
+        self.root.get_mut(&self.path).expect("the current path is valid")
+    }
+
+    /// Executes a command (other than `quit`), returning its output.
+    fn exec(&mut self, line: &str) -> Result<Option<String>, String> {
+        let line = line.trim();
+        let (cmd, arg) = line.split_once(' ').map_or((line, ""), |(cmd, arg)| (cmd, arg.trim()));
+        match cmd {
+            "" => Ok(None),
+            "help" => Ok(Some(HELP.to_string())),
+            "cd" => self.cd(arg).map(|_| None),
+            "ls" => Ok(Some(match self.current() {
+                Node::Leaf(_) => "Leaf without children.".to_string(),
+                Node::Inner(nodes) => {
+                    let lines: Vec<String> = nodes.iter().enumerate().map(|(idx, node)| format!("{:>4}  {}", idx, truncate(&node.to_string(), 72))).collect();
+                    lines.join("\n")
+                }
+            })),
+            "print" => Ok(Some(self.current().pretty_print(80))),
+            "set" => {
+                *self.current() = parse(arg)?;
+                Ok(None)
+            }
+            "insert" => {
+                let (idx, text) = match arg.split_once(' ') {
+                    Some((idx, text)) if idx.bytes().all(|b| b.is_ascii_digit()) => (Some(idx.parse::<usize>().map_err(|e| e.to_string())?), text),
+                    _ => (None, arg),
+                };
+                let node = parse(text)?;
+                let len = match self.current() {
+                    Node::Inner(nodes) => nodes.len(),
+                    Node::Leaf(_) => return Err("Can't insert into a leaf.".to_string()),
+                };
+                let mut path = self.path.clone();
+                path.push(idx.unwrap_or(len));
+                self.root.graft(&path, node).map_err(|_| format!("Index {} is out of bounds.", path.last().unwrap()))?;
+                Ok(None)
+            }
+            "rm" => {
+                let mut path = self.path.clone();
+                path.push(arg.parse().map_err(|_| format!("Invalid index '{}'.", arg))?);
+                match self.root.take_subtree(&path) {
+                    Some(_) => Ok(None),
+                    None => Err(format!("No child {}.", arg)),
+                }
+            }
+            "save" if !arg.is_empty() => {
+                std::fs::write(arg, self.root.serialize()).map_err(|e| format!("Can't write {}: {}", arg, e))?;
+                Ok(Some(format!("Saved {}.", arg)))
+            }
+            _ => Err(format!("Unknown command '{}', see `help`.", line)),
+        }
+    }
+
+    fn cd(&mut self, arg: &str) -> Result<(), String> {
+        let mut path = match arg.starts_with('/') {
+            true => vec!(),
+            false => self.path.clone(),
+        };
+        for part in arg.split('/').filter(|part| !part.is_empty() && *part != ".") {
+            if part == ".." {
+                path.pop();
+                continue;
+            }
+            let idx: usize = part.parse().map_err(|_| format!("Invalid index '{}'.", part))?;
+            match self.root.get(&path) {
+                Some(Node::Inner(nodes)) if idx < nodes.len() => path.push(idx),
+                _ => return Err(format!("No node at {}/{}.", path.iter().map(|idx| format!("/{}", idx)).collect::<String>(), idx)),
+            }
+        }
+        self.path = path;
+        Ok(())
+    }
+}
+
+fn parse(text: &str) -> Result<Node, String> {
+    match Node::parse(text) {
+        ParseResult::Ok(node) => Ok(node),
+        r => Err(r.err_message().to_string()),
+    }
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    match s.char_indices().nth(width) {
+        Some((idx, _)) => format!("{}…", &s[..idx]),
+        None => s.to_string(),
+    }
+}
+
+
+#[test]
+fn repl() {
+    let mut repl = Repl { root: Node::Inner(vec!()), path: vec!() };
+    let mut exec = |line: &str| repl.exec(line);
+    assert_eq!(exec("insert 0x01"), Ok(None));
+    assert_eq!(exec("insert (0x02 ())"), Ok(None));
+    assert_eq!(exec("insert 0 \"a\""), Ok(None));
+    assert_eq!(exec("ls"), Ok(Some("   0  0x61\n   1  0x01\n   2  (0x02 ())".to_string())));
+    assert_eq!(exec("cd 2/1"), Ok(None));
+    assert_eq!(exec("insert 0x03"), Ok(None));
+    assert_eq!(exec("cd ../0"), Ok(None));
+    assert_eq!(exec("set 0x04_05"), Ok(None));
+    assert_eq!(exec("insert 0x06"), Err("Can't insert into a leaf.".to_string()));
+    assert_eq!(exec("cd /"), Ok(None));
+    assert_eq!(exec("rm 1"), Ok(None));
+    assert_eq!(exec("rm 5"), Err("No child 5.".to_string()));
+    assert_eq!(exec("insert 9 0x"), Err("Index 9 is out of bounds.".to_string()));
+    assert_eq!(exec("cd 1/1/0/0"), Err("No node at /1/1/0/0.".to_string()));
+    assert_eq!(exec("cd 0/0"), Err("No node at /0/0.".to_string()));
+    assert!(exec("set (").is_err());
+    assert_eq!(exec("frobnicate"), Err("Unknown command 'frobnicate', see `help`.".to_string()));
+    assert_eq!(exec("print"), Ok(Some("(0x61 (0x04_05 (0x03)))".to_string())));
+    assert_eq!(repl.pwd(), "/");
+    repl.path = vec!(1, 1);
+    assert_eq!(repl.pwd(), "/1/1");
+}
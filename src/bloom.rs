@@ -0,0 +1,206 @@
+use crate::{read_u64, Error, ErrorKind, Node, Section};
+
+use std::convert::TryInto;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8] = b"BLOOM1";
+/// Upper bound for the number of hash functions, which `contains` and
+/// `insert` evaluate one by one.
+const MAX_HASH_COUNT: u64 = 32;
+
+/// Bloom filter over leaf contents, for quickly ruling out that a tree
+/// contains a leaf without loading it. `contains` may return false
+/// positives, but never false negatives.
+///
+/// Filters built with a prefix length only store the first bytes of each
+/// leaf, which makes them smaller for trees with many similar leaves.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hash_count: u64,
+    prefix_len: Option<u64>,
+}
+
+/// FNV-1a with a configurable offset basis. The hash function must not
+/// change, since filters are persisted.
+fn fnv1a(bytes: &[u8], basis: u64) -> u64 {
+    bytes.iter().fold(basis, |h, b| (h ^ *b as u64).wrapping_mul(0x100_0000_01b3))
+}
+
+impl BloomFilter {
+    /// Creates an empty filter sized for `items` entries with the given
+    /// false positive rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_rate` isn't strictly between 0 and 1.
+    pub fn new(items: usize, false_positive_rate: f64, prefix_len: Option<usize>) -> Self {
+        assert!(
+            false_positive_rate > 0.0 && false_positive_rate < 1.0,
+            "false positive rate {} isn't between 0 and 1", false_positive_rate,
+        );
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-(items.max(1) as f64) * false_positive_rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+        let hash_count = (bit_count / items.max(1) as f64 * ln2).round().clamp(1.0, MAX_HASH_COUNT as f64);
+        BloomFilter {
+            bits: vec!(0; (bit_count as usize).div_ceil(64)),
+            hash_count: hash_count as u64,
+            prefix_len: prefix_len.map(|len| len as u64),
+        }
+    }
+
+    /// Creates a filter containing all leaves of `node`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_rate` isn't strictly between 0 and 1.
+    pub fn from_leaves(node: &Node, false_positive_rate: f64, prefix_len: Option<usize>) -> Self {
+        let mut leaves = vec!();
+        collect_leaves(node, &mut leaves);
+        let mut filter = BloomFilter::new(leaves.len(), false_positive_rate, prefix_len);
+        for leaf in leaves {
+            filter.insert(leaf);
+        }
+        filter
+    }
+
+    pub fn prefix_len(&self) -> Option<usize> {
+        // longer prefixes than fit into memory cover any leaf
+        self.prefix_len.map(|len| len.try_into().unwrap_or(usize::MAX))
+    }
+
+    fn bit_indices(&self, bytes: &[u8]) -> impl Iterator<Item = usize> {
+        let bytes = match self.prefix_len() {
+            Some(len) => &bytes[..bytes.len().min(len)],
+            None => bytes,
+        };
+        let (h1, h2) = (fnv1a(bytes, 0xcbf2_9ce4_8422_2325), fnv1a(bytes, 0x6c62_272e_07bb_0142) | 1);
+        let bit_count = self.bits.len() as u64 * 64;
+        (0..self.hash_count).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % bit_count) as usize)
+    }
+
+    pub fn insert(&mut self, bytes: &[u8]) {
+        for idx in self.bit_indices(bytes).collect::<Vec<_>>() {
+            self.bits[idx / 64] |= 1 << (idx % 64);
+        }
+    }
+
+    /// Returns `false` if no leaf with the given bytes (or their prefix) has
+    /// been inserted.
+    pub fn contains(&self, bytes: &[u8]) -> bool {
+        self.bit_indices(bytes).all(|idx| self.bits[idx / 64] & (1 << (idx % 64)) != 0)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = MAGIC.to_vec();
+        res.extend_from_slice(&self.hash_count.to_le_bytes());
+        res.extend_from_slice(&self.prefix_len.map_or(u64::MAX, |len| len).to_le_bytes());
+        res.extend_from_slice(&(self.bits.len() as u64).to_le_bytes());
+        for word in &self.bits {
+            res.extend_from_slice(&word.to_le_bytes());
+        }
+        res
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(match bytes.len() < MAGIC.len() {
+                true => Error::unexpected_eof(Section::Magic, MAGIC.len() as u64, bytes.len() as u64),
+                false => Error::new(ErrorKind::InvalidMagicNumber),
+            });
+        }
+        bytes = &bytes[MAGIC.len()..];
+        let hash_count = read_u64(&mut bytes)?;
+        if hash_count == 0 || hash_count > MAX_HASH_COUNT {
+            return Err(Error::message(format!("Bloom filter with {} hash functions.", hash_count)));
+        }
+        let prefix_len = Some(read_u64(&mut bytes)?).filter(|len| *len != u64::MAX);
+        let word_count = read_u64(&mut bytes)?;
+        if word_count == 0 {
+            return Err(Error::message("Bloom filter without bits.".to_string()));
+        }
+        if bytes.len() as u64 != word_count.saturating_mul(8) {
+            return Err(match (bytes.len() as u64) < word_count.saturating_mul(8) {
+                true => Error::unexpected_eof(Section::Payload, word_count.saturating_mul(8), bytes.len() as u64),
+                false => Error::new(ErrorKind::AdditionalBytes),
+            });
+        }
+        let bits = bytes.chunks(8).map(|mut word| read_u64(&mut word)).collect::<Result<_, _>>()?;
+        Ok(BloomFilter { bits, hash_count, prefix_len })
+    }
+
+    /// Returns the path of the filter stored alongside the file at `path`.
+    pub fn path_for(path: &Path) -> PathBuf {
+        let mut res = path.as_os_str().to_owned();
+        res.push(".bloom");
+        res.into()
+    }
+
+    /// Stores the filter alongside the file at `path`.
+    pub fn save_for(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(Self::path_for(path), self.to_bytes())
+    }
+
+    /// Loads the filter stored alongside the file at `path`.
+    pub fn load_for(path: &Path) -> Result<Self, Error> {
+        let mut bytes = vec!();
+        std::fs::File::open(Self::path_for(path))?.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+fn collect_leaves<'a>(node: &'a Node, leaves: &mut Vec<&'a [u8]>) {
+    match node {
+        Node::Leaf(bytes) => leaves.push(bytes),
+        Node::Inner(nodes) => nodes.iter().for_each(|n| collect_leaves(n, leaves)),
+    }
+}
+
+
+#[test]
+fn bloom_filter() {
+    let node = Node::Inner((0..1000u32).map(|i| Node::Inner(vec!(Node::Leaf(i.to_le_bytes().to_vec())))).collect());
+    let filter = BloomFilter::from_leaves(&node, 0.01, None);
+    assert!((0..1000u32).all(|i| filter.contains(&i.to_le_bytes())));
+    let false_positives = (1000..11000u32).filter(|i| filter.contains(&i.to_le_bytes())).count();
+    assert!(false_positives < 200, "{} false positives", false_positives);
+
+    assert_eq!(BloomFilter::from_bytes(&filter.to_bytes()).unwrap(), filter);
+    let bytes = filter.to_bytes();
+    assert!(matches!(BloomFilter::from_bytes(&bytes[..bytes.len() - 1]), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+    assert!(matches!(BloomFilter::from_bytes(b"BLOOM2"), Err(e) if e.kind() == ErrorKind::InvalidMagicNumber));
+    let mut long = bytes.clone();
+    long.push(0);
+    assert!(matches!(BloomFilter::from_bytes(&long), Err(e) if e.kind() == ErrorKind::AdditionalBytes));
+    // without hash functions, every leaf would be reported as contained;
+    // with too many, lookups would take forever
+    for hash_count in &[0, MAX_HASH_COUNT + 1, u64::MAX] {
+        let mut invalid = bytes.clone();
+        invalid[MAGIC.len()..MAGIC.len() + 8].copy_from_slice(&hash_count.to_le_bytes());
+        assert!(matches!(BloomFilter::from_bytes(&invalid), Err(e) if e.kind() == ErrorKind::Message));
+    }
+
+    // leaves sharing their first two bytes
+    let filter = BloomFilter::from_leaves(&node, 0.01, Some(2));
+    assert_eq!(filter.prefix_len(), Some(2));
+    assert!(filter.contains(&[0xe7, 0x03, 0x55, 0x55]));
+    assert!(!filter.contains(&[0x55, 0x55]));
+
+    let path = std::env::temp_dir().join(format!("baum_bloom_{}", std::process::id()));
+    filter.save_for(&path).unwrap();
+    assert_eq!(BloomFilter::path_for(&path).file_name().unwrap().to_str().unwrap(), format!("baum_bloom_{}.bloom", std::process::id()));
+    assert_eq!(BloomFilter::load_for(&path).unwrap(), filter);
+    std::fs::remove_file(BloomFilter::path_for(&path)).unwrap();
+}
+
+#[test]
+fn bloom_filter_rates() {
+    for rate in &[1e-300, 0.5, 0.999] {
+        let filter = BloomFilter::new(100, *rate, None);
+        assert!((1..=MAX_HASH_COUNT).contains(&filter.hash_count));
+    }
+    for rate in &[0.0, -0.5, 1.0, 2.0, f64::NAN] {
+        assert!(std::panic::catch_unwind(|| BloomFilter::new(100, *rate, None)).is_err(), "rate {}", rate);
+    }
+}
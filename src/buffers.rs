@@ -0,0 +1,100 @@
+use crate::{Node, ParseResult, DEFAULT_MAX_DEPTH};
+use crate::parser::{parse_tokens, tokenize_into, ParseError, Token};
+
+/// Text format parser that keeps its scratch buffers between calls, so
+/// parsing many small trees doesn't allocate them over and over again.
+pub struct Parser {
+    max_depth: usize,
+    tokens: Vec<Token>,
+    // children of the inner nodes that are currently open
+    stack: Vec<Vec<Node>>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::with_max_depth(DEFAULT_MAX_DEPTH)
+    }
+
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Parser { max_depth, tokens: vec!(), stack: vec!() }
+    }
+
+    /// Parses `s` like `Node::parse_with_max_depth`.
+    pub fn parse(&mut self, s: &str) -> ParseResult {
+        self.tokens.clear();
+        if let Err(e) = tokenize_into(s, &mut self.tokens) {
+            return ParseResult::LexingError(e);
+        }
+        let leaf = |token| match token {
+            Token::Bytes(b) => Ok(Node::Leaf(b)),
+            token => Err(ParseError::Invalid(token.unexpected())),
+        };
+        match parse_tokens(self.tokens.drain(..), self.max_depth, &mut self.stack, leaf, Node::Inner) {
+            Ok(node) => ParseResult::Ok(node),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Binary format serializer that keeps its output buffer between calls.
+#[derive(Default)]
+pub struct Serializer {
+    buf: Vec<u8>,
+}
+
+impl Serializer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the serialized node, including magic number, to `out`.
+    pub fn serialize(&mut self, node: &Node, out: &mut Vec<u8>) {
+        out.extend_from_slice(b"BAUM1");
+        node._serialize(out);
+    }
+
+    /// Serializes `node` into the internal buffer and writes it to `writer`
+    /// with a single `write_all` call.
+    pub fn serialize_into<W: std::io::Write>(&mut self, node: &Node, writer: &mut W) -> std::io::Result<()> {
+        self.buf.clear();
+        let mut buf = std::mem::take(&mut self.buf);
+        self.serialize(node, &mut buf);
+        let res = writer.write_all(&buf);
+        self.buf = buf;
+        res
+    }
+}
+
+
+#[test]
+fn reuse_buffers() {
+    let mut parser = Parser::new();
+    let mut serializer = Serializer::new();
+    let mut out = vec!();
+    let mut written = vec!();
+    for s in &["0x01", "(0x02 (0x) ())", "()"] {
+        let node = match parser.parse(s) {
+            ParseResult::Ok(node) => node,
+            r => panic!("{}", r.err_message()),
+        };
+        assert_eq!(node.to_string(), *s);
+        out.clear();
+        serializer.serialize(&node, &mut out);
+        assert_eq!(out, node.serialize());
+        serializer.serialize_into(&node, &mut written).unwrap();
+    }
+    assert_eq!(written.len(), 15 + 51 + 14);
+
+    assert_eq!(parser.parse("(0x01").err_message(), "Unexpected end of input.");
+    assert_eq!(parser.parse("0x01)").err_message(), "Unexpected characters after node.");
+    assert!(matches!(parser.parse("($a)"), ParseResult::ParsingError(_)));
+    assert!(matches!(parser.parse("(0x01 x)"), ParseResult::LexingError(_)));
+    assert!(matches!(Parser::with_max_depth(1).parse("(())"), ParseResult::DepthLimitExceeded));
+    assert!(parser.parse("(0x01 0x02)").is_ok());
+}
@@ -0,0 +1,106 @@
+use crate::Error;
+
+use std::io::Write;
+
+/// Builds a tree by writing its binary encoding straight to a writer, so
+/// trees larger than memory can be generated without materializing a
+/// `Node`.
+///
+/// The encoding stores the number of children in front of them, so inner
+/// nodes have to announce it. Writing more or fewer children is an error.
+pub struct StreamBuilder<W> {
+    writer: W,
+    /// Number of children still to be written for each open inner node,
+    /// with the outermost entry counting the root.
+    remaining: Vec<u64>,
+}
+
+impl<W: Write> StreamBuilder<W> {
+    /// Creates a builder writing the encoding of a single node (without
+    /// magic number).
+    pub fn new(writer: W) -> Self {
+        StreamBuilder { writer, remaining: vec!(1) }
+    }
+
+    /// Writes a baum document (including the magic number) whose root is
+    /// written by `f`.
+    pub fn document<F>(mut writer: W, f: F) -> Result<W, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>
+    {
+        writer.write_all(b"BAUM1")?;
+        let mut builder = Self::new(writer);
+        f(&mut builder)?;
+        builder.finish()
+    }
+
+    fn start_node(&mut self) -> Result<(), Error> {
+        match self.remaining.last_mut() {
+            Some(0) | None => Err(Error::message("More children than announced.".to_string())),
+            Some(remaining) => {
+                *remaining -= 1;
+                Ok(())
+            }
+        }
+    }
+
+    pub fn leaf(&mut self, bytes: &[u8]) -> Result<&mut Self, Error> {
+        self.start_node()?;
+        self.writer.write_all(&[0])?;
+        self.writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        self.writer.write_all(bytes)?;
+        Ok(self)
+    }
+
+    /// Writes an inner node with `len` children, which have to be written
+    /// by `f`.
+    pub fn inner<F>(&mut self, len: usize, f: F) -> Result<&mut Self, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), Error>
+    {
+        self.start_node()?;
+        self.writer.write_all(&[1])?;
+        self.writer.write_all(&(len as u64).to_le_bytes())?;
+        self.remaining.push(len as u64);
+        f(self)?;
+        match self.remaining.pop() {
+            Some(0) => Ok(self),
+            _ => Err(Error::message("Fewer children than announced.".to_string())),
+        }
+    }
+
+    /// Returns the writer after checking that the root has been written.
+    pub fn finish(self) -> Result<W, Error> {
+        match self.remaining.as_slice() {
+            [0] => Ok(self.writer),
+            _ => Err(Error::message("No node written.".to_string())),
+        }
+    }
+}
+
+
+#[test]
+fn stream_builder() {
+    let node = crate::parse("(0x01 (0x02_03 ()) 0x)");
+    let bytes = StreamBuilder::document(vec!(), |b| {
+        b.inner(3, |b| {
+            b.leaf(&[1])?.inner(2, |b| {
+                b.leaf(&[2, 3])?.inner(0, |_| Ok(()))?;
+                Ok(())
+            })?.leaf(&[])?;
+            Ok(())
+        })?;
+        Ok(())
+    }).unwrap();
+    assert_eq!(bytes, node.serialize());
+
+    let mut builder = StreamBuilder::new(vec!());
+    builder.leaf(b"abc").unwrap();
+    assert_eq!(builder.finish().unwrap(), crate::Node::Leaf(b"abc".to_vec()).serialize()[5..]);
+
+    let err = |res: Result<_, Error>| res.err().unwrap().to_string();
+    assert_eq!(err(StreamBuilder::document(vec!(), |b| b.inner(1, |_| Ok(())).map(|_| ()))), "Fewer children than announced.");
+    assert_eq!(err(StreamBuilder::document(vec!(), |b| b.inner(0, |b| b.leaf(&[]).map(|_| ())).map(|_| ()))), "More children than announced.");
+    assert_eq!(err(StreamBuilder::document(vec!(), |b| b.leaf(&[])?.leaf(&[]).map(|_| ()))), "More children than announced.");
+    assert_eq!(err(StreamBuilder::document(vec!(), |_| Ok(()))), "No node written.");
+}
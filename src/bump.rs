@@ -0,0 +1,119 @@
+use crate::{Error, ErrorKind, Node, Section, DEFAULT_MAX_DEPTH};
+use crate::node_ref::decode_with;
+use crate::parser::{parse_tokens, tokenize, ParseError, Token};
+
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BumpVec;
+
+/// Tree allocated in a `bumpalo::Bump` arena. Nodes don't need to be dropped
+/// individually; their memory is released together with the arena.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum BumpNode<'a> {
+    Leaf(&'a [u8]),
+    Inner(&'a [BumpNode<'a>]),
+}
+
+impl<'a> BumpNode<'a> {
+    pub fn leaf(&self) -> Option<&'a [u8]> {
+        match self {
+            BumpNode::Leaf(bytes) => Some(bytes),
+            BumpNode::Inner(_) => None,
+        }
+    }
+
+    /// Returns the children of an inner node, or an empty slice for leaves.
+    pub fn children(&self) -> &'a [BumpNode<'a>] {
+        match self {
+            BumpNode::Leaf(_) => &[],
+            BumpNode::Inner(nodes) => nodes,
+        }
+    }
+
+    /// Copies `node` into `bump`.
+    pub fn from_node_in(node: &Node, bump: &'a Bump) -> Self {
+        match node {
+            Node::Leaf(bytes) => BumpNode::Leaf(bump.alloc_slice_copy(bytes)),
+            Node::Inner(nodes) => {
+                let mut children = BumpVec::with_capacity_in(nodes.len(), bump);
+                children.extend(nodes.iter().map(|n| BumpNode::from_node_in(n, bump)));
+                BumpNode::Inner(children.into_bump_slice())
+            }
+        }
+    }
+
+    pub fn to_node(&self) -> Node {
+        match self {
+            BumpNode::Leaf(bytes) => Node::Leaf(bytes.to_vec()),
+            BumpNode::Inner(nodes) => Node::Inner(nodes.iter().map(BumpNode::to_node).collect()),
+        }
+    }
+
+    /// Deserializes a tree like `Node::deserialize`, allocating the nodes and
+    /// leaves in `bump`.
+    pub fn deserialize_in(bytes: &[u8], bump: &'a Bump) -> Result<Self, Error> {
+        if !bytes.starts_with(b"BAUM1") {
+            return Err(match bytes.len() < 5 {
+                true => Error::unexpected_eof(Section::Magic, 5, bytes.len() as u64),
+                false => Error::new(ErrorKind::InvalidMagicNumber),
+            });
+        }
+        let mut pos = 5;
+        let res = decode_with(
+            bytes,
+            &mut pos,
+            |leaf| BumpNode::Leaf(bump.alloc_slice_copy(leaf)),
+            |children| BumpNode::Inner(bump.alloc_slice_copy(&children)),
+        )?;
+        match pos == bytes.len() {
+            true => Ok(res),
+            false => Err(Error::new(ErrorKind::AdditionalBytes)),
+        }
+    }
+
+    /// Parses the text format like `Node::parse`, allocating the nodes and
+    /// leaves in `bump`. Returns the error message on failure.
+    pub fn parse_in(s: &str, bump: &'a Bump) -> Result<Self, String> {
+        let leaf = |token| match token {
+            Token::Bytes(b) => Ok(BumpNode::Leaf(bump.alloc_slice_copy(&b))),
+            token => Err(ParseError::Invalid(token.unexpected())),
+        };
+        let inner = |children: Vec<_>| BumpNode::Inner(bump.alloc_slice_copy(&children));
+        Ok(parse_tokens(tokenize(s)?, DEFAULT_MAX_DEPTH, &mut vec!(), leaf, inner)?)
+    }
+}
+
+#[test]
+fn bump_node() {
+    let bump = Bump::new();
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1, 2)),
+        Node::Inner(vec!(Node::Leaf(vec!()), Node::Inner(vec!()))),
+    ));
+    let bump_node = BumpNode::deserialize_in(&node.serialize(), &bump).unwrap();
+    assert_eq!(bump_node, BumpNode::from_node_in(&node, &bump));
+    assert_eq!(bump_node.to_node(), node);
+    assert_eq!(bump_node.children()[0].leaf(), Some(&[1, 2][..]));
+    assert_eq!(BumpNode::parse_in("(0x0102 (0x ()))", &bump), Ok(bump_node));
+
+    assert!(matches!(BumpNode::deserialize_in(b"BAUM1\x02", &bump), Err(e) if e.kind() == ErrorKind::InvalidNodeType));
+    assert!(matches!(BumpNode::deserialize_in(b"BAUM1\x01\xff\xff\xff\xff\xff\xff\xff\xff", &bump), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+    assert_eq!(BumpNode::parse_in("(0x01", &bump), Err("Unexpected end of input.".to_string()));
+    assert_eq!(BumpNode::parse_in("($a)", &bump), Err("Unexpected placeholder '$a'.".to_string()));
+
+    // nesting is limited when parsing and doesn't exhaust the stack when
+    // decoding
+    let s = format!("{}{}", "(".repeat(DEFAULT_MAX_DEPTH + 1), ")".repeat(DEFAULT_MAX_DEPTH + 1));
+    assert_eq!(BumpNode::parse_in(&s, &bump), Err("Maximum nesting depth exceeded.".to_string()));
+    let mut deep = b"BAUM1".to_vec();
+    for _ in 0..200_000 {
+        deep.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+    deep.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let mut node = BumpNode::deserialize_in(&deep, &bump).unwrap();
+    for _ in 0..200_000 {
+        node = node.children()[0];
+    }
+    assert_eq!(node, BumpNode::Leaf(&[]));
+    let err = BumpNode::deserialize_in(&deep[..deep.len() - 1], &bump).unwrap_err();
+    assert_eq!((err.kind(), err.path().map(Vec::len)), (ErrorKind::UnexpectedEof, Some(200_000)));
+}
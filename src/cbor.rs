@@ -0,0 +1,198 @@
+//! Conversion between `Node`s and CBOR (RFC 8949).
+//!
+//! Inner nodes map to arrays, leaves to byte strings. Encoding always uses
+//! definite lengths in their shortest form; decoding additionally accepts
+//! indefinite-length arrays and byte strings. Other data items (integers,
+//! text strings, maps, tags, ...) have no equivalent in `Node` and are
+//! rejected.
+
+use crate::Node;
+use crate::wire;
+
+use std::convert::TryInto;
+
+pub use crate::wire::Error;
+
+const BYTES: u8 = 2;
+const ARRAY: u8 = 4;
+const BREAK: u8 = 0xff;
+
+pub fn encode(node: &Node) -> Vec<u8> {
+    let mut res = vec!();
+    encode_into(node, &mut res);
+    res
+}
+
+fn encode_into(node: &Node, w: &mut Vec<u8>) {
+    match node {
+        Node::Leaf(bytes) => {
+            write_head(BYTES, bytes.len() as u64, w);
+            w.extend_from_slice(bytes);
+        }
+        Node::Inner(nodes) => {
+            write_head(ARRAY, nodes.len() as u64, w);
+            for node in nodes {
+                encode_into(node, w);
+            }
+        }
+    }
+}
+
+fn write_head(major: u8, arg: u64, w: &mut Vec<u8>) {
+    let major = major << 5;
+    if arg < 24 {
+        w.push(major | arg as u8);
+    } else if arg <= u64::from(u8::MAX) {
+        w.push(major | 24);
+        w.push(arg as u8);
+    } else if arg <= u64::from(u16::MAX) {
+        w.push(major | 25);
+        w.extend_from_slice(&(arg as u16).to_be_bytes());
+    } else if arg <= u64::from(u32::MAX) {
+        w.push(major | 26);
+        w.extend_from_slice(&(arg as u32).to_be_bytes());
+    } else {
+        w.push(major | 27);
+        w.extend_from_slice(&arg.to_be_bytes());
+    }
+}
+
+/// Reads the head of the data item at `pos`. Returns the major type and the
+/// argument, which is `None` for indefinite lengths.
+fn read_head(bytes: &[u8], pos: &mut usize) -> Result<(u8, Option<u64>), Error> {
+    let start = *pos;
+    let initial = *bytes.get(start).ok_or(Error::UnexpectedEnd)?;
+    *pos += 1;
+    let size = match initial & 0x1f {
+        n @ 0..=23 => return Ok((initial >> 5, Some(u64::from(n)))),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        31 => return Ok((initial >> 5, None)),
+        _ => return Err(Error::UnexpectedByte(initial, start)),
+    };
+    let arg = bytes.get(*pos..*pos + size).ok_or(Error::UnexpectedEnd)?;
+    *pos += size;
+    Ok((initial >> 5, Some(arg.iter().fold(0, |acc, b| (acc << 8) | u64::from(*b)))))
+}
+
+fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize, len: u64, start: usize) -> Result<&'a [u8], Error> {
+    let len: usize = len.try_into().map_err(|_| Error::InvalidLength(start))?;
+    wire::read_bytes(bytes, pos, len, start)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Node, Error> {
+    // children of the arrays that are currently open and their announced
+    // length (`None` for indefinite-length arrays)
+    let mut stack: Vec<(Vec<Node>, Option<u64>)> = vec!();
+    let mut pos = 0;
+
+    loop {
+        let start = pos;
+        let mut node = if bytes.get(pos) == Some(&BREAK) {
+            pos += 1;
+            match stack.last() {
+                Some((_, None)) => Node::Inner(stack.pop().unwrap().0),
+                _ => return Err(Error::UnexpectedByte(BREAK, start)),
+            }
+        } else {
+            match read_head(bytes, &mut pos)? {
+                (BYTES, Some(len)) => Node::Leaf(read_bytes(bytes, &mut pos, len, start)?.to_vec()),
+                (BYTES, None) => {
+                    // concatenation of definite-length chunks
+                    let mut data = vec!();
+                    while bytes.get(pos) != Some(&BREAK) {
+                        let chunk_start = pos;
+                        match read_head(bytes, &mut pos)? {
+                            (BYTES, Some(len)) => data.extend_from_slice(read_bytes(bytes, &mut pos, len, chunk_start)?),
+                            _ => return Err(Error::UnexpectedByte(bytes[chunk_start], chunk_start)),
+                        }
+                    }
+                    pos += 1;
+                    Node::Leaf(data)
+                }
+                (ARRAY, Some(0)) => Node::Inner(vec!()),
+                (ARRAY, len) => {
+                    // don't trust the announced length for the allocation
+                    let capacity = len.unwrap_or(0).min((bytes.len() - pos) as u64) as usize;
+                    stack.push((Vec::with_capacity(capacity), len));
+                    continue;
+                }
+                _ => return Err(Error::UnexpectedByte(bytes[start], start)),
+            }
+        };
+
+        // add the node to its parent, closing all arrays that are complete
+        loop {
+            match stack.last_mut() {
+                Some((children, len)) => {
+                    children.push(node);
+                    if *len != Some(children.len() as u64) {
+                        break;
+                    }
+                    node = Node::Inner(stack.pop().unwrap().0);
+                }
+                None => {
+                    if pos != bytes.len() {
+                        return Err(Error::AdditionalBytes);
+                    }
+                    return Ok(node);
+                }
+            }
+        }
+    }
+}
+
+impl Node {
+    pub fn to_cbor(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn from_cbor(bytes: &[u8]) -> Result<Node, Error> {
+        decode(bytes)
+    }
+}
+
+
+#[test]
+fn cbor_roundtrip() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"foo".to_vec()),
+        Node::Inner(vec!(
+            Node::Leaf(vec!()),
+            Node::Inner(vec!()),
+        )),
+        Node::Leaf(vec!(7; 300)),
+    ));
+    let mut exp = vec!(0x83, 0x43, b'f', b'o', b'o', 0x82, 0x40, 0x80, 0x59, 0x01, 0x2c);
+    exp.extend_from_slice(&[7; 300]);
+    assert_eq!(node.to_cbor(), exp);
+    assert_eq!(Node::from_cbor(&exp), Ok(node));
+
+    // indefinite lengths and non-shortest heads
+    let indefinite = [0x9f, 0x5f, 0x41, 1, 0x42, 2, 3, 0xff, 0x9f, 0xff, 0x58, 0x01, 4, 0xff];
+    assert_eq!(Node::from_cbor(&indefinite), Ok(Node::Inner(vec!(
+        Node::Leaf(vec!(1, 2, 3)),
+        Node::Inner(vec!()),
+        Node::Leaf(vec!(4)),
+    ))));
+}
+
+#[test]
+fn cbor_errors() {
+    assert_eq!(Node::from_cbor(b""), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_cbor(&[0x82, 0x40]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_cbor(&[0x43, 1]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_cbor(&[0x59, 1]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_cbor(&[0x40, 0x40]), Err(Error::AdditionalBytes));
+    // unsigned integer, text string, map
+    assert_eq!(Node::from_cbor(&[0x81, 0x01]), Err(Error::UnexpectedByte(0x01, 1)));
+    assert_eq!(Node::from_cbor(&[0x61, b'a']), Err(Error::UnexpectedByte(0x61, 0)));
+    assert_eq!(Node::from_cbor(&[0xa0]), Err(Error::UnexpectedByte(0xa0, 0)));
+    // break outside of indefinite-length item, reserved additional info
+    assert_eq!(Node::from_cbor(&[0x81, 0xff]), Err(Error::UnexpectedByte(0xff, 1)));
+    assert_eq!(Node::from_cbor(&[0x5c]), Err(Error::UnexpectedByte(0x5c, 0)));
+    // chunks of indefinite-length byte strings must be definite byte strings
+    assert_eq!(Node::from_cbor(&[0x5f, 0x80, 0xff]), Err(Error::UnexpectedByte(0x80, 1)));
+}
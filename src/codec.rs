@@ -0,0 +1,178 @@
+use crate::{FromNodeError, Node};
+use crate::convert::leaf;
+use crate::varint;
+
+use std::convert::TryInto;
+
+/// Encoding of values of type `T` as leaf bytes. Errors are reported like
+/// those of `FromBaum`, relative to the decoded leaf.
+pub trait LeafCodec<T> {
+    fn encode(&self, value: &T) -> Vec<u8>;
+    fn decode(&self, bytes: &[u8]) -> Result<T, FromNodeError>;
+}
+
+/// Numbers as little-endian bytes of the type's width.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct LittleEndian;
+
+/// Numbers as big-endian bytes of the type's width.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct BigEndian;
+
+/// Strings as their UTF-8 encoding.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Utf8;
+
+/// Bools as a single byte, `0` or `1`.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Bool;
+
+/// Unsigned integers as LEB128 varints, taking one byte per 7 bits of the
+/// value.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Varint;
+
+/// Signed integers as zigzag-encoded LEB128 varints, so that values close
+/// to zero take few bytes regardless of their sign.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct ZigZag;
+
+pub(crate) fn fixed<const N: usize>(bytes: &[u8]) -> Result<[u8; N], FromNodeError> {
+    bytes.try_into().map_err(|_| FromNodeError::LeafLength { path: vec!(), expected: N, actual: bytes.len() })
+}
+
+macro_rules! num_codecs {
+    ($($ty:ty)*) => {
+        $(
+            impl LeafCodec<$ty> for LittleEndian {
+                fn encode(&self, value: &$ty) -> Vec<u8> {
+                    value.to_le_bytes().to_vec()
+                }
+
+                fn decode(&self, bytes: &[u8]) -> Result<$ty, FromNodeError> {
+                    fixed(bytes).map(<$ty>::from_le_bytes)
+                }
+            }
+
+            impl LeafCodec<$ty> for BigEndian {
+                fn encode(&self, value: &$ty) -> Vec<u8> {
+                    value.to_be_bytes().to_vec()
+                }
+
+                fn decode(&self, bytes: &[u8]) -> Result<$ty, FromNodeError> {
+                    fixed(bytes).map(<$ty>::from_be_bytes)
+                }
+            }
+        )*
+    };
+}
+
+num_codecs!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 f32 f64);
+
+impl LeafCodec<String> for Utf8 {
+    fn encode(&self, value: &String) -> Vec<u8> {
+        value.as_bytes().to_vec()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<String, FromNodeError> {
+        String::from_utf8(bytes.to_vec()).map_err(|_| FromNodeError::InvalidValue(vec!()))
+    }
+}
+
+impl LeafCodec<bool> for Bool {
+    fn encode(&self, value: &bool) -> Vec<u8> {
+        vec!(*value as u8)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<bool, FromNodeError> {
+        match fixed(bytes)? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(FromNodeError::InvalidValue(vec!())),
+        }
+    }
+}
+
+/// Leaves have to consist of exactly one varint.
+impl LeafCodec<u64> for Varint {
+    fn encode(&self, value: &u64) -> Vec<u8> {
+        let mut res = vec!();
+        varint::encode(*value, &mut res);
+        res
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<u64, FromNodeError> {
+        let mut pos = 0;
+        match varint::decode(bytes, &mut pos) {
+            Some(n) if pos == bytes.len() => Ok(n),
+            _ => Err(FromNodeError::InvalidValue(vec!())),
+        }
+    }
+}
+
+impl LeafCodec<i64> for ZigZag {
+    fn encode(&self, value: &i64) -> Vec<u8> {
+        Varint.encode(&(((value << 1) ^ (value >> 63)) as u64))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<i64, FromNodeError> {
+        let n = Varint.decode(bytes)?;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+}
+
+impl Node {
+    pub fn leaf_with<T, C: LeafCodec<T>>(codec: &C, value: &T) -> Node {
+        Node::Leaf(codec.encode(value))
+    }
+
+    /// Decodes the leaf's bytes using `codec`. Fails for inner nodes.
+    pub fn decode_leaf<T, C: LeafCodec<T>>(&self, codec: &C) -> Result<T, FromNodeError> {
+        codec.decode(leaf(self)?)
+    }
+}
+
+
+#[test]
+fn leaf_codecs() {
+    assert_eq!(Node::leaf_with(&LittleEndian, &0x0102u16), Node::Leaf(vec!(2, 1)));
+    assert_eq!(Node::leaf_with(&BigEndian, &0x0102u16), Node::Leaf(vec!(1, 2)));
+    assert_eq!(Node::leaf_with(&BigEndian, &-1i32).decode_leaf(&BigEndian), Ok(-1i32));
+    assert_eq!(Node::leaf_with(&LittleEndian, &0.5f64).decode_leaf(&LittleEndian), Ok(0.5f64));
+    assert_eq!(Node::leaf_with(&Utf8, &"äb".to_string()).decode_leaf(&Utf8), Ok("äb".to_string()));
+    assert_eq!(Node::leaf_with(&Bool, &true), Node::Leaf(vec!(1)));
+
+    assert_eq!(Node::Leaf(vec!(0; 2)).decode_leaf::<u32, _>(&LittleEndian),
+        Err(FromNodeError::LeafLength { path: vec!(), expected: 4, actual: 2 }));
+    assert_eq!(Node::Leaf(vec!(2)).decode_leaf(&Bool), Err(FromNodeError::InvalidValue(vec!())));
+    assert_eq!(Node::Inner(vec!()).decode_leaf(&Utf8), Err(FromNodeError::ExpectedLeaf(vec!())));
+
+    // application-defined codec
+    struct Decimal;
+    impl LeafCodec<u32> for Decimal {
+        fn encode(&self, value: &u32) -> Vec<u8> {
+            value.to_string().into_bytes()
+        }
+
+        fn decode(&self, bytes: &[u8]) -> Result<u32, FromNodeError> {
+            std::str::from_utf8(bytes).ok().and_then(|s| s.parse().ok()).ok_or(FromNodeError::InvalidValue(vec!()))
+        }
+    }
+    assert_eq!(Node::leaf_with(&Decimal, &42), Node::Leaf(b"42".to_vec()));
+    assert_eq!(Node::Leaf(b"17".to_vec()).decode_leaf(&Decimal), Ok(17));
+}
+
+#[test]
+fn varint_codecs() {
+    assert_eq!(Node::leaf_with(&Varint, &300), Node::Leaf(vec!(0xac, 0x02)));
+    assert_eq!(Node::leaf_with(&Varint, &u64::MAX).decode_leaf(&Varint), Ok(u64::MAX));
+    for (n, encoded) in [(0, 0), (-1, 1), (1, 2), (-2, 3), (63, 126), (-64, 127)] {
+        assert_eq!(Node::leaf_with(&ZigZag, &n), Node::Leaf(vec!(encoded)));
+    }
+    for n in [i64::MIN, -300, 300, i64::MAX] {
+        assert_eq!(Node::leaf_with(&ZigZag, &n).decode_leaf(&ZigZag), Ok(n));
+    }
+    assert_eq!(Node::Leaf(vec!(0x80)).decode_leaf(&Varint), Err(FromNodeError::InvalidValue(vec!())));
+    assert_eq!(Node::Leaf(vec!(0x01, 0x02)).decode_leaf(&Varint), Err(FromNodeError::InvalidValue(vec!())));
+    assert_eq!(Node::Leaf(vec!()).decode_leaf(&ZigZag), Err(FromNodeError::InvalidValue(vec!())));
+}
@@ -0,0 +1,247 @@
+use crate::schema::{Field, LeafSchema, Schema};
+
+use std::fmt::Write;
+
+impl Schema {
+    /// Generates Rust code for typed accessors of nodes matching the schema,
+    /// e.g. for use in a build script:
+    ///
+    /// ```ignore
+    /// let code = schema.generate_rust("Message").unwrap();
+    /// std::fs::write(out_dir.join("message.rs"), code).unwrap();
+    /// ```
+    ///
+    /// The code contains a wrapper struct named `name` that's created from a
+    /// `&Node` using `Message::new(&node)`, which validates the node. Inner
+    /// nodes with fields get one method per field (named after the field or
+    /// `field_<idx>` for unnamed fields), lists get `len`, `get` and `iter`.
+    /// Leaves are returned as `&[u8]`, `&[u8; N]` (fixed length) or `&str`
+    /// (UTF-8), nested inner nodes as further wrapper structs named after the
+    /// path of fields leading to them (e.g. `MessageHeader`), other nodes as
+    /// `&Node`.
+    ///
+    /// Returns an error if `name` or a field name isn't a valid identifier.
+    pub fn generate_rust(&self, name: &str) -> Result<String, String> {
+        let mut s = String::new();
+        s.push_str("// Generated by `baum::Schema::generate_rust`. Do not edit.\n");
+        generate_struct(self, name, &mut s)?;
+        Ok(s)
+    }
+
+    /// Returns a Rust expression constructing the schema.
+    fn to_rust(&self) -> String {
+        match self {
+            Schema::Any => "::baum::Schema::Any".to_string(),
+            Schema::Leaf(LeafSchema { min_len, max_len, utf8 }) => format!(
+                "::baum::Schema::Leaf(::baum::schema::LeafSchema {{ min_len: {}, max_len: {:?}, utf8: {} }})",
+                min_len, max_len, utf8
+            ),
+            Schema::Inner(fields) => {
+                let fields: Vec<_> = fields.iter().map(|f| format!(
+                    "::baum::schema::Field {{ name: {}, schema: {} }}",
+                    match &f.name {
+                        Some(name) => format!("Some({:?}.to_string())", name),
+                        None => "None".to_string(),
+                    },
+                    f.schema.to_rust()
+                )).collect();
+                format!("::baum::Schema::Inner(vec![{}])", fields.join(", "))
+            }
+            Schema::List { item, min, max } => format!(
+                "::baum::Schema::List {{ item: Box::new({}), min: {}, max: {:?} }}",
+                item.to_rust(), min, max
+            ),
+            Schema::OneOf(schemas) => {
+                let schemas: Vec<_> = schemas.iter().map(Schema::to_rust).collect();
+                format!("::baum::Schema::OneOf(vec![{}])", schemas.join(", "))
+            }
+        }
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+fn camel_case(s: &str) -> String {
+    s.split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| part[..1].to_ascii_uppercase() + &part[1..])
+        .collect()
+}
+
+/// How an accessor converts a child node into its return type.
+enum Conversion {
+    /// Returns the `&Node` itself.
+    Node,
+    /// Wraps the node into the given struct.
+    Wrap(String),
+    Bytes,
+    FixedBytes,
+    Str,
+}
+
+impl Conversion {
+    fn new(schema: &Schema, name: &str) -> Conversion {
+        match schema {
+            Schema::Leaf(LeafSchema { utf8: true, .. }) => Conversion::Str,
+            Schema::Leaf(LeafSchema { min_len, max_len: Some(max_len), .. }) if min_len == max_len => Conversion::FixedBytes,
+            Schema::Leaf(_) => Conversion::Bytes,
+            Schema::Inner(_) | Schema::List { .. } => Conversion::Wrap(name.to_string()),
+            Schema::Any | Schema::OneOf(_) => Conversion::Node,
+        }
+    }
+
+    fn return_type(&self, schema: &Schema) -> String {
+        match (self, schema) {
+            (Conversion::Node, _) => "&'a ::baum::Node".to_string(),
+            (Conversion::Wrap(name), _) => format!("{}<'a>", name),
+            (Conversion::Bytes, _) => "&'a [u8]".to_string(),
+            (Conversion::FixedBytes, Schema::Leaf(LeafSchema { min_len, .. })) => format!("&'a [u8; {}]", min_len),
+            (Conversion::FixedBytes, _) => unreachable!(),
+            (Conversion::Str, _) => "&'a str".to_string(),
+        }
+    }
+
+    fn uses_bytes(&self) -> bool {
+        matches!(self, Conversion::Bytes | Conversion::FixedBytes | Conversion::Str)
+    }
+
+    /// Returns an expression converting `node`.
+    fn apply(&self, node: &str) -> String {
+        match self {
+            Conversion::Node => node.to_string(),
+            Conversion::Wrap(name) => format!("{}({})", name, node),
+            Conversion::Bytes => format!("Self::bytes({})", node),
+            Conversion::FixedBytes => format!("::std::convert::TryFrom::try_from(Self::bytes({})).unwrap()", node),
+            Conversion::Str => format!("::std::str::from_utf8(Self::bytes({})).unwrap()", node),
+        }
+    }
+
+    /// Returns the adapter converting an iterator/option of nodes.
+    fn map(&self) -> String {
+        match self {
+            Conversion::Node => String::new(),
+            Conversion::Wrap(name) => format!(".map({})", name),
+            Conversion::Bytes => ".map(Self::bytes)".to_string(),
+            _ => format!(".map(|node| {})", self.apply("node")),
+        }
+    }
+}
+
+fn generate_struct(schema: &Schema, name: &str, s: &mut String) -> Result<(), String> {
+    if !is_identifier(name) {
+        return Err(format!("Invalid identifier `{}`.", name));
+    }
+    // nested structs, generated after this one
+    let mut nested: Vec<(&Schema, String)> = vec!();
+    let mut uses_bytes = false;
+
+    write!(s, "
+#[derive(Clone, Copy, Debug)]
+pub struct {name}<'a>(&'a ::baum::Node);
+
+impl<'a> {name}<'a> {{
+    pub fn schema() -> ::baum::Schema {{
+        {schema}
+    }}
+
+    /// Validates `node` against the schema and wraps it.
+    pub fn new(node: &'a ::baum::Node) -> Result<Self, Vec<::baum::schema::Violation>> {{
+        Self::schema().validate(node)?;
+        Ok({name}(node))
+    }}
+
+    pub fn node(&self) -> &'a ::baum::Node {{
+        self.0
+    }}
+", name = name, schema = schema.to_rust()).unwrap();
+
+    match schema {
+        Schema::Inner(fields) => {
+            for (idx, Field { name: field_name, schema }) in fields.iter().enumerate() {
+                let method = match field_name {
+                    Some(field_name) if is_identifier(field_name) => field_name.clone(),
+                    Some(field_name) => return Err(format!("Invalid identifier `{}`.", field_name)),
+                    None => format!("field_{}", idx),
+                };
+                let child_name = format!("{}{}", name, camel_case(&method));
+                let conversion = Conversion::new(schema, &child_name);
+                uses_bytes |= conversion.uses_bytes();
+                write!(s, "
+    pub fn {method}(&self) -> {ty} {{
+        {expr}
+    }}
+", method = method, ty = conversion.return_type(schema), expr = conversion.apply(&format!("&self.children()[{}]", idx))).unwrap();
+                if let Conversion::Wrap(_) = conversion {
+                    nested.push((schema, child_name));
+                }
+            }
+        }
+        Schema::List { item, .. } => {
+            let item_name = format!("{}Item", name);
+            let conversion = Conversion::new(item, &item_name);
+            uses_bytes |= conversion.uses_bytes();
+            write!(s, "
+    pub fn len(&self) -> usize {{
+        self.children().len()
+    }}
+
+    pub fn is_empty(&self) -> bool {{
+        self.children().is_empty()
+    }}
+
+    pub fn get(&self, idx: usize) -> Option<{ty}> {{
+        self.children().get(idx){map}
+    }}
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = {ty}> {{
+        self.children().iter(){map}
+    }}
+", ty = conversion.return_type(item), map = conversion.map()).unwrap();
+            if let Conversion::Wrap(_) = conversion {
+                nested.push((item, item_name));
+            }
+        }
+        _ => (),
+    }
+
+    if let Schema::Inner(_) | Schema::List { .. } = schema {
+        s.push_str("
+    fn children(&self) -> &'a [::baum::Node] {
+        match self.0 {
+            ::baum::Node::Inner(nodes) => nodes,
+            ::baum::Node::Leaf(_) => unreachable!(),
+        }
+    }
+");
+    }
+    if uses_bytes {
+        s.push_str("
+    fn bytes(node: &'a ::baum::Node) -> &'a [u8] {
+        match node {
+            ::baum::Node::Leaf(bytes) => bytes,
+            ::baum::Node::Inner(_) => unreachable!(),
+        }
+    }
+");
+    }
+    s.push_str("}\n");
+
+    for (schema, name) in nested {
+        generate_struct(schema, &name, s)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn generate_rust_errors() {
+    assert_eq!(Schema::Any.generate_rust("1abc"), Err("Invalid identifier `1abc`.".to_string()));
+    let schema = Schema::record(vec!(("a-b", Schema::leaf())));
+    assert_eq!(schema.generate_rust("Abc"), Err("Invalid identifier `a-b`.".to_string()));
+    assert_eq!(camel_case("sequence_number"), "SequenceNumber");
+}
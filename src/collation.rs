@@ -0,0 +1,96 @@
+use crate::Node;
+
+use std::cmp::Ordering;
+
+/// Ordering of nodes used by `Node::cmp_with` and the sorting helpers, for
+/// applications whose semantics differ from the derived `Ord`.
+///
+/// Leaves always precede inner nodes, like with the derived `Ord`. Inner
+/// nodes that `cmp_inner` considers equal are compared child by child.
+pub trait Collation {
+    fn cmp_leaves(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.cmp(b)
+    }
+
+    /// Compares inner nodes before comparing their children.
+    fn cmp_inner(&self, _a: &[Node], _b: &[Node]) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+/// Lexicographic order, i.e. the derived `Ord`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Lexicographic;
+
+impl Collation for Lexicographic { }
+
+/// Shorter leaves and inner nodes with fewer children come first, nodes of
+/// equal length are ordered lexicographically.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Shortlex;
+
+impl Collation for Shortlex {
+    fn cmp_leaves(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    }
+
+    fn cmp_inner(&self, a: &[Node], b: &[Node]) -> Ordering {
+        a.len().cmp(&b.len())
+    }
+}
+
+impl Node {
+    pub fn cmp_with(&self, other: &Node, collation: &dyn Collation) -> Ordering {
+        match (self, other) {
+            (Node::Leaf(a), Node::Leaf(b)) => collation.cmp_leaves(a, b),
+            (Node::Leaf(_), Node::Inner(_)) => Ordering::Less,
+            (Node::Inner(_), Node::Leaf(_)) => Ordering::Greater,
+            (Node::Inner(a), Node::Inner(b)) => collation.cmp_inner(a, b).then_with(|| {
+                a.iter().zip(b).map(|(a, b)| a.cmp_with(b, collation)).find(|o| o.is_ne()).unwrap_or_else(|| a.len().cmp(&b.len()))
+            }),
+        }
+    }
+
+    /// Sorts the children of an inner node (stable). Leaves are left as is.
+    pub fn sort_children_with(&mut self, collation: &dyn Collation) {
+        if let Node::Inner(nodes) = self {
+            nodes.sort_by(|a, b| a.cmp_with(b, collation));
+        }
+    }
+
+    /// Sorts the children of all inner nodes in the tree, innermost first,
+    /// e.g. to bring trees whose child order doesn't matter into a canonical
+    /// form.
+    pub fn sort_recursive_with(&mut self, collation: &dyn Collation) {
+        if let Node::Inner(nodes) = self {
+            for node in nodes.iter_mut() {
+                node.sort_recursive_with(collation);
+            }
+        }
+        self.sort_children_with(collation);
+    }
+}
+
+
+#[test]
+fn collation() {
+    let mut nodes = vec!(crate::parse("(0x01 0x02)"), crate::parse("0x02"), crate::parse("0x01_00"), crate::parse("(0x03)"), crate::parse("((0x01) 0x01)"));
+    for i in 0..nodes.len() {
+        for j in 0..nodes.len() {
+            assert_eq!(nodes[i].cmp_with(&nodes[j], &Lexicographic), nodes[i].cmp(&nodes[j]));
+        }
+    }
+
+    nodes.sort_by(|a, b| a.cmp_with(b, &Shortlex));
+    let expected = vec!(crate::parse("0x02"), crate::parse("0x01_00"), crate::parse("(0x03)"), crate::parse("(0x01 0x02)"), crate::parse("((0x01) 0x01)"));
+    assert_eq!(nodes, expected);
+
+    let mut node = crate::parse("((0x03 0x01_00 0x02) 0x04_05 ())");
+    node.sort_children_with(&Shortlex);
+    assert_eq!(node, crate::parse("(0x04_05 () (0x03 0x01_00 0x02))"));
+    node.sort_recursive_with(&Shortlex);
+    assert_eq!(node, crate::parse("(0x04_05 () (0x02 0x03 0x01_00))"));
+    let mut leaf = crate::parse("0x02_01");
+    leaf.sort_recursive_with(&Shortlex);
+    assert_eq!(leaf, crate::parse("0x02_01"));
+}
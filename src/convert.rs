@@ -0,0 +1,268 @@
+//! Typed conversion between Rust values and nodes, see `ToBaum` and
+//! `FromBaum`. Both traits can be derived for structs and enums (with the
+//! `derive` feature): fields map to the children of an inner node in
+//! declaration order; enum variants are preceded by the variant's tag.
+
+use crate::{Node, Path};
+
+/// Conversion of a value into a node.
+pub trait ToBaum {
+    fn to_node(&self) -> Node;
+}
+
+/// Conversion of a node into a value.
+pub trait FromBaum: Sized {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError>;
+}
+
+/// Error when converting a node into a value. Paths are relative to the node
+/// passed to `FromBaum::from_node`.
+#[derive(Debug, PartialEq)]
+pub enum FromNodeError {
+    ExpectedLeaf(Path),
+    ExpectedInner(Path),
+    /// A leaf with an unexpected number of bytes.
+    LeafLength { path: Path, expected: usize, actual: usize },
+    /// An inner node with an unexpected number of children.
+    ChildCount { path: Path, expected: usize, actual: usize },
+    /// A leaf whose content isn't valid for the expected type, e.g. a bool
+    /// that's neither `0` nor `1`.
+    InvalidValue(Path),
+    /// An enum encoded as an inner node without children.
+    MissingTag(Path),
+    /// An enum tag that doesn't belong to any variant.
+    UnknownVariant { path: Path, tag: u32 },
+}
+
+impl FromNodeError {
+    pub fn path(&self) -> &Path {
+        match self {
+            FromNodeError::ExpectedLeaf(p) => p,
+            FromNodeError::ExpectedInner(p) => p,
+            FromNodeError::LeafLength { path, .. } => path,
+            FromNodeError::ChildCount { path, .. } => path,
+            FromNodeError::InvalidValue(p) => p,
+            FromNodeError::MissingTag(p) => p,
+            FromNodeError::UnknownVariant { path, .. } => path,
+        }
+    }
+
+    fn path_mut(&mut self) -> &mut Path {
+        match self {
+            FromNodeError::ExpectedLeaf(p) => p,
+            FromNodeError::ExpectedInner(p) => p,
+            FromNodeError::LeafLength { path, .. } => path,
+            FromNodeError::ChildCount { path, .. } => path,
+            FromNodeError::InvalidValue(p) => p,
+            FromNodeError::MissingTag(p) => p,
+            FromNodeError::UnknownVariant { path, .. } => path,
+        }
+    }
+
+    /// Makes the error relative to the parent of the node it occurred in,
+    /// given the node's index `idx` within its parent.
+    fn in_child(mut self, idx: usize) -> Self {
+        self.path_mut().insert(0, idx);
+        self
+    }
+}
+
+impl std::fmt::Display for FromNodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FromNodeError::ExpectedLeaf(p) => write!(f, "Expected leaf at path {:?}.", p),
+            FromNodeError::ExpectedInner(p) => write!(f, "Expected inner node at path {:?}.", p),
+            FromNodeError::LeafLength { path, expected, actual } =>
+                write!(f, "Expected leaf of {} bytes at path {:?}, found {} bytes.", expected, path, actual),
+            FromNodeError::ChildCount { path, expected, actual } =>
+                write!(f, "Expected {} children at path {:?}, found {}.", expected, path, actual),
+            FromNodeError::InvalidValue(p) => write!(f, "Invalid value at path {:?}.", p),
+            FromNodeError::MissingTag(p) => write!(f, "Missing variant tag at path {:?}.", p),
+            FromNodeError::UnknownVariant { path, tag } => write!(f, "Unknown variant tag {} at path {:?}.", tag, path),
+        }
+    }
+}
+
+impl std::error::Error for FromNodeError { }
+
+/// Returns the bytes of `node`, which is expected to be a leaf.
+pub fn leaf(node: &Node) -> Result<&[u8], FromNodeError> {
+    match node {
+        Node::Leaf(bytes) => Ok(bytes),
+        Node::Inner(_) => Err(FromNodeError::ExpectedLeaf(vec!())),
+    }
+}
+
+/// Returns the children of `node`, which is expected to be an inner node
+/// with `expected` children.
+pub fn children(node: &Node, expected: usize) -> Result<&[Node], FromNodeError> {
+    match node {
+        Node::Inner(nodes) if nodes.len() == expected => Ok(nodes),
+        Node::Inner(nodes) => Err(FromNodeError::ChildCount { path: vec!(), expected, actual: nodes.len() }),
+        Node::Leaf(_) => Err(FromNodeError::ExpectedInner(vec!())),
+    }
+}
+
+/// Converts the child at `idx`, adjusting the path of errors accordingly.
+pub fn child<T: FromBaum>(children: &[Node], idx: usize) -> Result<T, FromNodeError> {
+    T::from_node(&children[idx]).map_err(|e| e.in_child(idx))
+}
+
+/// Returns the tag of `node`, which is expected to be an inner node whose
+/// first child is a `u32` leaf.
+pub fn tag(node: &Node) -> Result<u32, FromNodeError> {
+    match node {
+        Node::Inner(nodes) if nodes.is_empty() => Err(FromNodeError::MissingTag(vec!())),
+        Node::Inner(nodes) => child(nodes, 0),
+        Node::Leaf(_) => Err(FromNodeError::ExpectedInner(vec!())),
+    }
+}
+
+pub fn unknown_variant(tag: u32) -> FromNodeError {
+    FromNodeError::UnknownVariant { path: vec!(), tag }
+}
+
+pub(crate) fn fixed<const N: usize>(node: &Node) -> Result<[u8; N], FromNodeError> {
+    crate::codec::fixed(leaf(node)?)
+}
+
+macro_rules! impl_int {
+    ($($ty:ty)*) => {
+        $(
+            /// Little-endian leaf of the type's width.
+            impl ToBaum for $ty {
+                fn to_node(&self) -> Node {
+                    Node::Leaf(self.to_le_bytes().to_vec())
+                }
+            }
+
+            impl FromBaum for $ty {
+                fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+                    fixed(node).map(<$ty>::from_le_bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_int!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 f32 f64);
+
+/// Single byte leaf, `0` or `1`.
+impl ToBaum for bool {
+    fn to_node(&self) -> Node {
+        Node::Leaf(vec!(*self as u8))
+    }
+}
+
+impl FromBaum for bool {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        match fixed(node)? {
+            [0] => Ok(false),
+            [1] => Ok(true),
+            _ => Err(FromNodeError::InvalidValue(vec!())),
+        }
+    }
+}
+
+/// UTF-8 encoded leaf.
+impl ToBaum for String {
+    fn to_node(&self) -> Node {
+        Node::Leaf(self.as_bytes().to_vec())
+    }
+}
+
+impl FromBaum for String {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        String::from_utf8(leaf(node)?.to_vec()).map_err(|_| FromNodeError::InvalidValue(vec!()))
+    }
+}
+
+/// Leaf containing the array's bytes.
+impl<const N: usize> ToBaum for [u8; N] {
+    fn to_node(&self) -> Node {
+        Node::Leaf(self.to_vec())
+    }
+}
+
+impl<const N: usize> FromBaum for [u8; N] {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        fixed(node)
+    }
+}
+
+/// Inner node with one child per element.
+impl<T: ToBaum> ToBaum for Vec<T> {
+    fn to_node(&self) -> Node {
+        Node::Inner(self.iter().map(ToBaum::to_node).collect())
+    }
+}
+
+impl<T: FromBaum> FromBaum for Vec<T> {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        match node {
+            Node::Inner(nodes) => (0..nodes.len()).map(|idx| child(nodes, idx)).collect(),
+            Node::Leaf(_) => Err(FromNodeError::ExpectedInner(vec!())),
+        }
+    }
+}
+
+/// Empty inner node for `None`, inner node with a single child for `Some`.
+impl<T: ToBaum> ToBaum for Option<T> {
+    fn to_node(&self) -> Node {
+        Node::Inner(self.iter().map(ToBaum::to_node).collect())
+    }
+}
+
+impl<T: FromBaum> FromBaum for Option<T> {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        match node {
+            Node::Inner(nodes) if nodes.is_empty() => Ok(None),
+            _ => child(children(node, 1)?, 0).map(Some),
+        }
+    }
+}
+
+impl<T: ToBaum> ToBaum for Box<T> {
+    fn to_node(&self) -> Node {
+        (**self).to_node()
+    }
+}
+
+impl<T: FromBaum> FromBaum for Box<T> {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        T::from_node(node).map(Box::new)
+    }
+}
+
+impl ToBaum for Node {
+    fn to_node(&self) -> Node {
+        self.clone()
+    }
+}
+
+impl FromBaum for Node {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        Ok(node.clone())
+    }
+}
+
+
+#[test]
+fn convert_primitives() {
+    assert_eq!(0x0102u16.to_node(), Node::Leaf(vec!(2, 1)));
+    assert_eq!(i32::from_node(&Node::Leaf(vec!(0xff; 4))), Ok(-1));
+    assert_eq!(u32::from_node(&Node::Leaf(vec!(0; 2))),
+        Err(FromNodeError::LeafLength { path: vec!(), expected: 4, actual: 2 }));
+    assert_eq!(bool::from_node(&Node::Leaf(vec!(2))), Err(FromNodeError::InvalidValue(vec!())));
+    assert_eq!(String::from_node(&"äb".to_string().to_node()), Ok("äb".to_string()));
+
+    let v: Vec<Option<u8>> = vec!(Some(1), None);
+    let node = v.to_node();
+    assert_eq!(node, Node::Inner(vec!(
+        Node::Inner(vec!(Node::Leaf(vec!(1)))),
+        Node::Inner(vec!()),
+    )));
+    assert_eq!(Vec::<Option<u8>>::from_node(&node), Ok(v));
+    assert_eq!(Vec::<Option<u16>>::from_node(&node),
+        Err(FromNodeError::LeafLength { path: vec!(0, 0), expected: 2, actual: 1 }));
+}
@@ -0,0 +1,103 @@
+//! Conversion between `Node`s and Rivest canonical S-expressions.
+//!
+//! Inner nodes map to lists, leaves to atoms, e.g. `(3:foo4:barx)`. Display
+//! hints (`[...]`) aren't supported since they have no equivalent in `Node`.
+
+use crate::Node;
+use crate::wire::{read_atom, write_atom};
+
+pub use crate::wire::Error;
+
+pub fn encode(node: &Node) -> Vec<u8> {
+    let mut res = vec!();
+    encode_into(node, &mut res);
+    res
+}
+
+fn encode_into(node: &Node, w: &mut Vec<u8>) {
+    match node {
+        Node::Leaf(bytes) => write_atom(bytes, w),
+        Node::Inner(nodes) => {
+            w.push(b'(');
+            for node in nodes {
+                encode_into(node, w);
+            }
+            w.push(b')');
+        }
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Node, Error> {
+    // children of the lists that are currently open
+    let mut stack: Vec<Vec<Node>> = vec!();
+    let mut pos = 0;
+
+    loop {
+        let node = match bytes.get(pos) {
+            None => return Err(Error::UnexpectedEnd),
+            Some(b'(') => {
+                pos += 1;
+                stack.push(vec!());
+                continue;
+            }
+            Some(b')') => {
+                pos += 1;
+                match stack.pop() {
+                    Some(children) => Node::Inner(children),
+                    None => return Err(Error::UnexpectedByte(b')', pos - 1)),
+                }
+            }
+            Some(b'0'..=b'9') => Node::Leaf(read_atom(bytes, &mut pos)?.to_vec()),
+            Some(b) => return Err(Error::UnexpectedByte(*b, pos)),
+        };
+
+        match stack.last_mut() {
+            Some(children) => children.push(node),
+            None => {
+                if pos != bytes.len() {
+                    return Err(Error::AdditionalBytes);
+                }
+                return Ok(node);
+            }
+        }
+    }
+}
+
+impl Node {
+    pub fn to_csexp(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn from_csexp(bytes: &[u8]) -> Result<Node, Error> {
+        decode(bytes)
+    }
+}
+
+
+#[test]
+fn csexp_roundtrip() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"foo".to_vec()),
+        Node::Inner(vec!(
+            Node::Leaf(vec!()),
+            Node::Inner(vec!()),
+        )),
+        Node::Leaf(b"0123456789(:)".to_vec()),
+    ));
+    let exp = b"(3:foo(0:())13:0123456789(:))";
+    assert_eq!(node.to_csexp(), exp.to_vec());
+    assert_eq!(Node::from_csexp(exp), Ok(node));
+    assert_eq!(Node::from_csexp(b"3:abc"), Ok(Node::Leaf(b"abc".to_vec())));
+}
+
+#[test]
+fn csexp_errors() {
+    assert_eq!(Node::from_csexp(b""), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_csexp(b"(3:ab"), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_csexp(b"(1:a"), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_csexp(b"01:a"), Err(Error::InvalidLength(0)));
+    assert_eq!(Node::from_csexp(b"(1:a)()"), Err(Error::AdditionalBytes));
+    assert_eq!(Node::from_csexp(b"( 1:a)"), Err(Error::UnexpectedByte(b' ', 1)));
+    assert_eq!(Node::from_csexp(b"[4:text]3:foo"), Err(Error::UnexpectedByte(b'[', 0)));
+    assert_eq!(Node::from_csexp(b"99999999999999999999999:a"), Err(Error::InvalidLength(0)));
+}
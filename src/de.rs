@@ -0,0 +1,483 @@
+//! Serde deserializer for the baum binary format. See the `ser` module for
+//! how values are mapped onto nodes.
+
+use crate::{read_payload, read_section, read_u8, read_u64, skip_payload, Error, ErrorKind, Section};
+
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+
+use std::convert::TryInto;
+use std::io::Read;
+
+/// Deserializes a value from a baum document (including the magic number).
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Error> {
+    from_reader(bytes)
+}
+
+/// Deserializes a value from a baum document (including the magic number)
+/// read from `reader`.
+pub fn from_reader<R: Read, T: DeserializeOwned>(mut reader: R) -> Result<T, Error> {
+    let mut magic_num = [0; 5];
+    read_section(&mut reader, &mut magic_num, Section::Magic)?;
+    if &magic_num != b"BAUM1" {
+        return Err(Error::new(ErrorKind::InvalidMagicNumber))
+    }
+
+    let mut de = Deserializer::new(reader);
+    let res = T::deserialize(&mut de)?;
+
+    // check if whole input has been processed
+    let mut buf = [0];
+    if de.reader.read(&mut buf)? != 0 {
+        return Err(Error::new(ErrorKind::AdditionalBytes));
+    }
+    Ok(res)
+}
+
+/// Deserializer reading the encoding of a single node (without magic number).
+pub struct Deserializer<R> {
+    reader: R,
+}
+
+enum Header {
+    Leaf(u64),
+    Inner(u64),
+}
+
+impl<R: Read> Deserializer<R> {
+    pub fn new(reader: R) -> Self {
+        Deserializer { reader }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    fn header(&mut self) -> Result<Header, Error> {
+        match read_u8(&mut self.reader)? {
+            0 => Ok(Header::Leaf(read_u64(&mut self.reader)?)),
+            1 => Ok(Header::Inner(read_u64(&mut self.reader)?)),
+            _ => Err(Error::new(ErrorKind::InvalidNodeType)),
+        }
+    }
+
+    fn bytes(&mut self, len: u64) -> Result<Vec<u8>, Error> {
+        read_payload(&mut self.reader, len)
+    }
+
+    fn leaf(&mut self) -> Result<Vec<u8>, Error> {
+        match self.header()? {
+            Header::Leaf(len) => self.bytes(len),
+            Header::Inner(_) => Err(Error::message("Expected leaf, found inner node.".to_string())),
+        }
+    }
+
+    fn fixed<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let bytes = self.leaf()?;
+        let len = bytes.len();
+        bytes.try_into().map_err(|_| Error::message(format!("Expected leaf of {} bytes, found {} bytes.", N, len)))
+    }
+
+    fn inner(&mut self) -> Result<u64, Error> {
+        match self.header()? {
+            Header::Inner(len) => Ok(len),
+            Header::Leaf(_) => Err(Error::message("Expected inner node, found leaf.".to_string())),
+        }
+    }
+
+    fn inner_of_len(&mut self, expected: u64) -> Result<(), Error> {
+        let len = self.inner()?;
+        if len != expected {
+            return Err(Error::message(format!("Expected inner node with {} children, found {}.", expected, len)));
+        }
+        Ok(())
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        String::from_utf8(self.leaf()?).map_err(|_| Error::message("Leaf isn't valid UTF-8.".to_string()))
+    }
+
+    fn skip(&mut self) -> Result<(), Error> {
+        // number of nodes that still have to be skipped
+        let mut remaining = 1u64;
+        while remaining > 0 {
+            remaining -= 1;
+            match self.header()? {
+                Header::Leaf(len) => skip_payload(&mut self.reader, len)?,
+                Header::Inner(len) => remaining = remaining.saturating_add(len),
+            }
+        }
+        Ok(())
+    }
+
+    fn visit_children<'de, V: Visitor<'de>>(&mut self, len: u64, visitor: V) -> Result<V::Value, Error> {
+        let mut seq = Children { de: self, remaining: len };
+        let res = visitor.visit_seq(&mut seq)?;
+        if seq.remaining != 0 {
+            return Err(Error::message(format!("{} unexpected trailing children.", seq.remaining)));
+        }
+        Ok(res)
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident($ty:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(<$ty>::from_le_bytes(self.fixed()?))
+            }
+        )*
+    };
+}
+
+impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
+    type Error = Error;
+
+    /// Leaves are passed to the visitor as bytes, inner nodes as sequences.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.header()? {
+            Header::Leaf(len) => visitor.visit_byte_buf(self.bytes(len)?),
+            Header::Inner(len) => self.visit_children(len, visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.fixed()? {
+            [0] => visitor.visit_bool(false),
+            [1] => visitor.visit_bool(true),
+            [b] => Err(Error::message(format!("Invalid bool value {}.", b))),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_i128 => visit_i128(i128),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_u128 => visit_u128(u128),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let s = self.string()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::message("Expected a single character.".to_string())),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.string()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_byte_buf(self.leaf()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_byte_buf(self.leaf()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.inner()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            len => Err(Error::message(format!("Expected inner node with 0 or 1 children, found {}.", len))),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.inner_of_len(0)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let len = self.inner()?;
+        self.visit_children(len, visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.inner_of_len(len as u64)?;
+        self.visit_children(len as u64, visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let len = self.inner()?;
+        let mut map = Children { de: self, remaining: len };
+        let res = visitor.visit_map(&mut map)?;
+        if map.remaining != 0 {
+            return Err(Error::message(format!("{} unexpected trailing entries.", map.remaining)));
+        }
+        Ok(res)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.inner()? {
+            0 => Err(Error::message("Expected variant index, found empty inner node.".to_string())),
+            len => visitor.visit_enum(Variant { de: self, fields: len - 1 }),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.skip()?;
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Children of an inner node, accessed as sequence or as map entries.
+struct Children<'a, R> {
+    de: &'a mut Deserializer<R>,
+    remaining: u64,
+}
+
+impl<'de, 'a, R: Read> de::SeqAccess<'de> for Children<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining.try_into().ok()
+    }
+}
+
+impl<'de, 'a, R: Read> de::MapAccess<'de> for Children<'a, R> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        self.de.inner_of_len(2)?;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.remaining.try_into().ok()
+    }
+}
+
+/// Enum variant whose inner node has been read up to the variant index.
+struct Variant<'a, R> {
+    de: &'a mut Deserializer<R>,
+    fields: u64,
+}
+
+impl<'de, 'a, R: Read> de::EnumAccess<'de> for Variant<'a, R> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let index = u32::from_le_bytes(self.de.fixed()?);
+        let value = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a, R: Read> de::VariantAccess<'de> for Variant<'a, R> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        self.expect_fields(0)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        self.expect_fields(1)?;
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.expect_fields(len as u64)?;
+        self.de.visit_children(self.fields, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        self.tuple_variant(fields.len(), visitor)
+    }
+}
+
+impl<'a, R: Read> Variant<'a, R> {
+    fn expect_fields(&self, expected: u64) -> Result<(), Error> {
+        if self.fields != expected {
+            return Err(Error::message(format!("Expected variant with {} fields, found {}.", expected, self.fields)));
+        }
+        Ok(())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::message(msg.to_string())
+    }
+}
+
+
+#[cfg(test)]
+use crate::{to_vec, Node};
+#[cfg(test)]
+use serde::{Serialize, Deserialize};
+#[cfg(test)]
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Rect { w: u32, h: u32 },
+    Line(i8, i8),
+}
+
+#[cfg(test)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Drawing {
+    name: String,
+    visible: bool,
+    shapes: Vec<Shape>,
+    parent: Option<Box<Drawing>>,
+    tags: BTreeMap<String, u64>,
+    initial: char,
+    unit: (),
+}
+
+#[test]
+fn serde_round_trip() {
+    let mut tags = BTreeMap::new();
+    tags.insert("a".to_string(), 1);
+    tags.insert("bc".to_string(), u64::MAX);
+    let drawing = Drawing {
+        name: "main".to_string(),
+        visible: true,
+        shapes: vec!(Shape::Empty, Shape::Circle(1.5), Shape::Rect { w: 3, h: 4 }, Shape::Line(-1, 1)),
+        parent: Some(Box::new(Drawing {
+            name: String::new(),
+            visible: false,
+            shapes: vec!(),
+            parent: None,
+            tags: BTreeMap::new(),
+            initial: 'ä',
+            unit: (),
+        })),
+        tags,
+        initial: 'x',
+        unit: (),
+    };
+    let bytes = to_vec(&drawing).unwrap();
+    assert_eq!(from_slice::<Drawing>(&bytes).unwrap(), drawing);
+
+    // the encoding is a regular baum document
+    let node = Node::deserialize(&bytes).unwrap();
+    match node {
+        Node::Inner(fields) => {
+            assert_eq!(fields.len(), 7);
+            assert_eq!(fields[0], Node::Leaf(b"main".to_vec()));
+            assert_eq!(fields[1], Node::Leaf(vec!(1)));
+            assert_eq!(fields[2], Node::Inner(vec!(
+                Node::Inner(vec!(Node::Leaf(vec!(0, 0, 0, 0)))),
+                Node::Inner(vec!(Node::Leaf(vec!(1, 0, 0, 0)), Node::Leaf(1.5f64.to_le_bytes().to_vec()))),
+                Node::Inner(vec!(Node::Leaf(vec!(2, 0, 0, 0)), Node::Leaf(vec!(3, 0, 0, 0)), Node::Leaf(vec!(4, 0, 0, 0)))),
+                Node::Inner(vec!(Node::Leaf(vec!(3, 0, 0, 0)), Node::Leaf(vec!(0xff)), Node::Leaf(vec!(1)))),
+            )));
+            assert_eq!(fields[4], Node::Inner(vec!(
+                Node::Inner(vec!(Node::Leaf(b"a".to_vec()), Node::Leaf(1u64.to_le_bytes().to_vec()))),
+                Node::Inner(vec!(Node::Leaf(b"bc".to_vec()), Node::Leaf(vec!(0xff; 8)))),
+            )));
+            assert_eq!(fields[6], Node::Inner(vec!()));
+        }
+        Node::Leaf(_) => panic!("expected inner node"),
+    }
+}
+
+#[test]
+fn serde_unknown_lengths() {
+    // iterators serialized via `collect_seq` don't always announce a length
+    struct Evens(u8);
+    impl Serialize for Evens {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.collect_seq((0..self.0).filter(|x| x % 2 == 0))
+        }
+    }
+    let bytes = to_vec(&Evens(7)).unwrap();
+    assert_eq!(from_slice::<Vec<u8>>(&bytes).unwrap(), vec!(0, 2, 4, 6));
+}
+
+#[test]
+fn serde_errors() {
+    let bytes = to_vec(&(1u8, 2u16)).unwrap();
+    assert!(matches!(from_slice::<(u8, u8)>(&bytes), Err(e) if e.kind() == ErrorKind::Message));
+    assert!(matches!(from_slice::<(u8, u16, u8)>(&bytes), Err(e) if e.kind() == ErrorKind::Message));
+    assert!(matches!(from_slice::<String>(&bytes), Err(e) if e.kind() == ErrorKind::Message));
+    assert!(matches!(from_slice::<(u8, u16)>(&bytes[1..]), Err(e) if e.kind() == ErrorKind::InvalidMagicNumber));
+    assert!(matches!(from_slice::<(u8, u16)>(&bytes[..bytes.len() - 1]), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+
+    let mut extra = bytes.clone();
+    extra.push(0);
+    assert!(matches!(from_slice::<(u8, u16)>(&extra), Err(e) if e.kind() == ErrorKind::AdditionalBytes));
+    assert_eq!(from_slice::<(u8, u16)>(&bytes).unwrap(), (1, 2));
+
+    let err = from_slice::<(u8, u16)>(b"BAU").unwrap_err();
+    assert_eq!((err.kind(), err.truncation().map(|t| t.section)), (ErrorKind::UnexpectedEof, Some(Section::Magic)));
+
+    // ignored values are skipped without recursing into them
+    let mut deep = b"BAUM1".to_vec();
+    for _ in 0..200_000 {
+        deep.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+    deep.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(from_slice::<de::IgnoredAny>(&deep).is_ok());
+    assert!(matches!(from_slice::<de::IgnoredAny>(&deep[..deep.len() - 1]), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+}
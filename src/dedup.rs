@@ -0,0 +1,328 @@
+//! Serialization format with back-references to repeated subtrees.
+//!
+//! The format is the regular one with the magic number `BAUM2` and an
+//! additional node type `2`, followed by the index (u64 LE) of an earlier
+//! node. Leaves and inner nodes are indexed in the order they're completed,
+//! i.e. children before their parents; back-references don't get an index.
+
+use crate::{read_payload, read_section, read_u64, read_u8, skip_payload, to_usize, ArcNode, DecodeMode, DecodeState, DEFAULT_MAX_DEPTH, Error, ErrorKind, Limits, Node, Section};
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::Read;
+
+const MAGIC: &[u8] = b"BAUM2";
+
+/// Structural key of a subtree, using the IDs of its children.
+#[derive(Eq, PartialEq, Hash)]
+enum Key<'a> {
+    Leaf(&'a [u8]),
+    Inner(Vec<usize>),
+}
+
+/// Assigns equal IDs to structurally equal subtrees and appends
+/// `(id, node_count)` for all nodes in pre-order.
+fn assign_ids<'a>(node: &'a Node, keys: &mut HashMap<Key<'a>, usize>, res: &mut Vec<(usize, usize)>) -> usize {
+    let pos = res.len();
+    res.push((0, 0));
+    let key = match node {
+        Node::Leaf(bytes) => Key::Leaf(bytes),
+        Node::Inner(nodes) => Key::Inner(nodes.iter().map(|n| assign_ids(n, keys, res)).collect()),
+    };
+    let next_id = keys.len();
+    let id = *keys.entry(key).or_insert(next_id);
+    res[pos] = (id, res.len() - pos);
+    id
+}
+
+struct Writer<'a> {
+    ids: &'a [(usize, usize)],
+    pos: usize,
+    /// Index of the first occurrence of every ID.
+    written: HashMap<usize, u64>,
+    /// Index of the next node written in full, counting every such node
+    /// like the decoder does, including repeated empty ones.
+    next_idx: u64,
+    out: Vec<u8>,
+}
+
+impl Writer<'_> {
+    fn write(&mut self, node: &Node) {
+        let (id, count) = self.ids[self.pos];
+        let is_empty = match node {
+            Node::Leaf(bytes) => bytes.is_empty(),
+            Node::Inner(nodes) => nodes.is_empty(),
+        };
+        // references to empty nodes wouldn't be any shorter
+        if let (false, Some(idx)) = (is_empty, self.written.get(&id)) {
+            self.out.push(2);
+            self.out.extend_from_slice(&idx.to_le_bytes());
+            self.pos += count;
+            return;
+        }
+        self.pos += 1;
+        match node {
+            Node::Leaf(bytes) => {
+                self.out.push(0);
+                self.out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+                self.out.extend_from_slice(bytes);
+            }
+            Node::Inner(nodes) => {
+                self.out.push(1);
+                self.out.extend_from_slice(&(nodes.len() as u64).to_le_bytes());
+                for node in nodes {
+                    self.write(node);
+                }
+            }
+        }
+        self.written.entry(id).or_insert(self.next_idx);
+        self.next_idx += 1;
+    }
+}
+
+/// A decoded node along with the size of its expanded subtree, which
+/// back-references to it are counted with when checking limits.
+#[derive(Clone)]
+struct Entry {
+    node: ArcNode,
+    /// Number of nodes, saturating at `usize::MAX`.
+    count: usize,
+    /// Nesting of inner nodes, 0 for leaves.
+    depth: usize,
+}
+
+/// Reads the node at `state.pos` like `Node::_deserialize_from`, storing
+/// all completed nodes in `table`. Returns `None` for skipped nodes, which
+/// don't get an index either.
+fn read<R: Read>(reader: &mut R, allow_refs: bool, state: &mut DecodeState, table: &mut Vec<Entry>) -> Result<Option<Entry>, Error> {
+    let start = state.pos;
+    let path = &state.path;
+    let type_byte = read_u8(reader).map_err(|e| e.at(start, path))?;
+    let known = type_byte <= 1 || (type_byte == 2 && allow_refs);
+    if !known && state.mode == DecodeMode::Strict {
+        return Err(Error::new(ErrorKind::InvalidNodeType).at(start, path));
+    }
+    let len = read_u64(reader).map_err(|e| e.at(start + 1, path))?;
+    if type_byte <= 1 {
+        to_usize(len).map_err(|e| e.at(start + 1, path))?;
+        state.nodes += 1;
+        state.limits.check(type_byte == 0, len, path.len() + 1, state.nodes).map_err(|e| e.at(start, path))?;
+    }
+    state.pos += 9;
+    let entry = match type_byte {
+        0 => {
+            let bytes = read_payload(reader, len).map_err(|e| e.at(start + 9, path))?;
+            state.pos += len;
+            Entry { node: ArcNode::Leaf(bytes.into()), count: 1, depth: 0 }
+        }
+        1 => {
+            let mut nodes = Vec::with_capacity(len.min(1024) as usize);
+            let (mut count, mut depth) = (1usize, 0);
+            for idx in 0..len {
+                state.path.push(idx as usize);
+                if let Some(child) = read(reader, allow_refs, state, table)? {
+                    count = count.saturating_add(child.count);
+                    depth = depth.max(child.depth);
+                    nodes.push(child.node);
+                }
+                state.path.pop();
+            }
+            Entry { node: ArcNode::Inner(nodes.into()), count, depth: depth + 1 }
+        }
+        2 if allow_refs => {
+            let entry = usize::try_from(len).ok().and_then(|idx| table.get(idx)).cloned()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidReference).at(start, path))?;
+            // the referenced subtree passed the leaf length and children
+            // limits when it was decoded, but its copy adds to the node
+            // count and may be nested deeper
+            state.nodes = state.nodes.saturating_add(entry.count);
+            state.limits.check(false, 0, path.len() + entry.depth, state.nodes).map_err(|e| e.at(start, path))?;
+            return Ok(Some(entry));
+        }
+        // unknown, laid out like a leaf
+        _ => {
+            skip_payload(reader, len).map_err(|e| e.at(start + 9, path))?;
+            state.pos += len;
+            return Ok(None);
+        }
+    };
+    table.push(entry.clone());
+    Ok(Some(entry))
+}
+
+/// Reads a tree after the magic number, which is passed in to decide whether
+/// back-references are allowed.
+fn read_tree<R: Read>(mut reader: R, magic: &[u8], mode: DecodeMode, limits: &Limits) -> Result<ArcNode, Error> {
+    let allow_refs = match magic {
+        b"BAUM1" => false,
+        MAGIC => true,
+        _ => return Err(Error::new(ErrorKind::InvalidMagicNumber)),
+    };
+    // `read` recurses once per level, so nesting is always bounded
+    let limits = Limits { max_depth: Some(limits.max_depth.unwrap_or(DEFAULT_MAX_DEPTH)), ..limits.clone() };
+    let mut state = DecodeState { pos: 5, path: vec!(), mode, limits: &limits, nodes: 0 };
+    let res = read(&mut reader, allow_refs, &mut state, &mut vec!())?
+        .ok_or_else(|| Error::new(ErrorKind::InvalidNodeType).at(5, &[]))?;
+    if mode == DecodeMode::Strict && reader.read(&mut [0])? != 0 {
+        return Err(Error::new(ErrorKind::AdditionalBytes));
+    }
+    Ok(res.node)
+}
+
+impl Node {
+    /// Serializes the tree, writing repeated subtrees only once. The result
+    /// can be deserialized using `Node::deserialize_dedup`, which copies
+    /// repeated subtrees, or `ArcNode::deserialize`, which shares them.
+    pub fn serialize_dedup(&self) -> Vec<u8> {
+        let mut ids = vec!();
+        assign_ids(self, &mut HashMap::new(), &mut ids);
+        let mut writer = Writer { ids: &ids, pos: 0, written: HashMap::new(), next_idx: 0, out: MAGIC.to_vec() };
+        writer.write(self);
+        writer.out
+    }
+
+    /// Deserializes a tree written by `Node::serialize` or
+    /// `Node::serialize_dedup`, copying repeated subtrees.
+    ///
+    /// Since every back-reference copies a subtree, a small input can expand
+    /// into a huge tree. Plain `Node::deserialize` rejects deduplicated
+    /// documents for this reason; use `deserialize_dedup_limited` for
+    /// untrusted input.
+    pub fn deserialize_dedup(bytes: &[u8]) -> Result<Node, Error> {
+        Self::deserialize_dedup_from(bytes, DecodeMode::Strict)
+    }
+
+    /// Like `deserialize_dedup`, with `mode` deciding how trailing bytes and
+    /// unknown node types are treated.
+    pub fn deserialize_dedup_from<R: Read>(reader: R, mode: DecodeMode) -> Result<Node, Error> {
+        Self::deserialize_dedup_limited(reader, mode, &Limits::default())
+    }
+
+    /// Like `deserialize_dedup_from`, failing as soon as the expanded tree
+    /// would exceed `limits`, before copying any subtrees. Back-references
+    /// count with the size and depth of the subtree they refer to. Without a
+    /// `max_depth`, nesting is limited to `DEFAULT_MAX_DEPTH` as when
+    /// parsing.
+    pub fn deserialize_dedup_limited<R: Read>(mut reader: R, mode: DecodeMode, limits: &Limits) -> Result<Node, Error> {
+        let mut magic = [0; 5];
+        read_section(&mut reader, &mut magic, Section::Magic)?;
+        Ok(read_tree(reader, &magic, mode, limits)?.to_node())
+    }
+}
+
+impl ArcNode {
+    /// Deserializes a tree like `Node::deserialize`. Subtrees that are
+    /// written once and referenced multiple times (see
+    /// `Node::serialize_dedup`) are shared instead of copied.
+    pub fn deserialize(bytes: &[u8]) -> Result<ArcNode, Error> {
+        if bytes.len() < 5 {
+            return Err(Error::unexpected_eof(Section::Magic, 5, bytes.len() as u64));
+        }
+        read_tree(&bytes[5..], &bytes[..5], DecodeMode::Strict, &Limits::default())
+    }
+}
+
+
+#[test]
+fn serialize_dedup() {
+    let node = crate::parse("((0x01_02 (0x03)) (0x01_02 (0x03)) 0x01_02 () ())");
+    let bytes = node.serialize_dedup();
+    let mut expected = b"BAUM2".to_vec();
+    expected.extend_from_slice(&[1, 5, 0, 0, 0, 0, 0, 0, 0]);
+    // index 3
+    expected.extend_from_slice(&[1, 2, 0, 0, 0, 0, 0, 0, 0]);
+    // index 0
+    expected.extend_from_slice(&[0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 2]);
+    // index 2
+    expected.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0]);
+    // index 1
+    expected.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0, 0, 3]);
+    expected.extend_from_slice(&[2, 3, 0, 0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0, 0]);
+    // empty nodes aren't deduplicated: index 4 and 5
+    expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    expected.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(bytes, expected);
+
+    assert_eq!(Node::deserialize_dedup(&bytes).unwrap(), node);
+    assert_eq!(Node::deserialize_dedup(&node.serialize()).unwrap(), node);
+    assert!(matches!(Node::deserialize(&bytes), Err(e) if e.kind() == ErrorKind::InvalidMagicNumber));
+    let arc = ArcNode::deserialize(&bytes).unwrap();
+    assert_eq!(arc.to_node(), node);
+    assert!(arc.children()[0].ptr_eq(&arc.children()[1]));
+    assert_eq!(ArcNode::deserialize(&node.serialize()).unwrap(), arc);
+
+    // references are only valid in the dedup format and to completed nodes
+    let mut plain = b"BAUM1".to_vec();
+    plain.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(matches!(Node::deserialize(&plain), Err(e) if e.kind() == ErrorKind::InvalidNodeType));
+    let mut invalid = b"BAUM2".to_vec();
+    invalid.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let err = Node::deserialize_dedup(&invalid).unwrap_err();
+    assert_eq!((err.kind(), err.offset(), err.path()), (ErrorKind::InvalidReference, Some(14), Some(&vec!(0))));
+    assert!(matches!(ArcNode::deserialize(b"BAUM2\x00\x05\0\0\0\0\0\0\0"), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+
+    // decode modes apply like for plain documents
+    let mut unknown = b"BAUM2".to_vec();
+    unknown.extend_from_slice(&[1, 2, 0, 0, 0, 0, 0, 0, 0, 7, 1, 0, 0, 0, 0, 0, 0, 0, 9, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    assert!(matches!(Node::deserialize_dedup(&unknown), Err(e) if e.kind() == ErrorKind::InvalidNodeType));
+    let lenient = Node::deserialize_dedup_from(&unknown[..], DecodeMode::Lenient).unwrap();
+    assert_eq!(lenient, Node::Inner(vec!(Node::Leaf(vec!()))));
+
+    // limits apply to the expanded tree: child `k` of this root refers to
+    // child `k - 1` twice, doubling the number of nodes with every child
+    let chain = |len: u64| {
+        let mut bytes = b"BAUM2".to_vec();
+        bytes.push(1);
+        bytes.extend_from_slice(&(len + 1).to_le_bytes());
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        for idx in 0..len {
+            bytes.extend_from_slice(&[1, 2, 0, 0, 0, 0, 0, 0, 0]);
+            for _ in 0..2 {
+                bytes.push(2);
+                bytes.extend_from_slice(&idx.to_le_bytes());
+            }
+        }
+        bytes
+    };
+    let decode = |bytes: &[u8], limits: Limits| Node::deserialize_dedup_limited(bytes, DecodeMode::Strict, &limits);
+    let limits = |max_depth, max_nodes| Limits { max_depth, max_nodes, ..Limits::default() };
+    let node = decode(&chain(5), Limits::default()).unwrap();
+    assert_eq!((node.node_count(), node.check_limits(&limits(Some(6), Some(121))).is_ok()), (121, true));
+    assert!(decode(&chain(5), limits(Some(6), Some(121))).is_ok());
+    let err = decode(&chain(5), limits(None, Some(120))).unwrap_err();
+    assert_eq!((err.kind(), err.path()), (ErrorKind::LimitExceeded, Some(&vec!(5, 1))));
+    let err = decode(&chain(5), limits(Some(5), None)).unwrap_err();
+    assert_eq!((err.kind(), err.path()), (ErrorKind::LimitExceeded, Some(&vec!(5, 0))));
+    // fails before expanding anything
+    let err = decode(&chain(200), limits(None, Some(1000))).unwrap_err();
+    assert_eq!((err.kind(), err.path()), (ErrorKind::LimitExceeded, Some(&vec!(8, 1))));
+}
+
+#[test]
+fn serialize_dedup_empty_nodes() {
+    // repeated empty nodes are written in full and get an index each, which
+    // later back-references have to account for
+    for s in &["(() () (0x01) (0x01))", "(0x 0x () () ((0x02)) 0x ((0x02)) (0x03 0x03))"] {
+        let node = crate::parse(s);
+        assert_eq!(Node::deserialize_dedup(&node.serialize_dedup()).unwrap(), node);
+        assert_eq!(ArcNode::deserialize(&node.serialize_dedup()).unwrap().to_node(), node);
+    }
+}
+
+#[test]
+fn deserialize_dedup_depth() {
+    let nested = |depth: usize| {
+        let mut bytes = b"BAUM2".to_vec();
+        for _ in 0..depth {
+            bytes.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0]);
+        }
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes
+    };
+    assert!(Node::deserialize_dedup(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+    let err = Node::deserialize_dedup(&nested(1_000_000)).unwrap_err();
+    assert_eq!((err.kind(), err.path().map(Vec::len)), (ErrorKind::LimitExceeded, Some(DEFAULT_MAX_DEPTH)));
+    let limits = Limits { max_depth: Some(300), ..Limits::default() };
+    assert!(Node::deserialize_dedup_limited(&nested(300)[..], DecodeMode::Strict, &limits).is_ok());
+}
@@ -0,0 +1,237 @@
+//! Conversion between `Node`s and ASN.1 DER.
+//!
+//! Every TLV maps to an inner node whose first child is a leaf containing
+//! the identifier octets (the tag, including class and constructed bit). For
+//! constructed encodings (e.g. SEQUENCE), the remaining children are the
+//! contained TLVs; for primitive encodings (e.g. OCTET STRING), the second
+//! and last child is a leaf with the content octets:
+//!
+//! ```text
+//! (0x30 (0x04 0x01_02) (0x05 0x))
+//! ```
+//!
+//! is `SEQUENCE { OCTET STRING 01 02, NULL }`.
+
+use crate::{Node, Path};
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnexpectedEnd,
+    InvalidTag(usize),
+    InvalidLength(usize),
+    AdditionalBytes,
+    /// A node that doesn't follow the mapping described in the module docs.
+    InvalidNode(Path),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::UnexpectedEnd => write!(f, "Unexpected end of input."),
+            Error::InvalidTag(pos) => write!(f, "Invalid tag at position {}.", pos),
+            Error::InvalidLength(pos) => write!(f, "Invalid length at position {}.", pos),
+            Error::AdditionalBytes => write!(f, "Input contains additional bytes."),
+            Error::InvalidNode(p) => write!(f, "Node at path {:?} isn't a valid TLV.", p),
+        }
+    }
+}
+
+impl std::error::Error for Error { }
+
+const CONSTRUCTED: u8 = 0x20;
+
+pub fn encode(node: &Node) -> Result<Vec<u8>, Error> {
+    let mut res = vec!();
+    encode_into(node, &mut vec!(), &mut res)?;
+    Ok(res)
+}
+
+fn encode_into(node: &Node, path: &mut Path, w: &mut Vec<u8>) -> Result<(), Error> {
+    let (tag, rest) = match node {
+        Node::Inner(nodes) => match nodes.split_first() {
+            Some((Node::Leaf(tag), rest)) if read_tag(tag, &mut 0) == Ok(tag.len()) => (tag, rest),
+            _ => return Err(Error::InvalidNode(path.clone())),
+        },
+        Node::Leaf(_) => return Err(Error::InvalidNode(path.clone())),
+    };
+    w.extend_from_slice(tag);
+    if tag[0] & CONSTRUCTED != 0 {
+        let mut content = vec!();
+        for (idx, child) in rest.iter().enumerate() {
+            path.push(idx + 1);
+            encode_into(child, path, &mut content)?;
+            path.pop();
+        }
+        write_len(content.len(), w);
+        w.extend_from_slice(&content);
+    } else {
+        match rest {
+            [Node::Leaf(content)] => {
+                write_len(content.len(), w);
+                w.extend_from_slice(content);
+            }
+            _ => return Err(Error::InvalidNode(path.clone())),
+        }
+    }
+    Ok(())
+}
+
+fn write_len(len: usize, w: &mut Vec<u8>) {
+    if len < 0x80 {
+        w.push(len as u8);
+    } else {
+        let bytes = len.to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        w.push(0x80 | (bytes.len() - skip) as u8);
+        w.extend_from_slice(&bytes[skip..]);
+    }
+}
+
+/// Reads the identifier octets at `pos`, returns the position after them.
+fn read_tag(bytes: &[u8], pos: &mut usize) -> Result<usize, Error> {
+    let start = *pos;
+    let first = *bytes.get(start).ok_or(Error::UnexpectedEnd)?;
+    *pos += 1;
+    if first & 0x1f == 0x1f {
+        // high tag number form, base 128 without leading zeros
+        if bytes.get(*pos) == Some(&0x80) {
+            return Err(Error::InvalidTag(start));
+        }
+        loop {
+            let b = *bytes.get(*pos).ok_or(Error::UnexpectedEnd)?;
+            *pos += 1;
+            if b & 0x80 == 0 {
+                break;
+            }
+        }
+        // numbers below 31 have to use the low tag number form
+        if *pos - start == 2 && bytes[start + 1] < 0x1f {
+            return Err(Error::InvalidTag(start));
+        }
+    }
+    Ok(*pos)
+}
+
+fn read_len(bytes: &[u8], pos: &mut usize) -> Result<usize, Error> {
+    let start = *pos;
+    let first = *bytes.get(start).ok_or(Error::UnexpectedEnd)?;
+    *pos += 1;
+    if first < 0x80 {
+        return Ok(usize::from(first));
+    }
+    // indefinite lengths (0x80) aren't allowed in DER
+    let size = usize::from(first & 0x7f);
+    if size == 0 || size > std::mem::size_of::<usize>() {
+        return Err(Error::InvalidLength(start));
+    }
+    let len_bytes = bytes.get(*pos..*pos + size).ok_or(Error::UnexpectedEnd)?;
+    *pos += size;
+    let len = len_bytes.iter().fold(0, |acc, b| (acc << 8) | usize::from(*b));
+    // the length has to be encoded in the minimum number of octets
+    if len_bytes[0] == 0 || len < 0x80 {
+        return Err(Error::InvalidLength(start));
+    }
+    Ok(len)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Node, Error> {
+    // children of the constructed TLVs that are currently open and the
+    // position where their content ends
+    let mut stack: Vec<(Vec<Node>, usize)> = vec!();
+    let mut pos = 0;
+
+    loop {
+        let start = pos;
+        read_tag(bytes, &mut pos)?;
+        let tag = Node::Leaf(bytes[start..pos].to_vec());
+        let len_pos = pos;
+        let len = read_len(bytes, &mut pos)?;
+        let end = pos.checked_add(len).ok_or(Error::InvalidLength(len_pos))?;
+        match stack.last() {
+            Some((_, parent_end)) if end > *parent_end => return Err(Error::InvalidLength(len_pos)),
+            _ if end > bytes.len() => return Err(Error::UnexpectedEnd),
+            _ => (),
+        }
+
+        let mut node = if bytes[start] & CONSTRUCTED == 0 {
+            let content = Node::Leaf(bytes[pos..end].to_vec());
+            pos = end;
+            Node::Inner(vec!(tag, content))
+        } else if len == 0 {
+            Node::Inner(vec!(tag))
+        } else {
+            stack.push((vec!(tag), end));
+            continue;
+        };
+
+        // add the node to its parent, closing all TLVs that are complete
+        loop {
+            match stack.last_mut() {
+                Some((children, end)) => {
+                    children.push(node);
+                    if pos != *end {
+                        break;
+                    }
+                    node = Node::Inner(stack.pop().unwrap().0);
+                }
+                None => {
+                    if pos != bytes.len() {
+                        return Err(Error::AdditionalBytes);
+                    }
+                    return Ok(node);
+                }
+            }
+        }
+    }
+}
+
+impl Node {
+    pub fn to_der(&self) -> Result<Vec<u8>, Error> {
+        encode(self)
+    }
+
+    pub fn from_der(bytes: &[u8]) -> Result<Node, Error> {
+        decode(bytes)
+    }
+}
+
+
+#[test]
+fn der_roundtrip() {
+    // SEQUENCE { INTEGER 5, SEQUENCE { }, [1] { OCTET STRING (200 bytes) }, [APPLICATION 31] NULL }
+    let mut der = vec!(0x30, 0x81, 0xd6, 0x02, 0x01, 0x05, 0x30, 0x00, 0xa1, 0x81, 0xcb, 0x04, 0x81, 0xc8);
+    der.extend_from_slice(&[0xab; 200]);
+    der.extend_from_slice(&[0x5f, 0x1f, 0x00]);
+    let node = Node::from_der(&der).unwrap();
+    assert_eq!(node, Node::Inner(vec!(
+        Node::Leaf(vec!(0x30)),
+        Node::Inner(vec!(Node::Leaf(vec!(0x02)), Node::Leaf(vec!(0x05)))),
+        Node::Inner(vec!(Node::Leaf(vec!(0x30)))),
+        Node::Inner(vec!(
+            Node::Leaf(vec!(0xa1)),
+            Node::Inner(vec!(Node::Leaf(vec!(0x04)), Node::Leaf(vec!(0xab; 200)))),
+        )),
+        Node::Inner(vec!(Node::Leaf(vec!(0x5f, 0x1f)), Node::Leaf(vec!()))),
+    )));
+    assert_eq!(node.to_der(), Ok(der));
+}
+
+#[test]
+fn der_errors() {
+    assert_eq!(Node::from_der(b""), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_der(&[0x04, 0x02, 0x01]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_der(&[0x05, 0x00, 0x05, 0x00]), Err(Error::AdditionalBytes));
+    // indefinite and non-minimal lengths
+    assert_eq!(Node::from_der(&[0x30, 0x80, 0x00, 0x00]), Err(Error::InvalidLength(1)));
+    assert_eq!(Node::from_der(&[0x04, 0x81, 0x01, 0x00]), Err(Error::InvalidLength(1)));
+    // child exceeds its parent
+    assert_eq!(Node::from_der(&[0x30, 0x02, 0x04, 0x01, 0x00]), Err(Error::InvalidLength(3)));
+    // tag number 5 in high tag number form
+    assert_eq!(Node::from_der(&[0x1f, 0x05, 0x00]), Err(Error::InvalidTag(0)));
+
+    assert_eq!(Node::Leaf(vec!()).to_der(), Err(Error::InvalidNode(vec!())));
+    let node = Node::Inner(vec!(Node::Leaf(vec!(0x30)), Node::Inner(vec!(Node::Leaf(vec!(0x04))))));
+    assert_eq!(node.to_der(), Err(Error::InvalidNode(vec!(1))));
+    let node = Node::Inner(vec!(Node::Leaf(vec!(0x1f)), Node::Leaf(vec!())));
+    assert_eq!(node.to_der(), Err(Error::InvalidNode(vec!())));
+}
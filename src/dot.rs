@@ -0,0 +1,62 @@
+use crate::{Node, PrettyConfig};
+
+use std::fmt::Write;
+
+/// Number of bytes shown in leaf labels.
+const PREVIEW_BYTES: usize = 8;
+
+impl Node {
+    /// Returns a Graphviz digraph of the tree. Inner nodes are drawn as points,
+    /// leaves as boxes labeled with (up to 8 of) their bytes.
+    pub fn to_dot(&self) -> String {
+        let config = PrettyConfig {
+            max_width: usize::MAX,
+            max_leaf_bytes: Some(PREVIEW_BYTES),
+            ..PrettyConfig::default()
+        };
+        let mut s = String::new();
+        s.push_str("digraph baum {\n");
+        self._to_dot(&config, &mut 0, &mut s);
+        s.push_str("}\n");
+        s
+    }
+
+    /// Writes the node and its descendants, returns the node's id.
+    fn _to_dot(&self, config: &PrettyConfig, next_id: &mut usize, s: &mut String) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        match self {
+            Node::Leaf(_) => {
+                // labels only contain hex digits, `_`, `…` and the length
+                writeln!(s, "    n{} [shape=box, label=\"{}\"];", id, self.pretty_print_with(config)).unwrap();
+            }
+            Node::Inner(nodes) => {
+                writeln!(s, "    n{} [shape=point];", id).unwrap();
+                for node in nodes {
+                    let child = node._to_dot(config, next_id, s);
+                    writeln!(s, "    n{} -> n{};", id, child).unwrap();
+                }
+            }
+        }
+        id
+    }
+}
+
+
+#[test]
+fn to_dot() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1, 2)),
+        Node::Inner(vec!(Node::Leaf((0..10).collect()))),
+    ));
+    assert_eq!(node.to_dot(), r#"digraph baum {
+    n0 [shape=point];
+    n1 [shape=box, label="0x01_02"];
+    n0 -> n1;
+    n2 [shape=point];
+    n3 [shape=box, label="0x00_01_02_03_04_05_06_07… (10 bytes)"];
+    n2 -> n3;
+    n0 -> n2;
+}
+"#);
+}
@@ -0,0 +1,255 @@
+use crate::{Node, Path};
+
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+#[derive(Debug, PartialEq)]
+pub enum EditError {
+    /// The edited node is an inner node.
+    ExpectedLeaf,
+    /// The edited node is a leaf.
+    ExpectedInner,
+    /// The range exceeds the leaf's length or ends before it starts.
+    OutOfBounds { start: usize, end: usize, len: usize },
+    /// The child index exceeds the number of children.
+    InvalidIndex { idx: usize, len: usize },
+    /// There's no node at the path, or no parent to insert a node into.
+    InvalidPath(Path),
+}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EditError::ExpectedLeaf => write!(f, "Expected leaf, found inner node."),
+            EditError::ExpectedInner => write!(f, "Expected inner node, found leaf."),
+            EditError::OutOfBounds { start, end, len } => write!(f, "Range {}..{} is out of bounds for leaf of length {}.", start, end, len),
+            EditError::InvalidIndex { idx, len } => write!(f, "Index {} is out of bounds for inner node with {} children.", idx, len),
+            EditError::InvalidPath(path) => write!(f, "Invalid path {:?}.", path),
+        }
+    }
+}
+
+impl std::error::Error for EditError { }
+
+impl Node {
+    fn leaf_mut(&mut self) -> Result<&mut Vec<u8>, EditError> {
+        match self {
+            Node::Leaf(bytes) => Ok(bytes),
+            Node::Inner(_) => Err(EditError::ExpectedLeaf),
+        }
+    }
+
+    /// Replaces the bytes in `range` by `replacement` and returns the removed
+    /// bytes.
+    pub fn splice_leaf<R: RangeBounds<usize>>(&mut self, range: R, replacement: &[u8]) -> Result<Vec<u8>, EditError> {
+        let bytes = self.leaf_mut()?;
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => bytes.len(),
+        };
+        if start > end || end > bytes.len() {
+            return Err(EditError::OutOfBounds { start, end, len: bytes.len() });
+        }
+        Ok(bytes.splice(start..end, replacement.iter().copied()).collect())
+    }
+
+    pub fn append_leaf(&mut self, bytes: &[u8]) -> Result<(), EditError> {
+        self.leaf_mut()?.extend_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Binary searches the children, which have to be sorted consistently
+    /// with `f`, like `slice::binary_search_by`. Leaves are treated as having
+    /// no children.
+    pub fn binary_search_child_by<F: FnMut(&Node) -> Ordering>(&self, f: F) -> Result<usize, usize> {
+        match self {
+            Node::Leaf(_) => Err(0),
+            Node::Inner(nodes) => nodes.binary_search_by(f),
+        }
+    }
+
+    /// Inserts `child` into the children, which have to be sorted by `cmp`,
+    /// keeping them sorted. Returns the index of the inserted child; equal
+    /// children are inserted after existing ones.
+    pub fn insert_sorted_by<F: FnMut(&Node, &Node) -> Ordering>(&mut self, child: Node, mut cmp: F) -> Result<usize, EditError> {
+        match self {
+            Node::Leaf(_) => Err(EditError::ExpectedInner),
+            Node::Inner(nodes) => {
+                let idx = nodes.partition_point(|n| cmp(n, &child) != Ordering::Greater);
+                nodes.insert(idx, child);
+                Ok(idx)
+            }
+        }
+    }
+
+    /// Like `insert_sorted_by`, using the ordering of `Node`.
+    pub fn insert_sorted(&mut self, child: Node) -> Result<usize, EditError> {
+        self.insert_sorted_by(child, Node::cmp)
+    }
+
+    /// Splits the children at `idx`, returning an inner node with the
+    /// children from `idx` on and keeping the ones before.
+    pub fn split_off_children(&mut self, idx: usize) -> Result<Node, EditError> {
+        match self {
+            Node::Leaf(_) => Err(EditError::ExpectedInner),
+            Node::Inner(nodes) if idx > nodes.len() => Err(EditError::InvalidIndex { idx, len: nodes.len() }),
+            Node::Inner(nodes) => Ok(Node::Inner(nodes.split_off(idx))),
+        }
+    }
+
+    /// Inserts `node` so that it ends up at `path`, shifting later siblings
+    /// back. The parent has to exist and have at least as many children as
+    /// the last index of `path`.
+    pub fn graft(&mut self, path: &[usize], node: Node) -> Result<(), EditError> {
+        let invalid = || EditError::InvalidPath(path.to_vec());
+        let (last, parent) = path.split_last().ok_or_else(invalid)?;
+        match self.get_mut(parent).ok_or_else(invalid)? {
+            Node::Inner(nodes) if *last <= nodes.len() => {
+                nodes.insert(*last, node);
+                Ok(())
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Appends the children of `other` to an inner node, or the bytes of
+    /// `other` to a leaf. Both nodes have to be of the same kind.
+    pub fn concat(&mut self, other: Node) -> Result<(), EditError> {
+        match (self, other) {
+            (Node::Leaf(bytes), Node::Leaf(other)) => bytes.extend(other),
+            (Node::Inner(nodes), Node::Inner(other)) => nodes.extend(other),
+            (Node::Leaf(_), Node::Inner(_)) => return Err(EditError::ExpectedLeaf),
+            (Node::Inner(_), Node::Leaf(_)) => return Err(EditError::ExpectedInner),
+        }
+        Ok(())
+    }
+
+    /// Clones the tree down to `max_depth` levels below this node. Inner
+    /// nodes on the last level are cloned without their children, e.g. for
+    /// previews of large trees.
+    pub fn clone_truncated(&self, max_depth: usize) -> Node {
+        match self {
+            Node::Leaf(bytes) => Node::Leaf(bytes.clone()),
+            Node::Inner(_) if max_depth == 0 => Node::Inner(vec!()),
+            Node::Inner(nodes) => Node::Inner(nodes.iter().map(|n| n.clone_truncated(max_depth - 1)).collect()),
+        }
+    }
+
+    /// Removes the node at `path` from its parent and returns it, without
+    /// cloning. For the empty path, the whole tree is returned and replaced
+    /// by an empty inner node. Returns `None` if there's no node at `path`.
+    pub fn take_subtree(&mut self, path: &[usize]) -> Option<Node> {
+        let (last, parent) = match path.split_last() {
+            Some(split) => split,
+            None => return Some(std::mem::replace(self, Node::EMPTY_INNER)),
+        };
+        match self.get_mut(parent)? {
+            Node::Inner(nodes) if *last < nodes.len() => Some(nodes.remove(*last)),
+            _ => None,
+        }
+    }
+
+    /// Shortens the leaf to `len` bytes. Has no effect if the leaf isn't
+    /// longer than `len`.
+    pub fn truncate_leaf(&mut self, len: usize) -> Result<(), EditError> {
+        self.leaf_mut()?.truncate(len);
+        Ok(())
+    }
+}
+
+
+#[test]
+fn leaf_edits() {
+    let mut node = Node::Leaf(vec!(1, 2, 3, 4));
+    assert_eq!(node.splice_leaf(1..3, &[5, 6, 7]), Ok(vec!(2, 3)));
+    assert_eq!(node, Node::Leaf(vec!(1, 5, 6, 7, 4)));
+    assert_eq!(node.splice_leaf(..=1, &[]), Ok(vec!(1, 5)));
+    assert_eq!(node.splice_leaf(3.., &[8]), Ok(vec!()));
+    assert_eq!(node.append_leaf(&[9]), Ok(()));
+    assert_eq!(node, Node::Leaf(vec!(6, 7, 4, 8, 9)));
+    assert_eq!(node.truncate_leaf(2), Ok(()));
+    assert_eq!(node.truncate_leaf(3), Ok(()));
+    assert_eq!(node, Node::Leaf(vec!(6, 7)));
+
+    assert_eq!(node.splice_leaf(1..3, &[]), Err(EditError::OutOfBounds { start: 1, end: 3, len: 2 }));
+    #[allow(clippy::reversed_empty_ranges)]
+    let reversed = 2..1;
+    assert_eq!(node.splice_leaf(reversed, &[]), Err(EditError::OutOfBounds { start: 2, end: 1, len: 2 }));
+    let mut inner = Node::Inner(vec!());
+    assert_eq!(inner.splice_leaf(.., &[]), Err(EditError::ExpectedLeaf));
+    assert_eq!(inner.append_leaf(&[1]), Err(EditError::ExpectedLeaf));
+    assert_eq!(inner.truncate_leaf(0), Err(EditError::ExpectedLeaf));
+}
+
+#[test]
+fn sorted_children() {
+    let mut node = Node::Inner(vec!());
+    for b in &[5, 1, 3, 3] {
+        node.insert_sorted(Node::Leaf(vec!(*b))).unwrap();
+    }
+    assert_eq!(node.to_string(), "(0x01 0x03 0x03 0x05)");
+    assert_eq!(node.binary_search_child_by(|n| n.cmp(&Node::Leaf(vec!(5)))), Ok(3));
+    assert_eq!(node.binary_search_child_by(|n| n.cmp(&Node::Leaf(vec!(2)))), Err(1));
+
+    // sorted by node count, descending
+    let by_len = |a: &Node, b: &Node| b.node_count().cmp(&a.node_count());
+    let mut node = Node::Inner(vec!());
+    assert_eq!(node.insert_sorted_by(Node::Leaf(vec!()), by_len), Ok(0));
+    assert_eq!(node.insert_sorted_by(Node::Inner(vec!(Node::Leaf(vec!()))), by_len), Ok(0));
+    assert_eq!(node.insert_sorted_by(Node::Inner(vec!()), by_len), Ok(2));
+
+    let mut leaf = Node::Leaf(vec!());
+    assert_eq!(leaf.binary_search_child_by(|_| Ordering::Less), Err(0));
+    assert_eq!(leaf.insert_sorted(Node::Leaf(vec!())), Err(EditError::ExpectedInner));
+}
+
+#[test]
+fn truncate_and_take() {
+    let node = crate::parse("(0x01 (0x02 (0x03)) ())");
+    assert_eq!(node.clone_truncated(0), crate::parse("()"));
+    assert_eq!(node.clone_truncated(1), crate::parse("(0x01 () ())"));
+    assert_eq!(node.clone_truncated(2), crate::parse("(0x01 (0x02 ()) ())"));
+    assert_eq!(node.clone_truncated(3), node);
+    assert_eq!(crate::parse("0x01").clone_truncated(0), crate::parse("0x01"));
+
+    let mut tree = node.clone();
+    assert_eq!(tree.take_subtree(&[1, 1]), Some(crate::parse("(0x03)")));
+    assert_eq!(tree, crate::parse("(0x01 (0x02) ())"));
+    assert_eq!(tree.take_subtree(&[1, 1]), None);
+    assert_eq!(tree.take_subtree(&[0, 0]), None);
+    assert_eq!(tree.take_subtree(&[5]), None);
+    assert_eq!(tree.take_subtree(&[0]), Some(crate::parse("0x01")));
+    assert_eq!(tree.take_subtree(&[]), Some(crate::parse("((0x02) ())")));
+    assert_eq!(tree, Node::EMPTY_INNER);
+}
+
+#[test]
+fn compose() {
+    let mut node = crate::parse("(0x01 0x02 (0x03))");
+    let tail = node.split_off_children(1).unwrap();
+    assert_eq!((&node, &tail), (&crate::parse("(0x01)"), &crate::parse("(0x02 (0x03))")));
+    assert_eq!(node.split_off_children(2), Err(EditError::InvalidIndex { idx: 2, len: 1 }));
+    assert_eq!(node.split_off_children(1), Ok(crate::parse("()")));
+    assert_eq!(crate::parse("0x01").split_off_children(0), Err(EditError::ExpectedInner));
+
+    node.concat(tail).unwrap();
+    assert_eq!(node, crate::parse("(0x01 0x02 (0x03))"));
+    let mut leaf = crate::parse("0x01");
+    leaf.concat(crate::parse("0x02_03")).unwrap();
+    assert_eq!(leaf, crate::parse("0x01_02_03"));
+    assert_eq!(leaf.concat(crate::parse("()")), Err(EditError::ExpectedLeaf));
+    assert_eq!(node.concat(crate::parse("0x")), Err(EditError::ExpectedInner));
+
+    node.graft(&[2, 1], crate::parse("0x04")).unwrap();
+    node.graft(&[0], crate::parse("()")).unwrap();
+    assert_eq!(node, crate::parse("(() 0x01 0x02 (0x03 0x04))"));
+    assert_eq!(node.graft(&[3, 3], crate::parse("()")), Err(EditError::InvalidPath(vec!(3, 3))));
+    assert_eq!(node.graft(&[1, 0], crate::parse("()")), Err(EditError::InvalidPath(vec!(1, 0))));
+    assert_eq!(node.graft(&[], crate::parse("()")).unwrap_err().to_string(), "Invalid path [].");
+}
@@ -0,0 +1,191 @@
+use crate::{Node, DEFAULT_MAX_DEPTH};
+
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+/// How entries of a directory are represented in a tree.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum NamingStrategy {
+    /// Names aren't stored; a directory is an inner node of its entries'
+    /// contents, ordered by name. When writing, entries are named by their
+    /// index, zero-padded so that the order is preserved (e.g. `00`..`11`).
+    Omit,
+    /// Every entry is a `(name content)` node, ordered by name.
+    Include,
+}
+
+impl Node {
+    /// Reads a directory into a tree without storing names, see
+    /// `from_dir_with`.
+    pub fn from_dir<P: AsRef<Path>>(path: P) -> io::Result<Node> {
+        Self::from_dir_with(path, NamingStrategy::Omit)
+    }
+
+    /// Reads a directory into a tree. Directories become inner nodes, files
+    /// become leaves holding their contents. Entries are sorted by name so
+    /// that the result doesn't depend on the filesystem's order. Symlinks are
+    /// followed; directories nested deeper than `DEFAULT_MAX_DEPTH` (e.g.
+    /// because of symlink loops) and entries that are neither files nor
+    /// directories result in an error.
+    pub fn from_dir_with<P: AsRef<Path>>(path: P, naming: NamingStrategy) -> io::Result<Node> {
+        read_dir(path.as_ref(), naming, 0)
+    }
+
+    /// Writes the tree to the directory at `path`, which is created if it
+    /// doesn't exist. This is the inverse of `from_dir_with`: inner nodes
+    /// become directories, leaves become files. Existing files are
+    /// overwritten.
+    ///
+    /// The root has to be an inner node. With `NamingStrategy::Include`,
+    /// every entry has to be a `(name content)` node where `name` is a
+    /// single path component; `.`, `..` and names containing separators are
+    /// rejected, so that nothing is written outside of `path`.
+    pub fn write_dir<P: AsRef<Path>>(&self, path: P, naming: NamingStrategy) -> io::Result<()> {
+        match self {
+            Node::Inner(nodes) => write_dir(nodes, path.as_ref(), naming),
+            Node::Leaf(_) => Err(invalid_input(format!("Can't write leaf to directory {}", path.as_ref().display()))),
+        }
+    }
+}
+
+fn write_dir(nodes: &[Node], path: &Path, naming: NamingStrategy) -> io::Result<()> {
+    std::fs::create_dir_all(path)?;
+    let width = nodes.len().saturating_sub(1).to_string().len();
+    for (idx, node) in nodes.iter().enumerate() {
+        let (name, content) = match (naming, node) {
+            (NamingStrategy::Omit, _) => (format!("{:0width$}", idx, width = width).into(), node),
+            (NamingStrategy::Include, Node::Inner(entry)) => match entry.as_slice() {
+                [Node::Leaf(name), content] => (bytes_to_name(name)?, content),
+                _ => return Err(invalid_input(format!("Invalid entry {} in {}", idx, path.display()))),
+            },
+            (NamingStrategy::Include, Node::Leaf(_)) => {
+                return Err(invalid_input(format!("Invalid entry {} in {}", idx, path.display())));
+            }
+        };
+        let mut components = Path::new(&name).components();
+        match (components.next(), components.next()) {
+            (Some(std::path::Component::Normal(n)), None) if n == name.as_os_str() => (),
+            _ => return Err(invalid_input(format!("Invalid name {:?} in {}", name, path.display()))),
+        }
+        let path = path.join(name);
+        match content {
+            Node::Leaf(bytes) => std::fs::write(path, bytes)?,
+            Node::Inner(nodes) => write_dir(nodes, &path, naming)?,
+        }
+    }
+    Ok(())
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+fn read_dir(path: &Path, naming: NamingStrategy, depth: usize) -> io::Result<Node> {
+    if depth >= DEFAULT_MAX_DEPTH {
+        return Err(invalid_input(format!("{} is nested too deeply", path.display())));
+    }
+    let mut entries = std::fs::read_dir(path)?
+        .map(|entry| entry.map(|e| e.file_name()))
+        .collect::<io::Result<Vec<_>>>()?
+        .into_iter()
+        .map(|name| Ok((name_to_bytes(&name)?, name)))
+        .collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+
+    let mut nodes = vec!();
+    for (bytes, name) in entries {
+        let path = path.join(name);
+        let metadata = std::fs::metadata(&path)?;
+        let content = if metadata.is_dir() {
+            read_dir(&path, naming, depth + 1)?
+        } else if metadata.is_file() {
+            Node::Leaf(std::fs::read(&path)?)
+        } else {
+            return Err(invalid_input(format!("{} is neither a file nor a directory", path.display())));
+        };
+        nodes.push(match naming {
+            NamingStrategy::Omit => content,
+            NamingStrategy::Include => Node::Inner(vec!(Node::Leaf(bytes), content)),
+        });
+    }
+    Ok(Node::Inner(nodes))
+}
+
+#[cfg(unix)]
+fn name_to_bytes(name: &OsString) -> io::Result<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(name.as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn name_to_bytes(name: &OsString) -> io::Result<Vec<u8>> {
+    match name.to_str() {
+        Some(s) => Ok(s.as_bytes().to_vec()),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} isn't valid UTF-8", name))),
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_name(bytes: &[u8]) -> io::Result<OsString> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(std::ffi::OsStr::from_bytes(bytes).to_os_string())
+}
+
+#[cfg(not(unix))]
+fn bytes_to_name(bytes: &[u8]) -> io::Result<OsString> {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => Ok(s.into()),
+        Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("{:?} isn't valid UTF-8", bytes))),
+    }
+}
+
+
+/// Creates an empty directory that's unique to the calling test.
+#[cfg(test)]
+fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("baum-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn from_dir() {
+    let dir = test_dir("from-dir");
+    std::fs::create_dir_all(dir.join("b/c")).unwrap();
+    std::fs::create_dir(dir.join("empty")).unwrap();
+    std::fs::write(dir.join("a"), "abc").unwrap();
+    std::fs::write(dir.join("b/x"), [1, 2]).unwrap();
+    std::fs::write(dir.join("b/c/y"), "").unwrap();
+
+    let node = Node::from_dir(&dir).unwrap();
+    assert_eq!(node.to_string(), "(0x61_62_63 ((0x) 0x01_02) ())");
+    let node = Node::from_dir_with(&dir, NamingStrategy::Include).unwrap();
+    assert_eq!(node.to_string(), "((0x61 0x61_62_63) (0x62 ((0x63 ((0x79 0x))) (0x78 0x01_02))) (0x65_6d_70_74_79 ()))");
+
+    assert!(Node::from_dir(dir.join("a")).is_err());
+    assert!(Node::from_dir(dir.join("missing")).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn write_dir_round_trip() {
+    let dir = test_dir("write-dir");
+    let node = crate::parse("((0x61 0x61_62_63) (0x62 ((0x63 ((0x79 0x))) (0x78 0x01_02))) (0x65 ()))");
+    node.write_dir(dir.join("named"), NamingStrategy::Include).unwrap();
+    assert_eq!(std::fs::read(dir.join("named/b/x")).unwrap(), [1, 2]);
+    assert_eq!(Node::from_dir_with(dir.join("named"), NamingStrategy::Include).unwrap(), node);
+
+    let node = Node::Inner((0..11).map(|i| Node::Leaf(vec!(i))).collect());
+    node.write_dir(dir.join("indexed"), NamingStrategy::Omit).unwrap();
+    assert_eq!(std::fs::read(dir.join("indexed/03")).unwrap(), [3]);
+    assert_eq!(Node::from_dir(dir.join("indexed")).unwrap(), node);
+
+    for invalid in &["0x", "(0x)", "((0x 0x))", "((0x2e 0x))", "((0x2e_2e ()))", "((0x61_2f_62 0x))", "((0x61 0x 0x))"] {
+        let node = crate::parse(invalid);
+        assert_eq!(node.write_dir(dir.join("invalid"), NamingStrategy::Include).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+    assert!(!dir.join("b").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
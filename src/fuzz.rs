@@ -0,0 +1,55 @@
+use crate::Node;
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+/// Nesting depth of inner nodes at which `Node::arbitrary` only generates
+/// leaves, so the size of generated trees is bounded by the input's size
+/// and this depth.
+const MAX_DEPTH: usize = 16;
+
+impl<'a> Arbitrary<'a> for Node {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        arbitrary_node(u, 0)
+    }
+
+    fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+        // the choice between leaf and inner node
+        (1, None)
+    }
+}
+
+fn arbitrary_node(u: &mut Unstructured, depth: usize) -> Result<Node> {
+    if depth >= MAX_DEPTH || u.is_empty() || !bool::arbitrary(u)? {
+        return Ok(Node::Leaf(Vec::arbitrary(u)?));
+    }
+    // every child consumes at least one byte, bounding the number of children
+    let len = u.arbitrary_len::<u8>()?;
+    let mut nodes = Vec::with_capacity(len);
+    for _ in 0..len {
+        if u.is_empty() {
+            break;
+        }
+        nodes.push(arbitrary_node(u, depth + 1)?);
+    }
+    Ok(Node::Inner(nodes))
+}
+
+
+#[test]
+fn arbitrary_round_trips() {
+    // deterministic pseudo-random inputs of various sizes
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    for size in 0..200 {
+        let data: Vec<u8> = (0..size * 8).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect();
+        let node = Node::arbitrary_take_rest(Unstructured::new(&data)).unwrap();
+        assert!(node.node_count() <= data.len() + 1);
+        assert_eq!(Node::deserialize(&node.serialize()).unwrap(), node);
+        assert_eq!(crate::parse(&node.to_string()), node);
+    }
+    assert_eq!(Node::arbitrary(&mut Unstructured::new(&[])).unwrap(), Node::Leaf(vec!()));
+}
@@ -0,0 +1,177 @@
+use crate::Node;
+
+use std::borrow::Cow;
+
+/// Tree whose leaves are stored as `B`, e.g. borrowed slices (`NodeRef`) or
+/// `Cow`s (`CowNode`). Use `to_node` to convert it into an owned `Node`.
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum GenericNode<B> {
+    Leaf(B),
+    Inner(Vec<GenericNode<B>>),
+}
+
+/// Tree whose leaves are either borrowed or owned, so that a tree built over
+/// borrowed data only copies the leaves that are modified (`Cow::to_mut`).
+pub type CowNode<'a> = GenericNode<Cow<'a, [u8]>>;
+
+/// Tree whose leaves are reference-counted slices of shared buffers.
+#[cfg(feature = "bytes")]
+pub type BytesNode = GenericNode<bytes::Bytes>;
+
+/// Tree storing leaves of up to 16 bytes inline instead of allocating them.
+/// `Node` itself can't do that without changing the type of `Node::Leaf`.
+#[cfg(feature = "smallvec")]
+pub type SmallNode = GenericNode<smallvec::SmallVec<[u8; 16]>>;
+
+impl<B: AsRef<[u8]>> GenericNode<B> {
+    /// Returns the leaf's bytes, or `None` for inner nodes.
+    pub fn leaf(&self) -> Option<&[u8]> {
+        match self {
+            GenericNode::Leaf(bytes) => Some(bytes.as_ref()),
+            GenericNode::Inner(_) => None,
+        }
+    }
+
+    pub fn to_node(&self) -> Node {
+        match self {
+            GenericNode::Leaf(bytes) => Node::Leaf(bytes.as_ref().to_vec()),
+            GenericNode::Inner(nodes) => Node::Inner(nodes.iter().map(Self::to_node).collect()),
+        }
+    }
+}
+
+impl<B> GenericNode<B> {
+    /// Converts the storage of all leaves, keeping the tree's structure.
+    pub fn map_leaves<C, F: FnMut(B) -> C>(self, mut f: F) -> GenericNode<C> {
+        fn map<B, C, F: FnMut(B) -> C>(node: GenericNode<B>, f: &mut F) -> GenericNode<C> {
+            match node {
+                GenericNode::Leaf(bytes) => GenericNode::Leaf(f(bytes)),
+                GenericNode::Inner(nodes) => GenericNode::Inner(nodes.into_iter().map(|n| map(n, f)).collect()),
+            }
+        }
+        map(self, &mut f)
+    }
+}
+
+impl CowNode<'_> {
+    /// Converts the tree into a `Node`, only copying borrowed leaves.
+    pub fn into_node(self) -> Node {
+        match self {
+            GenericNode::Leaf(bytes) => Node::Leaf(bytes.into_owned()),
+            GenericNode::Inner(nodes) => Node::Inner(nodes.into_iter().map(CowNode::into_node).collect()),
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl BytesNode {
+    /// Deserializes a tree like `Node::deserialize`, with leaves sharing
+    /// `buf` instead of being copied.
+    pub fn deserialize(buf: &bytes::Bytes) -> Result<Self, crate::Error> {
+        Ok(crate::NodeRef::deserialize(buf)?.map_leaves(|leaf| buf.slice_ref(leaf)))
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl From<Node> for BytesNode {
+    fn from(node: Node) -> Self {
+        match node {
+            Node::Leaf(bytes) => GenericNode::Leaf(bytes.into()),
+            Node::Inner(nodes) => GenericNode::Inner(nodes.into_iter().map(BytesNode::from).collect()),
+        }
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl From<&Node> for SmallNode {
+    fn from(node: &Node) -> Self {
+        GenericNode::<&[u8]>::from(node).map_leaves(smallvec::SmallVec::from_slice)
+    }
+}
+
+impl<'a> From<&'a Node> for GenericNode<&'a [u8]> {
+    fn from(node: &'a Node) -> Self {
+        match node {
+            Node::Leaf(bytes) => GenericNode::Leaf(bytes),
+            Node::Inner(nodes) => GenericNode::Inner(nodes.iter().map(GenericNode::from).collect()),
+        }
+    }
+}
+
+impl<'a> From<&'a Node> for CowNode<'a> {
+    fn from(node: &'a Node) -> Self {
+        GenericNode::<&[u8]>::from(node).map_leaves(Cow::Borrowed)
+    }
+}
+
+impl From<Node> for CowNode<'_> {
+    fn from(node: Node) -> Self {
+        match node {
+            Node::Leaf(bytes) => GenericNode::Leaf(Cow::Owned(bytes)),
+            Node::Inner(nodes) => GenericNode::Inner(nodes.into_iter().map(CowNode::from).collect()),
+        }
+    }
+}
+
+impl<B: AsRef<[u8]>> From<&GenericNode<B>> for Node {
+    fn from(node: &GenericNode<B>) -> Self {
+        node.to_node()
+    }
+}
+
+
+#[test]
+fn cow_node() {
+    let node = Node::Inner(vec!(Node::Leaf(vec!(1)), Node::Inner(vec!(Node::Leaf(vec!(2, 3))))));
+    let mut cow = CowNode::from(&node);
+    if let GenericNode::Inner(nodes) = &mut cow {
+        if let GenericNode::Leaf(bytes) = &mut nodes[0] {
+            bytes.to_mut().push(4);
+        }
+    }
+    match &cow {
+        GenericNode::Inner(nodes) => {
+            assert!(matches!(nodes[0], GenericNode::Leaf(Cow::Owned(_))));
+            assert_eq!(nodes[0].leaf(), Some(&[1, 4][..]));
+            assert!(matches!(&nodes[1], GenericNode::Inner(n) if matches!(n[0], GenericNode::Leaf(Cow::Borrowed(_)))));
+        }
+        GenericNode::Leaf(_) => panic!(),
+    }
+    let expected = Node::Inner(vec!(Node::Leaf(vec!(1, 4)), Node::Inner(vec!(Node::Leaf(vec!(2, 3))))));
+    assert_eq!(cow.to_node(), expected);
+    assert_eq!(cow.into_node(), expected);
+    assert_eq!(CowNode::from(node.clone()).into_node(), node);
+
+    let lengths = GenericNode::<&[u8]>::from(&node).map_leaves(|b| vec!(b.len() as u8));
+    assert_eq!(lengths.to_node(), Node::Inner(vec!(Node::Leaf(vec!(1)), Node::Inner(vec!(Node::Leaf(vec!(2)))))));
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_node() {
+    let node = Node::Inner(vec!(Node::Leaf(vec!(1, 2)), Node::Inner(vec!(Node::Leaf(vec!(3))))));
+    let buf = bytes::Bytes::from(node.serialize());
+    let bytes_node = BytesNode::deserialize(&buf).unwrap();
+    match &bytes_node {
+        GenericNode::Inner(nodes) => assert_eq!(nodes[0].leaf().unwrap().as_ptr(), buf[23..].as_ptr()),
+        GenericNode::Leaf(_) => panic!(),
+    }
+    assert_eq!(bytes_node.to_node(), node);
+    assert_eq!(BytesNode::from(node.clone()), bytes_node);
+    assert!(BytesNode::deserialize(&buf.slice(..10)).is_err());
+}
+
+#[cfg(feature = "smallvec")]
+#[test]
+fn small_node() {
+    let node = Node::Inner(vec!(Node::Leaf(vec!(1; 16)), Node::Leaf(vec!(2; 17))));
+    let small = SmallNode::from(&node);
+    match &small {
+        GenericNode::Inner(nodes) => match (&nodes[0], &nodes[1]) {
+            (GenericNode::Leaf(a), GenericNode::Leaf(b)) => assert!(!a.spilled() && b.spilled()),
+            _ => panic!(),
+        },
+        GenericNode::Leaf(_) => panic!(),
+    }
+    assert_eq!(small.to_node(), node);
+}
@@ -0,0 +1,379 @@
+//! Conversion between `Node`s and git objects.
+//!
+//! A blob maps to a leaf containing its content. A tree maps to an inner
+//! node with one `(mode name id)` node per entry, e.g.
+//!
+//! ```text
+//! ("100644" "README" 0xe6_9d_..._91)
+//! ```
+//!
+//! Objects are encoded including their header (`blob 12\0...`) as used for
+//! computing object IDs, but without the zlib compression of loose objects.
+//!
+//! `Node::to_git_objects` and `Node::from_git_objects` convert whole object
+//! graphs to and from trees in the layout of `NamingStrategy::Include`, i.e.
+//! with `(name content)` entries, where the contents of subtrees and blobs
+//! are stored in place of their IDs.
+
+use crate::{Node, Path, DEFAULT_MAX_DEPTH};
+
+use sha1::Digest;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum HashAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    /// Length of object IDs in bytes.
+    pub fn id_len(self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// Computes the ID of an encoded object.
+    pub fn object_id(self, object: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha1 => sha1::Sha1::digest(object).to_vec(),
+            HashAlgorithm::Sha256 => sha2::Sha256::digest(object).to_vec(),
+        }
+    }
+}
+
+/// ID and encoding of an object.
+pub type Object = (Vec<u8>, Vec<u8>);
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// The object header is malformed or doesn't match the object's length.
+    InvalidHeader,
+    /// An object that's neither a blob nor a tree, e.g. a commit.
+    UnsupportedType(String),
+    /// A malformed tree entry starting at the given position.
+    InvalidEntry(usize),
+    /// A node that doesn't follow the mapping described in the module docs.
+    InvalidNode(Path),
+    /// A tree entry that's neither a file nor a tree, e.g. a symlink or a
+    /// submodule.
+    UnsupportedMode(Path),
+    MissingObject(Vec<u8>),
+    /// An object whose ID or type doesn't match the entry referencing it.
+    InvalidObject(Vec<u8>),
+    /// Trees nested deeper than `DEFAULT_MAX_DEPTH`.
+    NestingTooDeep(Path),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::InvalidHeader => write!(f, "Invalid object header."),
+            Error::UnsupportedType(t) => write!(f, "Unsupported object type {:?}.", t),
+            Error::InvalidEntry(pos) => write!(f, "Invalid tree entry at position {}.", pos),
+            Error::InvalidNode(p) => write!(f, "Node at path {:?} isn't a valid git object.", p),
+            Error::UnsupportedMode(p) => write!(f, "Tree entry at path {:?} has an unsupported mode.", p),
+            Error::MissingObject(id) => write!(f, "Missing object {}.", hex(id)),
+            Error::InvalidObject(id) => write!(f, "Object {} doesn't match its entry.", hex(id)),
+            Error::NestingTooDeep(p) => write!(f, "Tree at path {:?} is nested too deeply.", p),
+        }
+    }
+}
+
+impl std::error::Error for Error { }
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+const TREE_MODE: &[u8] = b"40000";
+const FILE_MODE: &[u8] = b"100644";
+const EXECUTABLE_MODE: &[u8] = b"100755";
+
+fn is_valid_mode(mode: &[u8]) -> bool {
+    !mode.is_empty() && mode.iter().all(|b| (b'0'..=b'7').contains(b))
+}
+
+fn is_valid_name(name: &[u8]) -> bool {
+    !name.is_empty() && !name.contains(&b'/') && !name.contains(&0)
+}
+
+pub fn encode(node: &Node, hash: HashAlgorithm) -> Result<Vec<u8>, Error> {
+    encode_at(node, hash, &vec!())
+}
+
+fn encode_at(node: &Node, hash: HashAlgorithm, path: &Path) -> Result<Vec<u8>, Error> {
+    let (kind, payload) = match node {
+        Node::Leaf(content) => ("blob", std::borrow::Cow::Borrowed(content)),
+        Node::Inner(entries) => ("tree", std::borrow::Cow::Owned(encode_tree(entries, hash, path)?)),
+    };
+    let mut res = format!("{} {}\0", kind, payload.len()).into_bytes();
+    res.extend_from_slice(&payload);
+    Ok(res)
+}
+
+struct Entry<'a> {
+    idx: usize,
+    mode: &'a [u8],
+    name: &'a [u8],
+    id: &'a [u8],
+}
+
+impl Entry<'_> {
+    /// Git sorts trees as if their names ended with a slash.
+    fn sort_key(&self) -> Vec<u8> {
+        let mut key = self.name.to_vec();
+        if self.mode == TREE_MODE {
+            key.push(b'/');
+        }
+        key
+    }
+}
+
+fn encode_tree(entries: &[Node], hash: HashAlgorithm, path: &Path) -> Result<Vec<u8>, Error> {
+    let invalid = |idx| {
+        let mut path = path.clone();
+        path.push(idx);
+        Error::InvalidNode(path)
+    };
+    let mut entries = entries.iter().enumerate().map(|(idx, entry)| match entry {
+        Node::Inner(fields) => match fields.as_slice() {
+            [Node::Leaf(mode), Node::Leaf(name), Node::Leaf(id)]
+                if is_valid_mode(mode) && is_valid_name(name) && id.len() == hash.id_len() => Ok(Entry { idx, mode, name, id }),
+            _ => Err(invalid(idx)),
+        },
+        Node::Leaf(_) => Err(invalid(idx)),
+    }).collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(Entry::sort_key);
+    if let Some(w) = entries.windows(2).find(|w| w[0].name == w[1].name) {
+        return Err(invalid(w[0].idx.max(w[1].idx)));
+    }
+
+    let mut res = vec!();
+    for Entry { mode, name, id, .. } in entries {
+        res.extend_from_slice(mode);
+        res.push(b' ');
+        res.extend_from_slice(name);
+        res.push(0);
+        res.extend_from_slice(id);
+    }
+    Ok(res)
+}
+
+pub fn decode(bytes: &[u8], hash: HashAlgorithm) -> Result<Node, Error> {
+    let header_len = bytes.iter().position(|&b| b == 0).ok_or(Error::InvalidHeader)?;
+    let (kind, len) = match bytes[..header_len].iter().position(|&b| b == b' ') {
+        Some(pos) => (&bytes[..pos], &bytes[pos + 1..header_len]),
+        None => return Err(Error::InvalidHeader),
+    };
+    let payload = &bytes[header_len + 1..];
+    if len.is_empty() || !len.iter().all(u8::is_ascii_digit) || len != payload.len().to_string().as_bytes() {
+        return Err(Error::InvalidHeader);
+    }
+    match kind {
+        b"blob" => Ok(Node::Leaf(payload.to_vec())),
+        b"tree" => decode_tree(payload, header_len + 1, hash),
+        _ => Err(Error::UnsupportedType(String::from_utf8_lossy(kind).into_owned())),
+    }
+}
+
+fn decode_tree(payload: &[u8], offset: usize, hash: HashAlgorithm) -> Result<Node, Error> {
+    let mut entries = vec!();
+    let mut pos = 0;
+    while pos < payload.len() {
+        let invalid = Error::InvalidEntry(offset + pos);
+        let rest = &payload[pos..];
+        let (mode_len, name_len) = match rest.iter().position(|&b| b == b' ') {
+            Some(mode_len) => match rest[mode_len + 1..].iter().position(|&b| b == 0) {
+                Some(name_len) => (mode_len, name_len),
+                None => return Err(invalid),
+            },
+            None => return Err(invalid),
+        };
+        let (mode, name) = (&rest[..mode_len], &rest[mode_len + 1..mode_len + 1 + name_len]);
+        let id_start = mode_len + name_len + 2;
+        if !is_valid_mode(mode) || !is_valid_name(name) || rest.len() < id_start + hash.id_len() {
+            return Err(invalid);
+        }
+        let id = &rest[id_start..id_start + hash.id_len()];
+        entries.push(Node::Inner(vec!(Node::Leaf(mode.to_vec()), Node::Leaf(name.to_vec()), Node::Leaf(id.to_vec()))));
+        pos += id_start + hash.id_len();
+    }
+    Ok(Node::Inner(entries))
+}
+
+fn write_objects(node: &Node, hash: HashAlgorithm, path: &mut Path, objects: &mut Vec<Object>) -> Result<Vec<u8>, Error> {
+    let object = match node {
+        Node::Leaf(_) => encode(node, hash)?,
+        Node::Inner(entries) => {
+            let mut tree = vec!();
+            for (idx, entry) in entries.iter().enumerate() {
+                path.push(idx);
+                match entry {
+                    Node::Inner(fields) => match fields.as_slice() {
+                        [name @ Node::Leaf(_), content] => {
+                            let mode = if let Node::Leaf(_) = content { FILE_MODE } else { TREE_MODE };
+                            let id = write_objects(content, hash, path, objects)?;
+                            tree.push(Node::Inner(vec!(Node::Leaf(mode.to_vec()), name.clone(), Node::Leaf(id))));
+                        }
+                        _ => return Err(Error::InvalidNode(path.clone())),
+                    },
+                    Node::Leaf(_) => return Err(Error::InvalidNode(path.clone())),
+                }
+                path.pop();
+            }
+            encode_at(&Node::Inner(tree), hash, path)?
+        }
+    };
+    let id = hash.object_id(&object);
+    objects.push((id.clone(), object));
+    Ok(id)
+}
+
+fn read_objects<F>(id: &[u8], hash: HashAlgorithm, lookup: &mut F, path: &mut Path) -> Result<Node, Error>
+    where F: FnMut(&[u8]) -> Option<Vec<u8>>
+{
+    if path.len() >= DEFAULT_MAX_DEPTH {
+        return Err(Error::NestingTooDeep(path.clone()));
+    }
+    let object = lookup(id).ok_or_else(|| Error::MissingObject(id.to_vec()))?;
+    if hash.object_id(&object) != id {
+        return Err(Error::InvalidObject(id.to_vec()));
+    }
+    let entries = match decode(&object, hash)? {
+        Node::Inner(entries) => entries,
+        leaf => return Ok(leaf),
+    };
+    let mut res = vec!();
+    for (idx, entry) in entries.into_iter().enumerate() {
+        path.push(idx);
+        let (mode, name, id) = match entry {
+            Node::Inner(mut fields) => match (fields.pop(), fields.pop(), fields.pop()) {
+                (Some(Node::Leaf(id)), Some(name), Some(Node::Leaf(mode))) => (mode, name, id),
+                _ => unreachable!(),
+            },
+            Node::Leaf(_) => unreachable!(),
+        };
+        let content = read_objects(&id, hash, lookup, path)?;
+        let is_tree = matches!(content, Node::Inner(_));
+        match mode.as_slice() {
+            TREE_MODE if is_tree => (),
+            FILE_MODE | EXECUTABLE_MODE if !is_tree => (),
+            TREE_MODE | FILE_MODE | EXECUTABLE_MODE => return Err(Error::InvalidObject(id)),
+            _ => return Err(Error::UnsupportedMode(path.clone())),
+        }
+        res.push(Node::Inner(vec!(name, content)));
+        path.pop();
+    }
+    // use the same order as `Node::from_dir_with`
+    res.sort();
+    Ok(Node::Inner(res))
+}
+
+impl Node {
+    /// Encodes a blob (leaf) or tree (inner node), see the module docs.
+    /// Tree entries are sorted in git's order.
+    pub fn to_git_object(&self, hash: HashAlgorithm) -> Result<Vec<u8>, Error> {
+        encode(self, hash)
+    }
+
+    pub fn from_git_object(bytes: &[u8], hash: HashAlgorithm) -> Result<Node, Error> {
+        decode(bytes, hash)
+    }
+
+    /// Encodes a tree of `(name content)` entries as blobs and trees.
+    /// Returns the IDs and encodings of all objects, with the root object
+    /// last. Leaves are stored as regular files (mode `100644`).
+    pub fn to_git_objects(&self, hash: HashAlgorithm) -> Result<Vec<Object>, Error> {
+        let mut objects = vec!();
+        write_objects(self, hash, &mut vec!(), &mut objects)?;
+        Ok(objects)
+    }
+
+    /// Reads the object graph starting at the object with ID `root`, using
+    /// `lookup` to get encoded objects by their IDs. Returns a tree of
+    /// `(name content)` entries, sorted by name. Objects are checked against
+    /// their IDs; symlinks and submodules aren't supported.
+    pub fn from_git_objects<F>(root: &[u8], hash: HashAlgorithm, mut lookup: F) -> Result<Node, Error>
+        where F: FnMut(&[u8]) -> Option<Vec<u8>>
+    {
+        read_objects(root, hash, &mut lookup, &mut vec!())
+    }
+}
+
+
+#[test]
+fn git_objects() {
+    let blob = Node::Leaf(vec!()).to_git_object(HashAlgorithm::Sha1).unwrap();
+    assert_eq!(blob, b"blob 0\0");
+    assert_eq!(hex(&HashAlgorithm::Sha1.object_id(&blob)), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+    assert_eq!(hex(&HashAlgorithm::Sha256.object_id(&blob)), "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813");
+    let tree = Node::Inner(vec!()).to_git_object(HashAlgorithm::Sha1).unwrap();
+    assert_eq!(hex(&HashAlgorithm::Sha1.object_id(&tree)), "4b825dc642cb6eb9a060e54bf8d69288fbee4904");
+
+    // trees are sorted as if their names ended with a slash
+    let id = Node::Leaf(vec!(0xab; 20));
+    let dir = Node::Inner(vec!(Node::Leaf(TREE_MODE.to_vec()), Node::Leaf(b"a".to_vec()), id.clone()));
+    let file = Node::Inner(vec!(Node::Leaf(FILE_MODE.to_vec()), Node::Leaf(b"a.txt".to_vec()), id));
+    let tree = Node::Inner(vec!(dir.clone(), file.clone()));
+    let object = tree.to_git_object(HashAlgorithm::Sha1).unwrap();
+    let mut expected = b"tree 61\x00100644 a.txt\0".to_vec();
+    expected.extend_from_slice(&[0xab; 20]);
+    expected.extend_from_slice(b"40000 a\0");
+    expected.extend_from_slice(&[0xab; 20]);
+    assert_eq!(object, expected);
+    let decoded = Node::from_git_object(&object, HashAlgorithm::Sha1).unwrap();
+    assert_eq!(decoded, Node::Inner(vec!(file, dir)));
+}
+
+#[test]
+fn git_object_graphs() {
+    let node = crate::parse("((\"a\" ((\"b\" \"content\"))) (\"a.txt\" 0x) (\"c\" ()))");
+    for &hash in &[HashAlgorithm::Sha1, HashAlgorithm::Sha256] {
+        let objects = node.to_git_objects(hash).unwrap();
+        assert_eq!(objects.len(), 5);
+        let root = objects.last().unwrap().0.clone();
+        let map: std::collections::HashMap<_, _> = objects.into_iter().collect();
+        let lookup = |id: &[u8]| map.get(id).cloned();
+        assert_eq!(Node::from_git_objects(&root, hash, lookup), Ok(node.clone()));
+    }
+    // verified using `git mktree`
+    let objects = node.to_git_objects(HashAlgorithm::Sha1).unwrap();
+    assert_eq!(hex(&objects.last().unwrap().0), "1659cf6846388b60834ec15df9f3c51fdd889152");
+}
+
+#[test]
+fn git_errors() {
+    let hash = HashAlgorithm::Sha1;
+    assert_eq!(Node::from_git_object(b"blob 1", hash), Err(Error::InvalidHeader));
+    assert_eq!(Node::from_git_object(b"blob 1\0", hash), Err(Error::InvalidHeader));
+    assert_eq!(Node::from_git_object(b"blob +1\0a", hash), Err(Error::InvalidHeader));
+    assert_eq!(Node::from_git_object(b"commit 0\0", hash), Err(Error::UnsupportedType("commit".to_string())));
+    assert_eq!(Node::from_git_object(b"tree 8\x00100644 a", hash), Err(Error::InvalidEntry(7)));
+    assert_eq!(Node::from_git_object(b"tree 10\x00100644 a\0\0", hash), Err(Error::InvalidEntry(8)));
+
+    let entry = |mode: &str, name: &str| format!("(\"{}\" \"{}\" 0x{})", mode, name, "ab".repeat(20));
+    let node = crate::parse(&format!("({} {})", entry("100644", "a"), entry("100644", "b/c")));
+    assert_eq!(node.to_git_object(hash), Err(Error::InvalidNode(vec!(1))));
+    let node = crate::parse(&format!("({} {})", entry("100644", "a"), entry("40000", "a")));
+    assert_eq!(node.to_git_object(hash), Err(Error::InvalidNode(vec!(1))));
+    let node = crate::parse("((\"a\" ((\"b\" 0x 0x))))");
+    assert_eq!(node.to_git_objects(hash), Err(Error::InvalidNode(vec!(0, 0))));
+
+    let node = crate::parse(&format!("({})", entry("120000", "link")));
+    let link = Node::Leaf(vec!()).to_git_object(hash).unwrap();
+    let mut tree = node.to_git_object(hash).unwrap();
+    let (root, blob) = (hash.object_id(&tree), hash.object_id(&link));
+    assert_eq!(Node::from_git_objects(&root, hash, |_| None), Err(Error::MissingObject(root.clone())));
+    let lookup = |id: &[u8]| if id == root.as_slice() { Some(tree.clone()) } else { None };
+    assert_eq!(Node::from_git_objects(&root, hash, lookup), Err(Error::MissingObject(vec!(0xab; 20))));
+    let lookup = |id: &[u8]| if id == root.as_slice() { Some(link.clone()) } else { None };
+    assert_eq!(Node::from_git_objects(&root, hash, lookup), Err(Error::InvalidObject(root.clone())));
+
+    let node = crate::parse(&format!("((\"120000\" \"link\" 0x{}))", hex(&blob)));
+    tree = node.to_git_object(hash).unwrap();
+    let root = hash.object_id(&tree);
+    let lookup = |id: &[u8]| if id == root.as_slice() { Some(tree.clone()) } else { Some(link.clone()) };
+    assert_eq!(Node::from_git_objects(&root, hash, lookup), Err(Error::UnsupportedMode(vec!(0))));
+}
@@ -0,0 +1,234 @@
+//! Conversions to and from the tree and graph types of other crates.
+//!
+//! Every node becomes a vertex with an `Option<Vec<u8>>` value: the bytes of
+//! a leaf, or `None` for an inner node. Children keep their order.
+
+use crate::{Node, Path};
+
+use std::convert::TryFrom;
+
+#[derive(Debug, PartialEq)]
+pub enum GraphError {
+    /// A vertex with a leaf value that has children.
+    LeafWithChildren(Path),
+    /// A graph that's empty, contains cycles, vertices with multiple parents
+    /// or vertices that aren't reachable from the root.
+    NotATree,
+}
+
+impl std::fmt::Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            GraphError::LeafWithChildren(p) => write!(f, "Leaf at path {:?} has children.", p),
+            GraphError::NotATree => write!(f, "Graph isn't a tree."),
+        }
+    }
+}
+
+impl std::error::Error for GraphError { }
+
+fn value(node: &Node) -> Option<Vec<u8>> {
+    match node {
+        Node::Leaf(bytes) => Some(bytes.clone()),
+        Node::Inner(_) => None,
+    }
+}
+
+fn children(node: &Node) -> &[Node] {
+    match node {
+        Node::Leaf(_) => &[],
+        Node::Inner(nodes) => nodes,
+    }
+}
+
+/// Builds a node from a vertex's value and its children.
+fn build(value: &Option<Vec<u8>>, children: Vec<Node>, path: &Path) -> Result<Node, GraphError> {
+    match value {
+        Some(_) if !children.is_empty() => Err(GraphError::LeafWithChildren(path.clone())),
+        Some(bytes) => Ok(Node::Leaf(bytes.clone())),
+        None => Ok(Node::Inner(children)),
+    }
+}
+
+#[cfg(feature = "ego-tree")]
+mod ego {
+    use super::*;
+    use ego_tree::{NodeMut, NodeRef, Tree};
+
+    impl From<&Node> for Tree<Option<Vec<u8>>> {
+        fn from(node: &Node) -> Self {
+            fn append(node: &Node, mut parent: NodeMut<Option<Vec<u8>>>) {
+                for child in children(node) {
+                    append(child, parent.append(value(child)));
+                }
+            }
+            let mut tree = Tree::new(value(node));
+            append(node, tree.root_mut());
+            tree
+        }
+    }
+
+    impl TryFrom<&Tree<Option<Vec<u8>>>> for Node {
+        type Error = GraphError;
+
+        fn try_from(tree: &Tree<Option<Vec<u8>>>) -> Result<Self, Self::Error> {
+            fn convert(node: NodeRef<Option<Vec<u8>>>, path: &mut Path) -> Result<Node, GraphError> {
+                let mut children = vec!();
+                for (idx, child) in node.children().enumerate() {
+                    path.push(idx);
+                    children.push(convert(child, path)?);
+                    path.pop();
+                }
+                build(node.value(), children, path)
+            }
+            convert(tree.root(), &mut vec!())
+        }
+    }
+}
+
+#[cfg(feature = "indextree")]
+mod index {
+    use super::*;
+    use indextree::{Arena, NodeId};
+
+    /// Returns the arena and the ID of the root.
+    impl From<&Node> for (Arena<Option<Vec<u8>>>, NodeId) {
+        fn from(node: &Node) -> Self {
+            fn add(node: &Node, arena: &mut Arena<Option<Vec<u8>>>) -> NodeId {
+                let id = arena.new_node(value(node));
+                for child in children(node) {
+                    let child = add(child, arena);
+                    id.append(child, arena);
+                }
+                id
+            }
+            let mut arena = Arena::new();
+            let root = add(node, &mut arena);
+            (arena, root)
+        }
+    }
+
+    /// Converts the subtree starting at the given node.
+    impl TryFrom<(&Arena<Option<Vec<u8>>>, NodeId)> for Node {
+        type Error = GraphError;
+
+        fn try_from((arena, root): (&Arena<Option<Vec<u8>>>, NodeId)) -> Result<Self, Self::Error> {
+            fn convert(arena: &Arena<Option<Vec<u8>>>, id: NodeId, path: &mut Path) -> Result<Node, GraphError> {
+                let mut children = vec!();
+                for (idx, child) in id.children(arena).enumerate() {
+                    path.push(idx);
+                    children.push(convert(arena, child, path)?);
+                    path.pop();
+                }
+                let value = arena.get(id).ok_or(GraphError::NotATree)?.get();
+                build(value, children, path)
+            }
+            convert(arena, root, &mut vec!())
+        }
+    }
+}
+
+#[cfg(feature = "petgraph")]
+mod petgraph_impl {
+    use super::*;
+    use petgraph::graph::{DiGraph, NodeIndex};
+    use petgraph::visit::EdgeRef;
+
+    /// Builds a directed graph with edges from parents to their children.
+    /// The root has index 0, edge weights are the children's indices.
+    impl From<&Node> for DiGraph<Option<Vec<u8>>, usize> {
+        fn from(node: &Node) -> Self {
+            fn add(node: &Node, graph: &mut DiGraph<Option<Vec<u8>>, usize>) -> NodeIndex {
+                let idx = graph.add_node(value(node));
+                for (child_idx, child) in children(node).iter().enumerate() {
+                    let child = add(child, graph);
+                    graph.add_edge(idx, child, child_idx);
+                }
+                idx
+            }
+            let mut graph = DiGraph::new();
+            add(node, &mut graph);
+            graph
+        }
+    }
+
+    /// Converts a graph with its root at index 0 whose edges lead from
+    /// parents to children. Children are ordered by edge weight.
+    impl TryFrom<&DiGraph<Option<Vec<u8>>, usize>> for Node {
+        type Error = GraphError;
+
+        fn try_from(graph: &DiGraph<Option<Vec<u8>>, usize>) -> Result<Self, Self::Error> {
+            fn convert(graph: &DiGraph<Option<Vec<u8>>, usize>, idx: NodeIndex, visited: &mut [bool], path: &mut Path) -> Result<Node, GraphError> {
+                if std::mem::replace(&mut visited[idx.index()], true) {
+                    return Err(GraphError::NotATree);
+                }
+                let mut edges: Vec<_> = graph.edges(idx).collect();
+                edges.sort_by_key(|e| *e.weight());
+                let mut children = vec!();
+                for (child_idx, edge) in edges.into_iter().enumerate() {
+                    path.push(child_idx);
+                    children.push(convert(graph, edge.target(), visited, path)?);
+                    path.pop();
+                }
+                build(&graph[idx], children, path)
+            }
+            if graph.node_count() == 0 {
+                return Err(GraphError::NotATree);
+            }
+            let mut visited = vec!(false; graph.node_count());
+            let node = convert(graph, NodeIndex::new(0), &mut visited, &mut vec!())?;
+            match visited.contains(&false) {
+                true => Err(GraphError::NotATree),
+                false => Ok(node),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+fn sample() -> Node {
+    crate::parse("(0x01 (0x02_03 () (0x04)) 0x)")
+}
+
+#[cfg(feature = "ego-tree")]
+#[test]
+fn ego_tree_conversion() {
+    let node = sample();
+    let mut tree = ego_tree::Tree::from(&node);
+    assert_eq!(tree.root().children().count(), 3);
+    assert_eq!(Node::try_from(&tree), Ok(node));
+
+    tree.root_mut().first_child().unwrap().append(None);
+    assert_eq!(Node::try_from(&tree), Err(GraphError::LeafWithChildren(vec!(0))));
+}
+
+#[cfg(feature = "indextree")]
+#[test]
+fn indextree_conversion() {
+    let node = sample();
+    let (mut arena, root) = <(indextree::Arena<_>, _)>::from(&node);
+    assert_eq!(root.descendants(&arena).count(), 8);
+    assert_eq!(Node::try_from((&arena, root)), Ok(node));
+
+    let leaf = root.children(&arena).nth(1).unwrap().children(&arena).next().unwrap();
+    let child = arena.new_node(None);
+    leaf.append(child, &mut arena);
+    assert_eq!(Node::try_from((&arena, root)), Err(GraphError::LeafWithChildren(vec!(1, 0))));
+}
+
+#[cfg(feature = "petgraph")]
+#[test]
+fn petgraph_conversion() {
+    use petgraph::graph::{DiGraph, NodeIndex};
+
+    let node = sample();
+    let mut graph = DiGraph::from(&node);
+    assert_eq!((graph.node_count(), graph.edge_count()), (8, 7));
+    assert_eq!(petgraph::algo::toposort(&graph, None).unwrap()[0], NodeIndex::new(0));
+    assert_eq!(Node::try_from(&graph), Ok(node));
+
+    graph.add_edge(NodeIndex::new(4), NodeIndex::new(0), 0);
+    assert_eq!(Node::try_from(&graph), Err(GraphError::NotATree));
+    assert_eq!(Node::try_from(&DiGraph::new()), Err(GraphError::NotATree));
+}
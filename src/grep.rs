@@ -0,0 +1,69 @@
+use crate::{Node, Path};
+
+use regex::bytes::{Match, Regex, Replacer};
+
+use std::borrow::Cow;
+
+impl Node {
+    /// Returns all matches of `regex` in the leaves of the tree, in
+    /// depth-first order, together with the paths of their leaves. Matches
+    /// don't span multiple leaves.
+    pub fn grep<'a>(&'a self, regex: &Regex) -> Vec<(Path, Match<'a>)> {
+        let mut res = vec!();
+        grep_into(self, regex, &mut vec!(), &mut res);
+        res
+    }
+
+    /// Replaces all matches of `regex` in the leaves of the tree and returns
+    /// the number of leaves that changed.
+    pub fn replace_all<R: Replacer>(&mut self, regex: &Regex, mut replacement: R) -> usize {
+        replace_all(self, regex, &mut replacement)
+    }
+}
+
+fn replace_all<R: Replacer>(node: &mut Node, regex: &Regex, replacement: &mut R) -> usize {
+    match node {
+        Node::Leaf(bytes) => match regex.replace_all(bytes, replacement.by_ref()) {
+            Cow::Owned(replaced) => {
+                *bytes = replaced;
+                1
+            }
+            Cow::Borrowed(_) => 0,
+        },
+        Node::Inner(nodes) => nodes.iter_mut().map(|n| replace_all(n, regex, replacement)).sum(),
+    }
+}
+
+fn grep_into<'a>(node: &'a Node, regex: &Regex, path: &mut Path, res: &mut Vec<(Path, Match<'a>)>) {
+    match node {
+        Node::Leaf(bytes) => res.extend(regex.find_iter(bytes).map(|m| (path.clone(), m))),
+        Node::Inner(nodes) => {
+            for (idx, node) in nodes.iter().enumerate() {
+                path.push(idx);
+                grep_into(node, regex, path, res);
+                path.pop();
+            }
+        }
+    }
+}
+
+
+#[test]
+fn grep() {
+    let mut node = Node::Inner(vec!(
+        Node::Leaf(b"user=alice pw=hunter2".to_vec()),
+        Node::Inner(vec!(Node::Leaf(b"pw=x".to_vec()), Node::Leaf(vec!(0xff, 0x00)))),
+    ));
+    let regex = Regex::new(r"pw=(\w+)").unwrap();
+    let matches: Vec<_> = node.grep(&regex).into_iter().map(|(path, m)| (path, m.as_bytes())).collect();
+    assert_eq!(matches, vec!((vec!(0), &b"pw=hunter2"[..]), (vec!(1, 0), &b"pw=x"[..])));
+    assert_eq!(node.grep(&Regex::new(r"(?-u)\xff").unwrap())[0].1.range(), 0..1);
+
+    assert_eq!(node.replace_all(&regex, &b"pw=***"[..]), 2);
+    assert_eq!(node.replace_all(&Regex::new("user=(\\w+)").unwrap(), &b"user=<$1>"[..]), 1);
+    assert_eq!(node, Node::Inner(vec!(
+        Node::Leaf(b"user=<alice> pw=***".to_vec()),
+        Node::Inner(vec!(Node::Leaf(b"pw=***".to_vec()), Node::Leaf(vec!(0xff, 0x00)))),
+    )));
+    assert_eq!(node.replace_all(&Regex::new("nothing").unwrap(), &b""[..]), 0);
+}
@@ -0,0 +1,76 @@
+use crate::HexCase;
+
+const LOWER: &[u8; 16] = b"0123456789abcdef";
+const UPPER: &[u8; 16] = b"0123456789ABCDEF";
+
+/// Values of the ASCII hex digits, `0xff` for all other bytes.
+const DIGITS: [u8; 256] = {
+    let mut table = [0xff; 256];
+    let mut i = 0;
+    while i < 10 {
+        table[b'0' as usize + i] = i as u8;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 6 {
+        table[b'a' as usize + i] = 10 + i as u8;
+        table[b'A' as usize + i] = 10 + i as u8;
+        i += 1;
+    }
+    table
+};
+
+/// Appends two hex digits per byte to `out`, putting `sep` between bytes if
+/// given.
+pub fn encode_into(bytes: &[u8], case: HexCase, sep: Option<char>, out: &mut String) {
+    let table = match case {
+        HexCase::Lower => LOWER,
+        HexCase::Upper => UPPER,
+    };
+    out.reserve(bytes.len() * if sep.is_some() { 3 } else { 2 });
+    for (i, b) in bytes.iter().enumerate() {
+        if let (true, Some(sep)) = (i > 0, sep) {
+            out.push(sep);
+        }
+        out.push(table[(b >> 4) as usize] as char);
+        out.push(table[(b & 0x0f) as usize] as char);
+    }
+}
+
+/// Returns the value of the hex digit `c`.
+pub fn digit(c: char) -> Option<u8> {
+    match DIGITS.get(c as usize) {
+        Some(&d) if d != 0xff => Some(d),
+        _ => None,
+    }
+}
+
+/// Packs pairs of digit values into bytes. For an odd number of digits, the
+/// first one forms a byte on its own.
+pub fn pack(digits: &[u8]) -> Vec<u8> {
+    let (head, rest) = digits.split_at(digits.len() % 2);
+    let mut res = Vec::with_capacity(digits.len() / 2 + 1);
+    res.extend_from_slice(head);
+    res.extend(rest.chunks_exact(2).map(|pair| (pair[0] << 4) | pair[1]));
+    res
+}
+
+
+#[test]
+fn hex_tables() {
+    for b in 0..=255u8 {
+        let mut s = String::new();
+        encode_into(&[b], HexCase::Lower, None, &mut s);
+        encode_into(&[b], HexCase::Upper, None, &mut s);
+        assert_eq!(s, format!("{:02x}{:02X}", b, b));
+        assert_eq!(digit(b as char), (b as char).to_digit(16).map(|d| d as u8));
+    }
+    assert_eq!(digit('\u{0130}'), None);
+
+    let mut s = String::new();
+    encode_into(&[0x01, 0xab, 0xff], HexCase::Lower, Some('_'), &mut s);
+    assert_eq!(s, "01_ab_ff");
+    assert_eq!(pack(&[1, 0xa, 0xb]), vec!(0x01, 0xab));
+    assert_eq!(pack(&[0xa, 0xb]), vec!(0xab));
+    assert!(pack(&[]).is_empty());
+}
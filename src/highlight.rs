@@ -0,0 +1,118 @@
+use crate::Node;
+
+use std::fmt::Write;
+
+/// Terminal color, rendered as ANSI SGR escape sequence.
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite,
+    /// One of the 256 colors of the extended palette.
+    Fixed(u8),
+}
+
+impl Color {
+    fn write_escape<W: Write>(self, w: &mut W) -> std::fmt::Result {
+        let code = match self {
+            Color::Black => 30,
+            Color::Red => 31,
+            Color::Green => 32,
+            Color::Yellow => 33,
+            Color::Blue => 34,
+            Color::Magenta => 35,
+            Color::Cyan => 36,
+            Color::White => 37,
+            Color::BrightBlack => 90,
+            Color::BrightRed => 91,
+            Color::BrightGreen => 92,
+            Color::BrightYellow => 93,
+            Color::BrightBlue => 94,
+            Color::BrightMagenta => 95,
+            Color::BrightCyan => 96,
+            Color::BrightWhite => 97,
+            Color::Fixed(n) => return write!(w, "\x1b[38;5;{}m", n),
+        };
+        write!(w, "\x1b[{}m", code)
+    }
+}
+
+/// Colors used for highlighted output.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Theme {
+    /// Colors of parentheses, cycled by nesting depth.
+    pub parens: Vec<Color>,
+    /// Color of leaves.
+    pub bytes: Color,
+    /// Color of leaves printed as strings.
+    pub strings: Color,
+}
+
+impl Theme {
+    pub(crate) fn paren_color(&self, depth: usize) -> Option<Color> {
+        if self.parens.is_empty() {
+            None
+        } else {
+            Some(self.parens[depth % self.parens.len()])
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            parens: vec!(Color::BrightYellow, Color::BrightMagenta, Color::BrightBlue),
+            bytes: Color::Green,
+            strings: Color::Cyan,
+        }
+    }
+}
+
+/// Hook for coloring individual nodes, e.g. to highlight the results of a
+/// query. `path` contains the child indices leading from the root to `node`.
+pub trait Style {
+    /// Returns the color of `node`, or `None` to use the default color.
+    fn color(&self, path: &[usize], node: &Node) -> Option<Color>;
+}
+
+impl<F> Style for F
+where
+    F: Fn(&[usize], &Node) -> Option<Color>
+{
+    fn color(&self, path: &[usize], node: &Node) -> Option<Color> {
+        self(path, node)
+    }
+}
+
+pub(crate) fn start<W: Write>(w: &mut W, color: Option<Color>) -> std::fmt::Result {
+    match color {
+        Some(c) => c.write_escape(w),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn end<W: Write>(w: &mut W, color: Option<Color>) -> std::fmt::Result {
+    match color {
+        Some(_) => write!(w, "\x1b[0m"),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn paint<W: Write>(w: &mut W, color: Option<Color>, s: &str) -> std::fmt::Result {
+    start(w, color)?;
+    write!(w, "{}", s)?;
+    end(w, color)
+}
@@ -0,0 +1,210 @@
+//! Versioned snapshots of trees on top of a `BaumStore`.
+//!
+//! A commit is stored as the inner node `("commit" root)` for the first
+//! commit of a ref and `("commit" root parent)` for later ones, so commits
+//! keep their snapshots and ancestors alive and share unchanged subtrees with
+//! them. Refs are named pointers to commits.
+
+use crate::{Node, Path};
+use crate::store::{Backend, BaumStore, Decoded, Error, Hash, MemoryBackend};
+
+use std::collections::BTreeMap;
+
+const COMMIT_TAG: &[u8] = b"commit";
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct Commit {
+    pub root: Hash,
+    pub parent: Option<Hash>,
+}
+
+/// Difference between two snapshots. Children are compared by index.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Change {
+    Added(Path, Node),
+    Removed(Path, Node),
+    /// A node replaced by a different one, where they aren't both inner
+    /// nodes.
+    Modified(Path, Node, Node),
+}
+
+/// Commits and refs stored in a `BaumStore`. Refs are only kept in memory.
+#[derive(Default)]
+pub struct History<B = MemoryBackend> {
+    store: BaumStore<B>,
+    refs: BTreeMap<String, Hash>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<B: Backend> History<B> {
+    /// Creates an empty history whose objects are stored in `store`.
+    pub fn with_store(store: BaumStore<B>) -> Self {
+        History { store, refs: BTreeMap::new() }
+    }
+
+    pub fn store(&self) -> &BaumStore<B> {
+        &self.store
+    }
+
+    /// Returns the commit `name` points to.
+    pub fn resolve(&self, name: &str) -> Result<Hash, Error> {
+        self.refs.get(name).copied().ok_or_else(|| Error::UnknownRef(name.to_string()))
+    }
+
+    /// Returns the refs sorted by name.
+    pub fn refs(&self) -> impl Iterator<Item = (&str, &Hash)> {
+        self.refs.iter().map(|(name, hash)| (name.as_str(), hash))
+    }
+
+    /// Makes `name` point to `commit`, creating the ref if necessary.
+    pub fn set_ref(&mut self, name: &str, commit: Hash) -> Result<(), Error> {
+        self.read_commit(&commit)?;
+        self.store.retain(&commit)?;
+        if let Some(old) = self.refs.insert(name.to_string(), commit) {
+            self.store.release(&old)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the ref and returns the commit it pointed to. Commits that
+    /// become unreachable are deleted by the next `gc`.
+    pub fn delete_ref(&mut self, name: &str) -> Result<Hash, Error> {
+        let commit = self.refs.remove(name).ok_or_else(|| Error::UnknownRef(name.to_string()))?;
+        self.store.release(&commit)?;
+        Ok(commit)
+    }
+
+    /// Stores `root` as a new commit on `name`, whose parent is the commit
+    /// `name` pointed to before, and returns the new commit.
+    pub fn commit(&mut self, name: &str, root: &Node) -> Result<Hash, Error> {
+        let parent = self.refs.get(name).copied();
+        let tag = self.store.put(&Node::Leaf(COMMIT_TAG.to_vec()))?;
+        let root = self.store.put(root)?;
+        let mut children = vec!(tag, root);
+        children.extend(parent);
+        let commit = self.store.put_inner(&children)?;
+        // the commit references them now
+        self.store.release(&tag)?;
+        self.store.release(&root)?;
+        if let Some(parent) = parent {
+            self.store.release(&parent)?;
+        }
+        self.refs.insert(name.to_string(), commit);
+        Ok(commit)
+    }
+
+    pub fn read_commit(&self, commit: &Hash) -> Result<Commit, Error> {
+        let children = match self.store.read(commit)? {
+            Decoded::Inner(children) => children,
+            Decoded::Leaf(_) => return Err(Error::InvalidCommit(*commit)),
+        };
+        match children.as_slice() {
+            [tag, root, parent @ ..] if parent.len() <= 1 && self.is_commit_tag(tag) => {
+                Ok(Commit { root: *root, parent: parent.first().copied() })
+            }
+            _ => Err(Error::InvalidCommit(*commit)),
+        }
+    }
+
+    fn is_commit_tag(&self, hash: &Hash) -> bool {
+        matches!(self.store.read(hash), Ok(Decoded::Leaf(tag)) if tag == COMMIT_TAG)
+    }
+
+    /// Returns `commit` and its ancestors, newest first.
+    pub fn log(&self, commit: &Hash) -> Result<Vec<Hash>, Error> {
+        let mut res = vec!(*commit);
+        while let Some(parent) = self.read_commit(res.last().unwrap())?.parent {
+            res.push(parent);
+        }
+        Ok(res)
+    }
+
+    /// Returns the snapshot stored by `commit`.
+    pub fn checkout(&self, commit: &Hash) -> Result<Node, Error> {
+        self.store.get(&self.read_commit(commit)?.root)
+    }
+
+    /// Returns the changes from the snapshot of `old` to the one of `new`.
+    /// Subtrees that are equal in both snapshots are skipped without being
+    /// loaded.
+    pub fn diff(&self, old: &Hash, new: &Hash) -> Result<Vec<Change>, Error> {
+        let (old, new) = (self.read_commit(old)?.root, self.read_commit(new)?.root);
+        let mut changes = vec!();
+        self.diff_objects(&old, &new, &mut vec!(), &mut changes)?;
+        Ok(changes)
+    }
+
+    fn diff_objects(&self, old: &Hash, new: &Hash, path: &mut Path, changes: &mut Vec<Change>) -> Result<(), Error> {
+        if old == new {
+            return Ok(());
+        }
+        match (self.store.read(old)?, self.store.read(new)?) {
+            (Decoded::Inner(old_children), Decoded::Inner(new_children)) => {
+                for idx in 0..old_children.len().max(new_children.len()) {
+                    path.push(idx);
+                    match (old_children.get(idx), new_children.get(idx)) {
+                        (Some(o), Some(n)) => self.diff_objects(o, n, path, changes)?,
+                        (Some(o), None) => changes.push(Change::Removed(path.clone(), self.store.get(o)?)),
+                        (None, Some(n)) => changes.push(Change::Added(path.clone(), self.store.get(n)?)),
+                        (None, None) => unreachable!(),
+                    }
+                    path.pop();
+                }
+            }
+            _ => changes.push(Change::Modified(path.clone(), self.store.get(old)?, self.store.get(new)?)),
+        }
+        Ok(())
+    }
+
+    /// Deletes objects that aren't reachable from any ref, see
+    /// `BaumStore::gc`.
+    pub fn gc(&mut self) -> Result<usize, Error> {
+        self.store.gc()
+    }
+}
+
+
+#[test]
+fn history() {
+    let v1 = crate::parse("(0x01 (0x02 0x03))");
+    let v2 = crate::parse("(0x01 (0x02 0x04) 0x05)");
+    let v3 = crate::parse("(0x06)");
+
+    let mut history = History::new();
+    let c1 = history.commit("main", &v1).unwrap();
+    let c2 = history.commit("main", &v2).unwrap();
+    assert_eq!(history.resolve("main"), Ok(c2));
+    assert_eq!(history.log(&c2), Ok(vec!(c2, c1)));
+    assert_eq!(history.read_commit(&c2).unwrap().parent, Some(c1));
+    assert_eq!(history.checkout(&c1), Ok(v1.clone()));
+    assert_eq!(history.checkout(&c2), Ok(v2.clone()));
+    assert_eq!(history.diff(&c1, &c2), Ok(vec!(
+        Change::Modified(vec!(1, 1), Node::Leaf(vec!(3)), Node::Leaf(vec!(4))),
+        Change::Added(vec!(2), Node::Leaf(vec!(5))),
+    )));
+    assert_eq!(history.diff(&c2, &c2), Ok(vec!()));
+
+    // branch off the first commit
+    history.set_ref("dev", c1).unwrap();
+    let c3 = history.commit("dev", &v3).unwrap();
+    assert_eq!(history.log(&c3), Ok(vec!(c3, c1)));
+    assert_eq!(history.refs().map(|(name, _)| name).collect::<Vec<_>>(), vec!("dev", "main"));
+
+    // everything is still reachable
+    assert_eq!(history.gc(), Ok(0));
+    assert_eq!(history.delete_ref("main"), Ok(c2));
+    assert!(history.gc().unwrap() > 0);
+    assert_eq!(history.store().contains(&c2), Ok(false));
+    assert_eq!(history.checkout(&c1), Ok(v1));
+    assert_eq!(history.checkout(&c3), Ok(v3));
+
+    assert_eq!(history.resolve("main"), Err(Error::UnknownRef("main".to_string())));
+    let root = history.read_commit(&c3).unwrap().root;
+    assert_eq!(history.read_commit(&root), Err(Error::InvalidCommit(root)));
+    assert_eq!(history.set_ref("main", root), Err(Error::InvalidCommit(root)));
+}
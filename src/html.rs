@@ -0,0 +1,117 @@
+use crate::{LeafFormat, Node, PrettyConfig};
+
+use std::fmt::Write;
+
+/// Number of bytes shown in the summary line of a leaf.
+const PREVIEW_BYTES: usize = 16;
+
+const HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>baum</title>
+<style>
+body { font-family: monospace; font-size: 14px; }
+details { margin-left: 1.5em; }
+details > summary { cursor: pointer; }
+.children { border-left: 1px dotted #aaa; }
+.info { color: #888; }
+pre { margin: 0.2em 0 0.2em 1.5em; padding: 0.3em; background: #f4f4f4; }
+</style>
+</head>
+<body>
+"#;
+
+const FOOTER: &str = "</body>\n</html>\n";
+
+impl Node {
+    /// Returns a self-contained HTML page that displays the tree with
+    /// collapsible subtrees. Leaves can be expanded to a hex/ASCII view.
+    pub fn to_html(&self) -> String {
+        let mut s = String::new();
+        s.push_str(HEADER);
+        self._to_html(true, &mut s);
+        s.push_str(FOOTER);
+        s
+    }
+
+    fn _to_html(&self, open: bool, s: &mut String) {
+        let open = if open { " open" } else { "" };
+        match self {
+            Node::Leaf(bytes) => {
+                let preview = self.pretty_print_with(&PrettyConfig {
+                    max_width: usize::MAX,
+                    max_leaf_bytes: Some(PREVIEW_BYTES),
+                    ..PrettyConfig::default()
+                });
+                let preview = escape(&preview);
+                if bytes.is_empty() {
+                    writeln!(s, "<details><summary>{}</summary></details>", preview).unwrap();
+                    return;
+                }
+                let dump = self.pretty_print_with(&PrettyConfig {
+                    leaf_format: LeafFormat::Hexdump,
+                    ..PrettyConfig::default()
+                });
+                writeln!(
+                    s, "<details{}><summary>{}</summary><pre>{}</pre></details>",
+                    open, preview, escape(&dump)
+                ).unwrap();
+            }
+            Node::Inner(nodes) => {
+                let info = match nodes.len() {
+                    1 => "1 child".to_string(),
+                    n => format!("{} children", n),
+                };
+                writeln!(s, "<details{}><summary>() <span class=\"info\">{}</span></summary>", open, info).unwrap();
+                if !nodes.is_empty() {
+                    s.push_str("<div class=\"children\">\n");
+                    for node in nodes {
+                        node._to_html(false, s);
+                    }
+                    s.push_str("</div>\n");
+                }
+                s.push_str("</details>\n");
+            }
+        }
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+
+#[test]
+fn to_html() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"<a&b>".to_vec()),
+        Node::Inner(vec!(Node::Leaf(vec!()))),
+    ));
+    let html = node.to_html();
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.ends_with("</html>\n"));
+    let body = &html[HEADER.len()..html.len() - FOOTER.len()];
+    assert_eq!(body, "\
+<details open><summary>() <span class=\"info\">2 children</span></summary>
+<div class=\"children\">
+<details><summary>0x3c_61_26_62_3e</summary><pre>00000000  3c 61 26 62 3e                                    |&lt;a&amp;b&gt;|</pre></details>
+<details><summary>() <span class=\"info\">1 child</span></summary>
+<div class=\"children\">
+<details><summary>0x</summary></details>
+</div>
+</details>
+</div>
+</details>
+");
+}
@@ -0,0 +1,217 @@
+use crate::{Error, ErrorKind, Node, Path, Section};
+use crate::node_ref::{read_len, take};
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::{Path as FilePath, PathBuf};
+
+/// Index of a serialized tree mapping the paths of all nodes to their
+/// offsets and leaf prefixes to the paths of the leaves starting with them.
+/// Selectors consult it to jump to nodes instead of scanning the tree, see
+/// `Selector::select_indexed`.
+///
+/// The index is only valid for the input it was built from, which is checked
+/// using the input's length and content hash. It's persisted as a baum
+/// document alongside the indexed file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct QueryIndex {
+    /// Length and content hash of the indexed input, for detecting
+    /// mismatching inputs.
+    len: usize,
+    hash: u128,
+    prefix_len: usize,
+    /// Offsets of the nodes relative to the end of the magic number.
+    offsets: HashMap<Path, usize>,
+    /// Paths of the leaves by their first `prefix_len` bytes, in depth-first
+    /// order.
+    prefixes: HashMap<Vec<u8>, Vec<Path>>,
+}
+
+impl QueryIndex {
+    /// Indexes the serialized tree `bytes` (including the magic number),
+    /// using the first `prefix_len` bytes of leaves as keys.
+    pub fn build(bytes: &[u8], prefix_len: usize) -> Result<Self, Error> {
+        let mut index = QueryIndex { len: bytes.len(), hash: content_hash(bytes), prefix_len, offsets: HashMap::new(), prefixes: HashMap::new() };
+        let content = crate::selector::strip_magic(bytes)?;
+        let end = index.add(content, 0, &mut vec!())?;
+        match end == content.len() {
+            true => Ok(index),
+            false => Err(Error::new(ErrorKind::AdditionalBytes)),
+        }
+    }
+
+    /// Indexes the node at `pos` and returns the position after it.
+    fn add(&mut self, bytes: &[u8], mut pos: usize, path: &mut Path) -> Result<usize, Error> {
+        self.offsets.insert(path.clone(), pos);
+        match take(bytes, &mut pos, 1, Section::Header)?[0] {
+            0 => {
+                let len = read_len(bytes, &mut pos)?;
+                let leaf = take(bytes, &mut pos, len, Section::Payload)?;
+                self.prefixes.entry(leaf[..len.min(self.prefix_len)].to_vec()).or_default().push(path.clone());
+            }
+            1 => {
+                for idx in 0..read_len(bytes, &mut pos)? {
+                    path.push(idx);
+                    pos = self.add(bytes, pos, path)?;
+                    path.pop();
+                }
+            }
+            _ => return Err(Error::new(ErrorKind::InvalidNodeType)),
+        }
+        Ok(pos)
+    }
+
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    pub(crate) fn check(&self, bytes: &[u8]) -> Result<(), Error> {
+        match bytes.len() == self.len && content_hash(bytes) == self.hash {
+            true => Ok(()),
+            false => Err(Error::message("Query index doesn't match the input.".to_string())),
+        }
+    }
+
+    /// Returns the offset of the node at `path`, relative to the end of the
+    /// magic number.
+    pub(crate) fn offset(&self, path: &[usize]) -> Option<usize> {
+        self.offsets.get(path).copied()
+    }
+
+    /// Returns the paths of the leaves that may be equal to `leaf`.
+    pub(crate) fn candidates(&self, leaf: &[u8]) -> &[Path] {
+        self.prefixes.get(&leaf[..leaf.len().min(self.prefix_len)]).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut offsets: Vec<_> = self.offsets.iter().collect();
+        offsets.sort();
+        let mut prefixes: Vec<_> = self.prefixes.iter().collect();
+        prefixes.sort();
+        Node::Inner(vec!(
+            Node::from(self.len as u64),
+            Node::Leaf(self.hash.to_le_bytes().to_vec()),
+            Node::from(self.prefix_len as u64),
+            Node::Inner(offsets.into_iter().map(|(path, offset)| {
+                Node::Inner(vec!(encode_path(path), Node::from(*offset as u64)))
+            }).collect()),
+            Node::Inner(prefixes.into_iter().map(|(prefix, paths)| {
+                let mut entry = vec!(Node::Leaf(prefix.clone()));
+                entry.extend(paths.iter().map(|path| encode_path(path)));
+                Node::Inner(entry)
+            }).collect()),
+        )).serialize()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = || Error::message("Invalid query index.".to_string());
+        let node = Node::deserialize(bytes)?;
+        let (len, hash, prefix_len, offsets, prefixes) = match &node {
+            Node::Inner(nodes) => match nodes.as_slice() {
+                [len, hash, prefix_len, Node::Inner(offsets), Node::Inner(prefixes)] => (len, hash, prefix_len, offsets, prefixes),
+                _ => return Err(invalid()),
+            },
+            Node::Leaf(_) => return Err(invalid()),
+        };
+        let mut index = QueryIndex {
+            len: decode_u64(len).ok_or_else(invalid)?,
+            hash: hash.to_array().map(u128::from_le_bytes).map_err(|_| invalid())?,
+            prefix_len: decode_u64(prefix_len).ok_or_else(invalid)?,
+            offsets: HashMap::new(),
+            prefixes: HashMap::new(),
+        };
+        for entry in offsets {
+            match entry {
+                Node::Inner(nodes) if nodes.len() == 2 => {
+                    let path = decode_path(&nodes[0]).ok_or_else(invalid)?;
+                    index.offsets.insert(path, decode_u64(&nodes[1]).ok_or_else(invalid)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        for entry in prefixes {
+            match entry {
+                Node::Inner(nodes) => match nodes.split_first() {
+                    Some((Node::Leaf(prefix), paths)) => {
+                        let paths = paths.iter().map(decode_path).collect::<Option<_>>().ok_or_else(invalid)?;
+                        index.prefixes.insert(prefix.clone(), paths);
+                    }
+                    _ => return Err(invalid()),
+                },
+                Node::Leaf(_) => return Err(invalid()),
+            }
+        }
+        Ok(index)
+    }
+
+    /// Returns the path of the index stored alongside the file at `path`.
+    pub fn path_for(path: &FilePath) -> PathBuf {
+        let mut res = path.as_os_str().to_owned();
+        res.push(".idx");
+        res.into()
+    }
+
+    /// Stores the index alongside the file at `path`.
+    pub fn save_for(&self, path: &FilePath) -> std::io::Result<()> {
+        std::fs::write(Self::path_for(path), self.to_bytes())
+    }
+
+    /// Loads the index stored alongside the file at `path`.
+    pub fn load_for(path: &FilePath) -> Result<Self, Error> {
+        Self::from_bytes(&std::fs::read(Self::path_for(path))?)
+    }
+}
+
+/// 128-bit FNV-1a hash of the indexed input. The hash function must not
+/// change, since indexes are persisted.
+fn content_hash(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(0x6c62_272e_07bb_0142_62b8_2175_6295_c58d, |h, b| (h ^ *b as u128).wrapping_mul(0x100_0000_0000_0000_0000_013b))
+}
+
+/// Encodes a path as a leaf of little-endian u64 indices.
+fn encode_path(path: &[usize]) -> Node {
+    Node::Leaf(path.iter().flat_map(|idx| (*idx as u64).to_le_bytes()).collect())
+}
+
+fn decode_path(node: &Node) -> Option<Path> {
+    match node {
+        Node::Leaf(bytes) if bytes.len() % 8 == 0 => {
+            bytes.chunks(8).map(|idx| (u64::from_le_bytes(idx.try_into().unwrap())).try_into().ok()).collect()
+        }
+        _ => None,
+    }
+}
+
+fn decode_u64(node: &Node) -> Option<usize> {
+    match node {
+        Node::Leaf(bytes) => u64::from_le_bytes(bytes.as_slice().try_into().ok()?).try_into().ok(),
+        Node::Inner(_) => None,
+    }
+}
+
+
+#[test]
+fn query_index() {
+    let node = crate::parse(r#"(0x01 (("key" 0x02) ("pw" 0x03) ("pw" (0x04))) "keys")"#);
+    let bytes = node.serialize();
+    let index = QueryIndex::build(&bytes, 3).unwrap();
+    assert_eq!(index.offset(&[]), Some(0));
+    assert_eq!(index.offset(&[0]), Some(9));
+    assert_eq!(index.offset(&[3]), None);
+    assert_eq!(index.candidates(b"keys"), &[vec!(1, 0, 0), vec!(2)]);
+    assert_eq!(index.candidates(b"pw"), &[vec!(1, 1, 0), vec!(1, 2, 0)]);
+
+    assert_eq!(QueryIndex::from_bytes(&index.to_bytes()).unwrap(), index);
+    assert!(index.check(&bytes).is_ok());
+    // edits that keep the length are detected as well
+    let mut edited = bytes.clone();
+    *edited.last_mut().unwrap() ^= 1;
+    assert!(index.check(&edited).is_err());
+    assert!(QueryIndex::from_bytes(&node.serialize()).is_err());
+    assert!(matches!(QueryIndex::build(b"BAUM1\x02", 3), Err(e) if e.kind() == ErrorKind::InvalidNodeType));
+
+    let path = std::env::temp_dir().join(format!("baum_index_{}", std::process::id()));
+    index.save_for(&path).unwrap();
+    assert_eq!(QueryIndex::load_for(&path).unwrap(), index);
+    std::fs::remove_file(QueryIndex::path_for(&path)).unwrap();
+}
@@ -0,0 +1,72 @@
+use crate::{ArcNode, Node};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Deduplicates structurally identical subtrees. All nodes interned by the
+/// same interner share their allocations if they're equal, so they can be
+/// compared using `ArcNode::ptr_eq`.
+#[derive(Default, Debug)]
+pub struct NodeInterner {
+    leaves: HashSet<Arc<[u8]>>,
+    /// Inner nodes by the addresses of their (interned) children.
+    inners: HashMap<Vec<usize>, ArcNode>,
+}
+
+fn addr(node: &ArcNode) -> usize {
+    match node {
+        ArcNode::Leaf(bytes) => Arc::as_ptr(bytes) as *const u8 as usize,
+        ArcNode::Inner(nodes) => Arc::as_ptr(nodes) as *const u8 as usize,
+    }
+}
+
+impl NodeInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&mut self, node: &Node) -> ArcNode {
+        match node {
+            Node::Leaf(bytes) => match self.leaves.get(bytes.as_slice()) {
+                Some(leaf) => ArcNode::Leaf(leaf.clone()),
+                None => {
+                    let leaf: Arc<[u8]> = bytes.as_slice().into();
+                    self.leaves.insert(leaf.clone());
+                    ArcNode::Leaf(leaf)
+                }
+            },
+            Node::Inner(nodes) => {
+                let children: Vec<_> = nodes.iter().map(|n| self.intern(n)).collect();
+                self.inners.entry(children.iter().map(addr).collect())
+                    .or_insert_with(|| ArcNode::Inner(children.into()))
+                    .clone()
+            }
+        }
+    }
+
+    /// Number of distinct nodes interned so far.
+    pub fn len(&self) -> usize {
+        self.leaves.len() + self.inners.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+
+#[test]
+fn node_interner() {
+    let node = crate::parse("((0x01 (0x02)) (0x01 (0x02)) 0x01 (0x02) ())");
+    let mut interner = NodeInterner::new();
+    let interned = interner.intern(&node);
+    assert_eq!(interned.to_node(), node);
+    // 0x01, 0x02, (0x02), (0x01 (0x02)), () and the root
+    assert_eq!(interner.len(), 6);
+    let children = interned.children();
+    assert!(children[0].ptr_eq(&children[1]));
+    assert!(children[0].children()[0].ptr_eq(&children[2]));
+    assert!(children[0].children()[1].ptr_eq(&children[3]));
+    assert!(interner.intern(&node).ptr_eq(&interned));
+    assert!(!children[0].ptr_eq(&children[3]));
+}
@@ -0,0 +1,91 @@
+use crate::{base64, Node, Path};
+
+use serde_json::Value;
+
+/// Error when converting a JSON value into a node.
+#[derive(Debug, PartialEq)]
+pub enum JsonError {
+    /// A value that's neither an array nor a string.
+    UnexpectedValue(Path),
+    /// A string that isn't valid base64.
+    InvalidBase64(Path),
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            JsonError::UnexpectedValue(p) => write!(f, "Expected array or string at path {:?}.", p),
+            JsonError::InvalidBase64(p) => write!(f, "Invalid base64 string at path {:?}.", p),
+        }
+    }
+}
+
+impl std::error::Error for JsonError { }
+
+impl Node {
+    /// Converts the node into a JSON value. Inner nodes become arrays, leaves
+    /// become base64 strings (standard alphabet, padded), e.g.
+    /// `["AQI=", [], ["/w=="]]`.
+    pub fn to_json_value(&self) -> Value {
+        match self {
+            Node::Leaf(bytes) => Value::String(base64::encode(bytes)),
+            Node::Inner(nodes) => Value::Array(nodes.iter().map(Node::to_json_value).collect()),
+        }
+    }
+
+    /// Converts a JSON value following the convention of `to_json_value`
+    /// back into a node.
+    pub fn from_json_value(value: &Value) -> Result<Node, JsonError> {
+        from_json_value(value, &mut vec!())
+    }
+}
+
+fn from_json_value(value: &Value, path: &mut Path) -> Result<Node, JsonError> {
+    match value {
+        Value::String(s) => match base64::decode(s) {
+            Some(bytes) => Ok(Node::Leaf(bytes)),
+            None => Err(JsonError::InvalidBase64(path.clone())),
+        },
+        Value::Array(values) => {
+            let mut nodes = Vec::with_capacity(values.len());
+            for (idx, value) in values.iter().enumerate() {
+                path.push(idx);
+                nodes.push(from_json_value(value, path)?);
+                path.pop();
+            }
+            Ok(Node::Inner(nodes))
+        }
+        _ => Err(JsonError::UnexpectedValue(path.clone())),
+    }
+}
+
+impl From<&Node> for Value {
+    fn from(node: &Node) -> Value {
+        node.to_json_value()
+    }
+}
+
+impl std::convert::TryFrom<&Value> for Node {
+    type Error = JsonError;
+    fn try_from(value: &Value) -> Result<Node, JsonError> {
+        Node::from_json_value(value)
+    }
+}
+
+
+#[test]
+fn json_value() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1, 2)),
+        Node::Inner(vec!()),
+        Node::Inner(vec!(Node::Leaf(vec!(0xff)), Node::Leaf(vec!()))),
+    ));
+    let value = node.to_json_value();
+    assert_eq!(value.to_string(), r#"["AQI=",[],["/w==",""]]"#);
+    assert_eq!(Node::from_json_value(&value), Ok(node));
+
+    let value: Value = serde_json::from_str(r#"[["AQI=", 1]]"#).unwrap();
+    assert_eq!(Node::from_json_value(&value), Err(JsonError::UnexpectedValue(vec!(0, 1))));
+    let value: Value = serde_json::from_str(r#"[[], "AQI"]"#).unwrap();
+    assert_eq!(Node::from_json_value(&value), Err(JsonError::InvalidBase64(vec!(1))));
+}
@@ -0,0 +1,61 @@
+use crate::Node;
+use crate::base64;
+
+use std::io::Write;
+
+impl Node {
+    /// Returns one JSON object per leaf, each on its own line, e.g.
+    /// `{"path":[0,2,1],"bytes":"AQI="}`. `path` contains the child indices
+    /// leading from the root to the leaf, `bytes` the base64 encoded content.
+    ///
+    /// Inner nodes without leaves don't produce any output.
+    pub fn to_json_lines(&self) -> String {
+        let mut v = vec!();
+        self.write_json_lines(&mut v).unwrap();
+        String::from_utf8(v).unwrap()
+    }
+
+    /// Like `to_json_lines`, but writes into `writer`.
+    pub fn write_json_lines<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        self._write_json_lines(&mut vec!(), writer)
+    }
+
+    fn _write_json_lines<W: Write>(&self, path: &mut Vec<usize>, writer: &mut W) -> std::io::Result<()> {
+        match self {
+            Node::Leaf(bytes) => {
+                write!(writer, "{{\"path\":[")?;
+                for (i, idx) in path.iter().enumerate() {
+                    if i > 0 {
+                        write!(writer, ",")?;
+                    }
+                    write!(writer, "{}", idx)?;
+                }
+                writeln!(writer, "],\"bytes\":\"{}\"}}", base64::encode(bytes))
+            }
+            Node::Inner(nodes) => {
+                for (idx, node) in nodes.iter().enumerate() {
+                    path.push(idx);
+                    node._write_json_lines(path, writer)?;
+                    path.pop();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+
+#[test]
+fn json_lines() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"foo".to_vec()),
+        Node::Inner(vec!()),
+        Node::Inner(vec!(Node::Leaf(vec!()), Node::Leaf(vec!(0xff)))),
+    ));
+    assert_eq!(node.to_json_lines(), "\
+{\"path\":[0],\"bytes\":\"Zm9v\"}
+{\"path\":[2,0],\"bytes\":\"\"}
+{\"path\":[2,1],\"bytes\":\"/w==\"}
+");
+    assert_eq!(Node::Leaf(vec!(1)).to_json_lines(), "{\"path\":[],\"bytes\":\"AQ==\"}\n");
+}
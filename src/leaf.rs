@@ -0,0 +1,241 @@
+//! Constructors and accessors for leaves holding fixed-width integers,
+//! IEEE 754 floats, UTF-8 strings, varints, UUIDs (feature `uuid`) and timestamps
+//! (feature `chrono`).
+
+use crate::{FromNodeError, Node};
+#[cfg(test)]
+use crate::{FromBaum, ToBaum};
+use crate::codec::{Varint, ZigZag};
+use crate::convert::{fixed, leaf};
+
+macro_rules! num_leaves {
+    ($($ty:ty: $leaf_le:ident $leaf_be:ident $as_le:ident $as_be:ident;)*) => {
+        impl Node {
+            $(
+                pub fn $leaf_le(value: $ty) -> Node {
+                    Node::Leaf(value.to_le_bytes().to_vec())
+                }
+
+                pub fn $leaf_be(value: $ty) -> Node {
+                    Node::Leaf(value.to_be_bytes().to_vec())
+                }
+
+                /// Fails unless the node is a leaf of the type's width.
+                pub fn $as_le(&self) -> Result<$ty, FromNodeError> {
+                    fixed(self).map(<$ty>::from_le_bytes)
+                }
+
+                /// Fails unless the node is a leaf of the type's width.
+                pub fn $as_be(&self) -> Result<$ty, FromNodeError> {
+                    fixed(self).map(<$ty>::from_be_bytes)
+                }
+            )*
+        }
+    };
+}
+
+num_leaves! {
+    u16: leaf_u16_le leaf_u16_be as_u16_le as_u16_be;
+    u32: leaf_u32_le leaf_u32_be as_u32_le as_u32_be;
+    u64: leaf_u64_le leaf_u64_be as_u64_le as_u64_be;
+    u128: leaf_u128_le leaf_u128_be as_u128_le as_u128_be;
+    i16: leaf_i16_le leaf_i16_be as_i16_le as_i16_be;
+    i32: leaf_i32_le leaf_i32_be as_i32_le as_i32_be;
+    i64: leaf_i64_le leaf_i64_be as_i64_le as_i64_be;
+    i128: leaf_i128_le leaf_i128_be as_i128_le as_i128_be;
+    f32: leaf_f32_le leaf_f32_be as_f32_le as_f32_be;
+    f64: leaf_f64_le leaf_f64_be as_f64_le as_f64_be;
+}
+
+impl Node {
+    pub fn leaf_u8(value: u8) -> Node {
+        Node::Leaf(vec!(value))
+    }
+
+    pub fn leaf_i8(value: i8) -> Node {
+        Node::Leaf(value.to_le_bytes().to_vec())
+    }
+
+    /// Fails unless the node is a single byte leaf.
+    pub fn as_u8(&self) -> Result<u8, FromNodeError> {
+        fixed(self).map(u8::from_le_bytes)
+    }
+
+    /// Fails unless the node is a single byte leaf.
+    pub fn as_i8(&self) -> Result<i8, FromNodeError> {
+        fixed(self).map(i8::from_le_bytes)
+    }
+}
+
+impl Node {
+    /// Leaf containing the UTF-8 encoding of `s`.
+    pub fn leaf_str(s: &str) -> Node {
+        Node::Leaf(s.as_bytes().to_vec())
+    }
+
+    /// Fails with `FromNodeError::InvalidValue` for leaves that aren't valid
+    /// UTF-8.
+    pub fn as_str(&self) -> Result<&str, FromNodeError> {
+        std::str::from_utf8(leaf(self)?).map_err(|_| FromNodeError::InvalidValue(vec!()))
+    }
+}
+
+impl Node {
+    /// Leaf containing `value` as LEB128 varint.
+    pub fn leaf_varint(value: u64) -> Node {
+        Node::leaf_with(&Varint, &value)
+    }
+
+    /// Leaf containing `value` as zigzag-encoded LEB128 varint.
+    pub fn leaf_zigzag(value: i64) -> Node {
+        Node::leaf_with(&ZigZag, &value)
+    }
+
+    /// Fails unless the node is a leaf consisting of exactly one varint.
+    pub fn as_varint(&self) -> Result<u64, FromNodeError> {
+        self.decode_leaf(&Varint)
+    }
+
+    /// Fails unless the node is a leaf consisting of exactly one varint.
+    pub fn as_zigzag(&self) -> Result<i64, FromNodeError> {
+        self.decode_leaf(&ZigZag)
+    }
+}
+
+/// UUIDs are stored as their 16 bytes in RFC 4122 order.
+#[cfg(feature = "uuid")]
+impl Node {
+    pub fn leaf_uuid(uuid: uuid::Uuid) -> Node {
+        Node::Leaf(uuid.as_bytes().to_vec())
+    }
+
+    /// Fails unless the node is a leaf of 16 bytes.
+    pub fn as_uuid(&self) -> Result<uuid::Uuid, FromNodeError> {
+        fixed(self).map(uuid::Uuid::from_bytes)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl crate::ToBaum for uuid::Uuid {
+    fn to_node(&self) -> Node {
+        Node::leaf_uuid(*self)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl crate::FromBaum for uuid::Uuid {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        node.as_uuid()
+    }
+}
+
+/// Timestamps are stored as little-endian `i64` seconds or nanoseconds since
+/// the Unix epoch, or as RFC 3339 strings.
+#[cfg(feature = "chrono")]
+impl Node {
+    /// Stores the whole seconds of `time`; fractions of a second are lost.
+    pub fn leaf_unix_secs<Tz: chrono::TimeZone>(time: &chrono::DateTime<Tz>) -> Node {
+        Node::leaf_i64_le(time.timestamp())
+    }
+
+    /// Returns `None` for times that don't fit, i.e. before 1677 or after
+    /// 2262.
+    pub fn leaf_unix_nanos<Tz: chrono::TimeZone>(time: &chrono::DateTime<Tz>) -> Option<Node> {
+        time.timestamp_nanos_opt().map(Node::leaf_i64_le)
+    }
+
+    pub fn leaf_rfc3339<Tz: chrono::TimeZone>(time: &chrono::DateTime<Tz>) -> Node
+    where
+        Tz::Offset: std::fmt::Display
+    {
+        Node::leaf_str(&time.to_rfc3339())
+    }
+
+    pub fn as_unix_secs(&self) -> Result<chrono::DateTime<chrono::Utc>, FromNodeError> {
+        chrono::DateTime::from_timestamp(self.as_i64_le()?, 0).ok_or(FromNodeError::InvalidValue(vec!()))
+    }
+
+    pub fn as_unix_nanos(&self) -> Result<chrono::DateTime<chrono::Utc>, FromNodeError> {
+        Ok(chrono::DateTime::from_timestamp_nanos(self.as_i64_le()?))
+    }
+
+    pub fn as_rfc3339(&self) -> Result<chrono::DateTime<chrono::FixedOffset>, FromNodeError> {
+        chrono::DateTime::parse_from_rfc3339(self.as_str()?).map_err(|_| FromNodeError::InvalidValue(vec!()))
+    }
+}
+
+
+#[test]
+fn int_leaves() {
+    assert_eq!(Node::leaf_u16_le(0x0102), Node::Leaf(vec!(2, 1)));
+    assert_eq!(Node::leaf_u16_be(0x0102), Node::Leaf(vec!(1, 2)));
+    assert_eq!(Node::leaf_i64_be(-2).as_i64_be(), Ok(-2));
+    assert_eq!(Node::leaf_u32_be(7).as_u32_le(), Ok(7 << 24));
+    assert_eq!(Node::leaf_u128_le(u128::MAX).as_u128_le(), Ok(u128::MAX));
+    assert_eq!(Node::leaf_i8(-1), Node::Leaf(vec!(0xff)));
+    assert_eq!(Node::leaf_u8(3).as_u8(), Ok(3));
+    assert_eq!(Node::Leaf(vec!(0xff)).as_i8(), Ok(-1));
+
+    assert_eq!(Node::leaf_u16_le(1).as_u32_le(),
+        Err(FromNodeError::LeafLength { path: vec!(), expected: 4, actual: 2 }));
+    assert_eq!(Node::Inner(vec!()).as_u64_be(), Err(FromNodeError::ExpectedLeaf(vec!())));
+}
+
+#[test]
+fn float_leaves() {
+    assert_eq!(Node::leaf_f32_be(1.0), Node::Leaf(vec!(0x3f, 0x80, 0, 0)));
+    assert_eq!(Node::leaf_f32_le(1.0), Node::Leaf(vec!(0, 0, 0x80, 0x3f)));
+    assert_eq!(Node::leaf_f64_le(-0.25).as_f64_le(), Ok(-0.25));
+    assert_eq!(Node::leaf_f64_be(f64::INFINITY).as_f64_be(), Ok(f64::INFINITY));
+    assert!(Node::leaf_f32_le(f32::NAN).as_f32_le().unwrap().is_nan());
+    assert_eq!(f64::from_node(&1.5f64.to_node()), Ok(1.5));
+    assert_eq!(Node::leaf_f32_le(1.0).as_f64_le(),
+        Err(FromNodeError::LeafLength { path: vec!(), expected: 8, actual: 4 }));
+}
+
+#[test]
+fn str_leaves() {
+    assert_eq!(Node::leaf_str("äb"), Node::Leaf(vec!(0xc3, 0xa4, b'b')));
+    assert_eq!(Node::leaf_str("äb").as_str(), Ok("äb"));
+    assert_eq!(Node::Leaf(vec!()).as_str(), Ok(""));
+    assert_eq!(Node::Leaf(vec!(0xc3)).as_str(), Err(FromNodeError::InvalidValue(vec!())));
+    assert_eq!(Node::Inner(vec!()).as_str(), Err(FromNodeError::ExpectedLeaf(vec!())));
+}
+
+#[cfg(feature = "uuid")]
+#[test]
+fn uuid_leaves() {
+    let uuid = uuid::Uuid::parse_str("67e55044-10b1-426f-9247-bb680e5fe0c8").unwrap();
+    let node = Node::leaf_uuid(uuid);
+    assert_eq!(node.to_string(), "0x67_e5_50_44_10_b1_42_6f_92_47_bb_68_0e_5f_e0_c8");
+    assert_eq!(node.as_uuid(), Ok(uuid));
+    assert_eq!(uuid::Uuid::from_node(&uuid.to_node()), Ok(uuid));
+    assert_eq!(Node::Leaf(vec!(0; 15)).as_uuid(),
+        Err(FromNodeError::LeafLength { path: vec!(), expected: 16, actual: 15 }));
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn timestamp_leaves() {
+    let time = chrono::DateTime::parse_from_rfc3339("2021-03-04T05:06:07.5+01:00").unwrap();
+    assert_eq!(Node::leaf_unix_secs(&time), Node::leaf_i64_le(1614830767));
+    assert_eq!(Node::leaf_unix_secs(&time).as_unix_secs().unwrap().timestamp(), 1614830767);
+    let nanos = Node::leaf_unix_nanos(&time).unwrap();
+    assert_eq!(nanos.as_unix_nanos(), Ok(time.to_utc()));
+    assert_eq!(Node::leaf_rfc3339(&time).as_str(), Ok("2021-03-04T05:06:07.500+01:00"));
+    assert_eq!(Node::leaf_rfc3339(&time).as_rfc3339(), Ok(time));
+
+    let far = chrono::DateTime::from_timestamp(1 << 40, 0).unwrap();
+    assert_eq!(Node::leaf_unix_nanos(&far), None);
+    assert_eq!(Node::leaf_i64_le(i64::MAX).as_unix_secs(), Err(FromNodeError::InvalidValue(vec!())));
+    assert_eq!(Node::leaf_str("yesterday").as_rfc3339(), Err(FromNodeError::InvalidValue(vec!())));
+}
+
+#[test]
+fn varint_leaves() {
+    assert_eq!(Node::leaf_varint(300), Node::Leaf(vec!(0xac, 0x02)));
+    assert_eq!(Node::leaf_varint(300).as_varint(), Ok(300));
+    assert_eq!(Node::leaf_zigzag(-2), Node::Leaf(vec!(3)));
+    assert_eq!(Node::leaf_zigzag(-300).as_zigzag(), Ok(-300));
+    assert_eq!(Node::Inner(vec!()).as_varint(), Err(FromNodeError::ExpectedLeaf(vec!())));
+}
@@ -1,10 +1,144 @@
 mod parser;
+mod outline;
+pub mod csexp;
+pub mod cbor;
+pub mod msgpack;
+pub mod bencode;
+pub mod der;
+pub mod protobuf;
+pub mod xml;
+mod varint;
+mod wire;
+mod template;
+mod highlight;
+mod pretty;
+mod tree_render;
+mod dot;
+mod html;
+mod base64;
+mod hex;
+mod json_lines;
+mod table;
+mod convert;
+pub mod schema;
+mod codegen;
+pub mod ser;
+pub mod de;
+mod fs;
+mod arena;
+mod node_ref;
+mod generic;
+mod arc_node;
+mod interner;
+mod dedup;
+mod rope;
+mod edit;
+mod map;
+mod set;
+mod buffers;
+mod builder;
+mod bloom;
+mod search;
+mod selector;
+mod query;
+mod transform;
+mod index;
+mod pattern;
+mod leaf;
+mod limits;
+mod collation;
+pub mod codec;
+#[cfg(feature = "json")]
+mod json;
+#[cfg(feature = "transcode")]
+pub mod transcode;
+#[cfg(feature = "git")]
+pub mod git;
+#[cfg(feature = "store")]
+pub mod store;
+#[cfg(feature = "store")]
+pub mod history;
+#[cfg(any(feature = "ego-tree", feature = "indextree", feature = "petgraph"))]
+mod graphs;
+#[cfg(feature = "rayon")]
+mod parallel;
+#[cfg(feature = "bumpalo")]
+mod bump;
+#[cfg(feature = "regex")]
+mod grep;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "rand")]
+mod random;
+#[cfg(feature = "rand")]
+pub mod mutate;
 
 use serde::{Serialize, Deserialize};
-pub use parser::ParseResult;
+pub use parser::{ParseResult, DEFAULT_MAX_DEPTH};
+pub use template::Template;
+pub use highlight::{Color, Style, Theme};
+pub use pretty::{HexCase, LeafFormat, LeafRenderer, PrettyConfig};
+pub use table::{Table, TableError};
+pub use convert::{FromBaum, FromNodeError, ToBaum};
+pub use schema::Schema;
+#[cfg(feature = "derive")]
+pub use baum_macros::{baum, baum_match, FromBaum, ToBaum};
+pub use ser::{to_vec, to_writer};
+pub use de::{from_slice, from_reader};
+pub use fs::NamingStrategy;
+pub use arena::{NodeData, NodeId, Tree};
+pub use node_ref::NodeRef;
+pub use arc_node::ArcNode;
+pub use interner::NodeInterner;
+pub use rope::{Rope, RopeNode};
+pub use edit::EditError;
+pub use map::BaumMap;
+pub use set::BaumSet;
+pub use buffers::{Parser, Serializer};
+pub use builder::StreamBuilder;
+pub use bloom::BloomFilter;
+pub use search::SubstringIndex;
+pub use selector::Selector;
+pub use query::Query;
+pub use index::QueryIndex;
+pub use transform::Transform;
+pub use pattern::Pattern;
+pub use limits::Limits;
+pub use collation::{Collation, Lexicographic, Shortlex};
+pub use codec::LeafCodec;
+pub use generic::{CowNode, GenericNode};
+#[cfg(feature = "bytes")]
+pub use generic::BytesNode;
+#[cfg(feature = "smallvec")]
+pub use generic::SmallNode;
+#[cfg(feature = "json")]
+pub use json::JsonError;
+#[cfg(any(feature = "ego-tree", feature = "indextree", feature = "petgraph"))]
+pub use graphs::GraphError;
+#[cfg(feature = "bumpalo")]
+pub use bump::BumpNode;
+#[cfg(feature = "rand")]
+pub use random::{GenConfig, SizeDistribution};
 
 use std::convert::TryInto;
 
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::convert::{child, children, tag, unknown_variant};
+
+    /// Accepts both nodes and references to them in `baum_match!`.
+    pub fn node<N: std::borrow::Borrow<crate::Node>>(node: &N) -> &crate::Node {
+        node.borrow()
+    }
+}
+
+/// Child indices leading from the root of a tree to one of its nodes.
+pub type Path = Vec<usize>;
+
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Serialize, Deserialize)]
 pub enum Node {
     Leaf(Vec<u8>),
@@ -12,16 +146,17 @@ pub enum Node {
 }
 
 impl std::fmt::Display for Node {
+    /// Formats the node on a single line, or pretty-printed using the default
+    /// `PrettyConfig` if the alternate flag (`{:#}`) is set.
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            return pretty::write_pretty(self, &PrettyConfig::default(), f);
+        }
         match self {
             Self::Leaf(bytes) => {
-                write!(f, "0x")?;
-                for (i, b) in bytes.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, "_")?;
-                    }
-                    write!(f, "{:02x}", b)?;
-                }
+                let mut s = String::from("0x");
+                hex::encode_into(bytes, HexCase::Lower, Some('_'), &mut s);
+                f.write_str(&s)?;
             },
             Self::Inner(nodes) => {
                 write!(f, "(")?;
@@ -39,6 +174,11 @@ impl std::fmt::Display for Node {
 }
 
 impl Node {
+    /// Leaf without bytes, commonly used as a null marker.
+    pub const EMPTY_LEAF: Node = Node::Leaf(Vec::new());
+    /// Inner node without children.
+    pub const EMPTY_INNER: Node = Node::Inner(Vec::new());
+
     pub fn new_leaf(bytes: Vec<u8>) -> Node {
         Node::Leaf(bytes)
     }
@@ -65,6 +205,46 @@ impl Node {
         matches!(*self, Node::Inner(_))
     }
 
+    /// Returns `true` for leaves without bytes and inner nodes without
+    /// children.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Node::Leaf(bytes) => bytes.is_empty(),
+            Node::Inner(nodes) => nodes.is_empty(),
+        }
+    }
+
+    /// Returns `true` for inner nodes with at least one child.
+    #[inline]
+    pub fn has_children(&self) -> bool {
+        matches!(self, Node::Inner(nodes) if !nodes.is_empty())
+    }
+
+    /// Returns the number of nodes in the tree, including `self`.
+    pub fn node_count(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Inner(nodes) => 1 + nodes.iter().map(Node::node_count).sum::<usize>(),
+        }
+    }
+
+    /// Returns the node at `path`, if there is one.
+    pub fn get(&self, path: &[usize]) -> Option<&Node> {
+        path.iter().try_fold(self, |node, &idx| match node {
+            Node::Inner(nodes) => nodes.get(idx),
+            Node::Leaf(_) => None,
+        })
+    }
+
+    /// Returns the node at `path` mutably, if there is one.
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        path.iter().try_fold(self, |node, &idx| match node {
+            Node::Inner(nodes) => nodes.get_mut(idx),
+            Node::Leaf(_) => None,
+        })
+    }
+
     pub fn serialize(&self) -> Vec<u8> {
         // include magic number
         let mut res = "BAUM1".as_bytes().to_vec();
@@ -77,7 +257,7 @@ impl Node {
             Node::Leaf(bytes) => {
                 w.push(0);
                 w.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
-                w.extend_from_slice(&bytes);
+                w.extend_from_slice(bytes);
             },
             Node::Inner(nodes) => {
                 w.push(1);
@@ -93,7 +273,7 @@ impl Node {
     where 
         W: std::io::Write 
     {
-        writer.write("BAUM1".as_bytes())?;
+        writer.write_all("BAUM1".as_bytes())?;
         self._serialize_into(writer)
     }
 
@@ -103,14 +283,14 @@ impl Node {
     {
         match self {
             Node::Leaf(b) => {
-                writer.write(&[0])?;
-                writer.write(&(b.len() as u64).to_le_bytes())?;
-                writer.write(&b)?;
+                writer.write_all(&[0])?;
+                writer.write_all(&(b.len() as u64).to_le_bytes())?;
+                writer.write_all(b)?;
                 Ok(())
             }
             Node::Inner(nodes) => {
-                writer.write(&[1])?;
-                writer.write(&(nodes.len() as u64).to_le_bytes())?;
+                writer.write_all(&[1])?;
+                writer.write_all(&(nodes.len() as u64).to_le_bytes())?;
                 for node in nodes {
                     node._serialize_into(writer)?;
                 }
@@ -119,150 +299,198 @@ impl Node {
         }
     }
 
-    pub fn deserialize<'a>(bytes: &'a [u8]) -> Result<Self, Error> {
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, Error> {
         Self::deserialize_from(bytes)
     }
 
-    pub fn deserialize_from<R>(mut reader: R) -> Result<Self, Error> 
+    pub fn deserialize_from<R>(reader: R) -> Result<Self, Error> 
+    where
+        R: std::io::Read
+    {
+        Self::deserialize_from_with(reader, DecodeMode::Strict)
+    }
+
+    /// Like `deserialize_from`, with `mode` deciding how trailing bytes and
+    /// unknown node types are treated.
+    pub fn deserialize_from_with<R>(reader: R, mode: DecodeMode) -> Result<Self, Error> 
+    where
+        R: std::io::Read
+    {
+        Self::deserialize_limited(reader, mode, &Limits::default())
+    }
+
+    /// Like `deserialize_from_with`, failing as soon as the tree exceeds
+    /// `limits` (see `Node::check_limits`) instead of decoding it first.
+    pub fn deserialize_limited<R>(mut reader: R, mode: DecodeMode, limits: &Limits) -> Result<Self, Error> 
     where
         R: std::io::Read
     {
         let mut magic_num = vec!(0; 5);
-        reader.read_exact(&mut magic_num)?;
+        read_section(&mut reader, &mut magic_num, Section::Magic)?;
         if magic_num != "BAUM1".as_bytes() {
-            return Err(Error::InvalidMagicNumber)
+            return Err(Error::new(ErrorKind::InvalidMagicNumber))
         }
         
-        let res = Self::_deserialize_from(&mut reader)?;
+        let mut state = DecodeState { pos: 5, path: vec!(), mode, limits, nodes: 0 };
+        let res = Self::_deserialize_from(&mut reader, &mut state)?
+            .ok_or_else(|| Error::new(ErrorKind::InvalidNodeType).at(5, &[]))?;
         
         // check if whole input has been processed
         let mut buf = [0];
-        if reader.read(&mut buf)? != 0 {
-            return Err(Error::AdditionalBytes);
+        if mode == DecodeMode::Strict && reader.read(&mut buf)? != 0 {
+            return Err(Error::new(ErrorKind::AdditionalBytes));
         }
 
         Ok(res)
     }
 
-    fn _deserialize_from<R>(reader: &mut R) -> Result<Self, Error> 
+    /// Decodes the node at `state.pos`. Returns `None` for skipped nodes.
+    fn _deserialize_from<R>(reader: &mut R, state: &mut DecodeState) -> Result<Option<Self>, Error> 
     where
         R: std::io::Read
     {
-        let type_byte = read_u8(reader)?;
+        let start = state.pos;
+        let path = &state.path;
+        let type_byte = read_u8(reader).map_err(|e| e.at(start, path))?;
+        if type_byte > 1 && state.mode == DecodeMode::Strict {
+            return Err(Error::new(ErrorKind::InvalidNodeType).at(start, path));
+        }
+        let len = read_u64(reader).map_err(|e| e.at(start + 1, path))?;
+        if type_byte <= 1 {
+            to_usize(len).map_err(|e| e.at(start + 1, path))?;
+            state.nodes += 1;
+            let depth = path.len() + 1;
+            state.limits.check(type_byte == 0, len, depth, state.nodes).map_err(|e| e.at(start, path))?;
+        }
+        state.pos += 9;
         match type_byte {
             // leaf
             0 => {
-                let len = read_u64(reader)?;
-                let mut bytes = vec!(0; len as usize);
-                reader.read_exact(&mut bytes)?;
-                Ok(Node::Leaf(bytes))
+                let bytes = read_payload(reader, len).map_err(|e| e.at(start + 9, path))?;
+                state.pos += len;
+                Ok(Some(Node::Leaf(bytes)))
             }
             // inner
             1 => {
-                let len = read_u64(reader)?;
-                let mut nodes = Vec::with_capacity(len as usize);
-                for _ in 0..len {
-                    nodes.push(Node::_deserialize_from(reader)?);
+                // don't trust `len` for the allocation, the input may be
+                // truncated
+                let mut nodes = Vec::with_capacity(len.min(1024) as usize);
+                for idx in 0..len {
+                    state.path.push(idx as usize);
+                    nodes.extend(Node::_deserialize_from(reader, state)?);
+                    state.path.pop();
                 }
-                Ok(Node::Inner(nodes))
+                Ok(Some(Node::Inner(nodes)))
             }
-            // error
+            // unknown, laid out like a leaf
             _ => {
-                Err(Error::InvalidNodeType)
+                skip_payload(reader, len).map_err(|e| e.at(start + 9, path))?;
+                state.pos += len;
+                Ok(None)
             }
         }
     }
 
+    /// Converts the leaf into an owned (`[u8; N]`) or borrowed (`&[u8; N]`)
+    /// byte array.
     pub fn try_into_array<'a, T>(&'a self) -> Result<T, TryIntoError>
     where 
-        T: std::convert::TryFrom<&'a [u8]> 
+        T: ByteArray<'a>
     {
-        self.try_into().and_then(|x: &[u8]| x.try_into().map_err(|_| TryIntoError::LengthMismatch))
+        self.try_into().and_then(|x: &[u8]| {
+            x.try_into().map_err(|_| TryIntoError::LengthMismatch { expected: T::LEN, actual: x.len() })
+        })
     }
 
-    pub fn pretty_print(&self, max_width: usize) -> String {
-        let mut s = String::new();
-        self._pretty_print(max_width, 0, &mut s).unwrap();
-        s
+    /// Returns a copy of the leaf's bytes as array.
+    pub fn to_array<const N: usize>(&self) -> Result<[u8; N], TryIntoError> {
+        self.try_into_array()
     }
 
-    fn _pretty_print<T>(&self, max_width: usize, indent: usize, fmt: &mut T) -> Result<(), std::fmt::Error> 
-    where
-        T: std::fmt::Write
-    {
-        match self {
-            Node::Leaf(bytes) => {
-                write!(fmt, "0x")?;
-                let mut pos = indent + 2;
-                for (idx, b) in bytes.iter().enumerate() {
-                    if pos + 3 > max_width {
-                        write!(fmt, "\n{}", " ".repeat(indent+2))?;
-                        pos = indent + 2;
-                    } else if idx > 0 {
-                        write!(fmt, "_")?;
-                        pos += 1;
-                    }
-                    write!(fmt, "{:02x}", b)?;
-                    pos += 2;
-                }
-            },
-            Node::Inner(nodes) => {
-                let width = self._width();
-                if (indent + width) <= max_width  {
-                    write!(fmt, "(")?;
-                    for (idx, n) in nodes.iter().enumerate() {
-                        if idx > 0 {
-                            write!(fmt, " ")?;
-                        }
-                        n._pretty_print(max_width, indent, fmt)?;
-                    }
-                    write!(fmt, ")")?;
-                } else {
-                    
-                    write!(fmt, "(\n")?;
-
-                    for n in nodes {
-                        write!(fmt, "{}", " ".repeat(indent+4))?;
-                        n._pretty_print(max_width, indent+4, fmt)?;
-                        writeln!(fmt)?;
-                    }
-                    
-                    write!(fmt, "{})", " ".repeat(indent))?;
-                    
-                }
-            }
-        }
-        Ok(())
+    pub fn as_array<const N: usize>(&self) -> Result<&[u8; N], TryIntoError> {
+        self.try_into_array()
     }
 
-    fn _width(&self) -> usize {
-        match self {
-            Node::Leaf(bytes) => 2 + 2 * bytes.len() + bytes.len().saturating_sub(1),
-            Node::Inner(nodes) => 2 + nodes.iter().map(|x| x._width()).sum::<usize>() + nodes.len().saturating_sub(1),
-        }
+    /// Returns a copy of the leaf's bytes.
+    pub fn try_into_vec(&self) -> Result<Vec<u8>, TryIntoError> {
+        self.try_into().map(<[u8]>::to_vec)
     }
 
     pub fn parse(s: &str) -> parser::ParseResult {
         parser::ParseResult::parse(s)
     }
+
+    /// Parses `s`, rejecting input with more than `max_depth` nested inner nodes.
+    pub fn parse_with_max_depth(s: &str, max_depth: usize) -> parser::ParseResult {
+        parser::ParseResult::parse_with_max_depth(s, max_depth)
+    }
 }
 
+/// Fills `buf`, reporting how many bytes were available if the input ends
+/// early.
+fn read_section<R: std::io::Read>(input: &mut R, buf: &mut [u8], section: Section) -> Result<(), Error> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => return Err(Error::unexpected_eof(section, buf.len() as u64, filled as u64)),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
 fn read_u8<R: std::io::Read>(input: &mut R) -> Result<u8, Error> {
     let mut buf = [0];
-    input.read_exact(&mut buf)?;
+    read_section(input, &mut buf, Section::Header)?;
     Ok(buf[0])
 }
 fn read_u64<R: std::io::Read>(input: &mut R) -> Result<u64, Error> {
     let mut buf: [u8; 8] = [0; 8];
-    input.read_exact(&mut buf)?;
+    read_section(input, &mut buf, Section::Length)?;
     Ok(u64::from_le_bytes(buf))
 }
+/// Position and settings of `Node::deserialize_limited`.
+struct DecodeState<'a> {
+    /// Byte offset and path of the current node, for error reporting.
+    pos: u64,
+    path: Path,
+    mode: DecodeMode,
+    limits: &'a Limits,
+    /// Number of nodes decoded so far.
+    nodes: usize,
+}
+
+/// Converts a length read from the input, which may not fit into `usize` on
+/// 32-bit targets.
+fn to_usize(len: u64) -> Result<usize, Error> {
+    len.try_into().map_err(|_| Error::new(ErrorKind::LengthOverflow))
+}
+fn skip_payload<R: std::io::Read>(input: &mut R, len: u64) -> Result<(), Error> {
+    use std::io::Read;
+    let skipped = std::io::copy(&mut input.take(len), &mut std::io::sink())?;
+    match skipped < len {
+        true => Err(Error::unexpected_eof(Section::Payload, len, skipped)),
+        false => Ok(()),
+    }
+}
+/// Reads a leaf payload of `len` bytes without trusting `len` for the
+/// allocation, since the input may be truncated.
+fn read_payload<R: std::io::Read>(input: &mut R, len: u64) -> Result<Vec<u8>, Error> {
+    use std::io::Read;
+    let mut bytes = vec!();
+    input.take(len).read_to_end(&mut bytes)?;
+    match (bytes.len() as u64) < len {
+        true => Err(Error::unexpected_eof(Section::Payload, len, bytes.len() as u64)),
+        false => Ok(bytes),
+    }
+}
 
 impl std::convert::TryFrom<Node> for Vec<u8> {
     type Error = TryIntoError;
     fn try_from(value: Node) -> Result<Vec<u8>, Self::Error> {
         match value {
-            Node::Inner(_) => Err(TryIntoError::ExpectedLeaf),
+            Node::Inner(nodes) => Err(TryIntoError::ExpectedLeaf { children: nodes.len() }),
             Node::Leaf(bytes) => Ok(bytes)
         }
     }
@@ -272,44 +500,320 @@ impl<'a> std::convert::TryFrom<&'a Node> for &'a [u8] {
     type Error = TryIntoError;
     fn try_from(value: &Node) -> Result<&[u8], Self::Error> {
         match value {
-            Node::Inner(_) => Err(TryIntoError::ExpectedLeaf),
+            Node::Inner(nodes) => Err(TryIntoError::ExpectedLeaf { children: nodes.len() }),
             Node::Leaf(bytes) => Ok(bytes.as_slice())
         }
     }
 }
 
+macro_rules! impl_try_from_int {
+    ($($ty:ty)*) => {
+        $(
+            /// Integers are stored little-endian in leaves of exactly the
+            /// type's width.
+            impl std::convert::TryFrom<&Node> for $ty {
+                type Error = TryIntoError;
+                fn try_from(value: &Node) -> Result<$ty, Self::Error> {
+                    value.try_into_array().map(<$ty>::from_le_bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_int!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128);
+
+impl From<Vec<u8>> for Node {
+    fn from(bytes: Vec<u8>) -> Self {
+        Node::Leaf(bytes)
+    }
+}
+
+impl From<&[u8]> for Node {
+    fn from(bytes: &[u8]) -> Self {
+        Node::Leaf(bytes.to_vec())
+    }
+}
+
+/// UTF-8 encoded leaf.
+impl From<&str> for Node {
+    fn from(s: &str) -> Self {
+        Node::Leaf(s.as_bytes().to_vec())
+    }
+}
+
+/// Little-endian leaf of 4 bytes.
+impl From<u32> for Node {
+    fn from(n: u32) -> Self {
+        Node::Leaf(n.to_le_bytes().to_vec())
+    }
+}
 
+/// Little-endian leaf of 8 bytes.
+impl From<u64> for Node {
+    fn from(n: u64) -> Self {
+        Node::Leaf(n.to_le_bytes().to_vec())
+    }
+}
+
+impl From<Vec<Node>> for Node {
+    fn from(nodes: Vec<Node>) -> Self {
+        Node::Inner(nodes)
+    }
+}
+
+
+/// How `Node::deserialize_from_with` treats input that isn't exactly one
+/// tree of known node types.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DecodeMode {
+    /// Bytes after the root and nodes of unknown types are errors.
+    #[default]
+    Strict,
+    /// Bytes after the root are left unread in the reader, e.g. to read
+    /// concatenated documents. Nodes of unknown types are skipped, assuming
+    /// that future node types are laid out like leaves (type byte, length,
+    /// payload).
+    Lenient,
+}
+
+/// Error raised while encoding or decoding trees.
+///
+/// Besides its kind, an error records where decoding failed (if known): the
+/// byte offset into the input and the path of the node being decoded.
 #[derive(Debug)]
-pub enum Error {
-    IOError(std::io::Error),
+#[non_exhaustive]
+pub struct Error {
+    kind: ErrorKind,
+    message: Option<String>,
+    offset: Option<u64>,
+    path: Option<Path>,
+    truncation: Option<Truncation>,
+    source: Option<Box<dyn std::error::Error + Send + Sync + 'static>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Reading or writing failed, e.g. because the input ended early. The
+    /// underlying `std::io::Error` is the error's source.
+    Io,
+    /// The input ended early, see `Error::truncation`.
+    UnexpectedEof,
     InvalidMagicNumber,
     InvalidNodeType,
     AdditionalBytes,
+    /// A back-reference to a node that doesn't precede it.
+    InvalidReference,
+    /// A length that doesn't fit into `usize`, which can only happen on
+    /// targets with pointers narrower than 64 bits.
+    LengthOverflow,
+    /// The tree exceeds the `Limits` it was checked against.
+    LimitExceeded,
+    /// A node couldn't be converted to the requested type. The
+    /// `TryIntoError` is the error's source.
+    Conversion,
+    /// Error reported by the serde serializer or deserializer, or any other
+    /// error described by its message.
+    Message,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind) -> Self {
+        Error { kind, message: None, offset: None, path: None, truncation: None, source: None }
+    }
+
+    /// Creates an error of kind `ErrorKind::UnexpectedEof` for input ending
+    /// in `section` with `available` of `expected` bytes left.
+    pub fn unexpected_eof(section: Section, expected: u64, available: u64) -> Self {
+        Error { truncation: Some(Truncation { section, expected, available }), ..Error::new(ErrorKind::UnexpectedEof) }
+    }
+
+    /// Creates an error of kind `ErrorKind::Message`.
+    pub fn message<S: Into<String>>(msg: S) -> Self {
+        Error { message: Some(msg.into()), ..Error::new(ErrorKind::Message) }
+    }
+
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn with_path(mut self, path: Path) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    pub fn with_source<E>(mut self, source: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync + 'static>>
+    {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Records the position of the node being decoded, unless the error
+    /// already knows a more precise one.
+    pub(crate) fn at(mut self, offset: u64, path: &[usize]) -> Self {
+        if self.offset.is_none() {
+            self.offset = Some(offset);
+        }
+        if self.path.is_none() {
+            self.path = Some(path.to_vec());
+        }
+        self
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Byte offset into the input at which decoding failed.
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    /// Path of the node that was being decoded.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_ref()
+    }
+
+    /// Returns where and by how much the input was truncated for errors of
+    /// kind `ErrorKind::UnexpectedEof`.
+    pub fn truncation(&self) -> Option<&Truncation> {
+        self.truncation.as_ref()
+    }
+
+    /// Returns the underlying I/O error of errors of kind `ErrorKind::Io`.
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        self.source.as_ref().and_then(|e| e.downcast_ref())
+    }
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match self {
-            Error::IOError(e) => e.fmt(f)?,
-            Error::InvalidMagicNumber => write!(f, "Invalid magic number.")?,
-            Error::InvalidNodeType => write!(f, "Input contains an invalid node type.")?,
-            Error::AdditionalBytes => write!(f, "Input contains additional bytes.")?,
+        match (&self.message, self.kind) {
+            (Some(msg), _) => write!(f, "{}", msg)?,
+            (None, ErrorKind::Io | ErrorKind::Conversion) if self.source.is_some() => {
+                self.source.as_ref().unwrap().fmt(f)?
+            }
+            (None, ErrorKind::Io) => write!(f, "I/O error.")?,
+            (None, ErrorKind::UnexpectedEof) => match &self.truncation {
+                Some(t) => write!(f, "Unexpected end of input in {}: expected {} bytes, found {}.", t.section, t.expected, t.available)?,
+                None => write!(f, "Unexpected end of input.")?,
+            },
+            (None, ErrorKind::Conversion) => write!(f, "Conversion failed.")?,
+            (None, ErrorKind::InvalidMagicNumber) => write!(f, "Invalid magic number.")?,
+            (None, ErrorKind::InvalidNodeType) => write!(f, "Input contains an invalid node type.")?,
+            (None, ErrorKind::AdditionalBytes) => write!(f, "Input contains additional bytes.")?,
+            (None, ErrorKind::InvalidReference) => write!(f, "Input contains an invalid back-reference.")?,
+            (None, ErrorKind::LengthOverflow) => write!(f, "Input contains a length exceeding the address space.")?,
+            (None, ErrorKind::LimitExceeded) => write!(f, "Tree exceeds its limits.")?,
+            (None, ErrorKind::Message) => write!(f, "Unknown error.")?,
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " (at offset {}", offset)?;
+            if let Some(path) = &self.path {
+                write!(f, ", node {:?}", path)?;
+            }
+            write!(f, ")")?;
+        } else if let Some(path) = &self.path {
+            write!(f, " (node {:?})", path)?;
         }
         Ok(())
     }
 }
 
-impl std::error::Error for Error { }
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.as_ref().map(|e| e.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+/// Where and by how much the input was truncated, see `Error::truncation`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Truncation {
+    pub section: Section,
+    /// Number of bytes the section takes.
+    pub expected: u64,
+    /// Number of bytes that were left in the input.
+    pub available: u64,
+}
+
+/// Part of the binary encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Section {
+    Magic,
+    /// The byte holding a node's type.
+    Header,
+    /// The number of bytes of a leaf or children of an inner node.
+    Length,
+    /// The bytes of a leaf.
+    Payload,
+}
+
+impl std::fmt::Display for Section {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Section::Magic => write!(f, "magic number"),
+            Section::Header => write!(f, "node header"),
+            Section::Length => write!(f, "length field"),
+            Section::Payload => write!(f, "leaf payload"),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub enum TryIntoError {
-    ExpectedLeaf,
-    LengthMismatch,
+    /// The node is an inner node with `children` children.
+    ExpectedLeaf { children: usize },
+    LengthMismatch { expected: usize, actual: usize },
+}
+
+impl std::fmt::Display for TryIntoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TryIntoError::ExpectedLeaf { children } => write!(f, "Expected leaf, found inner node with {} children.", children),
+            TryIntoError::LengthMismatch { expected, actual } => write!(f, "Expected leaf of {} bytes, found {} bytes.", expected, actual),
+        }
+    }
+}
+
+impl std::error::Error for TryIntoError { }
+
+impl From<TryIntoError> for Error {
+    fn from(err: TryIntoError) -> Self {
+        Error::new(ErrorKind::Conversion).with_source(err)
+    }
+}
+
+/// Byte arrays that leaves can be converted into with
+/// `Node::try_into_array`.
+pub trait ByteArray<'a>: std::convert::TryFrom<&'a [u8]> {
+    const LEN: usize;
+}
+
+impl<const N: usize> ByteArray<'_> for [u8; N] {
+    const LEN: usize = N;
+}
+
+impl<'a, const N: usize> ByteArray<'a> for &'a [u8; N] {
+    const LEN: usize = N;
 }
 
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
-        Error::IOError(err)
+        Error::new(ErrorKind::Io).with_source(err)
+    }
+}
+
+/// Parses `s`, panicking with the parser's message if it's invalid.
+#[cfg(test)]
+pub(crate) fn parse(s: &str) -> Node {
+    match Node::parse(s) {
+        ParseResult::Ok(node) => node,
+        r => panic!("{}", r.err_message()),
     }
 }
 
@@ -330,6 +834,16 @@ fn fmt() {
     assert_eq!(s, exp);
 }
 
+#[test]
+fn fmt_alternate() {
+    let baum = Node::Inner(vec!(
+        Node::Leaf((0..30).collect()),
+        Node::Inner(vec!(Node::Leaf(vec!(1)))),
+    ));
+    assert_eq!(format!("{:#}", baum), baum.pretty_print(80));
+    assert_eq!(format!("{:#}", Node::Leaf(vec!(1, 2))), "0x01_02");
+}
+
 #[test]
 fn test_basic() {
     let node = Node::Inner(vec!(
@@ -463,6 +977,75 @@ fn test_recursive() {
     assert_eq!(node, Node::deserialize_from(&mut bytes_slice).unwrap());
 }
 
+#[test]
+fn decode_errors() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(Node::Leaf(vec!(2)), Node::Leaf(vec!(3, 4)))),
+    ));
+    let bytes = node.serialize();
+
+    let err = Node::deserialize(&bytes[..bytes.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    assert_eq!(err.truncation(), Some(&Truncation { section: Section::Payload, expected: 2, available: 1 }));
+    assert_eq!((err.offset(), err.path()), (Some(52), Some(&vec!(1, 1))));
+    assert_eq!(err.to_string(), "Unexpected end of input in leaf payload: expected 2 bytes, found 1. (at offset 52, node [1, 1])");
+    assert_eq!(NodeRef::deserialize(&bytes[..bytes.len() - 1]).unwrap_err().to_string(), err.to_string());
+    let err = Node::deserialize(&bytes[..30]).unwrap_err();
+    assert_eq!(err.truncation(), Some(&Truncation { section: Section::Length, expected: 8, available: 5 }));
+    assert_eq!(Node::deserialize(b"BAU").unwrap_err().truncation().map(|t| t.section), Some(Section::Magic));
+
+    let err = Error::from(std::io::Error::other("Disk on fire."));
+    assert_eq!(err.kind(), ErrorKind::Io);
+    assert!(std::error::Error::source(&err).is_some());
+
+    let mut invalid = bytes.clone();
+    invalid[24] = 2;
+    let err = Node::deserialize(&invalid).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidNodeType);
+    assert_eq!(err.to_string(), "Input contains an invalid node type. (at offset 24, node [1])");
+    let err = NodeRef::deserialize(&invalid).unwrap_err();
+    assert_eq!((err.offset(), err.path()), (Some(24), Some(&vec!(1))));
+
+    let err = Node::deserialize(b"BAUMX").unwrap_err();
+    assert_eq!((err.kind(), err.offset()), (ErrorKind::InvalidMagicNumber, None));
+    assert_eq!(Error::message("Custom.").with_offset(3).to_string(), "Custom. (at offset 3)");
+}
+
+#[test]
+fn decode_modes() {
+    let a = Node::Inner(vec!(Node::Leaf(vec!(1)), Node::Inner(vec!())));
+    let b = Node::Leaf(vec!(2, 3));
+    let mut bytes = a.serialize();
+    bytes.extend(b.serialize());
+
+    assert_eq!(Node::deserialize(&bytes).unwrap_err().kind(), ErrorKind::AdditionalBytes);
+    let mut reader = &bytes[..];
+    assert_eq!(Node::deserialize_from_with(&mut reader, DecodeMode::Lenient).unwrap(), a);
+    assert_eq!(Node::deserialize_from_with(&mut reader, DecodeMode::Lenient).unwrap(), b);
+    assert!(reader.is_empty());
+
+    // (0x01 <type 7 with two bytes> ())
+    let unknown = b"BAUM1\x01\x03\0\0\0\0\0\0\0\x00\x01\0\0\0\0\0\0\0\x01\x07\x02\0\0\0\0\0\0\0ab\x01\0\0\0\0\0\0\0\0";
+    assert_eq!(Node::deserialize(unknown).unwrap_err().kind(), ErrorKind::InvalidNodeType);
+    assert_eq!(Node::deserialize_from_with(&unknown[..], DecodeMode::Lenient).unwrap(), a);
+    let err = Node::deserialize_from_with(&unknown[..unknown.len() - 10], DecodeMode::Lenient).unwrap_err();
+    assert_eq!(err.truncation().map(|t| t.section), Some(Section::Payload));
+    assert_eq!(Node::deserialize_from_with(&b"BAUM1\x02\0\0\0\0\0\0\0\0"[..], DecodeMode::Lenient).unwrap_err().kind(), ErrorKind::InvalidNodeType);
+}
+
+#[cfg(target_pointer_width = "32")]
+#[test]
+fn decode_length_overflow() {
+    // leaf of 2^32 bytes, which would be truncated to an empty leaf
+    let bytes = b"BAUM1\x00\0\0\0\0\x01\0\0\0";
+    let err = Node::deserialize(bytes).unwrap_err();
+    assert_eq!((err.kind(), err.offset()), (ErrorKind::LengthOverflow, Some(6)));
+    assert_eq!(NodeRef::deserialize(bytes).unwrap_err().kind(), ErrorKind::LengthOverflow);
+    let inner = b"BAUM1\x01\0\0\0\0\x01\0\0\0";
+    assert_eq!(Node::deserialize(inner).unwrap_err().kind(), ErrorKind::LengthOverflow);
+}
+
 #[test]
 fn try_into() {
     let node = Node::Leaf(vec!(1,2,3,4));
@@ -471,10 +1054,56 @@ fn try_into() {
     let arr2: Result<&[u8;10],_> = node.try_into_array();
     
     assert_eq!(arr, Ok(&[1, 2, 3, 4]));
-    assert_eq!(arr2, Err(TryIntoError::LengthMismatch));
+    assert_eq!(arr2, Err(TryIntoError::LengthMismatch { expected: 10, actual: 4 }));
 
     let node2 = Node::Inner(vec!());
-    assert_eq!(node2.try_into_array::<&[u8;1]>(), Err(TryIntoError::ExpectedLeaf));
+    assert_eq!(node2.try_into_array::<&[u8;1]>(), Err(TryIntoError::ExpectedLeaf { children: 0 }));
+
+    use std::convert::TryFrom;
+    assert_eq!(u32::try_from(&node), Ok(0x04030201));
+    assert_eq!(i16::try_from(&Node::Leaf(vec!(0xfe, 0xff))), Ok(-2));
+    assert_eq!(u64::try_from(&node), Err(TryIntoError::LengthMismatch { expected: 8, actual: 4 }));
+    assert_eq!(u8::try_from(&node2), Err(TryIntoError::ExpectedLeaf { children: 0 }));
+
+    assert_eq!(node.to_array(), Ok([1, 2, 3, 4]));
+    assert_eq!(node.as_array::<2>(), Err(TryIntoError::LengthMismatch { expected: 2, actual: 4 }));
+    assert_eq!(node.try_into_array::<[u8; 4]>(), Ok([1, 2, 3, 4]));
+    assert_eq!(node.try_into_vec(), Ok(vec!(1, 2, 3, 4)));
+    assert_eq!(node2.try_into_vec(), Err(TryIntoError::ExpectedLeaf { children: 0 }));
+
+    let err = Error::from(u16::try_from(&node).unwrap_err());
+    assert_eq!(err.kind(), ErrorKind::Conversion);
+    assert_eq!(err.to_string(), "Expected leaf of 2 bytes, found 4 bytes.");
+    assert_eq!(Vec::<u8>::try_from(Node::Inner(vec!(node))).unwrap_err().to_string(), "Expected leaf, found inner node with 1 children.");
+}
+
+#[test]
+fn from_values() {
+    let node: Node = vec!(Node::from("ab"), 1u32.into(), 2u64.into(), vec!(3u8).into(), Node::from(&[4u8][..])).into();
+    assert_eq!(node.to_string(), "(0x61_62 0x01_00_00_00 0x02_00_00_00_00_00_00_00 0x03 0x04)");
+}
+
+#[test]
+fn empty_nodes() {
+    assert_eq!(Node::EMPTY_LEAF, Node::Leaf(vec!()));
+    assert_eq!(Node::EMPTY_INNER, Node::Inner(vec!()));
+    assert!(Node::EMPTY_LEAF.is_empty() && Node::EMPTY_INNER.is_empty());
+    assert!(!Node::Leaf(vec!(0)).is_empty());
+    assert!(!Node::Inner(vec!(Node::EMPTY_LEAF)).is_empty());
+    assert!(Node::Inner(vec!(Node::EMPTY_LEAF)).has_children());
+    assert!(!Node::EMPTY_INNER.has_children() && !Node::Leaf(vec!(0)).has_children());
+}
+
+#[test]
+fn get() {
+    let mut node = Node::Inner(vec!(Node::Leaf(vec!(1)), Node::Inner(vec!(Node::Leaf(vec!(2))))));
+    assert_eq!(node.get(&[]), Some(&node));
+    assert_eq!(node.get(&[1, 0]), Some(&Node::Leaf(vec!(2))));
+    assert_eq!(node.get(&[2]), None);
+    assert_eq!(node.get(&[0, 0]), None);
+    *node.get_mut(&[1, 0]).unwrap() = Node::Leaf(vec!(3));
+    assert!(node.get_mut(&[1, 1]).is_none());
+    assert_eq!(node.to_string(), "(0x01 (0x03))");
 }
 
 #[test]
@@ -494,6 +1123,23 @@ fn pretty_print() {
     assert_eq!(node.pretty_print(18), "(\n    0x\n    (\n        0x02_03_04\n        ()\n    )\n    0x03_04\n)");
 }
 
+#[test]
+fn highlight() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1, 2)),
+        Node::Inner(vec!()),
+    ));
+    let theme = Theme { parens: vec!(Color::Red, Color::Blue), bytes: Color::Fixed(2), strings: Color::Cyan };
+    assert_eq!(
+        node.highlight_with(80, &theme),
+        "\x1b[31m(\x1b[0m\x1b[38;5;2m0x01_02\x1b[0m \x1b[34m(\x1b[0m\x1b[34m)\x1b[0m\x1b[31m)\x1b[0m"
+    );
+    assert_eq!(
+        Node::Leaf(vec!(1, 2, 3)).highlight_with(7, &theme),
+        "\x1b[38;5;2m0x01_02\x1b[0m\n  \x1b[38;5;2m03\x1b[0m"
+    );
+}
+
 #[test]
 fn pretty_print_long_bytes() {
     let node = Node::Inner(vec!(
@@ -0,0 +1,92 @@
+use crate::{Error, ErrorKind, Node, Path};
+
+/// Upper bounds for trees from untrusted sources, checked by
+/// `Node::check_limits` or while decoding by `Node::deserialize_limited` and
+/// `Node::deserialize_dedup_limited`.
+/// `None` means unlimited.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum nesting of inner nodes, counted like the depth limit of
+    /// `Node::parse_with_max_depth`.
+    pub max_depth: Option<usize>,
+    pub max_children: Option<usize>,
+    /// Maximum number of bytes in a leaf.
+    pub max_leaf_len: Option<usize>,
+    /// Maximum number of nodes in the tree, including the root.
+    pub max_nodes: Option<usize>,
+}
+
+impl Limits {
+    /// Checks a node whose encoding announces `len` bytes or children.
+    /// `depth` is the nesting of inner nodes including the node itself and
+    /// `nodes` the number of nodes seen so far, including the node.
+    pub(crate) fn check(&self, leaf: bool, len: u64, depth: usize, nodes: usize) -> Result<(), Error> {
+        let exceeds = |limit: Option<usize>, value: u64| limit.is_some_and(|limit| value > limit as u64);
+        let msg = if exceeds(self.max_nodes, nodes as u64) {
+            format!("Tree has more than {} nodes.", self.max_nodes.unwrap())
+        } else if leaf && exceeds(self.max_leaf_len, len) {
+            format!("Leaf of {} bytes exceeds the limit of {} bytes.", len, self.max_leaf_len.unwrap())
+        } else if !leaf && exceeds(self.max_depth, depth as u64) {
+            format!("Nesting exceeds the depth limit of {}.", self.max_depth.unwrap())
+        } else if !leaf && exceeds(self.max_children, len) {
+            format!("Inner node with {} children exceeds the limit of {} children.", len, self.max_children.unwrap())
+        } else {
+            return Ok(());
+        };
+        Err(Error { message: Some(msg), ..Error::new(ErrorKind::LimitExceeded) })
+    }
+}
+
+impl Node {
+    /// Checks the tree against `limits`. The error's path points to the
+    /// first offending node in depth-first order.
+    pub fn check_limits(&self, limits: &Limits) -> Result<(), Error> {
+        check(self, limits, &mut vec!(), &mut 0)
+    }
+}
+
+fn check(node: &Node, limits: &Limits, path: &mut Path, nodes: &mut usize) -> Result<(), Error> {
+    *nodes += 1;
+    let depth = path.len() + 1;
+    match node {
+        Node::Leaf(bytes) => limits.check(true, bytes.len() as u64, depth, *nodes).map_err(|e| e.with_path(path.clone())),
+        Node::Inner(children) => {
+            limits.check(false, children.len() as u64, depth, *nodes).map_err(|e| e.with_path(path.clone()))?;
+            for (idx, child) in children.iter().enumerate() {
+                path.push(idx);
+                check(child, limits, path, nodes)?;
+                path.pop();
+            }
+            Ok(())
+        }
+    }
+}
+
+
+#[test]
+fn check_limits() {
+    let node = crate::parse("(0x01 (0x02_03 (0x04)) ())");
+    let check = |limits: Limits| node.check_limits(&limits).map_err(|e| (e.kind(), e.path().cloned()));
+    assert_eq!(check(Limits::default()), Ok(()));
+    assert_eq!(check(Limits { max_depth: Some(3), max_children: Some(3), max_leaf_len: Some(2), max_nodes: Some(7) }), Ok(()));
+
+    assert_eq!(check(Limits { max_depth: Some(2), ..Limits::default() }), Err((ErrorKind::LimitExceeded, Some(vec!(1, 1)))));
+    assert_eq!(check(Limits { max_children: Some(2), ..Limits::default() }), Err((ErrorKind::LimitExceeded, Some(vec!()))));
+    assert_eq!(check(Limits { max_leaf_len: Some(1), ..Limits::default() }), Err((ErrorKind::LimitExceeded, Some(vec!(1, 0)))));
+    assert_eq!(check(Limits { max_nodes: Some(6), ..Limits::default() }), Err((ErrorKind::LimitExceeded, Some(vec!(2)))));
+    let err = node.check_limits(&Limits { max_leaf_len: Some(1), ..Limits::default() }).unwrap_err();
+    assert_eq!(err.to_string(), "Leaf of 2 bytes exceeds the limit of 1 bytes. (node [1, 0])");
+
+    // the same limits apply while decoding, before reading the offending node
+    let bytes = node.serialize();
+    let limits = Limits { max_depth: Some(2), ..Limits::default() };
+    let err = Node::deserialize_limited(&bytes[..], crate::DecodeMode::Strict, &limits).unwrap_err();
+    assert_eq!((err.kind(), err.path(), err.offset()), (ErrorKind::LimitExceeded, Some(&vec!(1, 1)), Some(44)));
+    for limits in [Limits { max_nodes: Some(6), ..Limits::default() }, Limits { max_children: Some(2), ..Limits::default() }] {
+        let expected = node.check_limits(&limits).unwrap_err();
+        let err = Node::deserialize_limited(&bytes[..], crate::DecodeMode::Strict, &limits).unwrap_err();
+        assert_eq!((err.kind(), err.path()), (expected.kind(), expected.path()));
+    }
+    let limits = Limits { max_depth: Some(3), max_children: Some(3), max_leaf_len: Some(2), max_nodes: Some(7) };
+    assert_eq!(Node::deserialize_limited(&bytes[..], crate::DecodeMode::Strict, &limits).unwrap(), node);
+}
@@ -0,0 +1,130 @@
+use crate::{FromBaum, FromNodeError, Node, ToBaum};
+
+/// Map from byte strings to nodes. It's represented as an inner node of
+/// `(key value)` pairs sorted by key, where keys are leaves.
+#[derive(Clone, Eq, PartialEq, Hash, Default, Debug)]
+pub struct BaumMap {
+    entries: Vec<(Vec<u8>, Node)>,
+}
+
+impl BaumMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn find(&self, key: &[u8]) -> Result<usize, usize> {
+        self.entries.binary_search_by(|(k, _)| k.as_slice().cmp(key))
+    }
+
+    pub fn get(&self, key: &[u8]) -> Option<&Node> {
+        self.find(key).ok().map(|idx| &self.entries[idx].1)
+    }
+
+    pub fn get_mut(&mut self, key: &[u8]) -> Option<&mut Node> {
+        self.find(key).ok().map(move |idx| &mut self.entries[idx].1)
+    }
+
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        self.find(key).is_ok()
+    }
+
+    /// Inserts a value, returning the previous value for `key`, if any.
+    pub fn insert(&mut self, key: Vec<u8>, value: Node) -> Option<Node> {
+        match self.find(&key) {
+            Ok(idx) => Some(std::mem::replace(&mut self.entries[idx].1, value)),
+            Err(idx) => {
+                self.entries.insert(idx, (key, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &[u8]) -> Option<Node> {
+        self.find(key).ok().map(|idx| self.entries.remove(idx).1)
+    }
+
+    /// Returns the entries sorted by key.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&[u8], &Node)> {
+        self.entries.iter().map(|(k, v)| (k.as_slice(), v))
+    }
+}
+
+impl std::iter::FromIterator<(Vec<u8>, Node)> for BaumMap {
+    /// Later entries replace earlier ones with the same key.
+    fn from_iter<I: IntoIterator<Item = (Vec<u8>, Node)>>(iter: I) -> Self {
+        let mut map = BaumMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl ToBaum for BaumMap {
+    fn to_node(&self) -> Node {
+        Node::Inner(self.entries.iter().map(|(k, v)| Node::Inner(vec!(Node::Leaf(k.clone()), v.clone()))).collect())
+    }
+}
+
+/// Fails with `FromNodeError::InvalidValue` for keys that aren't sorted or
+/// unique.
+impl FromBaum for BaumMap {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        let nodes = match node {
+            Node::Inner(nodes) => nodes,
+            Node::Leaf(_) => return Err(FromNodeError::ExpectedInner(vec!())),
+        };
+        let mut entries: Vec<(Vec<u8>, Node)> = Vec::with_capacity(nodes.len());
+        for (idx, entry) in nodes.iter().enumerate() {
+            let (key, value) = match entry {
+                Node::Inner(pair) => match pair.as_slice() {
+                    [Node::Leaf(key), value] => (key, value),
+                    [_, _] => return Err(FromNodeError::ExpectedLeaf(vec!(idx, 0))),
+                    _ => return Err(FromNodeError::ChildCount { path: vec!(idx), expected: 2, actual: pair.len() }),
+                },
+                Node::Leaf(_) => return Err(FromNodeError::ExpectedInner(vec!(idx))),
+            };
+            if entries.last().is_some_and(|(last, _)| last >= key) {
+                return Err(FromNodeError::InvalidValue(vec!(idx, 0)));
+            }
+            entries.push((key.clone(), value.clone()));
+        }
+        Ok(BaumMap { entries })
+    }
+}
+
+
+#[test]
+fn baum_map() {
+    let mut map = BaumMap::new();
+    assert_eq!(map.insert(b"b".to_vec(), Node::Leaf(vec!(2))), None);
+    assert_eq!(map.insert(b"a".to_vec(), Node::Leaf(vec!(1))), None);
+    assert_eq!(map.insert(b"c".to_vec(), Node::Inner(vec!())), None);
+    assert_eq!(map.insert(b"b".to_vec(), Node::Leaf(vec!(3))), Some(Node::Leaf(vec!(2))));
+    assert_eq!(map.get(b"b"), Some(&Node::Leaf(vec!(3))));
+    assert_eq!(map.remove(b"c"), Some(Node::Inner(vec!())));
+    assert_eq!(map.remove(b"c"), None);
+    *map.get_mut(b"a").unwrap() = Node::Leaf(vec!(4));
+    assert!(map.contains_key(b"a") && !map.contains_key(b"c"));
+    let keys: Vec<_> = map.iter().map(|(k, _)| k).collect();
+    assert_eq!(keys, vec!(&b"a"[..], &b"b"[..]));
+
+    let node = map.to_node();
+    assert_eq!(node.to_string(), "((0x61 0x04) (0x62 0x03))");
+    assert_eq!(BaumMap::from_node(&node), Ok(map.clone()));
+    assert_eq!(BaumMap::from_node(&Node::deserialize(&node.serialize()).unwrap()), Ok(map));
+
+    assert_eq!(BaumMap::from_node(&crate::parse("((0x62 0x) (0x61 0x))")), Err(FromNodeError::InvalidValue(vec!(1, 0))));
+    assert_eq!(BaumMap::from_node(&crate::parse("((0x61 0x) (0x61 0x))")), Err(FromNodeError::InvalidValue(vec!(1, 0))));
+    assert_eq!(BaumMap::from_node(&crate::parse("((() 0x))")), Err(FromNodeError::ExpectedLeaf(vec!(0, 0))));
+    assert_eq!(BaumMap::from_node(&crate::parse("((0x61))")), Err(FromNodeError::ChildCount { path: vec!(0), expected: 2, actual: 1 }));
+    assert_eq!(BaumMap::from_node(&crate::parse("0x")), Err(FromNodeError::ExpectedInner(vec!())));
+}
@@ -0,0 +1,164 @@
+//! Conversion between `Node`s and MessagePack.
+//!
+//! Inner nodes map to arrays, leaves to `bin` objects. Encoding uses the
+//! shortest available format; other types (integers, `str`, maps, ...) are
+//! rejected when decoding.
+
+use crate::Node;
+use crate::wire::read_bytes;
+
+use std::convert::TryFrom;
+
+pub use crate::wire::Error;
+
+/// Encodes `node` as MessagePack.
+///
+/// # Panics
+///
+/// Panics if a leaf or inner node has more than `u32::MAX` bytes or
+/// children, which MessagePack can't represent.
+pub fn encode(node: &Node) -> Vec<u8> {
+    let mut res = vec!();
+    encode_into(node, &mut res);
+    res
+}
+
+fn encode_into(node: &Node, w: &mut Vec<u8>) {
+    match node {
+        Node::Leaf(bytes) => {
+            let len = u32::try_from(bytes.len()).expect("leaf too long for MessagePack");
+            if len <= u32::from(u8::MAX) {
+                w.push(0xc4);
+                w.push(len as u8);
+            } else if len <= u32::from(u16::MAX) {
+                w.push(0xc5);
+                w.extend_from_slice(&(len as u16).to_be_bytes());
+            } else {
+                w.push(0xc6);
+                w.extend_from_slice(&len.to_be_bytes());
+            }
+            w.extend_from_slice(bytes);
+        }
+        Node::Inner(nodes) => {
+            let len = u32::try_from(nodes.len()).expect("inner node too long for MessagePack");
+            if len < 16 {
+                w.push(0x90 | len as u8);
+            } else if len <= u32::from(u16::MAX) {
+                w.push(0xdc);
+                w.extend_from_slice(&(len as u16).to_be_bytes());
+            } else {
+                w.push(0xdd);
+                w.extend_from_slice(&len.to_be_bytes());
+            }
+            for node in nodes {
+                encode_into(node, w);
+            }
+        }
+    }
+}
+
+fn read_len(bytes: &[u8], pos: &mut usize, size: usize) -> Result<usize, Error> {
+    let len = bytes.get(*pos..*pos + size).ok_or(Error::UnexpectedEnd)?;
+    *pos += size;
+    Ok(len.iter().fold(0, |acc, b| (acc << 8) | usize::from(*b)))
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Node, Error> {
+    // children of the arrays that are currently open and their length
+    let mut stack: Vec<(Vec<Node>, usize)> = vec!();
+    let mut pos = 0;
+
+    loop {
+        let start = pos;
+        let marker = *bytes.get(pos).ok_or(Error::UnexpectedEnd)?;
+        pos += 1;
+        let array_len = match marker {
+            0x90..=0x9f => Some(usize::from(marker & 0x0f)),
+            0xdc => Some(read_len(bytes, &mut pos, 2)?),
+            0xdd => Some(read_len(bytes, &mut pos, 4)?),
+            _ => None,
+        };
+        let mut node = match array_len {
+            Some(0) => Node::Inner(vec!()),
+            Some(len) => {
+                // don't trust the announced length for the allocation
+                stack.push((Vec::with_capacity(len.min(bytes.len() - pos)), len));
+                continue;
+            }
+            None => {
+                let len = match marker {
+                    0xc4 => read_len(bytes, &mut pos, 1)?,
+                    0xc5 => read_len(bytes, &mut pos, 2)?,
+                    0xc6 => read_len(bytes, &mut pos, 4)?,
+                    _ => return Err(Error::UnexpectedByte(marker, start)),
+                };
+                Node::Leaf(read_bytes(bytes, &mut pos, len, start + 1)?.to_vec())
+            }
+        };
+
+        // add the node to its parent, closing all arrays that are complete
+        loop {
+            match stack.last_mut() {
+                Some((children, len)) => {
+                    children.push(node);
+                    if children.len() != *len {
+                        break;
+                    }
+                    node = Node::Inner(stack.pop().unwrap().0);
+                }
+                None => {
+                    if pos != bytes.len() {
+                        return Err(Error::AdditionalBytes);
+                    }
+                    return Ok(node);
+                }
+            }
+        }
+    }
+}
+
+impl Node {
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        encode(self)
+    }
+
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Node, Error> {
+        decode(bytes)
+    }
+}
+
+
+#[test]
+fn msgpack_roundtrip() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"foo".to_vec()),
+        Node::Inner(vec!(
+            Node::Leaf(vec!()),
+            Node::Inner(vec!()),
+        )),
+        Node::Leaf(vec!(7; 300)),
+        Node::Inner(vec!(Node::Leaf(vec!(1)); 16)),
+    ));
+    let mut exp = vec!(0x94, 0xc4, 3, b'f', b'o', b'o', 0x92, 0xc4, 0, 0x90, 0xc5, 0x01, 0x2c);
+    exp.extend_from_slice(&[7; 300]);
+    exp.extend_from_slice(&[0xdc, 0, 16]);
+    for _ in 0..16 {
+        exp.extend_from_slice(&[0xc4, 1, 1]);
+    }
+    assert_eq!(node.to_msgpack(), exp);
+    assert_eq!(Node::from_msgpack(&exp), Ok(node));
+    assert_eq!(Node::from_msgpack(&[0xc6, 0, 0, 0, 1, 9]), Ok(Node::Leaf(vec!(9))));
+}
+
+#[test]
+fn msgpack_errors() {
+    assert_eq!(Node::from_msgpack(b""), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_msgpack(&[0x92, 0xc4, 0]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_msgpack(&[0xc4, 2, 1]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_msgpack(&[0xc5, 0]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_msgpack(&[0x90, 0x90]), Err(Error::AdditionalBytes));
+    // positive fixint, fixstr, fixmap
+    assert_eq!(Node::from_msgpack(&[0x91, 0x01]), Err(Error::UnexpectedByte(0x01, 1)));
+    assert_eq!(Node::from_msgpack(&[0xa1, b'a']), Err(Error::UnexpectedByte(0xa1, 0)));
+    assert_eq!(Node::from_msgpack(&[0x80]), Err(Error::UnexpectedByte(0x80, 0)));
+}
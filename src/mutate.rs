@@ -0,0 +1,140 @@
+//! Random structural mutations of trees (feature `rand`), e.g. for
+//! differential fuzzing starting from seed trees. Every mutation leaves a
+//! valid tree behind.
+
+use crate::{Node, Path};
+use crate::selector::descendants;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mutation {
+    /// Swaps two subtrees, neither of which contains the other.
+    SwapSubtrees,
+    /// Inserts a copy of a child right after it.
+    DuplicateChild,
+    /// XORs a byte of a leaf with a random non-zero value.
+    FlipLeafByte,
+    /// Shortens a non-empty leaf to a random shorter length.
+    TruncateLeaf,
+}
+
+impl Mutation {
+    pub const ALL: [Mutation; 4] = [Mutation::SwapSubtrees, Mutation::DuplicateChild, Mutation::FlipLeafByte, Mutation::TruncateLeaf];
+}
+
+/// Applies `mutation` at a randomly chosen place in the tree. Returns
+/// `false` and leaves the tree unchanged if there's no place to apply it,
+/// e.g. no non-empty leaf for `FlipLeafByte`.
+pub fn apply(node: &mut Node, mutation: Mutation, rng: &mut impl Rng) -> bool {
+    let mut nodes = vec!();
+    descendants(node, &mut vec!(), &mut nodes);
+    // paths with the length of leaves (0 for inner nodes)
+    let paths: Vec<(Path, usize)> = nodes.into_iter().map(|(path, node)| match node {
+        Node::Leaf(bytes) => (path, bytes.len()),
+        Node::Inner(_) => (path, 0),
+    }).collect();
+    match mutation {
+        Mutation::SwapSubtrees => {
+            // try the nodes in random order, since in some trees (e.g. a
+            // single chain of inner nodes) no node has a disjoint partner
+            let mut candidates: Vec<&Path> = paths.iter().map(|(path, _)| path).filter(|path| !path.is_empty()).collect();
+            candidates.shuffle(rng);
+            for a in candidates {
+                let partners: Vec<&Path> = paths.iter().map(|(path, _)| path).filter(|b| !a.starts_with(b) && !b.starts_with(a)).collect();
+                if let Some(b) = partners.choose(rng) {
+                    let (a, b) = (a.clone(), (*b).clone());
+                    let mut take = |path: &Path, replacement| {
+                        std::mem::replace(node.get_mut(path).expect("collected paths are valid"), replacement)
+                    };
+                    let taken = take(&a, Node::EMPTY_INNER);
+                    let taken = take(&b, taken);
+                    take(&a, taken);
+                    return true;
+                }
+            }
+            false
+        }
+        Mutation::DuplicateChild => {
+            let children: Vec<&Path> = paths.iter().map(|(path, _)| path).filter(|path| !path.is_empty()).collect();
+            let path = match children.choose(rng) {
+                Some(path) => (*path).clone(),
+                None => return false,
+            };
+            let (idx, parent) = path.split_last().expect("children have non-empty paths");
+            if let Some(Node::Inner(nodes)) = node.get_mut(parent) {
+                let copy = nodes[*idx].clone();
+                nodes.insert(idx + 1, copy);
+            }
+            true
+        }
+        Mutation::FlipLeafByte | Mutation::TruncateLeaf => {
+            let leaves: Vec<&Path> = paths.iter().filter(|(_, len)| *len > 0).map(|(path, _)| path).collect();
+            let path = match leaves.choose(rng) {
+                Some(path) => (*path).clone(),
+                None => return false,
+            };
+            if let Some(Node::Leaf(bytes)) = node.get_mut(&path) {
+                let idx = rng.gen_range(0..bytes.len());
+                match mutation {
+                    Mutation::FlipLeafByte => bytes[idx] ^= rng.gen_range(1..=u8::MAX),
+                    _ => bytes.truncate(idx),
+                }
+            }
+            true
+        }
+    }
+}
+
+/// Applies a randomly chosen mutation that's applicable to the tree and
+/// returns it, or `None` if none is (only for an empty leaf or inner node).
+pub fn apply_random(node: &mut Node, rng: &mut impl Rng) -> Option<Mutation> {
+    let mut mutations = Mutation::ALL;
+    mutations.shuffle(rng);
+    mutations.iter().copied().find(|mutation| apply(node, *mutation, rng))
+}
+
+
+#[test]
+fn mutations() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    let rng = &mut StdRng::seed_from_u64(0);
+
+    let seed = crate::parse("(0x01 (0x02_03))");
+    let mut node = seed.clone();
+    assert!(apply(&mut node, Mutation::SwapSubtrees, rng));
+    // the only disjoint pairs are 0x01 with one of the others
+    assert!([crate::parse("((0x02_03) 0x01)"), crate::parse("(0x02_03 (0x01))")].contains(&node));
+
+    let mut node = seed.clone();
+    assert!(apply(&mut node, Mutation::DuplicateChild, rng));
+    assert!([crate::parse("(0x01 0x01 (0x02_03))"), crate::parse("(0x01 (0x02_03) (0x02_03))"), crate::parse("(0x01 (0x02_03 0x02_03))")].contains(&node));
+
+    let leaf = |node: &Node| match node {
+        Node::Inner(nodes) => nodes[0].clone(),
+        Node::Leaf(_) => panic!("the root stays an inner node"),
+    };
+    let mut node = crate::parse("(0x01_02 ())");
+    assert!(apply(&mut node, Mutation::FlipLeafByte, rng));
+    assert!(matches!(leaf(&node), Node::Leaf(bytes) if bytes.len() == 2 && bytes != [1, 2]));
+    assert!(apply(&mut node, Mutation::TruncateLeaf, rng));
+    assert!(matches!(leaf(&node), Node::Leaf(bytes) if bytes.len() < 2));
+
+    // mutations without a place to apply them leave the tree unchanged
+    let mut node = crate::parse("((0x))");
+    for mutation in [Mutation::SwapSubtrees, Mutation::FlipLeafByte, Mutation::TruncateLeaf] {
+        assert!(!apply(&mut node, mutation, rng));
+    }
+    assert_eq!(node, crate::parse("((0x))"));
+    assert_eq!(apply_random(&mut Node::Leaf(vec!()), rng), None);
+
+    let mut node = seed;
+    for _ in 0..100 {
+        assert!(apply_random(&mut node, rng).is_some());
+        assert_eq!(Node::deserialize(&node.serialize()).unwrap(), node);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{Error, ErrorKind, GenericNode, Section};
+#[cfg(test)]
+use crate::Node;
+
+use std::convert::TryInto;
+
+/// Borrowed version of `Node` whose leaves point into a buffer, e.g. the
+/// serialized tree, avoiding copies of the leaves' bytes.
+pub type NodeRef<'a> = GenericNode<&'a [u8]>;
+
+impl<'a> NodeRef<'a> {
+    /// Deserializes a tree like `Node::deserialize`, with leaves borrowing
+    /// from `bytes`.
+    pub fn deserialize(bytes: &'a [u8]) -> Result<Self, Error> {
+        if !bytes.starts_with(b"BAUM1") {
+            return Err(match bytes.len() < 5 {
+                true => Error::unexpected_eof(Section::Magic, 5, bytes.len() as u64),
+                false => Error::new(ErrorKind::InvalidMagicNumber),
+            });
+        }
+        let mut pos = 5;
+        let res = deserialize(bytes, &mut pos)?;
+        match pos == bytes.len() {
+            true => Ok(res),
+            false => Err(Error::new(ErrorKind::AdditionalBytes)),
+        }
+    }
+}
+
+pub(crate) fn take<'a>(bytes: &'a [u8], pos: &mut usize, len: usize, section: Section) -> Result<&'a [u8], Error> {
+    match bytes.len() - *pos >= len {
+        true => {
+            *pos += len;
+            Ok(&bytes[*pos - len..*pos])
+        }
+        false => Err(Error::unexpected_eof(section, len as u64, (bytes.len() - *pos) as u64)),
+    }
+}
+
+pub(crate) fn read_len(bytes: &[u8], pos: &mut usize) -> Result<usize, Error> {
+    let len = u64::from_le_bytes(take(bytes, pos, 8, Section::Length)?.try_into().unwrap());
+    len.try_into().map_err(|_| Error::new(ErrorKind::LengthOverflow))
+}
+
+pub(crate) fn deserialize<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<NodeRef<'a>, Error> {
+    decode_with(bytes, pos, NodeRef::Leaf, NodeRef::Inner)
+}
+
+/// Decodes the node at `pos`, creating leaves from their bytes with `leaf`
+/// and inner nodes from their children with `inner`. Decodes iteratively,
+/// so that deeply nested input can't overflow the stack. Paths in errors
+/// are relative to the decoded node.
+pub(crate) fn decode_with<'a, T, F, G>(bytes: &'a [u8], pos: &mut usize, mut leaf: F, mut inner: G) -> Result<T, Error>
+where
+    F: FnMut(&'a [u8]) -> T,
+    G: FnMut(Vec<T>) -> T,
+{
+    // children of the inner nodes that are currently open and their length
+    let mut stack: Vec<(Vec<T>, usize)> = vec!();
+    let mut path = vec!();
+    loop {
+        let start = *pos as u64;
+        let mut node = match take(bytes, pos, 1, Section::Header).map_err(|e| e.at(start, &path))?[0] {
+            0 => {
+                let len = read_len(bytes, pos).map_err(|e| e.at(start + 1, &path))?;
+                leaf(take(bytes, pos, len, Section::Payload).map_err(|e| e.at(start + 9, &path))?)
+            }
+            1 => {
+                let len = read_len(bytes, pos).map_err(|e| e.at(start + 1, &path))?;
+                if len > 0 {
+                    // every child takes at least 9 bytes
+                    stack.push((Vec::with_capacity(len.min((bytes.len() - *pos) / 9)), len));
+                    path.push(0);
+                    continue;
+                }
+                inner(vec!())
+            }
+            _ => return Err(Error::new(ErrorKind::InvalidNodeType).at(start, &path)),
+        };
+
+        // add the node to its parent, closing all inner nodes that are complete
+        loop {
+            let (children, len) = match stack.last_mut() {
+                Some(open) => open,
+                None => return Ok(node),
+            };
+            children.push(node);
+            if children.len() < *len {
+                *path.last_mut().unwrap() += 1;
+                break;
+            }
+            path.pop();
+            node = inner(stack.pop().unwrap().0);
+        }
+    }
+}
+
+#[test]
+fn node_ref_deserialize() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1, 2)),
+        Node::Inner(vec!(Node::Leaf(vec!()), Node::Inner(vec!()))),
+    ));
+    let bytes = node.serialize();
+    let node_ref = NodeRef::deserialize(&bytes).unwrap();
+    assert_eq!(node_ref, NodeRef::from(&node));
+    match &node_ref {
+        NodeRef::Inner(nodes) => match nodes[0] {
+            NodeRef::Leaf(leaf) => assert_eq!(leaf.as_ptr(), bytes[23..].as_ptr()),
+            _ => panic!(),
+        },
+        _ => panic!(),
+    }
+    assert_eq!(node_ref.to_node(), node);
+
+    assert!(matches!(NodeRef::deserialize(b"BAUM"), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+    assert!(matches!(NodeRef::deserialize(b"BAUM2"), Err(e) if e.kind() == ErrorKind::InvalidMagicNumber));
+    assert!(matches!(NodeRef::deserialize(&bytes[..bytes.len() - 1]), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+    let mut long = bytes.clone();
+    long.push(0);
+    assert!(matches!(NodeRef::deserialize(&long), Err(e) if e.kind() == ErrorKind::AdditionalBytes));
+    assert!(matches!(NodeRef::deserialize(b"BAUM1\x02"), Err(e) if e.kind() == ErrorKind::InvalidNodeType));
+    // announced length exceeding the input
+    assert!(matches!(NodeRef::deserialize(b"BAUM1\x01\xff\xff\xff\xff\xff\xff\xff\xff"), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+    assert!(matches!(NodeRef::deserialize(b"BAUM1\x00\xff\xff\xff\xff\xff\xff\xff\xff"), Err(e) if e.kind() == ErrorKind::UnexpectedEof));
+}
@@ -0,0 +1,181 @@
+use crate::Node;
+use crate::parser::{self, ParseResult, Token};
+
+// Outline format: one node per line, children are indented deeper than their
+// parent. Inner nodes are written as `()`, leaves use the regular `0x..` syntax.
+//
+// ()
+//   0x01
+//   ()
+//     0x02
+//     0x03
+//   0x04_05
+
+const INDENT: usize = 2;
+
+impl Node {
+    pub fn to_outline(&self) -> String {
+        let mut s = String::new();
+        self._to_outline(0, &mut s);
+        s
+    }
+
+    fn _to_outline(&self, indent: usize, s: &mut String) {
+        s.push_str(&" ".repeat(indent));
+        match self {
+            Node::Leaf(_) => s.push_str(&self.to_string()),
+            Node::Inner(_) => s.push_str("()"),
+        }
+        s.push('\n');
+        if let Node::Inner(nodes) = self {
+            for node in nodes {
+                node._to_outline(indent + INDENT, s);
+            }
+        }
+    }
+
+    pub fn parse_outline(s: &str) -> ParseResult {
+        parse(s)
+    }
+}
+
+struct Frame {
+    indent: usize,
+    child_indent: Option<usize>,
+    children: Vec<Node>,
+}
+
+enum Line {
+    Leaf(Vec<u8>),
+    Inner,
+}
+
+fn parse_line(content: &str) -> Result<Line, String> {
+    if content == "()" {
+        return Ok(Line::Inner);
+    }
+    let mut tokens = parser::tokenize(content)?;
+    match (tokens.pop(), tokens.is_empty()) {
+        (Some(Token::Bytes(b)), true) => Ok(Line::Leaf(b)),
+        _ => Err("Expected a single leaf or `()`.".to_string()),
+    }
+}
+
+pub fn parse(s: &str) -> ParseResult {
+    let mut stack: Vec<Frame> = vec!();
+    let mut root = None;
+    // indentation of the previous line if it was a leaf
+    let mut last_leaf = None;
+
+    for (idx, line) in s.lines().enumerate() {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let content = line.trim_start_matches(' ');
+        let indent = line.len() - content.len();
+        if content.starts_with('\t') {
+            return ParseResult::LexingError(format!("Line {}: Tabs are not allowed for indentation.", idx + 1));
+        }
+        let parsed = match parse_line(content) {
+            Ok(l) => l,
+            Err(e) => return ParseResult::LexingError(format!("Line {}: {}", idx + 1, e)),
+        };
+
+        if matches!(last_leaf, Some(i) if indent > i) {
+            return ParseResult::ParsingError(format!("Line {}: Leaves can't have children.", idx + 1));
+        }
+
+        // close all inner nodes that can't be the parent of this line
+        while let Some(frame) = stack.last() {
+            if indent > frame.indent {
+                break;
+            }
+            let frame = stack.pop().unwrap();
+            let node = Node::Inner(frame.children);
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => root = Some(node),
+            }
+        }
+
+        match stack.last_mut() {
+            Some(parent) => match parent.child_indent {
+                Some(i) if i != indent => {
+                    return ParseResult::ParsingError(format!("Line {}: Inconsistent indentation.", idx + 1));
+                }
+                _ => parent.child_indent = Some(indent),
+            },
+            None => {
+                if root.is_some() {
+                    return ParseResult::ParsingError(format!("Line {}: Unexpected node after root node.", idx + 1));
+                }
+                if indent != 0 {
+                    return ParseResult::ParsingError(format!("Line {}: Root node must not be indented.", idx + 1));
+                }
+            }
+        }
+
+        match parsed {
+            Line::Leaf(bytes) => {
+                last_leaf = Some(indent);
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(Node::Leaf(bytes)),
+                    None => root = Some(Node::Leaf(bytes)),
+                }
+            }
+            Line::Inner => {
+                last_leaf = None;
+                stack.push(Frame { indent, child_indent: None, children: vec!() });
+            }
+        }
+    }
+
+    while let Some(frame) = stack.pop() {
+        let node = Node::Inner(frame.children);
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => root = Some(node),
+        }
+    }
+
+    match root {
+        Some(node) => ParseResult::Ok(node),
+        None => ParseResult::ParsingError("Input doesn't contain a node.".to_string()),
+    }
+}
+
+
+#[test]
+fn outline_roundtrip() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(
+            Node::Leaf(vec!(2)),
+            Node::Inner(vec!()),
+        )),
+        Node::Leaf(vec!()),
+        Node::Leaf(vec!(4, 5)),
+    ));
+    let exp = "()\n  0x01\n  ()\n    0x02\n    ()\n  0x\n  0x04_05\n";
+    assert_eq!(node.to_outline(), exp);
+
+    match Node::parse_outline(exp) {
+        ParseResult::Ok(n) => assert_eq!(n, node),
+        r => panic!("{}", r.err_message()),
+    }
+}
+
+#[test]
+fn outline_parse_errors() {
+    // leaf with children
+    assert!(!Node::parse_outline("()\n  0x01\n    0x02\n").is_ok());
+    // siblings with different indentation
+    assert!(!Node::parse_outline("()\n    0x01\n  0x02\n").is_ok());
+    // multiple roots
+    assert!(!Node::parse_outline("0x01\n0x02\n").is_ok());
+    // invalid leaf
+    assert!(!Node::parse_outline("()\n  0x01 0x02\n").is_lexing_ok());
+    // blank lines and arbitrary (but consistent) indentation are fine
+    assert!(Node::parse_outline("()\n\n   0x01\n   ()\n      0x02\n   0x03").is_ok());
+}
@@ -0,0 +1,61 @@
+use crate::Node;
+
+use rayon::prelude::*;
+
+/// Nesting depth up to which children are serialized in parallel. Deeper
+/// subtrees are serialized by the thread handling their ancestor.
+const PARALLEL_DEPTH: usize = 4;
+
+impl Node {
+    /// Serializes the tree like `serialize`, serializing the children of
+    /// nodes close to the root in parallel into separate buffers, which are
+    /// concatenated afterwards.
+    pub fn par_serialize(&self) -> Vec<u8> {
+        let mut res = "BAUM1".as_bytes().to_vec();
+        par_serialize_into(self, 0, &mut res);
+        res
+    }
+
+    /// Returns a parallel iterator over all nodes of the tree, including
+    /// `self`.
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = &Node> {
+        rayon::iter::walk_tree_prefix(self, |node: &&Node| match node {
+            Node::Leaf(_) => [].iter(),
+            Node::Inner(nodes) => nodes.iter(),
+        })
+    }
+}
+
+fn par_serialize_into(node: &Node, depth: usize, w: &mut Vec<u8>) {
+    match node {
+        Node::Inner(nodes) if depth < PARALLEL_DEPTH && nodes.len() > 1 => {
+            w.push(1);
+            w.extend_from_slice(&(nodes.len() as u64).to_le_bytes());
+            let buffers: Vec<Vec<u8>> = nodes.par_iter().map(|node| {
+                let mut buf = vec!();
+                par_serialize_into(node, depth + 1, &mut buf);
+                buf
+            }).collect();
+            for buf in buffers {
+                w.extend_from_slice(&buf);
+            }
+        }
+        _ => node._serialize(w),
+    }
+}
+
+
+#[test]
+fn parallel() {
+    let node = Node::Inner((0..100u8).map(|i| match i % 3 {
+        0 => Node::Leaf(vec!(i; i as usize)),
+        _ => Node::Inner((0..i).map(|j| Node::Inner(vec!(Node::Leaf(vec!(j)), Node::Inner(vec!())))).collect()),
+    }).collect());
+    assert_eq!(node.par_serialize(), node.serialize());
+    assert_eq!(node.par_iter().count(), node.node_count());
+    let leaf_bytes: usize = node.par_iter().map(|n| match n {
+        Node::Leaf(bytes) => bytes.len(),
+        Node::Inner(_) => 0,
+    }).sum();
+    assert_eq!(leaf_bytes, (0..100).filter(|i| i % 3 == 0).sum::<usize>() + (0..100).filter(|i| i % 3 != 0).sum::<usize>());
+}
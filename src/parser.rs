@@ -1,14 +1,42 @@
 use crate::Node;
 
+use std::iter::Peekable;
+use std::str::Chars;
+
 pub enum Token {
     LParen,
     RParen,
     Bytes(Vec<u8>),
+    Placeholder(String),
+    /// `_`, matching any node in a `Pattern`.
+    Wildcard,
+    /// `...`, matching any number of children in a `Pattern`.
+    Rest,
+}
+
+impl Token {
+    /// Error message for tokens that aren't allowed in plain nodes.
+    pub fn unexpected(&self) -> String {
+        match self {
+            Token::LParen => "Unexpected '('.".to_string(),
+            Token::RParen => "Unexpected ')'.".to_string(),
+            Token::Bytes(_) => "Unexpected leaf.".to_string(),
+            Token::Placeholder(name) => format!("Unexpected placeholder '${}'.", name),
+            Token::Wildcard => "Unexpected wildcard '_'.".to_string(),
+            Token::Rest => "Unexpected '...'.".to_string(),
+        }
+    }
 }
 
 pub fn tokenize(s: &str) -> Result<Vec<Token>, String> {
-    let mut char_iter = s.chars().into_iter().peekable();
     let mut tokens = vec!();
+    tokenize_into(s, &mut tokens)?;
+    Ok(tokens)
+}
+
+/// Like `tokenize`, appending the tokens to `tokens`.
+pub fn tokenize_into(s: &str, tokens: &mut Vec<Token>) -> Result<(), String> {
+    let mut char_iter = s.chars().peekable();
 
     while let Some(c) = char_iter.next() {
         match c {
@@ -18,33 +46,30 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, String> {
                 if char_iter.next() != Some('x') {
                     return Err("Expected 'x'!".to_string());
                 }
-                
-                
-                let mut digits = vec!();
+                tokens.push(Token::Bytes(lex_hex(&mut char_iter)));
+            }
+            '"' => tokens.push(Token::Bytes(lex_string(&mut char_iter)?)),
+            '$' => {
+                let mut name = String::new();
                 while let Some(c) = char_iter.peek() {
-                    if let Some(d) = c.to_digit(16) {
-                        digits.push(d as u8);
+                    if c.is_ascii_alphanumeric() || *c == '_' {
+                        name.push(*c);
                         char_iter.next();
-                    } else if *c == '_' {
-                        // ignore underscores
-                        char_iter.next();
-                    }else {
+                    } else {
                         break;
                     }
                 }
-
-                let is_odd = digits.len() % 2 == 1;
-                let mut num_iter = digits.into_iter();
-                let mut ret = vec!();
-                if is_odd {
-                    ret.push(num_iter.next().unwrap());
+                if name.is_empty() {
+                    return Err("Expected placeholder name after '$'!".to_string());
                 }
-                while let Some(d) = num_iter.next() {
-                    let next = num_iter.next().unwrap();
-                    ret.push(d*0x10 + next);
+                tokens.push(Token::Placeholder(name));
+            }
+            '_' => tokens.push(Token::Wildcard),
+            '.' => {
+                if char_iter.next() != Some('.') || char_iter.next() != Some('.') {
+                    return Err("Expected '...'!".to_string());
                 }
-                
-                tokens.push(Token::Bytes(ret));
+                tokens.push(Token::Rest);
             }
             c if c.is_ascii_whitespace() => {
                 // ignore
@@ -54,79 +79,245 @@ pub fn tokenize(s: &str) -> Result<Vec<Token>, String> {
             }
         }
     }
-    Ok(tokens)
+    Ok(())
+}
+
+/// Lexes the digits of a hex leaf after its `0x` prefix.
+pub(crate) fn lex_hex(char_iter: &mut Peekable<Chars>) -> Vec<u8> {
+    let mut digits = vec!();
+    while let Some(c) = char_iter.peek() {
+        if let Some(d) = crate::hex::digit(*c) {
+            digits.push(d);
+            char_iter.next();
+        } else if *c == '_' {
+            // ignore underscores
+            char_iter.next();
+        } else {
+            break;
+        }
+    }
+    crate::hex::pack(&digits)
+}
+
+/// Lexes a string leaf after its opening quote.
+pub(crate) fn lex_string(char_iter: &mut Peekable<Chars>) -> Result<Vec<u8>, String> {
+    let mut bytes = vec!();
+    loop {
+        match char_iter.next() {
+            Some('"') => break,
+            Some('\\') => match char_iter.next() {
+                Some('"') => bytes.push(b'"'),
+                Some('\\') => bytes.push(b'\\'),
+                Some('n') => bytes.push(b'\n'),
+                Some('r') => bytes.push(b'\r'),
+                Some('t') => bytes.push(b'\t'),
+                Some('0') => bytes.push(0),
+                Some('x') => {
+                    let hi = char_iter.next().and_then(crate::hex::digit);
+                    let lo = char_iter.next().and_then(crate::hex::digit);
+                    match (hi, lo) {
+                        (Some(hi), Some(lo)) => bytes.push(hi * 0x10 + lo),
+                        _ => return Err("Expected two hex digits after '\\x'!".to_string()),
+                    }
+                }
+                _ => return Err("Invalid escape sequence!".to_string()),
+            },
+            Some(c) => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            None => return Err("Unterminated string!".to_string()),
+        }
+    }
+    Ok(bytes)
 }
 
-pub fn parse(tokens: Vec<Token>) -> Result<Node, String> {
-    let mut token_iter = tokens.into_iter().peekable();
-    let res = parse_node(&mut token_iter)?;
-    if let Some(_) = token_iter.next() {
-        return Err("Unexpected characters after node.".to_string());
+/// Syntax tree of the text format, which may contain elements that have no
+/// `Node` equivalent (e.g. placeholders).
+pub enum Ast {
+    Leaf(Vec<u8>),
+    Inner(Vec<Ast>),
+    Placeholder(String),
+    Wildcard,
+    Rest,
+}
+
+impl Ast {
+    pub fn into_node(self) -> Result<Node, String> {
+        match self {
+            Ast::Leaf(b) => Ok(Node::Leaf(b)),
+            Ast::Inner(children) => Ok(Node::Inner(
+                children.into_iter().map(Ast::into_node).collect::<Result<_, _>>()?
+            )),
+            Ast::Placeholder(name) => Err(Token::Placeholder(name).unexpected()),
+            Ast::Wildcard => Err(Token::Wildcard.unexpected()),
+            Ast::Rest => Err(Token::Rest.unexpected()),
+        }
     }
-    Ok(res)
 }
 
-type Tokens<'a> = std::iter::Peekable<std::vec::IntoIter<Token>>;
+/// Maximum nesting depth of inner nodes accepted by `Node::parse`.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
 
+pub enum ParseError {
+    Invalid(String),
+    DepthLimitExceeded,
+}
 
-fn parse_node(data: &mut Tokens) -> Result<Node, String> {
-    match data.next() {
-        Some(Token::Bytes(b)) => Ok(Node::Leaf(b)),
-        Some(Token::LParen) => parse_inner_node(data),
-        _ => Err("Parse Error".to_string()),
+impl From<ParseError> for String {
+    fn from(err: ParseError) -> String {
+        match err {
+            ParseError::Invalid(s) => s,
+            ParseError::DepthLimitExceeded => "Maximum nesting depth exceeded.".to_string(),
+        }
     }
 }
 
-fn parse_inner_node(data: &mut Tokens) -> Result<Node, String> {
-    let mut children = vec!();
+pub fn parse_ast(tokens: Vec<Token>, max_depth: usize) -> Result<Ast, ParseError> {
+    parse_tokens(tokens, max_depth, &mut vec!(), |token| Ok(match token {
+        Token::Bytes(b) => Ast::Leaf(b),
+        Token::Placeholder(name) => Ast::Placeholder(name),
+        Token::Wildcard => Ast::Wildcard,
+        Token::Rest => Ast::Rest,
+        // handled by `parse_tokens`
+        Token::LParen | Token::RParen => unreachable!(),
+    }), Ast::Inner)
+}
+
+/// Assembles the tree of `tokens`, creating nodes for tokens other than
+/// parentheses with `atom` and inner nodes from their children with
+/// `inner`. Parses iteratively, so that deeply nested input can't overflow
+/// the stack. `stack` holds the children of the inner nodes that are
+/// currently open; it's cleared first and can be reused between calls.
+pub(crate) fn parse_tokens<T, I, F, G>(tokens: I, max_depth: usize, stack: &mut Vec<Vec<T>>, mut atom: F, mut inner: G) -> Result<T, ParseError>
+where
+    I: IntoIterator<Item = Token>,
+    F: FnMut(Token) -> Result<T, ParseError>,
+    G: FnMut(Vec<T>) -> T,
+{
+    stack.clear();
+    let mut token_iter = tokens.into_iter();
+
     loop {
-        if let Some(Token::RParen) = data.peek() {
-            data.next();
-            break;
+        let node = match token_iter.next() {
+            Some(Token::LParen) => {
+                if stack.len() >= max_depth {
+                    return Err(ParseError::DepthLimitExceeded);
+                }
+                stack.push(vec!());
+                continue;
+            }
+            Some(Token::RParen) => match stack.pop() {
+                Some(children) => inner(children),
+                None => return Err(ParseError::Invalid("Unexpected ')'.".to_string())),
+            },
+            Some(token) => atom(token)?,
+            None => return Err(ParseError::Invalid("Unexpected end of input.".to_string())),
+        };
+
+        match stack.last_mut() {
+            Some(children) => children.push(node),
+            None => {
+                if token_iter.next().is_some() {
+                    return Err(ParseError::Invalid("Unexpected characters after node.".to_string()));
+                }
+                return Ok(node);
+            }
         }
-        children.push(parse_node(data)?);
     }
-    Ok(Node::Inner(children))
 }
 
 pub enum ParseResult {
     LexingError(String),
     ParsingError(String),
+    /// The input is nested deeper than the maximum depth.
+    DepthLimitExceeded,
     Ok(Node)
 }
 
+impl From<ParseError> for ParseResult {
+    fn from(err: ParseError) -> ParseResult {
+        match err {
+            ParseError::Invalid(s) => ParseResult::ParsingError(s),
+            ParseError::DepthLimitExceeded => ParseResult::DepthLimitExceeded,
+        }
+    }
+}
+
 impl ParseResult {
     pub fn is_lexing_ok(&self) -> bool {
-        match self {
-            ParseResult::LexingError(_) => false,
-            _ => true,
-        }
+        !matches!(self, ParseResult::LexingError(_))
     }
     
     pub fn is_ok(&self) -> bool {
-        match self {
-            ParseResult::Ok(_) => true,
-            _ => false,
-        }
+        matches!(self, ParseResult::Ok(_))
     }
 
     pub fn err_message(&self) -> &str {
         match self {
             ParseResult::Ok(_) => "",
-            ParseResult::LexingError(s) => &s,
-            ParseResult::ParsingError(s) => &s,
+            ParseResult::LexingError(s) => s,
+            ParseResult::ParsingError(s) => s,
+            ParseResult::DepthLimitExceeded => "Maximum nesting depth exceeded.",
         }
     }
 
     pub fn parse(s: &str) -> ParseResult {
+        Self::parse_with_max_depth(s, DEFAULT_MAX_DEPTH)
+    }
+
+    pub fn parse_with_max_depth(s: &str, max_depth: usize) -> ParseResult {
         match tokenize(s) {
             Ok(tokens) => {
-                match parse(tokens) {
-                    Ok(node) => ParseResult::Ok(node),
-                    Err(s) => ParseResult::ParsingError(s)
+                match parse_ast(tokens, max_depth) {
+                    Ok(ast) => match ast.into_node() {
+                        Ok(node) => ParseResult::Ok(node),
+                        Err(s) => ParseResult::ParsingError(s),
+                    },
+                    Err(e) => e.into(),
                 }
             },
             Err(s) => ParseResult::LexingError(s)
         }
     }
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn parse_max_depth() {
+    assert!(ParseResult::parse_with_max_depth("(0x01 (0x02))", 2).is_ok());
+    assert!(ParseResult::parse_with_max_depth("0x01", 0).is_ok());
+    assert!(matches!(ParseResult::parse_with_max_depth("(0x01 (0x02))", 1), ParseResult::DepthLimitExceeded));
+
+    // deeply nested input must neither overflow the stack nor be accepted by default
+    let deep = "(".repeat(1_000_000);
+    assert!(matches!(ParseResult::parse(&deep), ParseResult::DepthLimitExceeded));
+    let s = format!("{}{}", "(".repeat(DEFAULT_MAX_DEPTH), ")".repeat(DEFAULT_MAX_DEPTH));
+    assert!(ParseResult::parse(&s).is_ok());
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(ParseResult::parse("(0x01").err_message(), "Unexpected end of input.");
+    assert_eq!(ParseResult::parse(")").err_message(), "Unexpected ')'.");
+    assert_eq!(ParseResult::parse("(0x01) 0x02").err_message(), "Unexpected characters after node.");
+    assert_eq!(ParseResult::parse("(0x01 _)").err_message(), "Unexpected wildcard '_'.");
+    assert_eq!(ParseResult::parse("(..)").err_message(), "Expected '...'!");
+}
+
+#[test]
+fn parse_strings() {
+    let exp = Node::Inner(vec!(
+        Node::Leaf(b"abc".to_vec()),
+        Node::Leaf(vec!()),
+        Node::Leaf("a \"q\" \\ \n\r\t\0 ü".as_bytes().to_vec()),
+        Node::Leaf(vec!(0xff, 0x01)),
+    ));
+    match ParseResult::parse(r#"("abc" "" "a \"q\" \\ \n\r\t\0 ü" "\xff\x01")"#) {
+        ParseResult::Ok(n) => assert_eq!(n, exp),
+        r => panic!("{}", r.err_message()),
+    }
+    assert_eq!(ParseResult::parse(r#""abc"#).err_message(), "Unterminated string!");
+    assert_eq!(ParseResult::parse(r#""\q""#).err_message(), "Invalid escape sequence!");
+    assert!(!ParseResult::parse(r#""\x1""#).is_lexing_ok());
+}
@@ -0,0 +1,148 @@
+use crate::Node;
+use crate::parser::{self, Ast};
+
+/// Shape of a tree in text syntax with wildcards, e.g. `(0x01 _ (0x02 ...))`.
+/// `_` matches any node and `...` matches any number of consecutive
+/// children, including none. Placeholders like `$payload` match any node and
+/// capture it. A placeholder used multiple times only matches equal nodes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Pattern {
+    root: PatternNode,
+    placeholders: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum PatternNode {
+    Leaf(Vec<u8>),
+    Inner(Vec<PatternNode>),
+    Any,
+    Rest,
+    /// Placeholder with the given index into `Pattern::placeholders`.
+    Capture(usize),
+}
+
+/// Nodes captured so far, in order of capturing.
+type Captures<'a> = Vec<(usize, &'a Node)>;
+
+impl PatternNode {
+    fn from_ast(ast: Ast, placeholders: &mut Vec<String>) -> PatternNode {
+        match ast {
+            Ast::Leaf(b) => PatternNode::Leaf(b),
+            Ast::Inner(children) => PatternNode::Inner(
+                children.into_iter().map(|c| PatternNode::from_ast(c, placeholders)).collect()
+            ),
+            Ast::Wildcard => PatternNode::Any,
+            Ast::Rest => PatternNode::Rest,
+            Ast::Placeholder(name) => match placeholders.iter().position(|p| *p == name) {
+                Some(idx) => PatternNode::Capture(idx),
+                None => {
+                    placeholders.push(name);
+                    PatternNode::Capture(placeholders.len() - 1)
+                }
+            },
+        }
+    }
+
+    fn matches<'a>(&self, node: &'a Node, captures: &mut Captures<'a>) -> bool {
+        match (self, node) {
+            (PatternNode::Any, _) => true,
+            (PatternNode::Capture(idx), node) => match captures.iter().find(|(i, _)| i == idx) {
+                Some((_, captured)) => *captured == node,
+                None => {
+                    captures.push((*idx, node));
+                    true
+                }
+            },
+            (PatternNode::Leaf(expected), Node::Leaf(bytes)) => expected == bytes,
+            (PatternNode::Inner(patterns), Node::Inner(nodes)) => match_children(patterns, nodes, captures),
+            _ => false,
+        }
+    }
+}
+
+fn match_children<'a>(patterns: &[PatternNode], nodes: &'a [Node], captures: &mut Captures<'a>) -> bool {
+    match patterns.split_first() {
+        None => nodes.is_empty(),
+        Some((PatternNode::Rest, patterns)) => (0..=nodes.len()).any(|skip| {
+            let len = captures.len();
+            // drop the captures of failed attempts
+            match_children(patterns, &nodes[skip..], captures) || { captures.truncate(len); false }
+        }),
+        Some((pattern, patterns)) => match nodes.split_first() {
+            Some((node, nodes)) => pattern.matches(node, captures) && match_children(patterns, nodes, captures),
+            None => false,
+        },
+    }
+}
+
+impl Pattern {
+    pub fn parse(s: &str) -> Result<Pattern, String> {
+        let ast = parser::parse_ast(parser::tokenize(s)?, parser::DEFAULT_MAX_DEPTH)?;
+        let mut placeholders = vec!();
+        match PatternNode::from_ast(ast, &mut placeholders) {
+            PatternNode::Rest => Err("'...' is only allowed among children.".to_string()),
+            root => Ok(Pattern { root, placeholders }),
+        }
+    }
+
+    /// Returns the names of all placeholders in order of first occurrence.
+    pub fn placeholders(&self) -> Vec<&str> {
+        self.placeholders.iter().map(String::as_str).collect()
+    }
+
+    /// Returns the nodes captured by the placeholders, in the order of
+    /// `placeholders`, or `None` if the tree doesn't match.
+    pub fn captures<'a>(&self, node: &'a Node) -> Option<Vec<&'a Node>> {
+        let mut captures = vec!();
+        if !self.root.matches(node, &mut captures) {
+            return None;
+        }
+        captures.sort_by_key(|(idx, _)| *idx);
+        Some(captures.into_iter().map(|(_, node)| node).collect())
+    }
+}
+
+impl Node {
+    /// Returns `true` if the tree has the shape described by `pattern`.
+    pub fn matches(&self, pattern: &Pattern) -> bool {
+        pattern.captures(self).is_some()
+    }
+}
+
+
+#[test]
+fn pattern() {
+    let node = crate::parse("(0x01 (0x02 0x03 0x04) ())");
+    let matches = |s: &str| node.matches(&Pattern::parse(s).unwrap());
+    assert!(matches("_"));
+    assert!(matches("(0x01 _ _)"));
+    assert!(matches("(0x01 ...)"));
+    assert!(matches("(... ())"));
+    assert!(matches("(_ (... 0x03 ...) ...)"));
+    assert!(matches("(0x01 (0x02 ... 0x04 ...) () ...)"));
+    assert!(!matches("(0x02 ...)"));
+    assert!(!matches("(0x01 _)"));
+    assert!(!matches("(_ (... 0x05 ...) ...)"));
+    assert!(!matches("(_ _ (_))"));
+    assert!(!matches("(_ 0x02 ...)"));
+
+    assert_eq!(Pattern::parse("..."), Err("'...' is only allowed among children.".to_string()));
+    assert!(Pattern::parse("(_").is_err());
+}
+
+#[test]
+fn pattern_captures() {
+    let node = crate::parse("(0x01 (0x02 0x03) 0x04 0x03)");
+    let pattern = Pattern::parse("(0x01 $head ... $last)").unwrap();
+    assert_eq!(pattern.placeholders(), vec!("head", "last"));
+    assert_eq!(pattern.captures(&node), Some(vec!(&crate::parse("(0x02 0x03)"), &Node::Leaf(vec!(3)))));
+
+    // captures of failed attempts to match `...` are discarded
+    let pattern = Pattern::parse("(... $x ... (_ $x))").unwrap();
+    assert_eq!(pattern.captures(&node), None);
+    let pattern = Pattern::parse("(_ (_ $x) ... $x)").unwrap();
+    assert_eq!(pattern.captures(&node), Some(vec!(&Node::Leaf(vec!(3)))));
+    let pattern = Pattern::parse("(... $x ... $x)").unwrap();
+    assert_eq!(pattern.captures(&node), None);
+    assert_eq!(pattern.captures(&crate::parse("(0x01 0x02 0x01)")), Some(vec!(&Node::Leaf(vec!(1)))));
+}
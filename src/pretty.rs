@@ -0,0 +1,662 @@
+use crate::{Color, Node, Style, Theme};
+use crate::{highlight, hex};
+
+use std::fmt::Write;
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum HexCase {
+    Lower,
+    Upper,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum LeafFormat {
+    /// Hex bytes, e.g. `0x01_02`.
+    Hex,
+    /// Classic hexdump with offset column, 16 bytes per line and ASCII gutter.
+    /// Non-empty leaves are always printed on separate lines.
+    Hexdump,
+    /// Quoted strings like `"abc"` for leaves that contain printable UTF-8,
+    /// hex bytes for all other leaves.
+    Text,
+}
+
+/// Options for `Node::pretty_print_with`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PrettyConfig {
+    /// Maximum line width. Inner nodes that don't fit are split over multiple lines.
+    pub max_width: usize,
+    /// Number of spaces children of multi-line inner nodes are indented by.
+    pub indent: usize,
+    pub hex_case: HexCase,
+    pub leaf_format: LeafFormat,
+    /// Separator between groups of bytes of a leaf. Note that output using
+    /// separators other than `_` or `""` can't be parsed again.
+    pub byte_separator: String,
+    /// Number of bytes that are printed without separator in between, e.g.
+    /// `0xdeadbeef_01020304` for a group size of 4.
+    pub group_size: usize,
+    /// Maximum number of bytes printed on a single line. Longer leaves are
+    /// continued on the next line.
+    pub bytes_per_line: Option<usize>,
+    /// Leaves longer than this are truncated, e.g. `0xde_ad… (4096 bytes)`.
+    pub max_leaf_bytes: Option<usize>,
+    /// Non-empty inner nodes at this depth are summarized, e.g. `(… 37 nodes)`.
+    /// The root node has depth 0.
+    pub max_depth: Option<usize>,
+    /// Whether to end the output with a newline.
+    pub trailing_newline: bool,
+    /// Colors the output using ANSI escape sequences if set.
+    pub theme: Option<Theme>,
+}
+
+impl Default for PrettyConfig {
+    fn default() -> Self {
+        PrettyConfig {
+            max_width: 80,
+            indent: 4,
+            hex_case: HexCase::Lower,
+            leaf_format: LeafFormat::Hex,
+            byte_separator: "_".to_string(),
+            group_size: 1,
+            bytes_per_line: None,
+            max_leaf_bytes: None,
+            max_depth: None,
+            trailing_newline: false,
+            theme: None,
+        }
+    }
+}
+
+impl Node {
+    pub fn pretty_print(&self, max_width: usize) -> String {
+        let mut s = String::new();
+        self.pretty_print_fmt(&mut s, max_width).expect("writing to a String doesn't fail");
+        s
+    }
+
+    /// Like `pretty_print`, but writes to `out`, passing on its errors.
+    pub fn pretty_print_fmt(&self, out: &mut impl Write, max_width: usize) -> std::fmt::Result {
+        self.pretty_print_to(out, &PrettyConfig { max_width, ..PrettyConfig::default() })
+    }
+
+    pub fn pretty_print_with(&self, config: &PrettyConfig) -> String {
+        let mut s = String::new();
+        self.pretty_print_to(&mut s, config).expect("writing to a String doesn't fail");
+        s
+    }
+
+    /// Pretty-prints the node into `out` without building an intermediate `String`.
+    pub fn pretty_print_to<W: Write>(&self, out: &mut W, config: &PrettyConfig) -> std::fmt::Result {
+        write_pretty(self, config, out)
+    }
+
+    /// Pretty-prints the node into an `io::Write`. Output is written in many
+    /// small pieces, so wrapping `writer` in a `BufWriter` is recommended.
+    pub fn pretty_print_into<W: std::io::Write>(&self, writer: &mut W, config: &PrettyConfig) -> std::io::Result<()> {
+        let mut adapter = IoAdapter { writer, error: None };
+        match write_pretty(self, config, &mut adapter) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(adapter.error.unwrap_or_else(|| std::io::Error::other("formatting error"))),
+        }
+    }
+
+    /// Like `pretty_print`, but colors the output using ANSI escape sequences.
+    pub fn highlight(&self, max_width: usize) -> String {
+        self.highlight_with(max_width, &Theme::default())
+    }
+
+    pub fn highlight_with(&self, max_width: usize, theme: &Theme) -> String {
+        self.pretty_print_with(&PrettyConfig {
+            max_width,
+            theme: Some(theme.clone()),
+            ..PrettyConfig::default()
+        })
+    }
+
+    /// Pretty-prints the node, coloring nodes for which `style` returns a
+    /// color. All other nodes are colored according to `config.theme`.
+    pub fn pretty_print_styled(&self, config: &PrettyConfig, style: &dyn Style) -> String {
+        let mut s = String::new();
+        self.pretty_print_styled_to(&mut s, config, style).expect("writing to a String doesn't fail");
+        s
+    }
+
+    pub fn pretty_print_styled_to<W: Write>(&self, out: &mut W, config: &PrettyConfig, style: &dyn Style) -> std::fmt::Result {
+        Printer { config, style: Some(style), renderer: None, path: vec!(), out }.print(self)
+    }
+
+    /// Pretty-prints the node, printing leaves as returned by `renderer`.
+    /// Leaves for which `renderer` returns `None` are printed as usual.
+    pub fn pretty_print_rendered(&self, config: &PrettyConfig, renderer: &dyn LeafRenderer) -> String {
+        let mut s = String::new();
+        self.pretty_print_rendered_to(&mut s, config, renderer).expect("writing to a String doesn't fail");
+        s
+    }
+
+    pub fn pretty_print_rendered_to<W: Write>(&self, out: &mut W, config: &PrettyConfig, renderer: &dyn LeafRenderer) -> std::fmt::Result {
+        Printer { config, style: None, renderer: Some(renderer), path: vec!(), out }.print(self)
+    }
+}
+
+/// Hook for printing leaves in an application-specific way, e.g. as IP
+/// addresses or timestamps. `path` contains the child indices leading from
+/// the root to the leaf.
+pub trait LeafRenderer {
+    /// Returns the text printed for a leaf, or `None` to print it as usual.
+    /// The text must not contain newlines.
+    fn render(&self, path: &[usize], bytes: &[u8]) -> Option<String>;
+}
+
+impl<F> LeafRenderer for F
+where
+    F: Fn(&[usize], &[u8]) -> Option<String>
+{
+    fn render(&self, path: &[usize], bytes: &[u8]) -> Option<String> {
+        self(path, bytes)
+    }
+}
+
+struct IoAdapter<'a, W> {
+    writer: &'a mut W,
+    error: Option<std::io::Error>,
+}
+
+impl<'a, W: std::io::Write> Write for IoAdapter<'a, W> {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            std::fmt::Error
+        })
+    }
+}
+
+/// Summary of the children of a collapsed inner node.
+struct Summary<'a>(&'a Node);
+
+impl<'a> std::fmt::Display for Summary<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.node_count() - 1 {
+            1 => write!(f, "… 1 node"),
+            n => write!(f, "… {} nodes", n),
+        }
+    }
+}
+
+pub(crate) fn write_pretty<W: Write>(node: &Node, config: &PrettyConfig, out: &mut W) -> std::fmt::Result {
+    Printer { config, style: None, renderer: None, path: vec!(), out }.print(node)
+}
+
+struct Printer<'a, W> {
+    config: &'a PrettyConfig,
+    style: Option<&'a dyn Style>,
+    renderer: Option<&'a dyn LeafRenderer>,
+    /// Path of the node that's currently printed.
+    path: Vec<usize>,
+    out: &'a mut W,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+    fn print(&mut self, node: &Node) -> std::fmt::Result {
+        self.node(node, 0)?;
+        if self.config.trailing_newline {
+            writeln!(self.out)?;
+        }
+        Ok(())
+    }
+
+    fn node(&mut self, node: &Node, indent: usize) -> std::fmt::Result {
+        let depth = self.path.len();
+        let custom = self.style.and_then(|s| s.color(&self.path, node));
+        let theme = self.config.theme.as_ref();
+        match node {
+            Node::Leaf(bytes) => match self.rendered(&self.path, bytes) {
+                Some(text) => {
+                    let color = custom.or_else(|| theme.map(|t| t.bytes));
+                    highlight::paint(self.out, color, &text)
+                }
+                None => match self.text(bytes) {
+                    Some(text) => self.text_leaf(text, bytes.len(), custom.or_else(|| theme.map(|t| t.strings))),
+                    None => self.leaf(bytes, indent, custom.or_else(|| theme.map(|t| t.bytes))),
+                },
+            },
+            Node::Inner(nodes) => {
+                let color = custom.or_else(|| theme.and_then(|t| t.paren_color(depth)));
+                if self.is_collapsed(node, depth) {
+                    highlight::paint(self.out, color, "(")?;
+                    write!(self.out, "{}", Summary(node))?;
+                    highlight::paint(self.out, color, ")")
+                } else if self.fits(node, self.config.max_width.saturating_sub(indent)) {
+                    highlight::paint(self.out, color, "(")?;
+                    for (idx, n) in nodes.iter().enumerate() {
+                        if idx > 0 {
+                            write!(self.out, " ")?;
+                        }
+                        self.path.push(idx);
+                        self.node(n, indent)?;
+                        self.path.pop();
+                    }
+                    highlight::paint(self.out, color, ")")
+                } else {
+                    let child_indent = indent + self.config.indent;
+                    highlight::paint(self.out, color, "(")?;
+                    writeln!(self.out)?;
+                    for (idx, n) in nodes.iter().enumerate() {
+                        write!(self.out, "{:1$}", "", child_indent)?;
+                        self.path.push(idx);
+                        self.node(n, child_indent)?;
+                        self.path.pop();
+                        writeln!(self.out)?;
+                    }
+                    write!(self.out, "{:1$}", "", indent)?;
+                    highlight::paint(self.out, color, ")")
+                }
+            }
+        }
+    }
+
+    fn leaf(&mut self, bytes: &[u8], indent: usize, color: Option<Color>) -> std::fmt::Result {
+        if self.config.leaf_format == LeafFormat::Hexdump && !bytes.is_empty() {
+            return self.hexdump(bytes, indent, color);
+        }
+        let len = bytes.len();
+        let bytes = self.shown_bytes(bytes);
+        let sep = &self.config.byte_separator;
+        let sep_width = sep.chars().count();
+        let bytes_per_line = self.config.bytes_per_line.map(|n| n.max(1));
+        highlight::start(self.out, color)?;
+        write!(self.out, "0x")?;
+        let mut pos = indent + 2;
+        let mut line_bytes = 0;
+        let mut digits = String::new();
+        for (idx, group) in bytes.chunks(self.group_size()).enumerate() {
+            let line_full = matches!(bytes_per_line, Some(n) if line_bytes + group.len() > n);
+            if pos + sep_width + 2 * group.len() > self.config.max_width || (idx > 0 && line_full) {
+                highlight::end(self.out, color)?;
+                write!(self.out, "\n{:1$}", "", indent + 2)?;
+                highlight::start(self.out, color)?;
+                pos = indent + 2;
+                line_bytes = 0;
+            } else if idx > 0 {
+                write!(self.out, "{}", sep)?;
+                pos += sep_width;
+            }
+            digits.clear();
+            hex::encode_into(group, self.config.hex_case, None, &mut digits);
+            self.out.write_str(&digits)?;
+            pos += 2 * group.len();
+            line_bytes += group.len();
+        }
+        if bytes.len() < len {
+            write!(self.out, "… ({} bytes)", len)?;
+        }
+        highlight::end(self.out, color)
+    }
+
+    fn hexdump(&mut self, bytes: &[u8], indent: usize, color: Option<Color>) -> std::fmt::Result {
+        let len = bytes.len();
+        let bytes = self.shown_bytes(bytes);
+        highlight::start(self.out, color)?;
+        for (line_idx, line) in bytes.chunks(16).enumerate() {
+            if line_idx > 0 {
+                highlight::end(self.out, color)?;
+                write!(self.out, "\n{:1$}", "", indent)?;
+                highlight::start(self.out, color)?;
+            }
+            write!(self.out, "{:08x}  ", line_idx * 16)?;
+            let mut digits = String::with_capacity(50);
+            for (half_idx, half) in line.chunks(8).chain(std::iter::repeat(&[][..])).take(2).enumerate() {
+                if half_idx > 0 {
+                    digits.push(' ');
+                }
+                hex::encode_into(half, self.config.hex_case, Some(' '), &mut digits);
+                if !half.is_empty() {
+                    digits.push(' ');
+                }
+                digits.extend(std::iter::repeat(' ').take(3 * (8 - half.len())));
+            }
+            self.out.write_str(&digits)?;
+            write!(self.out, " |")?;
+            for b in line {
+                let c = if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '.' };
+                write!(self.out, "{}", c)?;
+            }
+            write!(self.out, "|")?;
+        }
+        if bytes.len() < len {
+            highlight::end(self.out, color)?;
+            write!(self.out, "\n{:1$}", "", indent)?;
+            highlight::start(self.out, color)?;
+            write!(self.out, "… ({} bytes)", len)?;
+        }
+        highlight::end(self.out, color)
+    }
+
+    fn text_leaf(&mut self, text: &str, len: usize, color: Option<Color>) -> std::fmt::Result {
+        highlight::start(self.out, color)?;
+        write!(self.out, "\"")?;
+        for c in text.chars() {
+            match c {
+                '"' => write!(self.out, "\\\"")?,
+                '\\' => write!(self.out, "\\\\")?,
+                c => write!(self.out, "{}", c)?,
+            }
+        }
+        write!(self.out, "\"")?;
+        if text.len() < len {
+            write!(self.out, "… ({} bytes)", len)?;
+        }
+        highlight::end(self.out, color)
+    }
+
+    /// Returns the (possibly truncated) text of a leaf if it should be
+    /// printed as string.
+    fn text<'b>(&self, bytes: &'b [u8]) -> Option<&'b str> {
+        if self.config.leaf_format != LeafFormat::Text {
+            return None;
+        }
+        let text = std::str::from_utf8(bytes).ok()?;
+        if text.chars().any(char::is_control) {
+            return None;
+        }
+        match self.config.max_leaf_bytes {
+            Some(n) if text.len() > n => {
+                // truncate at a char boundary
+                let end = (0..=n).rev().find(|i| text.is_char_boundary(*i)).unwrap_or(0);
+                Some(&text[..end])
+            }
+            _ => Some(text),
+        }
+    }
+
+    /// Returns the part of `bytes` that's printed.
+    fn shown_bytes<'b>(&self, bytes: &'b [u8]) -> &'b [u8] {
+        match self.config.max_leaf_bytes {
+            Some(n) if bytes.len() > n => &bytes[..n],
+            _ => bytes,
+        }
+    }
+
+    fn group_size(&self) -> usize {
+        self.config.group_size.max(1)
+    }
+
+    /// Returns whether `node` fits into `width` columns when printed on a
+    /// single line.
+    fn fits(&mut self, node: &Node, width: usize) -> bool {
+        let mut path = std::mem::take(&mut self.path);
+        let fits = self.width(node, &mut path, width) <= width;
+        self.path = path;
+        fits
+    }
+
+    fn rendered(&self, path: &[usize], bytes: &[u8]) -> Option<String> {
+        self.renderer.and_then(|r| r.render(path, bytes))
+    }
+
+    fn is_collapsed(&self, node: &Node, depth: usize) -> bool {
+        matches!(node, Node::Inner(nodes) if !nodes.is_empty())
+            && matches!(self.config.max_depth, Some(d) if depth >= d)
+    }
+
+    /// Width of `node` when printed on a single line. Stops counting as soon
+    /// as `limit` is exceeded, so that checking whether large subtrees fit on
+    /// a line stays cheap.
+    fn width(&self, node: &Node, path: &mut Vec<usize>, limit: usize) -> usize {
+        match node {
+            Node::Leaf(bytes) if self.renderer.is_some() && self.rendered(path, bytes).is_some() => {
+                self.rendered(path, bytes).unwrap().chars().count()
+            }
+            Node::Leaf(bytes) if self.config.leaf_format == LeafFormat::Hexdump && !bytes.is_empty() => {
+                usize::MAX
+            }
+            Node::Leaf(bytes) if self.text(bytes).is_some() => {
+                let text = self.text(bytes).unwrap();
+                let mut width = 2 + text.chars().map(|c| if c == '"' || c == '\\' { 2 } else { 1 }).sum::<usize>();
+                if text.len() < bytes.len() {
+                    width += 10 + bytes.len().to_string().len();
+                }
+                width
+            }
+            Node::Leaf(bytes) => {
+                let len = bytes.len();
+                let bytes = self.shown_bytes(bytes);
+                if matches!(self.config.bytes_per_line, Some(n) if bytes.len() > n.max(1)) {
+                    return usize::MAX;
+                }
+                let groups = bytes.len().div_ceil(self.group_size());
+                let mut width = 2 + 2 * bytes.len() + self.config.byte_separator.chars().count() * groups.saturating_sub(1);
+                if bytes.len() < len {
+                    // "… (<len> bytes)"
+                    width += 10 + len.to_string().len();
+                }
+                width
+            }
+            Node::Inner(_) if self.is_collapsed(node, path.len()) => {
+                2 + Summary(node).to_string().chars().count()
+            }
+            Node::Inner(nodes) => {
+                let mut width = 2 + nodes.len().saturating_sub(1);
+                for (idx, n) in nodes.iter().enumerate() {
+                    if width > limit {
+                        break;
+                    }
+                    path.push(idx);
+                    width = width.saturating_add(self.width(n, path, limit - width));
+                    path.pop();
+                }
+                width
+            }
+        }
+    }
+}
+
+
+#[test]
+fn pretty_config() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(0xab, 0xcd)),
+        Node::Inner(vec!(
+            Node::Leaf(vec!(0xef)),
+        )),
+    ));
+    let config = PrettyConfig {
+        max_width: 10,
+        indent: 2,
+        hex_case: HexCase::Upper,
+        leaf_format: LeafFormat::Hex,
+        byte_separator: "".to_string(),
+        group_size: 1,
+        bytes_per_line: None,
+        max_leaf_bytes: None,
+        max_depth: None,
+        trailing_newline: true,
+        theme: None,
+    };
+    assert_eq!(node.pretty_print_with(&config), "(\n  0xABCD\n  (0xEF)\n)\n");
+
+    let config = PrettyConfig { max_width: 17, byte_separator: ", ".to_string(), ..PrettyConfig::default() };
+    assert_eq!(node.pretty_print_with(&config), "(0xab, cd (0xef))");
+    let config = PrettyConfig { max_width: 16, ..config };
+    assert_eq!(node.pretty_print_with(&config), "(\n    0xab, cd\n    (0xef)\n)");
+}
+
+#[test]
+fn pretty_hex_groups() {
+    let config = PrettyConfig {
+        byte_separator: " ".to_string(),
+        group_size: 4,
+        bytes_per_line: Some(8),
+        ..PrettyConfig::default()
+    };
+    let node = Node::Inner(vec!(Node::Leaf((0..20).collect()), Node::Leaf(vec!(1, 2))));
+    assert_eq!(
+        node.pretty_print_with(&config),
+        "(\n    0x00010203 04050607\n      08090a0b 0c0d0e0f\n      10111213\n    0x0102\n)"
+    );
+
+    // leaves fitting on a single line don't break their parent
+    let node = Node::Inner(vec!(Node::Leaf((0..6).collect())));
+    assert_eq!(node.pretty_print_with(&config), "(0x00010203 0405)");
+
+    // groups are wrapped as a whole
+    let config = PrettyConfig { max_width: 15, group_size: 2, ..PrettyConfig::default() };
+    assert_eq!(Node::Leaf((0..6).collect()).pretty_print_with(&config), "0x0001_0203\n  0405");
+}
+
+#[test]
+fn pretty_print_writers() {
+    let node = Node::Inner(vec!(
+        Node::Leaf((0..30).collect()),
+        Node::Inner(vec!(Node::Leaf(vec!(1)), Node::Inner(vec!()))),
+    ));
+    let config = PrettyConfig { max_width: 40, trailing_newline: true, ..PrettyConfig::default() };
+    let exp = node.pretty_print_with(&config);
+
+    let mut s = String::new();
+    node.pretty_print_to(&mut s, &config).unwrap();
+    assert_eq!(s, exp);
+
+    let mut v = vec!();
+    node.pretty_print_into(&mut v, &config).unwrap();
+    assert_eq!(String::from_utf8(v).unwrap(), exp);
+
+    let mut buf = [0u8; 10];
+    let err = node.pretty_print_into(&mut &mut buf[..], &config).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+
+    let mut s = String::new();
+    node.pretty_print_fmt(&mut s, 40).unwrap();
+    assert_eq!(s, node.pretty_print(40));
+
+    // errors of the writer are passed on
+    struct Limited(usize);
+    impl Write for Limited {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            self.0 = self.0.checked_sub(s.len()).ok_or(std::fmt::Error)?;
+            Ok(())
+        }
+    }
+    assert!(node.pretty_print_fmt(&mut Limited(20), 40).is_err());
+    assert!(node.pretty_print_fmt(&mut Limited(1000), 40).is_ok());
+}
+
+#[test]
+fn pretty_truncate_leaves() {
+    let config = PrettyConfig { max_leaf_bytes: Some(2), ..PrettyConfig::default() };
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(0xde, 0xad, 0xbe, 0xef)),
+        Node::Leaf(vec!(1, 2)),
+    ));
+    assert_eq!(node.pretty_print_with(&config), "(0xde_ad… (4 bytes) 0x01_02)");
+    let config = PrettyConfig { max_width: 28, ..config };
+    assert_eq!(node.pretty_print_with(&config), "(0xde_ad… (4 bytes) 0x01_02)");
+    let config = PrettyConfig { max_width: 27, ..config };
+    assert_eq!(node.pretty_print_with(&config), "(\n    0xde_ad… (4 bytes)\n    0x01_02\n)");
+
+    let config = PrettyConfig { max_leaf_bytes: Some(0), ..PrettyConfig::default() };
+    assert_eq!(Node::Leaf(vec!(0; 4096)).pretty_print_with(&config), "0x… (4096 bytes)");
+}
+
+#[test]
+fn pretty_max_depth() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(
+            Node::Leaf(vec!(2)),
+            Node::Inner(vec!(Node::Leaf(vec!(3)))),
+        )),
+        Node::Inner(vec!(Node::Leaf(vec!(4)))),
+        Node::Inner(vec!()),
+    ));
+    let config = PrettyConfig { max_depth: Some(1), ..PrettyConfig::default() };
+    assert_eq!(node.pretty_print_with(&config), "(0x01 (… 3 nodes) (… 1 node) ())");
+    let config = PrettyConfig { max_depth: Some(2), ..PrettyConfig::default() };
+    assert_eq!(node.pretty_print_with(&config), "(0x01 (0x02 (… 1 node)) (0x04) ())");
+    let config = PrettyConfig { max_depth: Some(0), ..PrettyConfig::default() };
+    assert_eq!(node.pretty_print_with(&config), "(… 8 nodes)");
+    let config = PrettyConfig { max_depth: Some(1), max_width: 20, ..PrettyConfig::default() };
+    assert_eq!(node.pretty_print_with(&config), "(\n    0x01\n    (… 3 nodes)\n    (… 1 node)\n    ()\n)");
+}
+
+#[test]
+fn pretty_hexdump() {
+    let config = PrettyConfig { leaf_format: LeafFormat::Hexdump, ..PrettyConfig::default() };
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"Hello, world!\n\x00\xff baum".to_vec()),
+        Node::Leaf(vec!()),
+    ));
+    assert_eq!(node.pretty_print_with(&config), "(
+    00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|
+    00000010  20 62 61 75 6d                                    | baum|
+    0x
+)");
+
+    let config = PrettyConfig { max_leaf_bytes: Some(4), hex_case: HexCase::Upper, ..config };
+    assert_eq!(Node::Leaf(vec!(0xab; 100)).pretty_print_with(&config), "\
+00000000  AB AB AB AB                                       |....|
+… (100 bytes)");
+}
+
+#[test]
+fn pretty_print_styled() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(Node::Leaf(vec!(2)), Node::Leaf(vec!(3)))),
+    ));
+    let style = |path: &[usize], node: &Node| match (path, node) {
+        ([1, 1], _) => Some(Color::Red),
+        (_, Node::Inner(_)) if !path.is_empty() => Some(Color::Blue),
+        _ => None,
+    };
+    assert_eq!(
+        node.pretty_print_styled(&PrettyConfig::default(), &style),
+        "(0x01 \x1b[34m(\x1b[0m0x02 \x1b[31m0x03\x1b[0m\x1b[34m)\x1b[0m)"
+    );
+
+    let config = PrettyConfig { theme: Some(Theme { parens: vec!(), bytes: Color::Green, strings: Color::Cyan }), ..PrettyConfig::default() };
+    assert_eq!(
+        node.pretty_print_styled(&config, &style),
+        "(\x1b[32m0x01\x1b[0m \x1b[34m(\x1b[0m\x1b[32m0x02\x1b[0m \x1b[31m0x03\x1b[0m\x1b[34m)\x1b[0m)"
+    );
+}
+
+#[test]
+fn pretty_text_leaves() {
+    let config = PrettyConfig { leaf_format: LeafFormat::Text, ..PrettyConfig::default() };
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"hello world".to_vec()),
+        Node::Leaf(b"say \"hi\" \\o/".to_vec()),
+        Node::Leaf(b"line\n".to_vec()),
+        Node::Leaf(vec!(0xff)),
+        Node::Leaf(vec!()),
+    ));
+    let s = node.pretty_print_with(&config);
+    assert_eq!(s, r#"("hello world" "say \"hi\" \\o/" 0x6c_69_6e_65_0a 0xff "")"#);
+    // output can be parsed again
+    assert_eq!(crate::parse(&s), node);
+    let config = PrettyConfig { max_width: s.len() - 1, ..config };
+    assert_eq!(node.pretty_print_with(&config).lines().count(), 7);
+
+    let config = PrettyConfig { leaf_format: LeafFormat::Text, max_leaf_bytes: Some(4), ..PrettyConfig::default() };
+    assert_eq!(Node::Leaf("abcüd".as_bytes().to_vec()).pretty_print_with(&config), "\"abc\"… (6 bytes)");
+}
+
+#[test]
+fn pretty_print_rendered() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(192, 168, 0, 1)),
+        Node::Inner(vec!(Node::Leaf(vec!(10, 0, 0, 1)), Node::Leaf(vec!(1)))),
+    ));
+    // render the first child of every inner node as IPv4 address
+    let renderer = |path: &[usize], bytes: &[u8]| match (path.last(), bytes) {
+        (Some(0), [a, b, c, d]) => Some(format!("{}.{}.{}.{}", a, b, c, d)),
+        _ => None,
+    };
+    let config = PrettyConfig::default();
+    assert_eq!(node.pretty_print_rendered(&config, &renderer), "(192.168.0.1 (10.0.0.1 0x01))");
+    let config = PrettyConfig { max_width: 29, ..config };
+    assert_eq!(node.pretty_print_rendered(&config, &renderer), "(192.168.0.1 (10.0.0.1 0x01))");
+    let config = PrettyConfig { max_width: 28, ..config };
+    assert_eq!(node.pretty_print_rendered(&config, &renderer), "(\n    192.168.0.1\n    (10.0.0.1 0x01)\n)");
+}
@@ -0,0 +1,209 @@
+//! Schema-less conversion between `Node`s and the protobuf wire format.
+//!
+//! A message maps to an inner node with one child per field. Each field is
+//! an inner node `(number wire_type value)`:
+//!
+//! - `number` is the field number as 4 byte little-endian leaf
+//! - `wire_type` is a single byte leaf (0: varint, 1: i64, 2: len, 5: i32)
+//! - `value` is a leaf containing the varint as 8 byte little-endian
+//!   integer, the 8 or 4 raw bytes of fixed-width values, or the payload of
+//!   length-delimited fields
+//!
+//! Without a schema, nested messages can't be told apart from strings or
+//! bytes, so length-delimited payloads are kept as leaves; they can be
+//! decoded separately using `Node::from_protobuf`. Deprecated groups
+//! (wire types 3 and 4) aren't supported.
+
+use crate::{varint, Node, Path};
+
+use std::convert::{TryFrom, TryInto};
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnexpectedEnd,
+    InvalidVarint(usize),
+    InvalidWireType(u8, usize),
+    InvalidFieldNumber(usize),
+    /// A node that doesn't follow the mapping described in the module docs.
+    InvalidNode(Path),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::UnexpectedEnd => write!(f, "Unexpected end of input."),
+            Error::InvalidVarint(pos) => write!(f, "Invalid varint at position {}.", pos),
+            Error::InvalidWireType(t, pos) => write!(f, "Invalid wire type {} at position {}.", t, pos),
+            Error::InvalidFieldNumber(pos) => write!(f, "Invalid field number at position {}.", pos),
+            Error::InvalidNode(p) => write!(f, "Node at path {:?} isn't a valid field.", p),
+        }
+    }
+}
+
+impl std::error::Error for Error { }
+
+const VARINT: u8 = 0;
+const I64: u8 = 1;
+const LEN: u8 = 2;
+const I32: u8 = 5;
+
+/// Largest field number allowed by protobuf.
+const MAX_FIELD_NUMBER: u64 = (1 << 29) - 1;
+
+pub fn encode(node: &Node) -> Result<Vec<u8>, Error> {
+    let fields = match node {
+        Node::Inner(fields) => fields,
+        Node::Leaf(_) => return Err(Error::InvalidNode(vec!())),
+    };
+    let mut res = vec!();
+    for (idx, field) in fields.iter().enumerate() {
+        encode_field(field, &mut res).ok_or_else(|| Error::InvalidNode(vec!(idx)))?;
+    }
+    Ok(res)
+}
+
+fn encode_field(field: &Node, w: &mut Vec<u8>) -> Option<()> {
+    let (number, wire_type, value) = match field {
+        Node::Inner(children) => match children.as_slice() {
+            [Node::Leaf(n), Node::Leaf(t), Node::Leaf(v)] => (n, t, v),
+            _ => return None,
+        },
+        Node::Leaf(_) => return None,
+    };
+    let number = u32::from_le_bytes(number.as_slice().try_into().ok()?);
+    if number == 0 || u64::from(number) > MAX_FIELD_NUMBER {
+        return None;
+    }
+    let wire_type = match wire_type.as_slice() {
+        [t @ (VARINT | I64 | LEN | I32)] => *t,
+        _ => return None,
+    };
+    varint::encode(u64::from(number) << 3 | u64::from(wire_type), w);
+    match wire_type {
+        VARINT => varint::encode(u64::from_le_bytes(value.as_slice().try_into().ok()?), w),
+        I64 if value.len() == 8 => w.extend_from_slice(value),
+        I32 if value.len() == 4 => w.extend_from_slice(value),
+        LEN => {
+            varint::encode(value.len() as u64, w);
+            w.extend_from_slice(value);
+        }
+        _ => return None,
+    }
+    Some(())
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Node, Error> {
+    let mut fields = vec!();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let start = pos;
+        let key = varint::decode(bytes, &mut pos).ok_or(Error::InvalidVarint(start))?;
+        let number = key >> 3;
+        if number == 0 || number > MAX_FIELD_NUMBER {
+            return Err(Error::InvalidFieldNumber(start));
+        }
+        let wire_type = (key & 0x7) as u8;
+        let value = match wire_type {
+            VARINT => {
+                let value_start = pos;
+                let n = varint::decode(bytes, &mut pos).ok_or(Error::InvalidVarint(value_start))?;
+                n.to_le_bytes().to_vec()
+            }
+            I64 | I32 => {
+                let len = if wire_type == I64 { 8 } else { 4 };
+                let value = bytes.get(pos..pos + len).ok_or(Error::UnexpectedEnd)?;
+                pos += len;
+                value.to_vec()
+            }
+            LEN => {
+                let len_start = pos;
+                let len = varint::decode(bytes, &mut pos).ok_or(Error::InvalidVarint(len_start))?;
+                let end = usize::try_from(len).ok()
+                    .and_then(|len| pos.checked_add(len))
+                    .ok_or(Error::UnexpectedEnd)?;
+                let value = bytes.get(pos..end).ok_or(Error::UnexpectedEnd)?;
+                pos = end;
+                value.to_vec()
+            }
+            _ => return Err(Error::InvalidWireType(wire_type, start)),
+        };
+        fields.push(Node::Inner(vec!(
+            Node::Leaf((number as u32).to_le_bytes().to_vec()),
+            Node::Leaf(vec!(wire_type)),
+            Node::Leaf(value),
+        )));
+    }
+    Ok(Node::Inner(fields))
+}
+
+impl Node {
+    pub fn to_protobuf(&self) -> Result<Vec<u8>, Error> {
+        encode(self)
+    }
+
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Node, Error> {
+        decode(bytes)
+    }
+}
+
+
+#[cfg(test)]
+fn field(number: u32, wire_type: u8, value: &[u8]) -> Node {
+    Node::Inner(vec!(
+        Node::Leaf(number.to_le_bytes().to_vec()),
+        Node::Leaf(vec!(wire_type)),
+        Node::Leaf(value.to_vec()),
+    ))
+}
+
+#[test]
+fn protobuf_roundtrip() {
+    // 1: 150, 2: "testing", 3: fixed64 1, 4: fixed32 2, 1: 0
+    let bytes = [
+        0x08, 0x96, 0x01,
+        0x12, 0x07, b't', b'e', b's', b't', b'i', b'n', b'g',
+        0x19, 1, 0, 0, 0, 0, 0, 0, 0,
+        0x25, 2, 0, 0, 0,
+        0x08, 0x00,
+    ];
+    let node = Node::Inner(vec!(
+        field(1, 0, &150u64.to_le_bytes()),
+        field(2, 2, b"testing"),
+        field(3, 1, &1u64.to_le_bytes()),
+        field(4, 5, &2u32.to_le_bytes()),
+        field(1, 0, &0u64.to_le_bytes()),
+    ));
+    assert_eq!(Node::from_protobuf(&bytes), Ok(node.clone()));
+    assert_eq!(node.to_protobuf(), Ok(bytes.to_vec()));
+    assert_eq!(Node::from_protobuf(&[]), Ok(Node::Inner(vec!())));
+
+    // nested messages are decoded on demand
+    let outer = Node::from_protobuf(&[0x1a, 0x03, 0x08, 0x96, 0x01]).unwrap();
+    let inner = match &outer {
+        Node::Inner(fields) => match &fields[0] {
+            Node::Inner(f) => match &f[2] {
+                Node::Leaf(payload) => Node::from_protobuf(payload).unwrap(),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    assert_eq!(inner, Node::Inner(vec!(field(1, 0, &150u64.to_le_bytes()))));
+}
+
+#[test]
+fn protobuf_errors() {
+    assert_eq!(Node::from_protobuf(&[0x08]), Err(Error::InvalidVarint(1)));
+    assert_eq!(Node::from_protobuf(&[0x08, 0x80]), Err(Error::InvalidVarint(1)));
+    assert_eq!(Node::from_protobuf(&[0x12, 0x02, 0x00]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_protobuf(&[0x25, 0x00]), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_protobuf(&[0x00, 0x00]), Err(Error::InvalidFieldNumber(0)));
+    assert_eq!(Node::from_protobuf(&[0x08, 0x00, 0x0b]), Err(Error::InvalidWireType(3, 2)));
+
+    assert_eq!(Node::Leaf(vec!()).to_protobuf(), Err(Error::InvalidNode(vec!())));
+    let node = Node::Inner(vec!(field(1, 0, &[1]), field(1, 0, &[0; 8])));
+    assert_eq!(node.to_protobuf(), Err(Error::InvalidNode(vec!(0))));
+    let node = Node::Inner(vec!(field(1, 0, &[0; 8]), field(0, 2, b"")));
+    assert_eq!(node.to_protobuf(), Err(Error::InvalidNode(vec!(1))));
+}
@@ -0,0 +1,217 @@
+use crate::{Error, Node, NodeRef, Path, QueryIndex, Selector};
+use crate::selector::node_at_serialized;
+
+use std::collections::hash_map::RandomState;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{BuildHasher, Hash};
+use std::sync::Mutex;
+
+/// Selector that's parsed once and applied to many trees, optionally caching
+/// the selected paths for trees it has seen before.
+///
+/// Trees are identified by their size and a 128-bit fingerprint, which is
+/// keyed randomly per query so that collisions can't be provoked by crafted
+/// input. Cached paths that don't fit the tree are recomputed.
+pub struct Query {
+    selector: Selector,
+    cache: Option<Mutex<Cache>>,
+}
+
+struct Cache {
+    /// Two independently keyed hashers, combined into 128 bits.
+    hashers: [RandomState; 2],
+    capacity: usize,
+    results: HashMap<Fingerprint, Vec<Path>>,
+    /// Fingerprints in order of insertion, for evicting the oldest results.
+    order: VecDeque<Fingerprint>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Fingerprint {
+    /// Number of nodes of a tree, or number of bytes of a serialized one.
+    len: usize,
+    hash: u128,
+}
+
+impl Query {
+    pub fn new(selector: &str) -> Result<Query, String> {
+        Ok(Query { selector: Selector::parse(selector)?, cache: None })
+    }
+
+    /// Creates a query caching the results of up to `capacity` trees.
+    pub fn with_cache(selector: &str, capacity: usize) -> Result<Query, String> {
+        let cache = Cache { hashers: [RandomState::new(), RandomState::new()], capacity, results: HashMap::new(), order: VecDeque::new() };
+        Ok(Query { selector: Selector::parse(selector)?, cache: Some(Mutex::new(cache)) })
+    }
+
+    pub fn selector(&self) -> &Selector {
+        &self.selector
+    }
+
+    /// Returns the selected nodes and their paths, see `Selector::select`.
+    pub fn run<'a>(&self, node: &'a Node) -> Vec<(Path, &'a Node)> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.selector.select(node),
+        };
+        let fingerprint = {
+            let cache = cache.lock().unwrap();
+            let fingerprint = cache.fingerprint(node.node_count(), node);
+            let cached = cache.results.get(&fingerprint).and_then(|paths| {
+                paths.iter().map(|path| Some((path.clone(), node.get(path)?))).collect()
+            });
+            if let Some(res) = cached {
+                return res;
+            }
+            fingerprint
+        };
+        // the cache isn't locked while selecting, so other threads can use it
+        let res = self.selector.select(node);
+        cache.lock().unwrap().insert(fingerprint, res.iter().map(|(path, _)| path.clone()).collect());
+        res
+    }
+
+    /// Applies the query to a serialized tree, see
+    /// `Selector::select_serialized`. Results are cached by the fingerprint
+    /// of `bytes`.
+    pub fn run_serialized<'a>(&self, bytes: &'a [u8]) -> Result<Vec<(Path, NodeRef<'a>)>, Error> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.selector.select_serialized(bytes),
+        };
+        let fingerprint = {
+            let cache = cache.lock().unwrap();
+            let fingerprint = cache.fingerprint(bytes.len(), bytes);
+            let cached = cache.results.get(&fingerprint).and_then(|paths| {
+                paths.iter().map(|path| Some((path.clone(), node_at_serialized(bytes, path).ok()?))).collect()
+            });
+            if let Some(res) = cached {
+                return Ok(res);
+            }
+            fingerprint
+        };
+        let res = self.selector.select_serialized(bytes)?;
+        cache.lock().unwrap().insert(fingerprint, res.iter().map(|(path, _)| path.clone()).collect());
+        Ok(res)
+    }
+
+    /// Applies the query to a serialized tree using its index, see
+    /// `Selector::select_indexed`. Results aren't cached, since
+    /// fingerprinting the input would take longer than the indexed lookup.
+    pub fn run_indexed<'a>(&self, bytes: &'a [u8], index: &QueryIndex) -> Result<Vec<(Path, NodeRef<'a>)>, Error> {
+        self.selector.select_indexed(bytes, index)
+    }
+
+    /// Applies the query to a serialized tree read from `reader`, see
+    /// `Selector::select_reader`. Results aren't cached.
+    pub fn run_reader<R: std::io::Read + std::io::Seek>(&self, reader: R) -> Result<Vec<(Path, Node)>, Error> {
+        self.selector.select_reader(reader)
+    }
+
+    /// Applies the query to the serialized tree at `path`, using the index
+    /// stored alongside it if there's a matching one, see
+    /// `Selector::select_file`. Results aren't cached.
+    pub fn run_file(&self, path: &std::path::Path) -> Result<Vec<(Path, Node)>, Error> {
+        self.selector.select_file(path)
+    }
+
+    /// Returns the number of trees whose results are cached.
+    pub fn cached(&self) -> usize {
+        self.cache.as_ref().map_or(0, |cache| cache.lock().unwrap().results.len())
+    }
+
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            let mut cache = cache.lock().unwrap();
+            cache.results.clear();
+            cache.order.clear();
+        }
+    }
+}
+
+impl Cache {
+    fn fingerprint<T: Hash + ?Sized>(&self, len: usize, value: &T) -> Fingerprint {
+        let [a, b] = &self.hashers;
+        Fingerprint { len, hash: (a.hash_one(value) as u128) << 64 | b.hash_one(value) as u128 }
+    }
+
+    fn insert(&mut self, fingerprint: Fingerprint, paths: Vec<Path>) {
+        if self.capacity == 0 || self.results.contains_key(&fingerprint) {
+            return;
+        }
+        if self.results.len() >= self.capacity {
+            let oldest = self.order.pop_front().unwrap();
+            self.results.remove(&oldest);
+        }
+        self.results.insert(fingerprint, paths);
+        self.order.push_back(fingerprint);
+    }
+}
+
+
+#[test]
+fn query() {
+    let a = crate::parse("(0x01 (0x02 0x03))");
+    let b = crate::parse("(0x04 0x05 (0x06))");
+
+    let query = Query::new("*/[leaf]").unwrap();
+    assert_eq!(query.run(&a), query.selector().select(&a));
+    assert_eq!(query.cached(), 0);
+
+    let query = Query::with_cache("*/[leaf]", 2).unwrap();
+    for _ in 0..2 {
+        assert_eq!(query.run(&a), query.selector().select(&a));
+        assert_eq!(query.run(&b), query.selector().select(&b));
+    }
+    assert_eq!(query.cached(), 2);
+    // results of equal trees are shared
+    assert_eq!(query.run(&a.clone()).len(), 3);
+    assert_eq!(query.cached(), 2);
+
+    // the oldest result is evicted
+    let c = crate::parse("0x07");
+    assert!(query.run(&c).is_empty());
+    assert_eq!(query.cached(), 2);
+
+    // hits whose paths don't fit the tree, e.g. after a fingerprint
+    // collision, are recomputed
+    let bytes = c.serialize();
+    {
+        let mut cache = query.cache.as_ref().unwrap().lock().unwrap();
+        let fingerprints = [cache.fingerprint(c.node_count(), &c), cache.fingerprint(bytes.len(), &bytes[..])];
+        for fingerprint in fingerprints {
+            cache.results.insert(fingerprint, vec!(vec!(5, 5)));
+        }
+    }
+    assert!(query.run(&c).is_empty());
+    assert!(query.run_serialized(&bytes).unwrap().is_empty());
+    query.clear_cache();
+    assert_eq!(query.cached(), 0);
+
+    let bytes = b.serialize();
+    for _ in 0..2 {
+        let expected: Vec<_> = query.run(&b).into_iter().map(|(path, node)| (path, NodeRef::from(node))).collect();
+        assert_eq!(query.run_serialized(&bytes).unwrap(), expected);
+    }
+    assert!(query.run_serialized(b"BAUM1").is_err());
+    let index = QueryIndex::build(&bytes, 4).unwrap();
+    assert_eq!(query.run_indexed(&bytes, &index).unwrap(), query.run_serialized(&bytes).unwrap());
+
+    // files are queried using a matching index if there is one
+    let file = std::env::temp_dir().join(format!("baum_query_{}", std::process::id()));
+    std::fs::write(&file, &bytes).unwrap();
+    let expected: Vec<_> = query.run(&b).into_iter().map(|(path, node)| (path, node.clone())).collect();
+    assert_eq!(query.run_file(&file).unwrap(), expected);
+    assert_eq!(query.run_reader(std::fs::File::open(&file).unwrap()).unwrap(), expected);
+    index.save_for(&file).unwrap();
+    assert_eq!(query.run_file(&file).unwrap(), expected);
+    // a stale index is ignored
+    let c = crate::parse("(0x14 0x15 (0x16))");
+    std::fs::write(&file, c.serialize()).unwrap();
+    let expected: Vec<_> = query.run(&c).into_iter().map(|(path, node)| (path, node.clone())).collect();
+    assert_eq!(query.run_file(&file).unwrap(), expected);
+    std::fs::remove_file(QueryIndex::path_for(&file)).unwrap();
+    std::fs::remove_file(&file).unwrap();
+
+    assert!(Query::new("*/").is_err());
+}
@@ -0,0 +1,119 @@
+use crate::Node;
+
+use rand::Rng;
+
+use std::ops::RangeInclusive;
+
+/// Distribution of a size, like the number of children of an inner node or
+/// the number of bytes in a leaf.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SizeDistribution {
+    Fixed(usize),
+    /// Uniformly distributed within the range.
+    Uniform(RangeInclusive<usize>),
+    /// Geometrically distributed with the given mean, so small sizes are
+    /// common and large ones rare. Values above `max` are capped.
+    Geometric { mean: f64, max: usize },
+}
+
+impl SizeDistribution {
+    fn sample(&self, rng: &mut impl Rng) -> usize {
+        match self {
+            SizeDistribution::Fixed(size) => *size,
+            SizeDistribution::Uniform(range) if range.is_empty() => *range.start(),
+            SizeDistribution::Uniform(range) => rng.gen_range(range.clone()),
+            SizeDistribution::Geometric { mean, max } if *mean <= 0.0 => 0,
+            SizeDistribution::Geometric { mean, max } => {
+                // inverse transform sampling, counting failures before the
+                // first success with success probability 1 / (mean + 1)
+                let p = 1.0 / (mean + 1.0);
+                let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+                let size = (u.ln() / (1.0 - p).ln()).floor();
+                if size >= *max as f64 { *max } else { size as usize }
+            }
+        }
+    }
+}
+
+/// Shape of the trees generated by `Node::random`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GenConfig {
+    /// Maximum nesting of inner nodes, counted like `Limits::max_depth`.
+    /// 0 generates leaves only.
+    pub max_depth: usize,
+    /// Probability that the root is an inner node.
+    pub inner_probability: f64,
+    /// Factor applied to `inner_probability` per level, so trees get
+    /// sparser towards the bottom.
+    pub inner_decay: f64,
+    /// Number of children of inner nodes.
+    pub branching: SizeDistribution,
+    /// Number of bytes in leaves.
+    pub leaf_len: SizeDistribution,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        GenConfig {
+            max_depth: 6,
+            inner_probability: 1.0,
+            inner_decay: 0.7,
+            branching: SizeDistribution::Uniform(1..=8),
+            leaf_len: SizeDistribution::Geometric { mean: 8.0, max: 1024 },
+        }
+    }
+}
+
+impl Node {
+    /// Generates a random tree shaped by `config`.
+    pub fn random(rng: &mut impl Rng, config: &GenConfig) -> Node {
+        random_node(rng, config, 0)
+    }
+}
+
+fn random_node(rng: &mut impl Rng, config: &GenConfig, depth: usize) -> Node {
+    let p = config.inner_probability * config.inner_decay.powi(depth.min(i32::MAX as usize) as i32);
+    if depth < config.max_depth && rng.gen_bool(p.clamp(0.0, 1.0)) {
+        let len = config.branching.sample(rng);
+        Node::Inner((0..len).map(|_| random_node(rng, config, depth + 1)).collect())
+    } else {
+        let mut bytes = vec!(0; config.leaf_len.sample(rng));
+        rng.fill(&mut bytes[..]);
+        Node::Leaf(bytes)
+    }
+}
+
+
+#[test]
+fn random() {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    let config = GenConfig::default();
+    let limits = crate::Limits { max_depth: Some(6), max_children: Some(8), max_leaf_len: Some(1024), ..crate::Limits::default() };
+    for seed in 0..20 {
+        let node = Node::random(&mut StdRng::seed_from_u64(seed), &config);
+        assert!(matches!(node, Node::Inner(_)));
+        assert!(node.check_limits(&limits).is_ok());
+        assert_eq!(node, Node::random(&mut StdRng::seed_from_u64(seed), &config));
+    }
+
+    let rng = &mut StdRng::seed_from_u64(0);
+    let config = GenConfig { max_depth: 0, leaf_len: SizeDistribution::Fixed(3), ..GenConfig::default() };
+    assert!(matches!(Node::random(rng, &config), Node::Leaf(bytes) if bytes.len() == 3));
+    let config = GenConfig {
+        max_depth: 3,
+        inner_decay: 1.0,
+        branching: SizeDistribution::Fixed(2),
+        leaf_len: SizeDistribution::Uniform(4..=4),
+        ..GenConfig::default()
+    };
+    // a full binary tree of depth 3 with 8 leaves
+    assert_eq!(Node::random(rng, &config).node_count(), 15);
+
+    let dist = SizeDistribution::Geometric { mean: 4.0, max: 10 };
+    let sizes: Vec<usize> = (0..1000).map(|_| dist.sample(rng)).collect();
+    assert!(sizes.iter().all(|size| *size <= 10));
+    let mean = sizes.iter().sum::<usize>() as f64 / sizes.len() as f64;
+    assert!(mean > 3.0 && mean < 4.5, "{}", mean);
+}
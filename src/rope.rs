@@ -0,0 +1,189 @@
+use crate::{GenericNode, Node};
+
+use std::ops::Range;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Byte string stored in chunks, so that inserting or removing bytes only
+/// moves the bytes of the affected chunks instead of the whole buffer.
+#[derive(Clone, Default, Debug)]
+pub struct Rope {
+    chunks: Vec<Vec<u8>>,
+    len: usize,
+}
+
+/// Tree whose leaves are ropes, for editing large leaves.
+pub type RopeNode = GenericNode<Rope>;
+
+impl Rope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &[u8]> {
+        self.chunks.iter().map(Vec::as_slice)
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.chunks.concat()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<u8> {
+        if idx >= self.len {
+            return None;
+        }
+        let (chunk, offset) = self.locate(idx);
+        Some(self.chunks[chunk][offset])
+    }
+
+    /// Inserts `bytes` at `pos`. Panics if `pos > len`.
+    pub fn insert(&mut self, pos: usize, bytes: &[u8]) {
+        let idx = self.split(pos);
+        let new: Vec<_> = bytes.chunks(CHUNK_SIZE).map(<[u8]>::to_vec).collect();
+        let count = new.len();
+        self.chunks.splice(idx..idx, new);
+        self.len += bytes.len();
+        self.merge(idx + count);
+        self.merge(idx);
+    }
+
+    /// Removes the bytes in `range`. Panics if the range is out of bounds.
+    pub fn remove(&mut self, range: Range<usize>) {
+        assert!(range.start <= range.end && range.end <= self.len, "range out of bounds");
+        let start = self.split(range.start);
+        let end = self.split(range.end);
+        self.chunks.drain(start..end);
+        self.len -= range.end - range.start;
+        self.merge(start);
+    }
+
+    /// Returns the chunk containing `pos` and the offset within it.
+    fn locate(&self, mut pos: usize) -> (usize, usize) {
+        for (idx, chunk) in self.chunks.iter().enumerate() {
+            if pos < chunk.len() {
+                return (idx, pos);
+            }
+            pos -= chunk.len();
+        }
+        (self.chunks.len(), pos)
+    }
+
+    /// Makes `pos` the start of a chunk and returns that chunk's index.
+    fn split(&mut self, pos: usize) -> usize {
+        assert!(pos <= self.len, "position out of bounds");
+        let (idx, offset) = self.locate(pos);
+        if offset == 0 {
+            return idx;
+        }
+        let tail = self.chunks[idx].split_off(offset);
+        self.chunks.insert(idx + 1, tail);
+        idx + 1
+    }
+
+    /// Merges the chunk at `idx` into its predecessor if they're small.
+    fn merge(&mut self, idx: usize) {
+        if idx > 0 && idx < self.chunks.len() && self.chunks[idx - 1].len() + self.chunks[idx].len() <= CHUNK_SIZE {
+            let chunk = self.chunks.remove(idx);
+            self.chunks[idx - 1].extend_from_slice(&chunk);
+        }
+    }
+}
+
+impl From<&[u8]> for Rope {
+    fn from(bytes: &[u8]) -> Self {
+        let mut rope = Rope::new();
+        rope.insert(0, bytes);
+        rope
+    }
+}
+
+impl PartialEq for Rope {
+    fn eq(&self, other: &Rope) -> bool {
+        self.len == other.len && self.chunks().flatten().eq(other.chunks().flatten())
+    }
+}
+
+impl Eq for Rope { }
+
+impl RopeNode {
+    /// Flattens the ropes into a `Node`.
+    pub fn to_node(&self) -> Node {
+        match self {
+            GenericNode::Leaf(rope) => Node::Leaf(rope.to_vec()),
+            GenericNode::Inner(nodes) => Node::Inner(nodes.iter().map(Self::to_node).collect()),
+        }
+    }
+
+    /// Serializes the tree like `Node::serialize`, without flattening the
+    /// ropes first.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut res = b"BAUM1".to_vec();
+        self.serialize_into(&mut res).unwrap();
+        res
+    }
+
+    pub fn serialize_into<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        match self {
+            GenericNode::Leaf(rope) => {
+                writer.write_all(&[0])?;
+                writer.write_all(&(rope.len() as u64).to_le_bytes())?;
+                for chunk in rope.chunks() {
+                    writer.write_all(chunk)?;
+                }
+            }
+            GenericNode::Inner(nodes) => {
+                writer.write_all(&[1])?;
+                writer.write_all(&(nodes.len() as u64).to_le_bytes())?;
+                for node in nodes {
+                    node.serialize_into(writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<&Node> for RopeNode {
+    fn from(node: &Node) -> Self {
+        GenericNode::<&[u8]>::from(node).map_leaves(Rope::from)
+    }
+}
+
+
+#[test]
+fn rope() {
+    let mut rope = Rope::from(&[1, 2, 3][..]);
+    rope.insert(1, &[4, 5]);
+    rope.insert(5, &[6]);
+    rope.remove(0..2);
+    assert_eq!(rope.to_vec(), vec!(5, 2, 3, 6));
+    assert_eq!(rope.chunks().count(), 1);
+    assert_eq!((rope.get(3), rope.get(4)), (Some(6), None));
+
+    // large ropes consist of multiple chunks
+    let data: Vec<u8> = (0..CHUNK_SIZE * 3 + 10).map(|i| i as u8).collect();
+    let mut rope = Rope::from(data.as_slice());
+    assert_eq!(rope.chunks().count(), 4);
+    let mut expected = data.clone();
+    rope.insert(CHUNK_SIZE + 5, b"abc");
+    expected.splice(CHUNK_SIZE + 5..CHUNK_SIZE + 5, b"abc".iter().copied());
+    rope.remove(10..CHUNK_SIZE * 2);
+    expected.drain(10..CHUNK_SIZE * 2);
+    assert_eq!(rope.len(), expected.len());
+    assert_eq!(rope.to_vec(), expected);
+    assert!(rope.chunks().all(|c| !c.is_empty() && c.len() <= CHUNK_SIZE));
+    assert_eq!(rope, Rope::from(expected.as_slice()));
+
+    let node = Node::Inner(vec!(Node::Leaf(data), Node::Inner(vec!(Node::Leaf(vec!())))));
+    let rope_node = RopeNode::from(&node);
+    assert_eq!(rope_node.serialize(), node.serialize());
+    assert_eq!(rope_node.to_node(), node);
+}
@@ -0,0 +1,339 @@
+//! Describing the expected shape of trees and validating nodes against it.
+//!
+//! ```
+//! use baum::schema::Schema;
+//! use baum::Node::{Inner, Leaf};
+//!
+//! let schema = Schema::record(vec!(
+//!     ("version", Schema::leaf_len(1)),
+//!     ("name", Schema::utf8()),
+//!     ("items", Schema::list(Schema::leaf_len(4))),
+//! ));
+//! let node = Inner(vec!(
+//!     Leaf(vec!(1)),
+//!     Leaf(b"foo".to_vec()),
+//!     Inner(vec!(Leaf(vec!(0, 0, 0, 1)), Leaf(vec!(2)))),
+//! ));
+//! let violations = schema.validate(&node).unwrap_err();
+//! assert_eq!(violations[0].to_string(), "[2, 1]: Expected leaf of at least 4 bytes, found 1 bytes.");
+//! ```
+
+use crate::{Node, Path};
+
+/// Expected shape of a node.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum Schema {
+    /// Any node.
+    Any,
+    /// A leaf satisfying the constraints.
+    Leaf(LeafSchema),
+    /// An inner node with exactly one child per field.
+    Inner(Vec<Field>),
+    /// An inner node with an arbitrary number of children (within the
+    /// bounds) that all match `item`.
+    List { item: Box<Schema>, min: usize, max: Option<usize> },
+    /// A node matching at least one of the schemas.
+    OneOf(Vec<Schema>),
+}
+
+/// Child of an inner node with a fixed number of children.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Field {
+    /// Optional name, used in generated code.
+    pub name: Option<String>,
+    pub schema: Schema,
+}
+
+/// Constraints of a leaf.
+#[derive(Clone, Eq, PartialEq, Default, Debug)]
+pub struct LeafSchema {
+    pub min_len: usize,
+    pub max_len: Option<usize>,
+    /// Whether the leaf has to contain valid UTF-8.
+    pub utf8: bool,
+}
+
+impl Schema {
+    /// Any leaf.
+    pub fn leaf() -> Schema {
+        Schema::Leaf(LeafSchema::default())
+    }
+
+    /// A leaf with exactly `len` bytes.
+    pub fn leaf_len(len: usize) -> Schema {
+        Schema::Leaf(LeafSchema { min_len: len, max_len: Some(len), utf8: false })
+    }
+
+    /// A leaf containing valid UTF-8.
+    pub fn utf8() -> Schema {
+        Schema::Leaf(LeafSchema { utf8: true, ..LeafSchema::default() })
+    }
+
+    /// An inner node with unnamed children matching `children`.
+    pub fn inner(children: Vec<Schema>) -> Schema {
+        Schema::Inner(children.into_iter().map(|schema| Field { name: None, schema }).collect())
+    }
+
+    /// An inner node with named children matching `fields`.
+    pub fn record(fields: Vec<(&str, Schema)>) -> Schema {
+        Schema::Inner(fields.into_iter().map(|(name, schema)| Field { name: Some(name.to_string()), schema }).collect())
+    }
+
+    /// An inner node with any number of children matching `item`.
+    pub fn list(item: Schema) -> Schema {
+        Schema::List { item: Box::new(item), min: 0, max: None }
+    }
+
+    /// Derives a schema that all `samples` match. Inner nodes with the same
+    /// number of children in all samples become `Schema::Inner`, others
+    /// `Schema::List`; leaf and list lengths are limited to the observed
+    /// ranges. Leaves are expected to be UTF-8 if all non-empty samples
+    /// are printable text.
+    pub fn infer(samples: &[Node]) -> Schema {
+        samples.iter()
+            .map(Schema::infer_node)
+            .reduce(Schema::merge)
+            .unwrap_or(Schema::Any)
+    }
+
+    fn infer_node(node: &Node) -> Schema {
+        match node {
+            Node::Leaf(bytes) => {
+                let text = std::str::from_utf8(bytes)
+                    .map(|s| s.chars().all(|c| !c.is_control() || c.is_ascii_whitespace()))
+                    .unwrap_or(false);
+                Schema::Leaf(LeafSchema {
+                    min_len: bytes.len(),
+                    max_len: Some(bytes.len()),
+                    utf8: text && !bytes.is_empty(),
+                })
+            }
+            Node::Inner(nodes) => Schema::inner(nodes.iter().map(Schema::infer_node).collect()),
+        }
+    }
+
+    fn merge(self, other: Schema) -> Schema {
+        match (self, other) {
+            (Schema::Any, _) | (_, Schema::Any) => Schema::Any,
+            (Schema::Leaf(a), Schema::Leaf(b)) => {
+                // empty leaves don't tell whether the leaf contains text
+                let empty = |l: &LeafSchema| l.max_len == Some(0);
+                Schema::Leaf(LeafSchema {
+                    min_len: a.min_len.min(b.min_len),
+                    max_len: a.max_len.zip(b.max_len).map(|(a, b)| a.max(b)),
+                    utf8: (a.utf8 || empty(&a)) && (b.utf8 || empty(&b)) && (a.utf8 || b.utf8),
+                })
+            }
+            (Schema::Inner(a), Schema::Inner(b)) if a.len() == b.len() => Schema::Inner(
+                a.into_iter().zip(b).map(|(a, b)| Field { name: a.name, schema: a.schema.merge(b.schema) }).collect()
+            ),
+            (a @ Schema::Inner(_), b @ Schema::Inner(_)) | (a @ Schema::Inner(_), b @ Schema::List { .. }) |
+            (a @ Schema::List { .. }, b @ Schema::Inner(_)) | (a @ Schema::List { .. }, b @ Schema::List { .. }) => {
+                let (a_item, a_min, a_max) = a.into_list();
+                let (b_item, b_min, b_max) = b.into_list();
+                let item = match (a_item, b_item) {
+                    (Some(a), Some(b)) => a.merge(b),
+                    (Some(item), None) | (None, Some(item)) => item,
+                    (None, None) => Schema::Any,
+                };
+                Schema::List { item: Box::new(item), min: a_min.min(b_min), max: a_max.zip(b_max).map(|(a, b)| a.max(b)) }
+            }
+            (Schema::OneOf(alternatives), other) | (other, Schema::OneOf(alternatives)) => {
+                let mut merged = vec!();
+                let mut other = Some(other);
+                for alt in alternatives {
+                    match other.take() {
+                        Some(o) if alt.is_leaf() == o.is_leaf() => merged.push(alt.merge(o)),
+                        o => {
+                            other = o;
+                            merged.push(alt);
+                        }
+                    }
+                }
+                merged.extend(other);
+                Schema::OneOf(merged)
+            }
+            (a, b) => Schema::OneOf(vec!(a, b)),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        matches!(self, Schema::Leaf(_))
+    }
+
+    /// Returns the item schema (`None` if there are no children) and the
+    /// length bounds of an inner node or list schema.
+    fn into_list(self) -> (Option<Schema>, usize, Option<usize>) {
+        match self {
+            Schema::Inner(fields) => {
+                let len = fields.len();
+                (fields.into_iter().map(|f| f.schema).reduce(Schema::merge), len, Some(len))
+            }
+            Schema::List { item, min, max } => (Some(*item), min, max),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Validates `node`, returning all violations if there are any.
+    pub fn validate(&self, node: &Node) -> Result<(), Vec<Violation>> {
+        let mut violations = vec!();
+        self._validate(node, &mut vec!(), &mut violations);
+        if violations.is_empty() { Ok(()) } else { Err(violations) }
+    }
+
+    pub fn is_valid(&self, node: &Node) -> bool {
+        self.validate(node).is_ok()
+    }
+
+    fn _validate(&self, node: &Node, path: &mut Path, violations: &mut Vec<Violation>) {
+        let mut report = |kind| violations.push(Violation { path: path.clone(), kind });
+        match (self, node) {
+            (Schema::Any, _) => (),
+            (Schema::Leaf(leaf), Node::Leaf(bytes)) => {
+                if bytes.len() < leaf.min_len {
+                    report(ViolationKind::LeafTooShort { min: leaf.min_len, actual: bytes.len() });
+                }
+                if let Some(max) = leaf.max_len.filter(|max| bytes.len() > *max) {
+                    report(ViolationKind::LeafTooLong { max, actual: bytes.len() });
+                }
+                if leaf.utf8 && std::str::from_utf8(bytes).is_err() {
+                    report(ViolationKind::InvalidUtf8);
+                }
+            }
+            (Schema::Leaf(_), Node::Inner(_)) => report(ViolationKind::ExpectedLeaf),
+            (Schema::Inner(fields), Node::Inner(nodes)) => {
+                if fields.len() != nodes.len() {
+                    report(ViolationKind::ChildCount { expected: fields.len(), actual: nodes.len() });
+                    return;
+                }
+                for (idx, (field, node)) in fields.iter().zip(nodes).enumerate() {
+                    path.push(idx);
+                    field.schema._validate(node, path, violations);
+                    path.pop();
+                }
+            }
+            (Schema::List { item, min, max }, Node::Inner(nodes)) => {
+                if nodes.len() < *min {
+                    report(ViolationKind::TooFewChildren { min: *min, actual: nodes.len() });
+                }
+                if let Some(max) = max.filter(|max| nodes.len() > *max) {
+                    report(ViolationKind::TooManyChildren { max, actual: nodes.len() });
+                }
+                for (idx, node) in nodes.iter().enumerate() {
+                    path.push(idx);
+                    item._validate(node, path, violations);
+                    path.pop();
+                }
+            }
+            (Schema::Inner(_), Node::Leaf(_)) | (Schema::List { .. }, Node::Leaf(_)) => report(ViolationKind::ExpectedInner),
+            (Schema::OneOf(schemas), node) => {
+                if !schemas.iter().any(|s| s.is_valid(node)) {
+                    report(ViolationKind::NoMatchingAlternative);
+                }
+            }
+        }
+    }
+}
+
+/// A node that doesn't match its schema.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Violation {
+    pub path: Path,
+    pub kind: ViolationKind,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ViolationKind {
+    ExpectedLeaf,
+    ExpectedInner,
+    LeafTooShort { min: usize, actual: usize },
+    LeafTooLong { max: usize, actual: usize },
+    InvalidUtf8,
+    ChildCount { expected: usize, actual: usize },
+    TooFewChildren { min: usize, actual: usize },
+    TooManyChildren { max: usize, actual: usize },
+    /// None of the alternatives of a `Schema::OneOf` matches.
+    NoMatchingAlternative,
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}: ", self.path)?;
+        match &self.kind {
+            ViolationKind::ExpectedLeaf => write!(f, "Expected leaf."),
+            ViolationKind::ExpectedInner => write!(f, "Expected inner node."),
+            ViolationKind::LeafTooShort { min, actual } =>
+                write!(f, "Expected leaf of at least {} bytes, found {} bytes.", min, actual),
+            ViolationKind::LeafTooLong { max, actual } =>
+                write!(f, "Expected leaf of at most {} bytes, found {} bytes.", max, actual),
+            ViolationKind::InvalidUtf8 => write!(f, "Leaf isn't valid UTF-8."),
+            ViolationKind::ChildCount { expected, actual } =>
+                write!(f, "Expected {} children, found {}.", expected, actual),
+            ViolationKind::TooFewChildren { min, actual } =>
+                write!(f, "Expected at least {} children, found {}.", min, actual),
+            ViolationKind::TooManyChildren { max, actual } =>
+                write!(f, "Expected at most {} children, found {}.", max, actual),
+            ViolationKind::NoMatchingAlternative => write!(f, "Node doesn't match any alternative."),
+        }
+    }
+}
+
+impl std::error::Error for Violation { }
+
+
+#[test]
+fn schema_validate() {
+    let schema = Schema::record(vec!(
+        ("header", Schema::inner(vec!(Schema::leaf_len(1), Schema::Any))),
+        ("name", Schema::utf8()),
+        ("items", Schema::List { item: Box::new(Schema::leaf()), min: 1, max: Some(2) }),
+        ("extra", Schema::OneOf(vec!(Schema::leaf_len(0), Schema::inner(vec!())))),
+    ));
+    let node = crate::parse("((0x01 ()) \"abc\" (0x 0x01) ())");
+    assert_eq!(schema.validate(&node), Ok(()));
+
+    let node = crate::parse("((0x01_02 ()) 0xff (0x ()) (0x))");
+    let violations = schema.validate(&node).unwrap_err();
+    assert_eq!(violations, vec!(
+        Violation { path: vec!(0, 0), kind: ViolationKind::LeafTooLong { max: 1, actual: 2 } },
+        Violation { path: vec!(1), kind: ViolationKind::InvalidUtf8 },
+        Violation { path: vec!(2, 1), kind: ViolationKind::ExpectedLeaf },
+        Violation { path: vec!(3), kind: ViolationKind::NoMatchingAlternative },
+    ));
+
+    let node = crate::parse("(() 0x () 0x 0x)");
+    assert_eq!(schema.validate(&node).unwrap_err(), vec!(
+        Violation { path: vec!(), kind: ViolationKind::ChildCount { expected: 4, actual: 5 } },
+    ));
+    let node = crate::parse("((0x01 ()) 0x (0x 0x 0x) ())");
+    assert_eq!(schema.validate(&node).unwrap_err(), vec!(
+        Violation { path: vec!(2), kind: ViolationKind::TooManyChildren { max: 2, actual: 3 } },
+    ));
+}
+
+#[test]
+fn schema_infer() {
+    let samples = vec!(
+        crate::parse("(0x01 \"ab\" (0x01 0x02) (0x01_02))"),
+        crate::parse("(0x02 \"\" (0x03) ((0x)))"),
+        crate::parse("(0x03 \"cde\" () 0x)"),
+    );
+    let schema = Schema::infer(&samples);
+    assert_eq!(schema, Schema::inner(vec!(
+        Schema::leaf_len(1),
+        Schema::Leaf(LeafSchema { min_len: 0, max_len: Some(3), utf8: true }),
+        Schema::List { item: Box::new(Schema::leaf_len(1)), min: 0, max: Some(2) },
+        Schema::OneOf(vec!(
+            Schema::inner(vec!(Schema::OneOf(vec!(
+                Schema::leaf_len(2),
+                Schema::inner(vec!(Schema::leaf_len(0))),
+            )))),
+            Schema::leaf_len(0),
+        )),
+    )));
+    for sample in &samples {
+        assert!(schema.is_valid(sample));
+    }
+    assert_eq!(Schema::infer(&[]), Schema::Any);
+    assert_eq!(Schema::infer(&[crate::parse("0xff")]), Schema::leaf_len(1));
+}
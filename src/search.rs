@@ -0,0 +1,101 @@
+use crate::{Error, Node, NodeRef, Path};
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Index of the trigrams (3-byte substrings) contained in the leaves of a
+/// tree, for finding the leaves that contain a byte string without scanning
+/// all of them.
+pub struct SubstringIndex {
+    /// Concatenated bytes of all leaves.
+    data: Vec<u8>,
+    leaves: Vec<(Path, Range<usize>)>,
+    /// Indices of the leaves containing each trigram, in ascending order.
+    trigrams: HashMap<[u8; 3], Vec<usize>>,
+}
+
+impl SubstringIndex {
+    pub fn new(node: &Node) -> Self {
+        Self::from_node_ref(&NodeRef::from(node))
+    }
+
+    /// Builds the index from a serialized tree.
+    pub fn from_serialized(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(Self::from_node_ref(&NodeRef::deserialize(bytes)?))
+    }
+
+    fn from_node_ref(node: &NodeRef) -> Self {
+        let mut index = SubstringIndex { data: vec!(), leaves: vec!(), trigrams: HashMap::new() };
+        index.add(node, &mut vec!());
+        index
+    }
+
+    fn add(&mut self, node: &NodeRef, path: &mut Path) {
+        match node {
+            NodeRef::Leaf(bytes) => {
+                let idx = self.leaves.len();
+                for trigram in bytes.windows(3) {
+                    let leaves = self.trigrams.entry([trigram[0], trigram[1], trigram[2]]).or_default();
+                    if leaves.last() != Some(&idx) {
+                        leaves.push(idx);
+                    }
+                }
+                let start = self.data.len();
+                self.data.extend_from_slice(bytes);
+                self.leaves.push((path.clone(), start..self.data.len()));
+            }
+            NodeRef::Inner(nodes) => {
+                for (idx, node) in nodes.iter().enumerate() {
+                    path.push(idx);
+                    self.add(node, path);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    /// Returns the paths of all leaves containing `needle`, in depth-first
+    /// order. Needles shorter than three bytes require scanning all leaves.
+    pub fn find(&self, needle: &[u8]) -> Vec<&Path> {
+        let candidates: Vec<usize> = match needle.len() {
+            0..=2 => (0..self.leaves.len()).collect(),
+            _ => {
+                let mut lists = vec!();
+                for trigram in needle.windows(3) {
+                    match self.trigrams.get(trigram) {
+                        Some(leaves) => lists.push(leaves),
+                        None => return vec!(),
+                    }
+                }
+                lists.sort_by_key(|l| l.len());
+                lists[0].iter().copied().filter(|idx| lists[1..].iter().all(|l| l.binary_search(idx).is_ok())).collect()
+            }
+        };
+        candidates.into_iter().map(|idx| &self.leaves[idx]).filter(|(_, range)| {
+            let leaf = &self.data[range.clone()];
+            needle.is_empty() || leaf.windows(needle.len()).any(|w| w == needle)
+        }).map(|(path, _)| path).collect()
+    }
+}
+
+
+#[test]
+fn substring_index() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"hello world".to_vec()),
+        Node::Inner(vec!(Node::Leaf(b"world wide".to_vec()), Node::Leaf(b"wo".to_vec()))),
+        Node::Leaf(b"lowor".to_vec()),
+    ));
+    let index = SubstringIndex::new(&node);
+    assert_eq!(index.find(b"world"), vec!(&vec!(0), &vec!(1, 0)));
+    assert_eq!(index.find(b"wor"), vec!(&vec!(0), &vec!(1, 0), &vec!(2)));
+    assert_eq!(index.find(b"wo"), vec!(&vec!(0), &vec!(1, 0), &vec!(1, 1), &vec!(2)));
+    // all trigrams occur in the leaf, but not the needle itself
+    assert!(SubstringIndex::new(&Node::Leaf(b"abcab".to_vec())).find(b"bcabc").is_empty());
+    assert!(index.find(b"xyz").is_empty());
+    assert_eq!(index.find(b"").len(), 4);
+
+    let index = SubstringIndex::from_serialized(&node.serialize()).unwrap();
+    assert_eq!(index.find(b"d w"), vec!(&vec!(1, 0)));
+    assert!(SubstringIndex::from_serialized(b"BAUM1").is_err());
+}
@@ -0,0 +1,766 @@
+use crate::{DecodeMode, DecodeState, Error, ErrorKind, Limits, Node, NodeRef, Path, QueryIndex, Section};
+use crate::node_ref::take;
+
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom};
+use std::iter::Peekable;
+use std::path::Path as FilePath;
+use std::str::{Chars, FromStr};
+
+/// Compiled query selecting nodes of a tree by their position and shape,
+/// e.g. `2/*/0` or `**/[leaf.len() == 16]`.
+///
+/// A selector is a sequence of steps separated by `/`, each applied to the
+/// nodes selected by the previous one, starting at the root:
+///
+/// - `N` selects the child with index `N`
+/// - `*` selects all children
+/// - `**` selects the node itself and all of its descendants
+///
+/// Steps may be followed by predicates in brackets that filter the selected
+/// nodes. A step consisting only of predicates is short for `**` with these
+/// predicates, so `[leaf.len() == 16]` selects all leaves of 16 bytes. The
+/// predicates are:
+///
+/// - `[leaf]` and `[inner]`
+/// - `[leaf.len() OP N]` and `[children.len() OP N]`, where `OP` is one of
+///   `==`, `!=`, `<`, `<=`, `>` and `>=`
+/// - `[leaf == 0x..]` and `[leaf != 0x..]`
+/// - `[tag == 0x..]` and `[tag != 0x..]`, comparing the first child of inner
+///   nodes, which has to be a leaf
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Selector {
+    steps: Vec<Step>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct Step {
+    axis: Axis,
+    predicates: Vec<Predicate>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Axis {
+    Child(usize),
+    Children,
+    Descendants,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Predicate {
+    Leaf,
+    Inner,
+    LeafLen(Cmp, usize),
+    ChildCount(Cmp, usize),
+    LeafBytes(bool, Vec<u8>),
+    Tag(bool, Vec<u8>),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Step {
+    /// Returns the bytes selected leaves have to be equal to, if any.
+    fn leaf_bytes(&self) -> Option<&[u8]> {
+        self.predicates.iter().find_map(|p| match p {
+            Predicate::LeafBytes(true, bytes) => Some(bytes.as_slice()),
+            _ => None,
+        })
+    }
+}
+
+impl Cmp {
+    fn eval(self, a: usize, b: usize) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+}
+
+/// The parts of a node that predicates depend on.
+enum Shape<'a> {
+    Leaf(Cow<'a, [u8]>),
+    Inner { len: usize, tag: Option<Cow<'a, [u8]>> },
+}
+
+impl<'a> From<&'a Node> for Shape<'a> {
+    fn from(node: &'a Node) -> Self {
+        match node {
+            Node::Leaf(bytes) => Shape::Leaf(Cow::Borrowed(bytes)),
+            Node::Inner(nodes) => Shape::Inner {
+                len: nodes.len(),
+                tag: match nodes.first() {
+                    Some(Node::Leaf(tag)) => Some(Cow::Borrowed(tag)),
+                    _ => None,
+                },
+            },
+        }
+    }
+}
+
+impl Predicate {
+    fn eval(&self, shape: &Shape) -> bool {
+        match (self, shape) {
+            (Predicate::Leaf, shape) => matches!(shape, Shape::Leaf(_)),
+            (Predicate::Inner, shape) => matches!(shape, Shape::Inner { .. }),
+            (Predicate::LeafLen(cmp, len), Shape::Leaf(bytes)) => cmp.eval(bytes.len(), *len),
+            (Predicate::ChildCount(cmp, len), Shape::Inner { len: count, .. }) => cmp.eval(*count, *len),
+            (Predicate::LeafBytes(eq, expected), Shape::Leaf(bytes)) => (bytes[..] == expected[..]) == *eq,
+            (Predicate::Tag(eq, expected), Shape::Inner { tag: Some(tag), .. }) => (tag[..] == expected[..]) == *eq,
+            _ => false,
+        }
+    }
+}
+
+struct SelectorParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl SelectorParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), String> {
+        self.skip_whitespace();
+        for c in s.chars() {
+            if self.chars.next() != Some(c) {
+                return Err(format!("Expected '{}'.", s));
+            }
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        self.skip_whitespace();
+        let mut digits = String::new();
+        while let Some(c) = self.chars.next_if(char::is_ascii_digit) {
+            digits.push(c);
+        }
+        digits.parse().map_err(|_| "Expected number.".to_string())
+    }
+
+    fn cmp(&mut self) -> Result<Cmp, String> {
+        self.skip_whitespace();
+        let c = self.chars.next();
+        let eq = self.chars.next_if_eq(&'=').is_some();
+        match (c, eq) {
+            (Some('='), true) => Ok(Cmp::Eq),
+            (Some('!'), true) => Ok(Cmp::Ne),
+            (Some('<'), eq) => Ok(if eq { Cmp::Le } else { Cmp::Lt }),
+            (Some('>'), eq) => Ok(if eq { Cmp::Ge } else { Cmp::Gt }),
+            _ => Err("Expected comparison operator.".to_string()),
+        }
+    }
+
+    fn hex(&mut self) -> Result<Vec<u8>, String> {
+        self.expect("0x")?;
+        let mut digits = vec!();
+        while let Some(c) = self.chars.next_if(|c| crate::hex::digit(*c).is_some() || *c == '_') {
+            digits.extend(crate::hex::digit(c));
+        }
+        Ok(crate::hex::pack(&digits))
+    }
+
+    /// Parses `== 0x..` or `!= 0x..`, returning whether it's an equality.
+    fn bytes_cmp(&mut self, what: &str) -> Result<(bool, Vec<u8>), String> {
+        match self.cmp()? {
+            Cmp::Eq => Ok((true, self.hex()?)),
+            Cmp::Ne => Ok((false, self.hex()?)),
+            _ => Err(format!("{} can only be compared using '==' and '!='.", what)),
+        }
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, String> {
+        self.skip_whitespace();
+        let mut name = String::new();
+        while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphabetic() || *c == '.' || *c == '(' || *c == ')') {
+            name.push(c);
+        }
+        let predicate = match name.as_str() {
+            "leaf" => {
+                self.skip_whitespace();
+                match self.chars.peek() {
+                    Some('=' | '!' | '<' | '>') => {
+                        let (eq, bytes) = self.bytes_cmp("Leaves")?;
+                        Predicate::LeafBytes(eq, bytes)
+                    }
+                    _ => Predicate::Leaf,
+                }
+            }
+            "inner" => Predicate::Inner,
+            "tag" => {
+                let (eq, bytes) = self.bytes_cmp("Tags")?;
+                Predicate::Tag(eq, bytes)
+            }
+            "leaf.len()" => Predicate::LeafLen(self.cmp()?, self.number()?),
+            "children.len()" => Predicate::ChildCount(self.cmp()?, self.number()?),
+            _ => return Err(format!("Unknown predicate '{}'.", name)),
+        };
+        self.expect("]")?;
+        Ok(predicate)
+    }
+
+    fn step(&mut self) -> Result<Step, String> {
+        self.skip_whitespace();
+        let axis = match self.chars.peek() {
+            Some('*') => {
+                self.chars.next();
+                match self.chars.next_if_eq(&'*') {
+                    Some(_) => Axis::Descendants,
+                    None => Axis::Children,
+                }
+            }
+            Some(c) if c.is_ascii_digit() => Axis::Child(self.number()?),
+            Some('[') => Axis::Descendants,
+            _ => return Err("Expected step.".to_string()),
+        };
+        let mut predicates = vec!();
+        self.skip_whitespace();
+        while self.chars.next_if_eq(&'[').is_some() {
+            predicates.push(self.predicate()?);
+            self.skip_whitespace();
+        }
+        Ok(Step { axis, predicates })
+    }
+}
+
+impl Selector {
+    pub fn parse(s: &str) -> Result<Selector, String> {
+        let mut parser = SelectorParser { chars: s.chars().peekable() };
+        let mut steps = vec!();
+        parser.skip_whitespace();
+        if parser.chars.peek().is_none() {
+            return Ok(Selector { steps });
+        }
+        loop {
+            steps.push(parser.step()?);
+            match parser.chars.next() {
+                Some('/') => continue,
+                None => return Ok(Selector { steps }),
+                Some(c) => return Err(format!("Unexpected character '{}'.", c)),
+            }
+        }
+    }
+
+    /// Returns the selected nodes and their paths in depth-first order.
+    pub fn select<'a>(&self, node: &'a Node) -> Vec<(Path, &'a Node)> {
+        let mut selected = vec!((vec!(), node));
+        for step in &self.steps {
+            let mut next = vec!();
+            for (path, node) in selected {
+                match (step.axis, node) {
+                    (Axis::Child(idx), Node::Inner(nodes)) => {
+                        if let Some(child) = nodes.get(idx) {
+                            next.push((child_path(&path, idx), child));
+                        }
+                    }
+                    (Axis::Children, Node::Inner(nodes)) => {
+                        next.extend(nodes.iter().enumerate().map(|(idx, child)| (child_path(&path, idx), child)));
+                    }
+                    (Axis::Descendants, node) => descendants(node, &mut path.clone(), &mut next),
+                    _ => {},
+                }
+            }
+            next.retain(|(_, node)| step.predicates.iter().all(|p| p.eval(&Shape::from(*node))));
+            // descendants of different nodes may overlap
+            next.sort_by(|(a, _), (b, _)| a.cmp(b));
+            next.dedup_by(|(a, _), (b, _)| a == b);
+            selected = next;
+        }
+        selected
+    }
+
+    /// Calls `f` with every selected node and its path, and returns the
+    /// number of selected nodes. Nodes are visited in reverse depth-first
+    /// order, so descendants are visited before their ancestors and changing
+    /// a node doesn't affect the paths of the ones visited after it.
+    pub fn select_mut<F: FnMut(&Path, &mut Node)>(&self, node: &mut Node, mut f: F) -> usize {
+        let paths: Vec<Path> = self.select(node).into_iter().map(|(path, _)| path).collect();
+        for path in paths.iter().rev() {
+            if let Some(selected) = node.get_mut(path) {
+                f(path, selected);
+            }
+        }
+        paths.len()
+    }
+}
+
+impl Selector {
+    /// Applies the selector to a serialized tree (including the magic
+    /// number), e.g. a memory-mapped file, and returns the selected nodes
+    /// with leaves borrowing from `bytes`.
+    ///
+    /// Only the headers of the nodes the selector passes are read and only
+    /// the selected subtrees are decoded, so the rest of the input isn't
+    /// validated.
+    pub fn select_serialized<'a>(&self, bytes: &'a [u8]) -> Result<Vec<(Path, NodeRef<'a>)>, Error> {
+        self.select_slice(bytes, None)
+    }
+
+    /// Like `select_serialized`, looking up nodes in `index` instead of
+    /// scanning for them. Steps selecting descendants by their bytes
+    /// (`**/[leaf == 0x..]`) only read the leaves with the same prefix.
+    pub fn select_indexed<'a>(&self, bytes: &'a [u8], index: &QueryIndex) -> Result<Vec<(Path, NodeRef<'a>)>, Error> {
+        index.check(bytes)?;
+        self.select_slice(bytes, Some(index))
+    }
+
+    /// Like `select_serialized`, reading the serialized tree from `reader`,
+    /// e.g. a buffered file. Subtrees the selector doesn't touch are
+    /// skipped by seeking past them, so the input doesn't have to fit into
+    /// memory.
+    pub fn select_reader<R: Read + Seek>(&self, mut reader: R) -> Result<Vec<(Path, Node)>, Error> {
+        let mut magic = [0; 5];
+        reader.seek(SeekFrom::Start(0))?;
+        crate::read_section(&mut reader, &mut magic, Section::Magic)?;
+        if &magic != b"BAUM1" {
+            return Err(Error::new(ErrorKind::InvalidMagicNumber));
+        }
+        let mut input = SeekInput(reader);
+        let selected = self.select_positions(&mut input, None)?;
+        let limits = Limits::default();
+        selected.into_iter().map(|(path, pos)| {
+            input.0.seek(SeekFrom::Start(5 + pos as u64))?;
+            let mut state = DecodeState { pos: 0, path: vec!(), mode: DecodeMode::Strict, limits: &limits, nodes: 0 };
+            let node = Node::_deserialize_from(&mut input.0, &mut state)?.ok_or_else(|| Error::new(ErrorKind::InvalidNodeType))?;
+            Ok((path, node))
+        }).collect()
+    }
+
+    /// Reads the serialized tree at `path` and applies the selector to it,
+    /// consulting the index stored alongside the file (see
+    /// `QueryIndex::save_for`) if there's one matching the file.
+    pub fn select_file(&self, path: &FilePath) -> Result<Vec<(Path, Node)>, Error> {
+        let bytes = std::fs::read(path)?;
+        let index = QueryIndex::load_for(path).ok().filter(|index| index.check(&bytes).is_ok());
+        let res = self.select_slice(&bytes, index.as_ref())?;
+        Ok(res.into_iter().map(|(path, node)| (path, node.to_node())).collect())
+    }
+
+    fn select_slice<'a>(&self, bytes: &'a [u8], index: Option<&QueryIndex>) -> Result<Vec<(Path, NodeRef<'a>)>, Error> {
+        let mut bytes = strip_magic(bytes)?;
+        let selected = self.select_positions(&mut bytes, index)?;
+        selected.into_iter().map(|(path, mut pos)| Ok((path, crate::node_ref::deserialize(bytes, &mut pos)?))).collect()
+    }
+
+    /// Returns the paths and positions of the selected nodes.
+    fn select_positions<'a, I: Input<'a>>(&self, input: &mut I, index: Option<&QueryIndex>) -> Result<Vec<(Path, usize)>, Error> {
+        // selected nodes as positions in `input`
+        let mut selected = vec!((vec!(), 0));
+        for step in &self.steps {
+            let mut next = vec!();
+            for (path, pos) in selected {
+                match (step.axis, index) {
+                    (Axis::Child(idx), Some(index)) => {
+                        let path = child_path(&path, idx);
+                        if let Some(child) = index.offset(&path) {
+                            next.push((path, child));
+                        }
+                    }
+                    (Axis::Child(idx), None) => {
+                        if let Some(child) = raw_child(input, pos, idx)? {
+                            next.push((child_path(&path, idx), child));
+                        }
+                    }
+                    (Axis::Children, Some(index)) => {
+                        for idx in 0.. {
+                            let path = child_path(&path, idx);
+                            match index.offset(&path) {
+                                Some(child) => next.push((path, child)),
+                                None => break,
+                            }
+                        }
+                    }
+                    (Axis::Children, None) => {
+                        let children = raw_children(input, pos)?;
+                        next.extend(children.into_iter().enumerate().map(|(idx, child)| (child_path(&path, idx), child)));
+                    }
+                    (Axis::Descendants, Some(index)) if step.leaf_bytes().is_some() => {
+                        for candidate in index.candidates(step.leaf_bytes().unwrap()) {
+                            if candidate.starts_with(&path) {
+                                next.extend(index.offset(candidate).map(|pos| (candidate.clone(), pos)));
+                            }
+                        }
+                    }
+                    (Axis::Descendants, _) => raw_descendants(input, pos, &path, &mut next)?,
+                }
+            }
+            let mut filtered = vec!();
+            for (path, pos) in next {
+                let shape = raw_shape(input, pos)?;
+                if step.predicates.iter().all(|p| p.eval(&shape)) {
+                    filtered.push((path, pos));
+                }
+            }
+            filtered.sort();
+            filtered.dedup();
+            selected = filtered;
+        }
+        Ok(selected)
+    }
+}
+
+/// Random access to the nodes of a serialized tree, at positions relative to
+/// the end of the magic number.
+trait Input<'a> {
+    /// Reads `len` bytes at `pos`.
+    fn read(&mut self, pos: usize, len: usize, section: Section) -> Result<Cow<'a, [u8]>, Error>;
+
+    /// Skips `len` bytes at `pos`, checking that they exist if that's cheap.
+    fn skip(&mut self, pos: usize, len: usize) -> Result<(), Error>;
+}
+
+impl<'a> Input<'a> for &'a [u8] {
+    fn read(&mut self, mut pos: usize, len: usize, section: Section) -> Result<Cow<'a, [u8]>, Error> {
+        take(self, &mut pos, len, section).map(Cow::Borrowed)
+    }
+
+    fn skip(&mut self, mut pos: usize, len: usize) -> Result<(), Error> {
+        take(self, &mut pos, len, Section::Payload).map(|_| ())
+    }
+}
+
+/// Reader that's sought to every read, starting after the magic number.
+struct SeekInput<R>(R);
+
+impl<'a, R: Read + Seek> Input<'a> for SeekInput<R> {
+    fn read(&mut self, pos: usize, len: usize, section: Section) -> Result<Cow<'a, [u8]>, Error> {
+        self.0.seek(SeekFrom::Start(5 + pos as u64))?;
+        // don't trust `len` for the allocation, the input may be truncated
+        let mut bytes = vec!();
+        (&mut self.0).take(len as u64).read_to_end(&mut bytes)?;
+        match bytes.len() < len {
+            true => Err(Error::unexpected_eof(section, len as u64, bytes.len() as u64)),
+            false => Ok(Cow::Owned(bytes)),
+        }
+    }
+
+    /// Seeking past the end isn't an error, so skipped bytes aren't checked.
+    fn skip(&mut self, _pos: usize, _len: usize) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Reads the type and length of the node at `pos` and advances `pos` past
+/// them.
+fn raw_header<'a, I: Input<'a>>(input: &mut I, pos: &mut usize) -> Result<(u8, usize), Error> {
+    let type_byte = input.read(*pos, 1, Section::Header)?[0];
+    let len = u64::from_le_bytes(input.read(*pos + 1, 8, Section::Length)?[..].try_into().unwrap());
+    *pos += 9;
+    Ok((type_byte, crate::to_usize(len)?))
+}
+
+pub(crate) fn strip_magic(bytes: &[u8]) -> Result<&[u8], Error> {
+    match bytes.strip_prefix(b"BAUM1") {
+        Some(rest) => Ok(rest),
+        None if bytes.len() < 5 => Err(Error::unexpected_eof(Section::Magic, 5, bytes.len() as u64)),
+        None => Err(Error::new(ErrorKind::InvalidMagicNumber)),
+    }
+}
+
+/// Decodes the node at `path` of a serialized tree (including the magic
+/// number), skipping the rest of the tree.
+pub(crate) fn node_at_serialized<'a>(bytes: &'a [u8], path: &[usize]) -> Result<NodeRef<'a>, Error> {
+    let mut bytes = strip_magic(bytes)?;
+    let mut pos = 0;
+    for idx in path {
+        pos = raw_child(&mut bytes, pos, *idx)?.ok_or_else(|| Error::message("Invalid path.".to_string()))?;
+    }
+    crate::node_ref::deserialize(bytes, &mut pos)
+}
+
+/// Reads the shape of the serialized node starting at `pos`. Of the first
+/// child, only the header is read unless it's a leaf.
+fn raw_shape<'a, I: Input<'a>>(input: &mut I, mut pos: usize) -> Result<Shape<'a>, Error> {
+    match raw_header(input, &mut pos)? {
+        (0, len) => Ok(Shape::Leaf(input.read(pos, len, Section::Payload)?)),
+        (1, 0) => Ok(Shape::Inner { len: 0, tag: None }),
+        (1, len) => {
+            let tag = match raw_header(input, &mut pos)? {
+                (0, tag_len) => Some(input.read(pos, tag_len, Section::Payload)?),
+                (1, _) => None,
+                _ => return Err(Error::new(ErrorKind::InvalidNodeType)),
+            };
+            Ok(Shape::Inner { len, tag })
+        }
+        _ => Err(Error::new(ErrorKind::InvalidNodeType)),
+    }
+}
+
+/// Returns the position after the serialized node starting at `pos`,
+/// skipping leaves without reading them.
+fn raw_skip<'a, I: Input<'a>>(input: &mut I, mut pos: usize) -> Result<usize, Error> {
+    // number of nodes that still have to be skipped
+    let mut remaining = 1usize;
+    while remaining > 0 {
+        remaining -= 1;
+        match raw_header(input, &mut pos)? {
+            (0, len) => {
+                input.skip(pos, len)?;
+                pos = pos.checked_add(len).ok_or_else(|| Error::new(ErrorKind::LengthOverflow))?;
+            }
+            (1, len) => remaining = remaining.saturating_add(len),
+            _ => return Err(Error::new(ErrorKind::InvalidNodeType)),
+        }
+    }
+    Ok(pos)
+}
+
+/// Returns the positions of the children of the serialized node starting at
+/// `pos`, which are empty for leaves.
+fn raw_children<'a, I: Input<'a>>(input: &mut I, mut pos: usize) -> Result<Vec<usize>, Error> {
+    let len = match raw_header(input, &mut pos)? {
+        (1, len) => len,
+        _ => return Ok(vec!()),
+    };
+    let mut res = vec!();
+    for _ in 0..len {
+        res.push(pos);
+        pos = raw_skip(input, pos)?;
+    }
+    Ok(res)
+}
+
+/// Returns the position of the child with index `idx` of the serialized
+/// node starting at `pos`, skipping only the children before it.
+fn raw_child<'a, I: Input<'a>>(input: &mut I, mut pos: usize, idx: usize) -> Result<Option<usize>, Error> {
+    match raw_header(input, &mut pos)? {
+        (1, len) if idx < len => {}
+        _ => return Ok(None),
+    }
+    for _ in 0..idx {
+        pos = raw_skip(input, pos)?;
+    }
+    Ok(Some(pos))
+}
+
+/// Appends the serialized node starting at `pos` and all of its
+/// descendants in depth-first order, reading the subtree once.
+fn raw_descendants<'a, I: Input<'a>>(input: &mut I, mut pos: usize, path: &[usize], res: &mut Vec<(Path, usize)>) -> Result<(), Error> {
+    let mut path = path.to_vec();
+    // number of children that are still to be read of the open inner nodes
+    let mut remaining: Vec<usize> = vec!();
+    loop {
+        res.push((path.clone(), pos));
+        match raw_header(input, &mut pos)? {
+            (0, len) => {
+                input.skip(pos, len)?;
+                pos = pos.checked_add(len).ok_or_else(|| Error::new(ErrorKind::LengthOverflow))?;
+            }
+            (1, 0) => {}
+            (1, len) => {
+                remaining.push(len);
+                path.push(0);
+                continue;
+            }
+            _ => return Err(Error::new(ErrorKind::InvalidNodeType)),
+        }
+        // the node is complete, continue with the next sibling of it or of
+        // its closest ancestor that has one
+        loop {
+            match remaining.last_mut() {
+                None => return Ok(()),
+                Some(1) => {
+                    remaining.pop();
+                    path.pop();
+                }
+                Some(count) => {
+                    *count -= 1;
+                    *path.last_mut().unwrap() += 1;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Selector::parse(s)
+    }
+}
+
+fn child_path(path: &[usize], idx: usize) -> Path {
+    let mut res = path.to_vec();
+    res.push(idx);
+    res
+}
+
+/// Collects `node` and all of its descendants with their paths in
+/// depth-first order.
+pub(crate) fn descendants<'a>(node: &'a Node, path: &mut Path, res: &mut Vec<(Path, &'a Node)>) {
+    res.push((path.clone(), node));
+    if let Node::Inner(nodes) = node {
+        for (idx, child) in nodes.iter().enumerate() {
+            path.push(idx);
+            descendants(child, path, res);
+            path.pop();
+        }
+    }
+}
+
+impl Node {
+    /// Returns the nodes selected by `selector` and their paths, see
+    /// `Selector`.
+    pub fn select(&self, selector: &str) -> Result<Vec<(Path, &Node)>, String> {
+        Ok(Selector::parse(selector)?.select(self))
+    }
+
+    /// Calls `f` with the nodes selected by `selector`, see
+    /// `Selector::select_mut`.
+    pub fn select_mut<F: FnMut(&Path, &mut Node)>(&mut self, selector: &str, f: F) -> Result<usize, String> {
+        Ok(Selector::parse(selector)?.select_mut(self, f))
+    }
+}
+
+
+#[test]
+fn selector() {
+    let node = crate::parse("(0x01 (0x02_03 (0x04)) ((0x05 0x06) 0x07_08_09))");
+    let paths = |s: &str| node.select(s).unwrap().into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+    assert_eq!(paths(""), vec!(Path::new()));
+    assert_eq!(node.select("1/0").unwrap(), vec!((vec!(1, 0), &Node::Leaf(vec!(2, 3)))));
+    assert_eq!(paths("*/*/0"), vec!(vec!(1, 1, 0), vec!(2, 0, 0)));
+    assert_eq!(paths("5"), Vec::<Path>::new());
+    assert_eq!(paths("0/0"), Vec::<Path>::new());
+    assert_eq!(paths("[leaf.len() == 1]"), vec!(vec!(0), vec!(1, 1, 0), vec!(2, 0, 0), vec!(2, 0, 1)));
+    assert_eq!(paths("2/[leaf]"), vec!(vec!(2, 0, 0), vec!(2, 0, 1), vec!(2, 1)));
+    assert_eq!(paths("*[inner][children.len() >= 2]"), vec!(vec!(1), vec!(2)));
+    assert_eq!(paths("**/**/[leaf == 0x0708_09]"), vec!(vec!(2, 1)));
+    assert_eq!(paths("* / [ leaf != 0x01 ] [leaf.len()<2]"), vec!(vec!(1, 1, 0), vec!(2, 0, 0), vec!(2, 0, 1)));
+    assert_eq!("**".parse::<Selector>().unwrap().select(&node).len(), node.node_count());
+
+    assert_eq!(Selector::parse("1//0"), Err("Expected step.".to_string()));
+    assert_eq!(Selector::parse("1x"), Err("Unexpected character 'x'.".to_string()));
+    assert_eq!(Selector::parse("[foo]"), Err("Unknown predicate 'foo'.".to_string()));
+    assert_eq!(Selector::parse("[leaf.len() 3]"), Err("Expected comparison operator.".to_string()));
+    assert_eq!(Selector::parse("[leaf < 0x01]"), Err("Leaves can only be compared using '==' and '!='.".to_string()));
+    assert_eq!(Selector::parse("[leaf"), Err("Expected ']'.".to_string()));
+}
+
+#[test]
+fn selector_mut() {
+    let mut node = crate::parse(r#"(("key" 0x01_02) ("pw" 0x04_05) ("pw" (0x06 0x07)) ("pw"))"#);
+    assert_eq!(node.select("*[tag == 0x7077]").unwrap().len(), 3);
+    assert_eq!(node.select("[tag != 0x7077]").unwrap().len(), 2);
+
+    // zero every leaf in the values of entries tagged "pw"
+    let count = node.select_mut("[tag == 0x7077]/1/[leaf]", |_, leaf| {
+        if let Node::Leaf(bytes) = leaf {
+            bytes.iter_mut().for_each(|b| *b = 0);
+        }
+    }).unwrap();
+    assert_eq!(count, 3);
+    assert_eq!(node, crate::parse(r#"(("key" 0x01_02) ("pw" 0x00_00) ("pw" (0x00 0x00)) ("pw"))"#));
+
+    // descendants are visited first, so replacing ancestors is fine
+    let mut visited = vec!();
+    node.select_mut("**/[inner]", |path, node| {
+        visited.push(path.clone());
+        *node = Node::EMPTY_LEAF;
+    }).unwrap();
+    assert_eq!(visited.first(), Some(&vec!(3)));
+    assert_eq!(visited.last(), Some(&vec!()));
+    assert_eq!(node, Node::EMPTY_LEAF);
+    assert_eq!(Selector::parse("[tag < 0x01]"), Err("Tags can only be compared using '==' and '!='.".to_string()));
+}
+
+#[test]
+fn selector_serialized() {
+    let node = crate::parse(r#"(0x01 (0x02_03 (0x04)) ("ab" (0x05 0x06) 0x07_08_09))"#);
+    let bytes = node.serialize();
+    for s in &["", "1/0", "*/*/0", "5", "[leaf.len() == 1]", "2/[leaf]", "*[children.len() >= 2]", "[tag == 0x6162]/*"] {
+        let selector = Selector::parse(s).unwrap();
+        let expected: Vec<_> = selector.select(&node).into_iter().map(|(path, node)| (path, NodeRef::from(node))).collect();
+        assert_eq!(selector.select_serialized(&bytes).unwrap(), expected, "{}", s);
+    }
+
+    // only the nodes on the way to the selected ones are read
+    let mut truncated = Node::Inner(vec!(Node::Leaf(vec!(1)), Node::Leaf(vec!(0; 100)))).serialize();
+    truncated.truncate(truncated.len() - 50);
+    let selected = Selector::parse("0").unwrap().select_serialized(&truncated).unwrap();
+    assert_eq!(selected, vec!((vec!(0), NodeRef::Leaf(&[1]))));
+    assert!(Selector::parse("1").unwrap().select_serialized(&truncated).is_err());
+    assert!(matches!(Selector::parse("").unwrap().select_serialized(b"BAUM2"), Err(e) if e.kind() == ErrorKind::InvalidMagicNumber));
+
+    // deep nesting doesn't exhaust the stack; built from bytes since
+    // dropping such a `Node` would
+    let mut deep = b"BAUM1".to_vec();
+    for _ in 0..200_000 {
+        deep.extend_from_slice(&[1, 1, 0, 0, 0, 0, 0, 0, 0]);
+    }
+    deep.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    for s in &["*[children.len() == 7]", "*[tag == 0x01]"] {
+        assert_eq!(Selector::parse(s).unwrap().select_serialized(&deep).unwrap(), vec!(), "{}", s);
+    }
+    // every descendant's path is collected, so keep this one shallower
+    let mut deep = deep[..5 + 9 * 2000].to_vec();
+    deep.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    let selected = Selector::parse("**[leaf]").unwrap().select_serialized(&deep).unwrap();
+    assert_eq!(selected, vec!((vec!(0; 2000), NodeRef::Leaf(&[]))));
+}
+
+#[test]
+fn selector_reader() {
+    use std::io::Cursor;
+
+    /// Counts the bytes read from the inner reader.
+    struct Counting<R>(R, usize);
+    impl<R: Read> Read for Counting<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.read(buf)?;
+            self.1 += n;
+            Ok(n)
+        }
+    }
+    impl<R: Seek> Seek for Counting<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    let node = crate::parse(r#"(0x01 (0x02_03 (0x04)) ("ab" (0x05 0x06) 0x07_08_09))"#);
+    let bytes = node.serialize();
+    for s in &["", "1/0", "*/*/0", "5", "[leaf.len() == 1]", "2/[leaf]", "*[children.len() >= 2]", "[tag == 0x6162]/*"] {
+        let selector = Selector::parse(s).unwrap();
+        let expected: Vec<_> = selector.select(&node).into_iter().map(|(path, node)| (path, node.clone())).collect();
+        assert_eq!(selector.select_reader(Cursor::new(&bytes)).unwrap(), expected, "{}", s);
+    }
+
+    // skipped subtrees aren't read
+    let large = Node::Inner(vec!(Node::Leaf(vec!(0; 100_000)), Node::Inner(vec!(Node::Leaf(vec!(0; 100_000)))), Node::Leaf(vec!(1))));
+    let mut reader = Counting(Cursor::new(large.serialize()), 0);
+    let selected = Selector::parse("2").unwrap().select_reader(&mut reader).unwrap();
+    assert_eq!(selected, vec!((vec!(2), Node::Leaf(vec!(1)))));
+    assert!(reader.1 < 100, "{}", reader.1);
+    assert!(matches!(Selector::parse("").unwrap().select_reader(Cursor::new(b"BAUM2")), Err(e) if e.kind() == ErrorKind::InvalidMagicNumber));
+}
+
+#[test]
+fn selector_indexed() {
+    let node = crate::parse(r#"(0x01 (0x02_03 (0x04)) ("ab" (0x05 0x06) 0x07_08_09 "abc") "ab")"#);
+    let bytes = node.serialize();
+    let index = QueryIndex::build(&bytes, 1).unwrap();
+    let selectors = ["", "1/0", "*/*/0", "5", "0/0", "2/*", "[leaf == 0x6162]", "2/[leaf == 0x6162]", "[leaf == 0x07]", "[tag == 0x6162]/*"];
+    for s in &selectors {
+        let selector = Selector::parse(s).unwrap();
+        assert_eq!(selector.select_indexed(&bytes, &index).unwrap(), selector.select_serialized(&bytes).unwrap(), "{}", s);
+    }
+    assert!(Selector::parse("").unwrap().select_indexed(&node.serialize()[..20], &index).is_err());
+}
@@ -0,0 +1,501 @@
+//! Serde serializer for the baum binary format.
+//!
+//! Values are mapped onto nodes as follows:
+//!
+//! - `bool` → leaf with a single byte (`0` or `1`)
+//! - integers and floats → little-endian leaf of the type's width
+//! - `char`, `str` → UTF-8 encoded leaf
+//! - bytes → leaf
+//! - `None`, `()`, unit structs → empty inner node
+//! - `Some(v)` → inner node with a single child `v`
+//! - newtype structs → the wrapped value
+//! - sequences, tuples, structs → inner node with one child per element/field
+//! - maps → inner node with one `(key value)` inner node per entry
+//! - enum variants → inner node whose first child is the variant index
+//!   (`u32` leaf), followed by the variant's fields
+
+use crate::Error;
+
+use serde::ser::{self, Serialize};
+
+use std::io::Write;
+
+/// Serializes `value` into a baum document (including the magic number).
+pub fn to_vec<T>(value: &T) -> Result<Vec<u8>, Error>
+where
+    T: ?Sized + Serialize
+{
+    let mut v = vec!();
+    to_writer(&mut v, value)?;
+    Ok(v)
+}
+
+/// Serializes `value` into a baum document (including the magic number)
+/// written to `writer`.
+pub fn to_writer<W, T>(mut writer: W, value: &T) -> Result<(), Error>
+where
+    W: Write,
+    T: ?Sized + Serialize
+{
+    writer.write_all("BAUM1".as_bytes())?;
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// Serializer writing the encoding of a single node (without magic number).
+pub struct Serializer<W> {
+    writer: W,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_leaf(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.writer.write_all(&[0])?;
+        self.writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn write_inner_header(&mut self, len: usize) -> Result<(), Error> {
+        self.writer.write_all(&[1])?;
+        self.writer.write_all(&(len as u64).to_le_bytes())?;
+        Ok(())
+    }
+
+    fn compound(&mut self, len: Option<usize>) -> Result<Compound<'_, W>, Error> {
+        let state = match len {
+            Some(len) => {
+                self.write_inner_header(len)?;
+                State::Direct { remaining: len }
+            }
+            // the number of children has to be known before writing the
+            // header, so children are buffered until the node is complete
+            None => State::Buffered { buf: Serializer::new(vec!()), count: 0 },
+        };
+        Ok(Compound { ser: self, state })
+    }
+
+    fn variant(&mut self, variant_index: u32, len: usize) -> Result<Compound<'_, W>, Error> {
+        let mut compound = self.compound(Some(len + 1))?;
+        compound.element(&variant_index)?;
+        Ok(compound)
+    }
+}
+
+/// State of an inner node that's currently serialized.
+pub struct Compound<'a, W> {
+    ser: &'a mut Serializer<W>,
+    state: State,
+}
+
+enum State {
+    Direct { remaining: usize },
+    Buffered { buf: Serializer<Vec<u8>>, count: usize },
+}
+
+impl<'a, W: Write> Compound<'a, W> {
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        match &mut self.state {
+            State::Direct { remaining } => {
+                if *remaining == 0 {
+                    return Err(Error::message("More elements than announced.".to_string()));
+                }
+                *remaining -= 1;
+                value.serialize(&mut *self.ser)
+            }
+            State::Buffered { buf, count } => {
+                *count += 1;
+                value.serialize(buf)
+            }
+        }
+    }
+
+    fn map_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        match &mut self.state {
+            State::Direct { remaining } => {
+                if *remaining == 0 {
+                    return Err(Error::message("More elements than announced.".to_string()));
+                }
+                *remaining -= 1;
+                self.ser.write_inner_header(2)?;
+                key.serialize(&mut *self.ser)
+            }
+            State::Buffered { buf, count } => {
+                *count += 1;
+                buf.write_inner_header(2)?;
+                key.serialize(buf)
+            }
+        }
+    }
+
+    fn map_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        match &mut self.state {
+            State::Direct { .. } => value.serialize(&mut *self.ser),
+            State::Buffered { buf, .. } => value.serialize(buf),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self.state {
+            State::Direct { remaining: 0 } => Ok(()),
+            State::Direct { .. } => Err(Error::message("Fewer elements than announced.".to_string())),
+            State::Buffered { buf, count } => {
+                self.ser.write_inner_header(count)?;
+                self.ser.writer.write_all(&buf.writer)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a, W>;
+    type SerializeTuple = Compound<'a, W>;
+    type SerializeTupleStruct = Compound<'a, W>;
+    type SerializeTupleVariant = Compound<'a, W>;
+    type SerializeMap = Compound<'a, W>;
+    type SerializeStruct = Compound<'a, W>;
+    type SerializeStructVariant = Compound<'a, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.write_leaf(&[v as u8])
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.write_leaf(&v.to_le_bytes())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        let mut buf = [0; 4];
+        self.write_leaf(v.encode_utf8(&mut buf).as_bytes())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write_leaf(v.as_bytes())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.write_leaf(v)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.write_inner_header(0)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        self.write_inner_header(1)?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.write_inner_header(0)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.write_inner_header(0)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, variant_index: u32, _variant: &'static str) -> Result<(), Error> {
+        self.variant(variant_index, 0)?.finish()
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let mut compound = self.variant(variant_index, 1)?;
+        compound.element(value)?;
+        compound.finish()
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.compound(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.compound(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        self.compound(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.variant(variant_index, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.compound(len)
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct, Error> {
+        self.compound(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.variant(variant_index, len)
+    }
+}
+
+impl<'a, W: Write> ser::SerializeSeq for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTuple for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleStruct for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeTupleVariant for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeMap for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.map_key(key)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.map_value(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStruct for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a, W: Write> ser::SerializeStructVariant for Compound<'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, _key: &'static str, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        Error::message(msg.to_string())
+    }
+}
+
+
+#[cfg(test)]
+use crate::ErrorKind;
+
+#[cfg(test)]
+use serde::ser::{SerializeMap, SerializeSeq};
+
+#[test]
+fn ser_unknown_lengths() {
+    // filtered iterators don't announce their length, so the children are
+    // buffered until the node is complete
+    struct Evens(u8);
+    impl Serialize for Evens {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.collect_seq((0..self.0).filter(|x| x % 2 == 0))
+        }
+    }
+    struct EvenSquares(u8);
+    impl Serialize for EvenSquares {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.collect_map((0..self.0).filter(|x| x % 2 == 0).map(|x| (x, Evens(x))))
+        }
+    }
+    assert_eq!(to_vec(&Evens(7)).unwrap(), crate::parse("(0x00 0x02 0x04 0x06)").serialize());
+    assert_eq!(to_vec(&Evens(0)).unwrap(), crate::parse("()").serialize());
+    assert_eq!(
+        to_vec(&EvenSquares(5)).unwrap(),
+        crate::parse("((0x00 ()) (0x02 (0x00)) (0x04 (0x00 0x02)))").serialize(),
+    );
+}
+
+#[test]
+fn ser_length_mismatch() {
+    struct Seq { announced: usize, actual: u8 }
+    impl Serialize for Seq {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            let mut seq = s.serialize_seq(Some(self.announced))?;
+            for i in 0..self.actual {
+                seq.serialize_element(&i)?;
+            }
+            seq.end()
+        }
+    }
+    struct Map { announced: usize, actual: u8 }
+    impl Serialize for Map {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            let mut map = s.serialize_map(Some(self.announced))?;
+            for i in 0..self.actual {
+                map.serialize_entry(&i, &i)?;
+            }
+            map.end()
+        }
+    }
+    let message = |e: Error| (e.kind(), e.to_string());
+    assert_eq!(to_vec(&Seq { announced: 2, actual: 2 }).unwrap(), crate::parse("(0x00 0x01)").serialize());
+    assert_eq!(to_vec(&Seq { announced: 2, actual: 3 }).map_err(message),
+        Err((ErrorKind::Message, "More elements than announced.".to_string())));
+    assert_eq!(to_vec(&Seq { announced: 2, actual: 1 }).map_err(message),
+        Err((ErrorKind::Message, "Fewer elements than announced.".to_string())));
+    assert_eq!(to_vec(&Map { announced: 1, actual: 1 }).unwrap(), crate::parse("((0x00 0x00))").serialize());
+    assert_eq!(to_vec(&Map { announced: 1, actual: 2 }).map_err(message),
+        Err((ErrorKind::Message, "More elements than announced.".to_string())));
+    assert_eq!(to_vec(&Map { announced: 1, actual: 0 }).map_err(message),
+        Err((ErrorKind::Message, "Fewer elements than announced.".to_string())));
+}
+
+#[test]
+fn ser_errors() {
+    // types that can't be represented report a custom error
+    struct Unsupported;
+    impl Serialize for Unsupported {
+        fn serialize<S: serde::Serializer>(&self, _s: S) -> Result<S::Ok, S::Error> {
+            Err(ser::Error::custom("unsupported type"))
+        }
+    }
+    let err = to_vec(&(1u8, vec!(Unsupported))).unwrap_err();
+    assert_eq!((err.kind(), err.to_string()), (ErrorKind::Message, "unsupported type".to_string()));
+
+    // errors of the writer are passed on
+    struct Failing;
+    impl Write for Failing {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::ErrorKind::BrokenPipe.into())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    assert!(matches!(to_writer(Failing, &1u8), Err(e) if e.kind() == ErrorKind::Io));
+}
@@ -0,0 +1,151 @@
+use crate::{FromBaum, FromNodeError, Node, ToBaum};
+
+/// Set of byte strings. It's represented as an inner node of leaves sorted
+/// in ascending order without duplicates, so equal sets have equal nodes.
+#[derive(Clone, Eq, PartialEq, Hash, Default, Debug)]
+pub struct BaumSet {
+    items: Vec<Vec<u8>>,
+}
+
+impl BaumSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    fn find(&self, item: &[u8]) -> Result<usize, usize> {
+        self.items.binary_search_by(|i| i.as_slice().cmp(item))
+    }
+
+    pub fn contains(&self, item: &[u8]) -> bool {
+        self.find(item).is_ok()
+    }
+
+    /// Returns `false` if the set already contained `item`.
+    pub fn insert(&mut self, item: Vec<u8>) -> bool {
+        match self.find(&item) {
+            Ok(_) => false,
+            Err(idx) => {
+                self.items.insert(idx, item);
+                true
+            }
+        }
+    }
+
+    /// Returns `false` if the set didn't contain `item`.
+    pub fn remove(&mut self, item: &[u8]) -> bool {
+        match self.find(item) {
+            Ok(idx) => {
+                self.items.remove(idx);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the items in ascending order.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &[u8]> {
+        self.items.iter().map(Vec::as_slice)
+    }
+
+    pub fn union(&self, other: &BaumSet) -> BaumSet {
+        self.merge(other, true)
+    }
+
+    pub fn intersection(&self, other: &BaumSet) -> BaumSet {
+        self.merge(other, false)
+    }
+
+    /// Merges both sorted lists, keeping items that are only contained in
+    /// one of them if `union` is set.
+    fn merge(&self, other: &BaumSet, union: bool) -> BaumSet {
+        let (mut a, mut b) = (self.items.iter().peekable(), other.items.iter().peekable());
+        let mut items = vec!();
+        loop {
+            let item = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) if x == y => {
+                    b.next();
+                    a.next()
+                }
+                (Some(x), Some(y)) if x < y => a.next().filter(|_| union),
+                (Some(_), Some(_)) => b.next().filter(|_| union),
+                (Some(_), None) => a.next().filter(|_| union),
+                (None, Some(_)) => b.next().filter(|_| union),
+                (None, None) => break,
+            };
+            items.extend(item.cloned());
+        }
+        BaumSet { items }
+    }
+}
+
+impl std::iter::FromIterator<Vec<u8>> for BaumSet {
+    fn from_iter<I: IntoIterator<Item = Vec<u8>>>(iter: I) -> Self {
+        let mut items: Vec<_> = iter.into_iter().collect();
+        items.sort();
+        items.dedup();
+        BaumSet { items }
+    }
+}
+
+impl ToBaum for BaumSet {
+    fn to_node(&self) -> Node {
+        Node::Inner(self.items.iter().cloned().map(Node::Leaf).collect())
+    }
+}
+
+/// Fails with `FromNodeError::InvalidValue` for items that aren't sorted or
+/// unique.
+impl FromBaum for BaumSet {
+    fn from_node(node: &Node) -> Result<Self, FromNodeError> {
+        let nodes = match node {
+            Node::Inner(nodes) => nodes,
+            Node::Leaf(_) => return Err(FromNodeError::ExpectedInner(vec!())),
+        };
+        let mut items: Vec<Vec<u8>> = Vec::with_capacity(nodes.len());
+        for (idx, item) in nodes.iter().enumerate() {
+            let item = match item {
+                Node::Leaf(bytes) => bytes,
+                Node::Inner(_) => return Err(FromNodeError::ExpectedLeaf(vec!(idx))),
+            };
+            if items.last().is_some_and(|last| last >= item) {
+                return Err(FromNodeError::InvalidValue(vec!(idx)));
+            }
+            items.push(item.clone());
+        }
+        Ok(BaumSet { items })
+    }
+}
+
+
+#[test]
+fn baum_set() {
+    let mut set = BaumSet::new();
+    assert!(set.insert(vec!(2)));
+    assert!(set.insert(vec!(1)));
+    assert!(!set.insert(vec!(2)));
+    assert!(set.insert(vec!(3)));
+    assert!(set.remove(&[3]));
+    assert!(!set.remove(&[3]));
+    assert!(set.contains(&[1]) && !set.contains(&[3]));
+    assert_eq!(set.len(), 2);
+
+    let other: BaumSet = vec!(vec!(4), vec!(2), vec!(0), vec!(4)).into_iter().collect();
+    assert_eq!(other.iter().collect::<Vec<_>>(), vec!(&[0][..], &[2], &[4]));
+    assert_eq!(set.union(&other).iter().collect::<Vec<_>>(), vec!(&[0][..], &[1], &[2], &[4]));
+    assert_eq!(set.intersection(&other).iter().collect::<Vec<_>>(), vec!(&[2][..]));
+    assert!(set.intersection(&BaumSet::new()).is_empty());
+
+    let node = set.to_node();
+    assert_eq!(node.to_string(), "(0x01 0x02)");
+    assert_eq!(BaumSet::from_node(&node), Ok(set));
+    assert_eq!(BaumSet::from_node(&Node::Inner(vec!(Node::Leaf(vec!(2)), Node::Leaf(vec!(2))))), Err(FromNodeError::InvalidValue(vec!(1))));
+    assert_eq!(BaumSet::from_node(&Node::Inner(vec!(Node::Inner(vec!())))), Err(FromNodeError::ExpectedLeaf(vec!(0))));
+}
@@ -0,0 +1,309 @@
+//! Content-addressed storage of trees.
+//!
+//! Every node is stored as a separate object identified by the SHA-256 hash
+//! of its encoding. Leaves are encoded as `0x00` followed by their bytes,
+//! inner nodes as `0x01` followed by the hashes of their children. Equal
+//! subtrees therefore have equal hashes and are only stored once.
+//!
+//! Objects are reference-counted: `BaumStore::put` adds a reference to the
+//! root it returns and `BaumStore::release` removes it again. Objects that
+//! are no longer referenced by a root or another object are deleted by
+//! `BaumStore::gc`.
+//!
+//! Objects and reference counts are kept by a `Backend`, which is in memory
+//! by default. `DirBackend` stores them in a directory, `SledBackend` and
+//! `SqliteBackend` in databases (features `sled` and `rusqlite`).
+
+use crate::{HexCase, Node};
+
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+
+mod backend;
+pub use backend::{Backend, DirBackend, MemoryBackend};
+#[cfg(feature = "sled")]
+pub use backend::SledBackend;
+#[cfg(feature = "rusqlite")]
+pub use backend::SqliteBackend;
+
+pub type Hash = [u8; 32];
+
+const HASH_LEN: usize = 32;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    MissingObject(Hash),
+    /// An object whose encoding is malformed.
+    InvalidObject(Hash),
+    /// `release` was called more often than the object was referenced.
+    NotReferenced(Hash),
+    UnknownRef(String),
+    /// An object that isn't a commit was used as one.
+    InvalidCommit(Hash),
+    /// Error reported by the storage backend.
+    Backend(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::MissingObject(hash) => write!(f, "Missing object {}.", hex(hash)),
+            Error::InvalidObject(hash) => write!(f, "Object {} is malformed.", hex(hash)),
+            Error::NotReferenced(hash) => write!(f, "Object {} isn't referenced.", hex(hash)),
+            Error::UnknownRef(name) => write!(f, "Unknown ref {:?}.", name),
+            Error::InvalidCommit(hash) => write!(f, "Object {} isn't a commit.", hex(hash)),
+            Error::Backend(msg) => write!(f, "Storage backend error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error { }
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Backend(err.to_string())
+    }
+}
+
+pub(crate) fn hex(hash: &Hash) -> String {
+    let mut s = String::new();
+    crate::hex::encode_into(hash, HexCase::Lower, None, &mut s);
+    s
+}
+
+/// Decoded object, see the module docs.
+pub(crate) enum Decoded {
+    Leaf(Vec<u8>),
+    Inner(Vec<Hash>),
+}
+
+fn decode(hash: &Hash, mut data: Vec<u8>) -> Result<Decoded, Error> {
+    match data.first() {
+        Some(0) => {
+            data.remove(0);
+            Ok(Decoded::Leaf(data))
+        }
+        Some(1) if (data.len() - 1) % HASH_LEN == 0 => {
+            Ok(Decoded::Inner(data[1..].chunks(HASH_LEN).map(|h| h.try_into().unwrap()).collect()))
+        }
+        _ => Err(Error::InvalidObject(*hash)),
+    }
+}
+
+/// Store of trees, keeping its objects in a `Backend`.
+#[derive(Default)]
+pub struct BaumStore<B = MemoryBackend> {
+    backend: B,
+}
+
+impl BaumStore {
+    /// Creates a store keeping its objects in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<B: Backend> BaumStore<B> {
+    pub fn with_backend(backend: B) -> Self {
+        BaumStore { backend }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn into_backend(self) -> B {
+        self.backend
+    }
+
+    /// Number of stored objects, i.e. distinct subtrees.
+    pub fn len(&self) -> Result<usize, Error> {
+        Ok(self.backend.hashes()?.len())
+    }
+
+    pub fn is_empty(&self) -> Result<bool, Error> {
+        Ok(self.len()? == 0)
+    }
+
+    pub fn contains(&self, hash: &Hash) -> Result<bool, Error> {
+        Ok(self.backend.refs(hash)?.is_some())
+    }
+
+    fn refs(&self, hash: &Hash) -> Result<usize, Error> {
+        self.backend.refs(hash)?.ok_or(Error::MissingObject(*hash))
+    }
+
+    /// Stores `node` and all of its subtrees and adds a reference to it.
+    pub fn put(&mut self, node: &Node) -> Result<Hash, Error> {
+        let data = match node {
+            Node::Leaf(bytes) => {
+                let mut data = Vec::with_capacity(bytes.len() + 1);
+                data.push(0);
+                data.extend_from_slice(bytes);
+                data
+            }
+            Node::Inner(nodes) => {
+                let mut data = Vec::with_capacity(nodes.len() * HASH_LEN + 1);
+                data.push(1);
+                for node in nodes {
+                    data.extend_from_slice(&self.put(node)?);
+                }
+                data
+            }
+        };
+        self.insert(data)
+    }
+
+    /// Stores an inner node with the given, already stored children and adds
+    /// a reference to it.
+    pub(crate) fn put_inner(&mut self, children: &[Hash]) -> Result<Hash, Error> {
+        for child in children {
+            self.refs(child)?;
+        }
+        let mut data = Vec::with_capacity(children.len() * HASH_LEN + 1);
+        data.push(1);
+        for child in children {
+            self.retain(child)?;
+            data.extend_from_slice(child);
+        }
+        self.insert(data)
+    }
+
+    /// Inserts an object whose children have been referenced for it already.
+    fn insert(&mut self, data: Vec<u8>) -> Result<Hash, Error> {
+        let hash: Hash = Sha256::digest(&data).into();
+        match self.backend.refs(&hash)? {
+            Some(refs) => {
+                self.backend.set_refs(&hash, refs + 1)?;
+                // the existing object already references the children
+                if let Decoded::Inner(children) = decode(&hash, data)? {
+                    for child in children {
+                        let refs = self.refs(&child)?;
+                        self.backend.set_refs(&child, refs - 1)?;
+                    }
+                }
+            }
+            None => self.backend.insert(&hash, &data, 1)?,
+        }
+        Ok(hash)
+    }
+
+    pub(crate) fn read(&self, hash: &Hash) -> Result<Decoded, Error> {
+        decode(hash, self.backend.get(hash)?.ok_or(Error::MissingObject(*hash))?)
+    }
+
+    pub fn get(&self, hash: &Hash) -> Result<Node, Error> {
+        match self.read(hash)? {
+            Decoded::Leaf(bytes) => Ok(Node::Leaf(bytes)),
+            Decoded::Inner(children) => Ok(Node::Inner(children.iter().map(|h| self.get(h)).collect::<Result<_, _>>()?)),
+        }
+    }
+
+    /// Adds another reference to a stored object.
+    pub fn retain(&mut self, hash: &Hash) -> Result<(), Error> {
+        let refs = self.refs(hash)?;
+        self.backend.set_refs(hash, refs + 1)
+    }
+
+    /// Removes a reference added by `put` or `retain`. The object is deleted
+    /// by the next `gc` if it isn't referenced anymore.
+    pub fn release(&mut self, hash: &Hash) -> Result<(), Error> {
+        match self.refs(hash)?.checked_sub(1) {
+            Some(refs) => self.backend.set_refs(hash, refs),
+            None => Err(Error::NotReferenced(*hash)),
+        }
+    }
+
+    /// Deletes all objects that aren't referenced anymore and returns their
+    /// number.
+    pub fn gc(&mut self) -> Result<usize, Error> {
+        let mut garbage = vec!();
+        for hash in self.backend.hashes()? {
+            if self.refs(&hash)? == 0 {
+                garbage.push(hash);
+            }
+        }
+        let mut count = 0;
+        while let Some(hash) = garbage.pop() {
+            let decoded = self.read(&hash);
+            self.backend.remove(&hash)?;
+            count += 1;
+            if let Ok(Decoded::Inner(children)) = decoded {
+                for child in children {
+                    // children already counted as garbage have no references left
+                    match self.backend.refs(&child)? {
+                        Some(refs) if refs > 0 => {
+                            self.backend.set_refs(&child, refs - 1)?;
+                            if refs == 1 {
+                                garbage.push(child);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+fn check_store<B: Backend>(mut store: BaumStore<B>) {
+    let a = crate::parse("(0x01 (0x02 0x03) (0x02 0x03))");
+    let b = crate::parse("((0x02 0x03) 0x04)");
+    let hash_a = store.put(&a).unwrap();
+    // 0x01, 0x02, 0x03, (0x02 0x03) and a
+    assert_eq!(store.len(), Ok(5));
+    let hash_b = store.put(&b).unwrap();
+    assert_eq!(store.len(), Ok(7));
+    assert_eq!(store.put(&a), Ok(hash_a));
+    assert_eq!(store.len(), Ok(7));
+    assert_eq!(store.get(&hash_a), Ok(a.clone()));
+    assert_eq!(store.get(&hash_b), Ok(b));
+
+    store.release(&hash_b).unwrap();
+    assert_eq!(store.gc(), Ok(2));
+    assert_eq!(store.contains(&hash_b), Ok(false));
+    assert_eq!(store.get(&hash_b), Err(Error::MissingObject(hash_b)));
+    store.release(&hash_a).unwrap();
+    assert_eq!(store.gc(), Ok(0));
+    assert_eq!(store.get(&hash_a), Ok(a));
+    store.release(&hash_a).unwrap();
+    assert_eq!(store.release(&hash_a), Err(Error::NotReferenced(hash_a)));
+    assert_eq!(store.gc(), Ok(5));
+    assert_eq!(store.is_empty(), Ok(true));
+
+    // hash of the empty leaf: sha256(0x00)
+    assert_eq!(hex(&store.put(&Node::Leaf(vec!())).unwrap()), "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d");
+}
+
+#[test]
+fn store() {
+    check_store(BaumStore::new());
+}
+
+#[test]
+fn dir_store() {
+    let dir = std::env::temp_dir().join(format!("baum_store_{}", std::process::id()));
+    check_store(BaumStore::with_backend(DirBackend::new(&dir).unwrap()));
+    // objects survive reopening the directory
+    let hash = BaumStore::with_backend(DirBackend::new(&dir).unwrap()).put(&Node::Inner(vec!())).unwrap();
+    let store = BaumStore::with_backend(DirBackend::new(&dir).unwrap());
+    assert_eq!(store.get(&hash), Ok(Node::Inner(vec!())));
+    assert_eq!(store.len(), Ok(2));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "sled")]
+#[test]
+fn sled_store() {
+    let db = sled::Config::new().temporary(true).open().unwrap();
+    check_store(BaumStore::with_backend(SledBackend::new(&db).unwrap()));
+}
+
+#[cfg(feature = "rusqlite")]
+#[test]
+fn sqlite_store() {
+    let conn = rusqlite::Connection::open_in_memory().unwrap();
+    check_store(BaumStore::with_backend(SqliteBackend::new(conn).unwrap()));
+}
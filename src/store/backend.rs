@@ -0,0 +1,272 @@
+use super::{hex, Error, Hash};
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+
+/// Storage of the encoded objects of a `BaumStore` and their reference
+/// counts. The store implements hashing, deduplication and garbage
+/// collection on top of it.
+pub trait Backend {
+    /// Returns the encoding of the object, if it's stored.
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error>;
+    /// Returns the reference count of the object, if it's stored.
+    fn refs(&self, hash: &Hash) -> Result<Option<usize>, Error>;
+    fn insert(&mut self, hash: &Hash, data: &[u8], refs: usize) -> Result<(), Error>;
+    /// Updates the reference count of a stored object.
+    fn set_refs(&mut self, hash: &Hash, refs: usize) -> Result<(), Error>;
+    fn remove(&mut self, hash: &Hash) -> Result<(), Error>;
+    /// Returns the hashes of all stored objects.
+    fn hashes(&self) -> Result<Vec<Hash>, Error>;
+}
+
+#[derive(Default)]
+pub struct MemoryBackend {
+    objects: HashMap<Hash, (Vec<u8>, usize)>,
+}
+
+impl Backend for MemoryBackend {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.objects.get(hash).map(|(data, _)| data.clone()))
+    }
+
+    fn refs(&self, hash: &Hash) -> Result<Option<usize>, Error> {
+        Ok(self.objects.get(hash).map(|(_, refs)| *refs))
+    }
+
+    fn insert(&mut self, hash: &Hash, data: &[u8], refs: usize) -> Result<(), Error> {
+        self.objects.insert(*hash, (data.to_vec(), refs));
+        Ok(())
+    }
+
+    fn set_refs(&mut self, hash: &Hash, refs: usize) -> Result<(), Error> {
+        let object = self.objects.get_mut(hash).ok_or(Error::MissingObject(*hash))?;
+        object.1 = refs;
+        Ok(())
+    }
+
+    fn remove(&mut self, hash: &Hash) -> Result<(), Error> {
+        self.objects.remove(hash);
+        Ok(())
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>, Error> {
+        Ok(self.objects.keys().copied().collect())
+    }
+}
+
+/// Stores every object in a file named after its hash, in subdirectories
+/// named after the hash's first byte like git's loose objects. Reference
+/// counts are stored next to them in files with the extension `refs`.
+pub struct DirBackend {
+    dir: PathBuf,
+}
+
+impl DirBackend {
+    /// Uses the directory at `dir`, creating it if necessary.
+    pub fn new(dir: &Path) -> Result<Self, Error> {
+        std::fs::create_dir_all(dir)?;
+        Ok(DirBackend { dir: dir.to_path_buf() })
+    }
+
+    fn object_path(&self, hash: &Hash) -> PathBuf {
+        let name = hex(hash);
+        self.dir.join(&name[..2]).join(&name[2..])
+    }
+
+    fn refs_path(&self, hash: &Hash) -> PathBuf {
+        self.object_path(hash).with_extension("refs")
+    }
+}
+
+/// Reads a file, returning `None` if it doesn't exist.
+fn read_file(path: &Path) -> Result<Option<Vec<u8>>, Error> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn decode_refs(bytes: &[u8]) -> Result<usize, Error> {
+    let refs: [u8; 8] = bytes.try_into().map_err(|_| Error::Backend("Invalid reference count.".to_string()))?;
+    Ok(u64::from_le_bytes(refs) as usize)
+}
+
+fn decode_hash(name: &str) -> Option<Hash> {
+    let digits = name.chars().map(crate::hex::digit).collect::<Option<Vec<u8>>>()?;
+    match digits.len() == 2 * super::HASH_LEN {
+        true => crate::hex::pack(&digits).try_into().ok(),
+        false => None,
+    }
+}
+
+impl Backend for DirBackend {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error> {
+        read_file(&self.object_path(hash))
+    }
+
+    fn refs(&self, hash: &Hash) -> Result<Option<usize>, Error> {
+        read_file(&self.refs_path(hash))?.map(|bytes| decode_refs(&bytes)).transpose()
+    }
+
+    fn insert(&mut self, hash: &Hash, data: &[u8], refs: usize) -> Result<(), Error> {
+        let path = self.object_path(hash);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        std::fs::write(&path, data)?;
+        // the reference count is written last, since it marks the object as stored
+        self.set_refs(hash, refs)
+    }
+
+    fn set_refs(&mut self, hash: &Hash, refs: usize) -> Result<(), Error> {
+        Ok(std::fs::write(self.refs_path(hash), (refs as u64).to_le_bytes())?)
+    }
+
+    fn remove(&mut self, hash: &Hash) -> Result<(), Error> {
+        std::fs::remove_file(self.refs_path(hash))?;
+        Ok(std::fs::remove_file(self.object_path(hash))?)
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>, Error> {
+        let mut res = vec!();
+        for dir in std::fs::read_dir(&self.dir)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue;
+            }
+            let prefix = dir.file_name().to_string_lossy().into_owned();
+            for file in std::fs::read_dir(dir.path())? {
+                let name = file?.file_name().to_string_lossy().into_owned();
+                if let Some(hash) = name.strip_suffix(".refs").and_then(|rest| decode_hash(&(prefix.clone() + rest))) {
+                    res.push(hash);
+                }
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// Stores objects and reference counts in two trees of a sled database.
+#[cfg(feature = "sled")]
+pub struct SledBackend {
+    objects: sled::Tree,
+    refs: sled::Tree,
+}
+
+#[cfg(feature = "sled")]
+impl From<sled::Error> for Error {
+    fn from(err: sled::Error) -> Self {
+        Error::Backend(err.to_string())
+    }
+}
+
+#[cfg(feature = "sled")]
+impl SledBackend {
+    /// Uses the trees `baum_objects` and `baum_refs` of `db`.
+    pub fn new(db: &sled::Db) -> Result<Self, Error> {
+        Ok(SledBackend { objects: db.open_tree("baum_objects")?, refs: db.open_tree("baum_refs")? })
+    }
+}
+
+#[cfg(feature = "sled")]
+impl Backend for SledBackend {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.objects.get(hash)?.map(|data| data.to_vec()))
+    }
+
+    fn refs(&self, hash: &Hash) -> Result<Option<usize>, Error> {
+        self.refs.get(hash)?.map(|bytes| decode_refs(&bytes)).transpose()
+    }
+
+    fn insert(&mut self, hash: &Hash, data: &[u8], refs: usize) -> Result<(), Error> {
+        self.objects.insert(hash, data)?;
+        self.set_refs(hash, refs)
+    }
+
+    fn set_refs(&mut self, hash: &Hash, refs: usize) -> Result<(), Error> {
+        self.refs.insert(hash, &(refs as u64).to_le_bytes())?;
+        Ok(())
+    }
+
+    fn remove(&mut self, hash: &Hash) -> Result<(), Error> {
+        self.refs.remove(hash)?;
+        self.objects.remove(hash)?;
+        Ok(())
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>, Error> {
+        self.refs.iter().keys().map(|key| {
+            let key = key?;
+            key.as_ref().try_into().map_err(|_| Error::Backend("Invalid key.".to_string()))
+        }).collect()
+    }
+}
+
+/// Stores objects in the table `baum_objects` of an SQLite database.
+#[cfg(feature = "rusqlite")]
+pub struct SqliteBackend {
+    conn: rusqlite::Connection,
+}
+
+#[cfg(feature = "rusqlite")]
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Backend(err.to_string())
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl SqliteBackend {
+    /// Uses the database of `conn`, creating the table if necessary.
+    pub fn new(conn: rusqlite::Connection) -> Result<Self, Error> {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS baum_objects (hash BLOB PRIMARY KEY, data BLOB NOT NULL, refs INTEGER NOT NULL)",
+            [],
+        )?;
+        Ok(SqliteBackend { conn })
+    }
+
+    pub fn into_connection(self) -> rusqlite::Connection {
+        self.conn
+    }
+}
+
+#[cfg(feature = "rusqlite")]
+impl Backend for SqliteBackend {
+    fn get(&self, hash: &Hash) -> Result<Option<Vec<u8>>, Error> {
+        use rusqlite::OptionalExtension;
+        Ok(self.conn.query_row("SELECT data FROM baum_objects WHERE hash = ?1", [&hash[..]], |row| row.get(0)).optional()?)
+    }
+
+    fn refs(&self, hash: &Hash) -> Result<Option<usize>, Error> {
+        use rusqlite::OptionalExtension;
+        let refs: Option<i64> = self.conn.query_row("SELECT refs FROM baum_objects WHERE hash = ?1", [&hash[..]], |row| row.get(0)).optional()?;
+        Ok(refs.map(|refs| refs as usize))
+    }
+
+    fn insert(&mut self, hash: &Hash, data: &[u8], refs: usize) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO baum_objects (hash, data, refs) VALUES (?1, ?2, ?3)",
+            rusqlite::params![&hash[..], data, refs as i64],
+        )?;
+        Ok(())
+    }
+
+    fn set_refs(&mut self, hash: &Hash, refs: usize) -> Result<(), Error> {
+        match self.conn.execute("UPDATE baum_objects SET refs = ?1 WHERE hash = ?2", rusqlite::params![refs as i64, &hash[..]])? {
+            0 => Err(Error::MissingObject(*hash)),
+            _ => Ok(()),
+        }
+    }
+
+    fn remove(&mut self, hash: &Hash) -> Result<(), Error> {
+        self.conn.execute("DELETE FROM baum_objects WHERE hash = ?1", [&hash[..]])?;
+        Ok(())
+    }
+
+    fn hashes(&self) -> Result<Vec<Hash>, Error> {
+        let mut stmt = self.conn.prepare("SELECT hash FROM baum_objects")?;
+        let hashes = stmt.query_map([], |row| row.get::<_, Vec<u8>>(0))?;
+        hashes.map(|hash| hash?.as_slice().try_into().map_err(|_| Error::Backend("Invalid hash.".to_string()))).collect()
+    }
+}
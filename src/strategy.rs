@@ -0,0 +1,43 @@
+//! `proptest` strategies for trees (feature `proptest`).
+
+use crate::Node;
+#[cfg(test)]
+use crate::Limits;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use std::convert::TryFrom;
+
+/// Maximum number of children of generated inner nodes.
+const MAX_CHILDREN: usize = 8;
+
+/// Generates arbitrary trees with inner nodes nested at most `max_depth`
+/// levels deep and leaves of at most `max_leaf_len` bytes.
+///
+/// A `max_depth` of 0 generates leaves only. Shrinking moves towards
+/// smaller trees and shorter leaves.
+pub fn any_node(max_depth: usize, max_leaf_len: usize) -> impl Strategy<Value = Node> {
+    let leaf = vec(any::<u8>(), 0..=max_leaf_len).prop_map(Node::Leaf);
+    let depth = u32::try_from(max_depth).unwrap_or(u32::MAX);
+    // the desired size keeps deep configurations from generating huge trees
+    leaf.prop_recursive(depth, 256, MAX_CHILDREN as u32, |inner| {
+        vec(inner, 0..=MAX_CHILDREN).prop_map(Node::Inner)
+    })
+}
+
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn any_node_bounds(node in any_node(3, 5)) {
+        let limits = Limits { max_depth: Some(3), max_children: Some(8), max_leaf_len: Some(5), ..Limits::default() };
+        prop_assert!(node.check_limits(&limits).is_ok());
+        prop_assert_eq!(Node::deserialize(&node.serialize()).unwrap(), node);
+    }
+
+    #[test]
+    fn any_node_leaves_only(node in any_node(0, 2)) {
+        prop_assert!(matches!(node, Node::Leaf(bytes) if bytes.len() <= 2));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{Node, Path};
+
+use std::collections::HashMap;
+
+/// Flattened representation of a tree. Every leaf is stored with its path
+/// and bytes, every inner node with its path and number of children.
+#[derive(Clone, Eq, PartialEq, Default, Debug)]
+pub struct Table {
+    pub leaves: Vec<(Path, Vec<u8>)>,
+    pub inners: Vec<(Path, usize)>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TableError {
+    /// There's no entry for a node that's expected to exist.
+    Missing(Path),
+    /// There are multiple entries with the same path.
+    Duplicate(Path),
+    /// An entry that isn't part of the tree, e.g. because its index exceeds
+    /// the number of children of its parent.
+    Unreachable(Path),
+}
+
+impl std::fmt::Display for TableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TableError::Missing(p) => write!(f, "Missing node at path {:?}.", p),
+            TableError::Duplicate(p) => write!(f, "Duplicate node at path {:?}.", p),
+            TableError::Unreachable(p) => write!(f, "Node at path {:?} isn't part of the tree.", p),
+        }
+    }
+}
+
+impl std::error::Error for TableError { }
+
+enum Entry {
+    Leaf(Vec<u8>),
+    Inner(usize),
+}
+
+impl Node {
+    /// Flattens the tree into a table. Entries are in pre-order.
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::default();
+        self._to_table(&mut vec!(), &mut table);
+        table
+    }
+
+    fn _to_table(&self, path: &mut Path, table: &mut Table) {
+        match self {
+            Node::Leaf(bytes) => table.leaves.push((path.clone(), bytes.clone())),
+            Node::Inner(nodes) => {
+                table.inners.push((path.clone(), nodes.len()));
+                for (idx, node) in nodes.iter().enumerate() {
+                    path.push(idx);
+                    node._to_table(path, table);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    /// Builds a tree from a table. The order of the entries doesn't matter.
+    pub fn from_table(table: Table) -> Result<Node, TableError> {
+        let mut entries = HashMap::new();
+        let leaves = table.leaves.into_iter().map(|(p, b)| (p, Entry::Leaf(b)));
+        let inners = table.inners.into_iter().map(|(p, n)| (p, Entry::Inner(n)));
+        for (path, entry) in leaves.chain(inners) {
+            if entries.contains_key(&path) {
+                return Err(TableError::Duplicate(path));
+            }
+            entries.insert(path, entry);
+        }
+
+        let root = build(&mut entries, &mut vec!())?;
+        match entries.into_keys().min() {
+            Some(path) => Err(TableError::Unreachable(path)),
+            None => Ok(root),
+        }
+    }
+}
+
+fn build(entries: &mut HashMap<Path, Entry>, path: &mut Path) -> Result<Node, TableError> {
+    match entries.remove(path) {
+        None => Err(TableError::Missing(path.clone())),
+        Some(Entry::Leaf(bytes)) => Ok(Node::Leaf(bytes)),
+        Some(Entry::Inner(len)) => {
+            let mut nodes = Vec::with_capacity(len.min(entries.len()));
+            for idx in 0..len {
+                path.push(idx);
+                nodes.push(build(entries, path)?);
+                path.pop();
+            }
+            Ok(Node::Inner(nodes))
+        }
+    }
+}
+
+
+#[test]
+fn table_roundtrip() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!()),
+        Node::Inner(vec!(Node::Leaf(vec!()), Node::Leaf(vec!(2, 3)))),
+    ));
+    let table = node.to_table();
+    assert_eq!(table.leaves, vec!(
+        (vec!(0), vec!(1)),
+        (vec!(2, 0), vec!()),
+        (vec!(2, 1), vec!(2, 3)),
+    ));
+    assert_eq!(table.inners, vec!((vec!(), 3), (vec!(1), 0), (vec!(2), 2)));
+
+    let mut shuffled = table.clone();
+    shuffled.leaves.reverse();
+    shuffled.inners.reverse();
+    assert_eq!(Node::from_table(shuffled), Ok(node));
+    assert_eq!(Node::from_table(Node::Leaf(vec!(1)).to_table()), Ok(Node::Leaf(vec!(1))));
+}
+
+#[test]
+fn table_errors() {
+    let table = Node::Inner(vec!(Node::Leaf(vec!(1)), Node::Leaf(vec!(2)))).to_table();
+
+    let mut t = table.clone();
+    t.leaves.pop();
+    assert_eq!(Node::from_table(t), Err(TableError::Missing(vec!(1))));
+
+    let mut t = table.clone();
+    t.leaves.push((vec!(2), vec!()));
+    assert_eq!(Node::from_table(t), Err(TableError::Unreachable(vec!(2))));
+
+    let mut t = table.clone();
+    t.inners.push((vec!(0), 0));
+    assert_eq!(Node::from_table(t), Err(TableError::Duplicate(vec!(0))));
+
+    assert_eq!(Node::from_table(Table::default()), Err(TableError::Missing(vec!())));
+}
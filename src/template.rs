@@ -0,0 +1,127 @@
+use crate::Node;
+use crate::parser::{self, Ast};
+
+use std::collections::HashMap;
+
+/// A tree in text syntax that contains `$name` placeholders, e.g.
+/// `(0x01 $payload ($a $a))`. Placeholders are replaced by nodes on
+/// instantiation.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Template {
+    root: TemplateNode,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum TemplateNode {
+    Node(Node),
+    Inner(Vec<TemplateNode>),
+    Placeholder(String),
+}
+
+impl TemplateNode {
+    fn from_ast(ast: Ast) -> Result<TemplateNode, String> {
+        Ok(match ast {
+            Ast::Leaf(b) => TemplateNode::Node(Node::Leaf(b)),
+            Ast::Inner(children) => {
+                let children = children.into_iter().map(TemplateNode::from_ast).collect::<Result<Vec<_>, _>>()?;
+                // collapse subtrees without placeholders, so they can be cloned directly
+                if children.iter().all(|c| matches!(c, TemplateNode::Node(_))) {
+                    TemplateNode::Node(Node::Inner(children.into_iter().map(|c| match c {
+                        TemplateNode::Node(n) => n,
+                        _ => unreachable!(),
+                    }).collect()))
+                } else {
+                    TemplateNode::Inner(children)
+                }
+            }
+            Ast::Placeholder(name) => TemplateNode::Placeholder(name),
+            Ast::Wildcard | Ast::Rest => return Err("Wildcards are only allowed in patterns.".to_string()),
+        })
+    }
+
+    fn instantiate(&self, values: &HashMap<String, Node>) -> Result<Node, String> {
+        match self {
+            TemplateNode::Node(n) => Ok(n.clone()),
+            TemplateNode::Inner(children) => Ok(Node::Inner(
+                children.iter().map(|c| c.instantiate(values)).collect::<Result<_, _>>()?
+            )),
+            TemplateNode::Placeholder(name) => values.get(name).cloned().ok_or_else(|| name.clone()),
+        }
+    }
+
+    fn placeholders<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            TemplateNode::Node(_) => {},
+            TemplateNode::Inner(children) => children.iter().for_each(|c| c.placeholders(names)),
+            TemplateNode::Placeholder(name) => {
+                if !names.contains(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(s: &str) -> Result<Template, String> {
+        let ast = parser::parse_ast(parser::tokenize(s)?, parser::DEFAULT_MAX_DEPTH)?;
+        Ok(Template { root: TemplateNode::from_ast(ast)? })
+    }
+
+    /// Returns the names of all placeholders in order of first occurrence.
+    pub fn placeholders(&self) -> Vec<&str> {
+        let mut names = vec!();
+        self.root.placeholders(&mut names);
+        names
+    }
+
+    /// Replaces all placeholders by the nodes in `values`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `values` doesn't contain a node for every placeholder. Use
+    /// [`try_instantiate`](#method.try_instantiate) for a non-panicking version.
+    pub fn instantiate(&self, values: &HashMap<String, Node>) -> Node {
+        match self.try_instantiate(values) {
+            Ok(node) => node,
+            Err(name) => panic!("No value for placeholder '${}'.", name),
+        }
+    }
+
+    /// Replaces all placeholders by the nodes in `values`, returning the name
+    /// of the first missing placeholder as error.
+    pub fn try_instantiate(&self, values: &HashMap<String, Node>) -> Result<Node, String> {
+        self.root.instantiate(values)
+    }
+}
+
+
+#[test]
+fn template_instantiate() {
+    let t = Template::parse("(0x01 $payload ($a 0x02 $a) ())").unwrap();
+    assert_eq!(t.placeholders(), vec!("payload", "a"));
+
+    let mut values = HashMap::new();
+    values.insert("payload".to_string(), Node::Inner(vec!(Node::Leaf(vec!(0xff)))));
+    values.insert("a".to_string(), Node::Leaf(vec!(3)));
+    let exp = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(Node::Leaf(vec!(0xff)))),
+        Node::Inner(vec!(Node::Leaf(vec!(3)), Node::Leaf(vec!(2)), Node::Leaf(vec!(3)))),
+        Node::Inner(vec!()),
+    ));
+    assert_eq!(t.instantiate(&values), exp);
+
+    values.remove("a");
+    assert_eq!(t.try_instantiate(&values), Err("a".to_string()));
+}
+
+#[test]
+fn template_parse_errors() {
+    assert!(Template::parse("($)").is_err());
+    assert!(Template::parse("($a").is_err());
+    assert!(Template::parse("$a $b").is_err());
+    // placeholders aren't valid in regular nodes
+    assert!(!Node::parse("(0x01 $a)").is_ok());
+    assert!(Node::parse("(0x01 $a)").is_lexing_ok());
+}
@@ -0,0 +1,79 @@
+//! Streaming conversion between baum documents and other serde formats.
+//!
+//! Values are transcoded without building an intermediate `Node` (or any
+//! other in-memory representation). Note that the baum format isn't
+//! self-describing: when reading, leaves are passed on as byte arrays and
+//! inner nodes as sequences, see the `ser` module for how values are mapped
+//! when writing.
+
+use crate::de::Deserializer;
+use crate::ser::Serializer;
+use crate::{Error, ErrorKind};
+
+use serde::ser::Error as _;
+
+use std::io::{Read, Write};
+
+pub use serde_transcode::transcode;
+
+/// Transcodes the value produced by `deserializer` into a baum document
+/// (including the magic number) written to `writer`.
+pub fn transcode_to_writer<'de, D, W>(deserializer: D, mut writer: W) -> Result<(), Error>
+where
+    D: serde::Deserializer<'de>,
+    W: Write
+{
+    writer.write_all("BAUM1".as_bytes())?;
+    transcode(deserializer, &mut Serializer::new(writer))
+}
+
+/// Transcodes the baum document (including the magic number) read from
+/// `reader` using `serializer`. Errors of the baum input are reported as
+/// custom errors of the serializer.
+pub fn transcode_from_reader<R, S>(mut reader: R, serializer: S) -> Result<S::Ok, S::Error>
+where
+    R: Read,
+    S: serde::Serializer
+{
+    let mut magic_num = [0; 5];
+    reader.read_exact(&mut magic_num).map_err(S::Error::custom)?;
+    if &magic_num != b"BAUM1" {
+        return Err(S::Error::custom(Error::new(ErrorKind::InvalidMagicNumber)));
+    }
+
+    let mut de = Deserializer::new(reader);
+    let res = transcode(&mut de, serializer)?;
+
+    // check if whole input has been processed
+    let mut buf = [0];
+    if de.into_inner().read(&mut buf).map_err(S::Error::custom)? != 0 {
+        return Err(S::Error::custom(Error::new(ErrorKind::AdditionalBytes)));
+    }
+    Ok(res)
+}
+
+
+#[test]
+fn transcode_json() {
+    use crate::Node;
+
+    let json = r#"{"a": [1, "xy"], "b": null}"#;
+    let mut bytes = vec!();
+    transcode_to_writer(&mut serde_json::Deserializer::from_str(json), &mut bytes).unwrap();
+    let node = Node::deserialize(&bytes).unwrap();
+    assert_eq!(node, Node::Inner(vec!(
+        Node::Inner(vec!(
+            Node::Leaf(b"a".to_vec()),
+            Node::Inner(vec!(Node::Leaf(1u64.to_le_bytes().to_vec()), Node::Leaf(b"xy".to_vec()))),
+        )),
+        Node::Inner(vec!(Node::Leaf(b"b".to_vec()), Node::Inner(vec!()))),
+    )));
+
+    let mut out = vec!();
+    transcode_from_reader(bytes.as_slice(), &mut serde_json::Serializer::new(&mut out)).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "[[[97],[[1,0,0,0,0,0,0,0],[120,121]]],[[98],[]]]");
+
+    let mut out = vec!();
+    let res = transcode_from_reader(&bytes[1..], &mut serde_json::Serializer::new(&mut out));
+    assert_eq!(res.unwrap_err().to_string(), "Invalid magic number.");
+}
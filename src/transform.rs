@@ -0,0 +1,172 @@
+use crate::{Node, Selector};
+use crate::parser::{lex_hex, lex_string};
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Expression computing a new tree from a tree, similar to jq filters.
+///
+/// - `.` is the input
+/// - leaves in text syntax (`0x01_02`, `"abc"`) are constants
+/// - `(e1 e2 ...)` constructs an inner node from the results of the
+///   expressions
+/// - `select("SELECTOR")` returns an inner node containing all nodes selected
+///   by the `Selector`, and `get("SELECTOR")` the first one
+/// - `map(e)` applies `e` to every child of an inner node
+/// - `e1 | e2` applies `e2` to the result of `e1`
+///
+/// For example, `(0x01 get("0") select("1/*") | map((. .)))` builds a node
+/// from the input's first child followed by the input's grandchildren below
+/// the second child, each duplicated.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Transform {
+    expr: Expr,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+enum Expr {
+    Input,
+    Leaf(Vec<u8>),
+    Construct(Vec<Expr>),
+    Select(Selector),
+    Get(Selector),
+    Map(Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, node: &Node) -> Result<Node, String> {
+        match self {
+            Expr::Input => Ok(node.clone()),
+            Expr::Leaf(bytes) => Ok(Node::Leaf(bytes.clone())),
+            Expr::Construct(exprs) => Ok(Node::Inner(exprs.iter().map(|e| e.eval(node)).collect::<Result<_, _>>()?)),
+            Expr::Select(selector) => Ok(Node::Inner(selector.select(node).into_iter().map(|(_, n)| n.clone()).collect())),
+            Expr::Get(selector) => match selector.select(node).first() {
+                Some((_, n)) => Ok((*n).clone()),
+                None => Err("Selector didn't select any node.".to_string()),
+            },
+            Expr::Map(expr) => match node {
+                Node::Inner(nodes) => Ok(Node::Inner(nodes.iter().map(|n| expr.eval(n)).collect::<Result<_, _>>()?)),
+                Node::Leaf(_) => Err("Can't map over the children of a leaf.".to_string()),
+            },
+            Expr::Pipe(first, second) => second.eval(&first.eval(node)?),
+        }
+    }
+}
+
+struct TransformParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl TransformParser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some(next) if next == c => Ok(()),
+            _ => Err(format!("Expected '{}'.", c)),
+        }
+    }
+
+    fn pipe(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+        self.skip_whitespace();
+        while self.chars.next_if_eq(&'|').is_some() {
+            expr = Expr::Pipe(Box::new(expr), Box::new(self.term()?));
+            self.skip_whitespace();
+        }
+        Ok(expr)
+    }
+
+    fn selector(&mut self) -> Result<Selector, String> {
+        self.expect('(')?;
+        self.expect('"')?;
+        let selector = String::from_utf8(lex_string(&mut self.chars)?).map_err(|_| "Invalid selector.".to_string())?;
+        self.expect(')')?;
+        Selector::parse(&selector)
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some('.') => Ok(Expr::Input),
+            Some('0') if self.chars.next_if_eq(&'x').is_some() => Ok(Expr::Leaf(lex_hex(&mut self.chars))),
+            Some('"') => Ok(Expr::Leaf(lex_string(&mut self.chars)?)),
+            Some('(') => {
+                let mut exprs = vec!();
+                loop {
+                    self.skip_whitespace();
+                    if self.chars.next_if_eq(&')').is_some() {
+                        return Ok(Expr::Construct(exprs));
+                    }
+                    exprs.push(self.pipe()?);
+                }
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut name = c.to_string();
+                while let Some(c) = self.chars.next_if(char::is_ascii_alphabetic) {
+                    name.push(c);
+                }
+                match name.as_str() {
+                    "select" => Ok(Expr::Select(self.selector()?)),
+                    "get" => Ok(Expr::Get(self.selector()?)),
+                    "map" => {
+                        self.expect('(')?;
+                        let expr = self.pipe()?;
+                        self.expect(')')?;
+                        Ok(Expr::Map(Box::new(expr)))
+                    }
+                    _ => Err(format!("Unknown function '{}'.", name)),
+                }
+            }
+            _ => Err("Expected expression.".to_string()),
+        }
+    }
+}
+
+impl Transform {
+    pub fn parse(s: &str) -> Result<Transform, String> {
+        let mut parser = TransformParser { chars: s.chars().peekable() };
+        let expr = parser.pipe()?;
+        match parser.chars.next() {
+            None => Ok(Transform { expr }),
+            Some(c) => Err(format!("Unexpected character '{}'.", c)),
+        }
+    }
+
+    pub fn apply(&self, node: &Node) -> Result<Node, String> {
+        self.expr.eval(node)
+    }
+}
+
+impl Node {
+    /// Computes a new tree from this one using a `Transform` expression.
+    pub fn transform(&self, expr: &str) -> Result<Node, String> {
+        Transform::parse(expr)?.apply(self)
+    }
+}
+
+
+#[test]
+fn transform() {
+    let node = crate::parse(r#"(0x01 (("a" 0x02) ("b" 0x03)))"#);
+    assert_eq!(node.transform(".").unwrap(), node);
+    assert_eq!(node.transform(r#"("x" 0x0102 ())"#).unwrap(), crate::parse(r#"("x" 0x0102 ())"#));
+    assert_eq!(node.transform(r#"select("1/*/0")"#).unwrap(), crate::parse(r#"("a" "b")"#));
+    assert_eq!(node.transform(r#"get("1") | map(get("1"))"#).unwrap(), crate::parse("(0x02 0x03)"));
+    assert_eq!(
+        node.transform(r#"(get("0") select("1/*") | map((get("1") get("0"))))"#).unwrap(),
+        crate::parse(r#"(0x01 ((0x02 "a") (0x03 "b")))"#),
+    );
+    assert_eq!(node.transform("(. | get(\"0\") .)").unwrap(), crate::parse(r#"(0x01 (0x01 (("a" 0x02) ("b" 0x03))))"#));
+
+    assert_eq!(node.transform(r#"get("5")"#), Err("Selector didn't select any node.".to_string()));
+    assert_eq!(node.transform("get(\"0\") | map(.)"), Err("Can't map over the children of a leaf.".to_string()));
+    assert_eq!(Transform::parse("foo(.)"), Err("Unknown function 'foo'.".to_string()));
+    assert_eq!(Transform::parse("(. "), Err("Expected expression.".to_string()));
+    assert_eq!(Transform::parse(". ."), Err("Unexpected character '.'.".to_string()));
+    assert_eq!(Transform::parse(r#"select("1/")"#), Err("Expected step.".to_string()));
+}
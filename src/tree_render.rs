@@ -0,0 +1,76 @@
+use crate::{Node, PrettyConfig};
+
+/// Number of bytes shown for each leaf by `render_tree`.
+const PREVIEW_BYTES: usize = 16;
+
+impl Node {
+    /// Renders the tree like the `tree` command, one node per line:
+    ///
+    /// ```text
+    /// ()
+    /// ├── 0x01
+    /// ├── ()
+    /// │   ├── 0x02
+    /// │   └── 0x03
+    /// └── 0x04_05
+    /// ```
+    ///
+    /// Leaves longer than 16 bytes are truncated.
+    pub fn render_tree(&self) -> String {
+        let config = PrettyConfig {
+            max_width: usize::MAX,
+            max_leaf_bytes: Some(PREVIEW_BYTES),
+            ..PrettyConfig::default()
+        };
+        let mut s = String::new();
+        let mut prefix = String::new();
+        self._render_tree(&config, &mut prefix, &mut s);
+        s
+    }
+
+    fn _render_tree(&self, config: &PrettyConfig, prefix: &mut String, s: &mut String) {
+        match self {
+            Node::Leaf(_) => s.push_str(&self.pretty_print_with(config)),
+            Node::Inner(_) => s.push_str("()"),
+        }
+        s.push('\n');
+        if let Node::Inner(nodes) = self {
+            for (idx, node) in nodes.iter().enumerate() {
+                let last = idx + 1 == nodes.len();
+                s.push_str(prefix);
+                s.push_str(if last { "└── " } else { "├── " });
+                let len = prefix.len();
+                prefix.push_str(if last { "    " } else { "│   " });
+                node._render_tree(config, prefix, s);
+                prefix.truncate(len);
+            }
+        }
+    }
+}
+
+
+#[test]
+fn render_tree() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(vec!(1)),
+        Node::Inner(vec!(
+            Node::Inner(vec!(
+                Node::Leaf(vec!(2)),
+            )),
+            Node::Leaf(vec!(3)),
+        )),
+        Node::Leaf((0..20).collect()),
+        Node::Inner(vec!()),
+    ));
+    assert_eq!(node.render_tree(), "\
+()
+├── 0x01
+├── ()
+│   ├── ()
+│   │   └── 0x02
+│   └── 0x03
+├── 0x00_01_02_03_04_05_06_07_08_09_0a_0b_0c_0d_0e_0f… (20 bytes)
+└── ()
+");
+    assert_eq!(Node::Leaf(vec!(1, 2)).render_tree(), "0x01_02\n");
+}
@@ -0,0 +1,44 @@
+// Unsigned LEB128 variable-length integers, as used by protobuf.
+
+pub fn encode(mut n: u64, w: &mut Vec<u8>) {
+    while n >= 0x80 {
+        w.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    w.push(n as u8);
+}
+
+/// Decodes the varint at `pos` and advances `pos`. Returns `None` if the
+/// input ends early or the value doesn't fit into a `u64`.
+pub fn decode(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let b = *bytes.get(*pos)?;
+        *pos += 1;
+        if shift == 63 && b > 1 {
+            return None;
+        }
+        n |= u64::from(b & 0x7f) << shift;
+        if b & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+
+#[test]
+fn varint() {
+    for n in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+        let mut v = vec!();
+        encode(n, &mut v);
+        let mut pos = 0;
+        assert_eq!(decode(&v, &mut pos), Some(n));
+        assert_eq!(pos, v.len());
+    }
+    let mut v = vec!();
+    encode(300, &mut v);
+    assert_eq!(v, vec!(0xac, 0x02));
+    assert_eq!(decode(&[0x80], &mut 0), None);
+    assert_eq!(decode(&[0xff; 9].iter().chain(&[0x02]).copied().collect::<Vec<_>>(), &mut 0), None);
+}
@@ -0,0 +1,131 @@
+//! JavaScript bindings (feature `wasm`), built with `wasm-bindgen`.
+//!
+//! Paths are passed as `Uint32Array`s of child indices and leaves as
+//! `Uint8Array`s. Functions taking a path return `undefined` if there's no
+//! node at the path.
+
+use crate::{Node, ParseResult, PrettyConfig};
+
+use wasm_bindgen::prelude::*;
+
+/// A tree, exported to JavaScript as `Document`.
+#[wasm_bindgen]
+pub struct Document {
+    node: Node,
+}
+
+#[wasm_bindgen]
+impl Document {
+    /// Parses the text format.
+    pub fn parse(text: &str) -> Result<Document, JsError> {
+        match Node::parse(text) {
+            ParseResult::Ok(node) => Ok(Document { node }),
+            r => Err(JsError::new(r.err_message())),
+        }
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Document, JsError> {
+        Node::deserialize(bytes).map(|node| Document { node }).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        self.node.serialize()
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_text(&self) -> String {
+        self.node.to_string()
+    }
+
+    #[wasm_bindgen(js_name = prettyPrint)]
+    pub fn pretty_print(&self, max_width: usize) -> String {
+        self.node.pretty_print_with(&PrettyConfig { max_width, ..PrettyConfig::default() })
+    }
+
+    /// Returns a copy of the subtree at `path`.
+    pub fn get(&self, path: &[u32]) -> Option<Document> {
+        self.node_at(path).map(|node| Document { node: node.clone() })
+    }
+
+    #[wasm_bindgen(js_name = isLeaf)]
+    pub fn is_leaf(&self, path: &[u32]) -> Option<bool> {
+        self.node_at(path).map(|node| matches!(node, Node::Leaf(_)))
+    }
+
+    /// Returns the bytes of the leaf at `path`.
+    pub fn leaf(&self, path: &[u32]) -> Option<Vec<u8>> {
+        match self.node_at(path)? {
+            Node::Leaf(bytes) => Some(bytes.clone()),
+            Node::Inner(_) => None,
+        }
+    }
+
+    #[wasm_bindgen(js_name = childCount)]
+    pub fn child_count(&self, path: &[u32]) -> Option<usize> {
+        match self.node_at(path)? {
+            Node::Leaf(_) => None,
+            Node::Inner(nodes) => Some(nodes.len()),
+        }
+    }
+
+    /// Replaces the node at `path` by a copy of `doc`.
+    pub fn replace(&mut self, path: &[u32], doc: &Document) -> Result<(), JsError> {
+        let node = self.node.get_mut(&to_path(path)).ok_or_else(|| JsError::new("Invalid path."))?;
+        *node = doc.node.clone();
+        Ok(())
+    }
+
+    /// Inserts a copy of `doc` so that it ends up at `path`, see `Node::graft`.
+    pub fn insert(&mut self, path: &[u32], doc: &Document) -> Result<(), JsError> {
+        self.node.graft(&to_path(path), doc.node.clone()).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Removes and returns the subtree at `path`, see `Node::take_subtree`.
+    pub fn remove(&mut self, path: &[u32]) -> Option<Document> {
+        self.node.take_subtree(&to_path(path)).map(|node| Document { node })
+    }
+}
+
+impl Document {
+    fn node_at(&self, path: &[u32]) -> Option<&Node> {
+        self.node.get(&to_path(path))
+    }
+}
+
+impl From<Node> for Document {
+    fn from(node: Node) -> Self {
+        Document { node }
+    }
+}
+
+impl From<Document> for Node {
+    fn from(doc: Document) -> Self {
+        doc.node
+    }
+}
+
+fn to_path(path: &[u32]) -> Vec<usize> {
+    path.iter().map(|idx| *idx as usize).collect()
+}
+
+
+// errors can only be created on wasm targets, so only the success paths are
+// tested natively
+#[test]
+fn document() {
+    let mut doc = Document::parse("(0x01 (0x02_03) ())").unwrap_or_else(|_| panic!());
+    assert_eq!(Document::deserialize(&doc.serialize()).unwrap_or_else(|_| panic!()).to_text(), doc.to_text());
+    assert_eq!(doc.pretty_print(10), doc.node.pretty_print(10));
+    assert_eq!(doc.is_leaf(&[1, 0]), Some(true));
+    assert_eq!(doc.is_leaf(&[3]), None);
+    assert_eq!(doc.leaf(&[1, 0]), Some(vec!(2, 3)));
+    assert_eq!(doc.leaf(&[1]), None);
+    assert_eq!(doc.child_count(&[]), Some(3));
+    assert_eq!(doc.get(&[1]).map(|d| d.to_text()), Some("(0x02_03)".to_string()));
+
+    let leaf = Document::from(Node::Leaf(vec!(4)));
+    doc.replace(&[2], &leaf).unwrap_or_else(|_| panic!());
+    doc.insert(&[1, 0], &leaf).unwrap_or_else(|_| panic!());
+    assert_eq!(doc.remove(&[0]).map(Node::from), Some(Node::Leaf(vec!(1))));
+    assert_eq!(doc.to_text(), "((0x04 0x02_03) 0x04)");
+}
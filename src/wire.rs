@@ -0,0 +1,63 @@
+//! Pieces shared by the conversions to other serialization formats
+//! (`csexp`, `bencode`, `cbor` and `msgpack`).
+
+/// Error returned when decoding one of the byte-oriented formats fails.
+/// Positions are byte offsets into the input.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnexpectedEnd,
+    UnexpectedByte(u8, usize),
+    InvalidLength(usize),
+    AdditionalBytes,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::UnexpectedEnd => write!(f, "Unexpected end of input."),
+            Error::UnexpectedByte(b, pos) => write!(f, "Unexpected byte 0x{:02x} at position {}.", b, pos),
+            Error::InvalidLength(pos) => write!(f, "Invalid length at position {}.", pos),
+            Error::AdditionalBytes => write!(f, "Input contains additional bytes."),
+        }
+    }
+}
+
+impl std::error::Error for Error { }
+
+/// Reads a `<len>:<bytes>` atom starting at `pos`, which must point at the
+/// first digit, and advances `pos` past it. Leading zeros are rejected.
+pub(crate) fn read_atom<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a [u8], Error> {
+    let start = *pos;
+    while let Some(b'0'..=b'9') = bytes.get(*pos) {
+        *pos += 1;
+    }
+    let digits = &bytes[start..*pos];
+    if digits.is_empty() || (digits.len() > 1 && digits[0] == b'0') {
+        return Err(Error::InvalidLength(start));
+    }
+    let len: usize = std::str::from_utf8(digits).unwrap()
+        .parse()
+        .map_err(|_| Error::InvalidLength(start))?;
+    match bytes.get(*pos) {
+        Some(b':') => *pos += 1,
+        Some(b) => return Err(Error::UnexpectedByte(*b, *pos)),
+        None => return Err(Error::UnexpectedEnd),
+    }
+    read_bytes(bytes, pos, len, start)
+}
+
+/// Reads `len` bytes at `pos` and advances `pos` past them. `start` is the
+/// position of the length, which is reported if it overflows.
+pub(crate) fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize, len: usize, start: usize) -> Result<&'a [u8], Error> {
+    let end = pos.checked_add(len).ok_or(Error::InvalidLength(start))?;
+    let data = bytes.get(*pos..end).ok_or(Error::UnexpectedEnd)?;
+    *pos = end;
+    Ok(data)
+}
+
+/// Writes `data` as a `<len>:<bytes>` atom.
+pub(crate) fn write_atom(data: &[u8], w: &mut Vec<u8>) {
+    w.extend_from_slice(data.len().to_string().as_bytes());
+    w.push(b':');
+    w.extend_from_slice(data);
+}
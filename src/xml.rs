@@ -0,0 +1,391 @@
+//! Conversion between `Node`s and XML.
+//!
+//! Inner nodes map to `<inner>` elements, leaves to `<leaf>` elements:
+//!
+//! ```text
+//! <?xml version="1.0" encoding="UTF-8"?>
+//! <inner>
+//!   <leaf>foo</leaf>
+//!   <leaf encoding="base64">/w==</leaf>
+//!   <inner/>
+//! </inner>
+//! ```
+//!
+//! Leaves containing text that can be represented in XML are written as
+//! text, other leaves are base64 encoded. When reading, leaf content may use
+//! entities and CDATA sections; whitespace between the children of
+//! `<inner>` elements, comments and processing instructions are ignored.
+
+use crate::{base64, Node};
+
+use std::fmt::Write;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnexpectedEnd,
+    Syntax(usize),
+    UnknownElement(String, usize),
+    UnknownAttribute(String, usize),
+    InvalidEntity(usize),
+    InvalidBase64(usize),
+    AdditionalContent(usize),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::UnexpectedEnd => write!(f, "Unexpected end of input."),
+            Error::Syntax(pos) => write!(f, "Syntax error at position {}.", pos),
+            Error::UnknownElement(name, pos) => write!(f, "Unknown element `{}` at position {}.", name, pos),
+            Error::UnknownAttribute(name, pos) => write!(f, "Unknown attribute `{}` at position {}.", name, pos),
+            Error::InvalidEntity(pos) => write!(f, "Invalid entity at position {}.", pos),
+            Error::InvalidBase64(pos) => write!(f, "Invalid base64 content at position {}.", pos),
+            Error::AdditionalContent(pos) => write!(f, "Unexpected content after root element at position {}.", pos),
+        }
+    }
+}
+
+impl std::error::Error for Error { }
+
+pub fn encode(node: &Node) -> String {
+    let mut s = String::new();
+    s.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    encode_into(node, 0, &mut s);
+    s
+}
+
+fn encode_into(node: &Node, indent: usize, s: &mut String) {
+    for _ in 0..indent {
+        s.push_str("  ");
+    }
+    match node {
+        Node::Leaf(bytes) if bytes.is_empty() => s.push_str("<leaf/>"),
+        Node::Leaf(bytes) => match text(bytes) {
+            Some(text) => {
+                s.push_str("<leaf>");
+                escape_into(text, s);
+                s.push_str("</leaf>");
+            }
+            None => write!(s, "<leaf encoding=\"base64\">{}</leaf>", base64::encode(bytes)).unwrap(),
+        },
+        Node::Inner(nodes) if nodes.is_empty() => s.push_str("<inner/>"),
+        Node::Inner(nodes) => {
+            s.push_str("<inner>\n");
+            for node in nodes {
+                encode_into(node, indent + 1, s);
+            }
+            for _ in 0..indent {
+                s.push_str("  ");
+            }
+            s.push_str("</inner>");
+        }
+    }
+    s.push('\n');
+}
+
+/// Returns `bytes` as string if it only contains characters allowed in XML.
+fn text(bytes: &[u8]) -> Option<&str> {
+    let s = std::str::from_utf8(bytes).ok()?;
+    let allowed = |c: char| match c {
+        '\t' | '\n' | '\r' => true,
+        '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => false,
+        _ => true,
+    };
+    if s.chars().all(allowed) { Some(s) } else { None }
+}
+
+fn escape_into(text: &str, s: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => s.push_str("&amp;"),
+            '<' => s.push_str("&lt;"),
+            '>' => s.push_str("&gt;"),
+            // would be normalized to `\n` by XML parsers otherwise
+            '\r' => s.push_str("&#13;"),
+            c => s.push(c),
+        }
+    }
+}
+
+struct Attribute<'a> {
+    name: &'a str,
+    value: &'a str,
+    pos: usize,
+}
+
+struct Reader<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn rest(&self) -> &'a str {
+        &self.s[self.pos..]
+    }
+
+    fn starts_with(&self, p: &str) -> bool {
+        self.rest().starts_with(p)
+    }
+
+    fn expect(&mut self, p: &str) -> Result<(), Error> {
+        if self.starts_with(p) {
+            self.pos += p.len();
+            Ok(())
+        } else if self.rest().is_empty() {
+            Err(Error::UnexpectedEnd)
+        } else {
+            Err(Error::Syntax(self.pos))
+        }
+    }
+
+    /// Advances to after the next occurrence of `p`.
+    fn skip_past(&mut self, p: &str) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let idx = rest.find(p).ok_or(Error::UnexpectedEnd)?;
+        self.pos += idx + p.len();
+        Ok(&rest[..idx])
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_whitespace()).len();
+    }
+
+    /// Skips whitespace, comments and processing instructions.
+    fn skip_misc(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let len = rest.find(|c: char| c.is_ascii_whitespace() || c == '/' || c == '>' || c == '=')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return if rest.is_empty() { Err(Error::UnexpectedEnd) } else { Err(Error::Syntax(self.pos)) };
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Reads the attributes of a start tag, up to and including `>` or `/>`.
+    /// Returns the attributes (with their positions) and whether the element
+    /// is empty.
+    fn attributes(&mut self) -> Result<(Vec<Attribute<'a>>, bool), Error> {
+        let mut attrs = vec!();
+        loop {
+            self.skip_whitespace();
+            if self.starts_with("/>") {
+                self.pos += 2;
+                return Ok((attrs, true));
+            }
+            if self.starts_with(">") {
+                self.pos += 1;
+                return Ok((attrs, false));
+            }
+            let start = self.pos;
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = if self.starts_with("\"") { "\"" } else { "'" };
+            self.expect(quote)?;
+            let value = self.skip_past(quote)?;
+            attrs.push(Attribute { name, value, pos: start });
+        }
+    }
+
+    fn entity(&mut self, text: &mut String) -> Result<(), Error> {
+        let start = self.pos;
+        self.pos += 1;
+        let name = self.skip_past(";").map_err(|_| Error::InvalidEntity(start))?;
+        let c = match name {
+            "lt" => '<',
+            "gt" => '>',
+            "amp" => '&',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = if let Some(hex) = name.strip_prefix("#x") {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = name.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                };
+                code.and_then(std::char::from_u32).ok_or(Error::InvalidEntity(start))?
+            }
+        };
+        text.push(c);
+        Ok(())
+    }
+
+    /// Reads the content of a `<leaf>` element, including its end tag.
+    fn leaf_content(&mut self) -> Result<String, Error> {
+        let mut text = String::new();
+        loop {
+            if self.starts_with("</") {
+                let start = self.pos;
+                self.pos += 2;
+                if self.name()? != "leaf" {
+                    return Err(Error::Syntax(start));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                return Ok(text);
+            } else if self.starts_with("<![CDATA[") {
+                self.pos += 9;
+                text.push_str(self.skip_past("]]>")?);
+            } else if self.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.starts_with("<") {
+                return Err(Error::Syntax(self.pos));
+            } else if self.starts_with("&") {
+                self.entity(&mut text)?;
+            } else if self.rest().is_empty() {
+                return Err(Error::UnexpectedEnd);
+            } else {
+                let rest = self.rest();
+                let len = rest.find(['<', '&']).unwrap_or(rest.len());
+                text.push_str(&rest[..len]);
+                self.pos += len;
+            }
+        }
+    }
+}
+
+pub fn decode(s: &str) -> Result<Node, Error> {
+    let mut r = Reader { s, pos: 0 };
+    // children of the `<inner>` elements that are currently open
+    let mut stack: Vec<Vec<Node>> = vec!();
+
+    loop {
+        r.skip_misc()?;
+        let start = r.pos;
+        let node = if r.starts_with("</") {
+            r.pos += 2;
+            if r.name()? != "inner" || stack.is_empty() {
+                return Err(Error::Syntax(start));
+            }
+            r.skip_whitespace();
+            r.expect(">")?;
+            Node::Inner(stack.pop().unwrap())
+        } else if r.starts_with("<") {
+            r.pos += 1;
+            let name = r.name()?;
+            let (attrs, empty) = r.attributes()?;
+            match name {
+                "inner" => {
+                    if let Some(attr) = attrs.first() {
+                        return Err(Error::UnknownAttribute(attr.name.to_string(), attr.pos));
+                    }
+                    if !empty {
+                        stack.push(vec!());
+                        continue;
+                    }
+                    Node::Inner(vec!())
+                }
+                "leaf" => {
+                    let mut base64 = false;
+                    for attr in attrs {
+                        match (attr.name, attr.value) {
+                            ("encoding", "base64") => base64 = true,
+                            ("encoding", "text") => base64 = false,
+                            _ => return Err(Error::UnknownAttribute(attr.name.to_string(), attr.pos)),
+                        }
+                    }
+                    let content_start = r.pos;
+                    let text = if empty { String::new() } else { r.leaf_content()? };
+                    if base64 {
+                        let text: String = text.split_ascii_whitespace().collect();
+                        Node::Leaf(base64::decode(&text).ok_or(Error::InvalidBase64(content_start))?)
+                    } else {
+                        Node::Leaf(text.into_bytes())
+                    }
+                }
+                _ => return Err(Error::UnknownElement(name.to_string(), start)),
+            }
+        } else if r.rest().is_empty() {
+            return Err(Error::UnexpectedEnd);
+        } else {
+            return Err(Error::Syntax(start));
+        };
+
+        match stack.last_mut() {
+            Some(children) => children.push(node),
+            None => {
+                r.skip_misc()?;
+                if !r.rest().is_empty() {
+                    return Err(Error::AdditionalContent(r.pos));
+                }
+                return Ok(node);
+            }
+        }
+    }
+}
+
+impl Node {
+    pub fn to_xml(&self) -> String {
+        encode(self)
+    }
+
+    pub fn from_xml(s: &str) -> Result<Node, Error> {
+        decode(s)
+    }
+}
+
+
+#[test]
+fn xml_roundtrip() {
+    let node = Node::Inner(vec!(
+        Node::Leaf(b"a < b & c\r\n".to_vec()),
+        Node::Inner(vec!(
+            Node::Leaf(vec!()),
+            Node::Inner(vec!()),
+        )),
+        Node::Leaf(vec!(0xff, 0x00)),
+    ));
+    let xml = node.to_xml();
+    assert_eq!(xml, "\
+<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<inner>
+  <leaf>a &lt; b &amp; c&#13;\n</leaf>
+  <inner>
+    <leaf/>
+    <inner/>
+  </inner>
+  <leaf encoding=\"base64\">/wA=</leaf>
+</inner>
+");
+    assert_eq!(Node::from_xml(&xml), Ok(node));
+
+    let xml = "<!-- comment --><inner>\n\t<leaf>&#x41;&#66;<![CDATA[<&>]]><!-- x -->&apos;</leaf>\
+        <leaf encoding='base64'>\n AQI=\n</leaf ><inner></inner ></inner>\n";
+    assert_eq!(Node::from_xml(xml), Ok(Node::Inner(vec!(
+        Node::Leaf(b"AB<&>'".to_vec()),
+        Node::Leaf(vec!(1, 2)),
+        Node::Inner(vec!()),
+    ))));
+}
+
+#[test]
+fn xml_errors() {
+    assert_eq!(Node::from_xml(""), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_xml("<inner><leaf/>"), Err(Error::UnexpectedEnd));
+    assert_eq!(Node::from_xml("<inner>x</inner>"), Err(Error::Syntax(7)));
+    assert_eq!(Node::from_xml("<inner></leaf>"), Err(Error::Syntax(7)));
+    assert_eq!(Node::from_xml("<leaf><inner/></leaf>"), Err(Error::Syntax(6)));
+    assert_eq!(Node::from_xml("<node/>"), Err(Error::UnknownElement("node".to_string(), 0)));
+    assert_eq!(Node::from_xml("<leaf id='1'/>"), Err(Error::UnknownAttribute("id".to_string(), 6)));
+    assert_eq!(Node::from_xml("<leaf>&nbsp;</leaf>"), Err(Error::InvalidEntity(6)));
+    assert_eq!(Node::from_xml("<leaf encoding=\"base64\">AQI</leaf>"), Err(Error::InvalidBase64(24)));
+    assert_eq!(Node::from_xml("<inner/><inner/>"), Err(Error::AdditionalContent(8)));
+}
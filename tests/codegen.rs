@@ -0,0 +1,54 @@
+use baum::schema::Schema;
+use baum::Node::{Inner, Leaf};
+
+#[allow(dead_code)]
+mod generated {
+    include!("generated/message.rs");
+}
+
+use generated::Message;
+
+fn schema() -> Schema {
+    Schema::record(vec!(
+        ("header", Schema::record(vec!(
+            ("version", Schema::leaf_len(1)),
+            ("sequence_number", Schema::leaf_len(4)),
+        ))),
+        ("name", Schema::utf8()),
+        ("entries", Schema::list(Schema::inner(vec!(Schema::leaf(), Schema::Any)))),
+    ))
+}
+
+#[test]
+fn generated_code_is_up_to_date() {
+    let code = schema().generate_rust("Message").unwrap();
+    if std::env::var_os("BAUM_BLESS").is_some() {
+        std::fs::write("tests/generated/message.rs", &code).unwrap();
+    }
+    assert_eq!(code, include_str!("generated/message.rs"));
+    assert_eq!(Message::schema(), schema());
+}
+
+#[test]
+fn generated_accessors() {
+    let node = Inner(vec!(
+        Inner(vec!(Leaf(vec!(1)), Leaf(vec!(0, 0, 0, 7)))),
+        Leaf(b"foo".to_vec()),
+        Inner(vec!(
+            Inner(vec!(Leaf(vec!(0xa)), Inner(vec!()))),
+            Inner(vec!(Leaf(vec!()), Leaf(vec!(0xb)))),
+        )),
+    ));
+    let msg = Message::new(&node).unwrap();
+    assert_eq!(msg.header().version(), &[1]);
+    assert_eq!(u32::from_be_bytes(*msg.header().sequence_number()), 7);
+    assert_eq!(msg.name(), "foo");
+    assert_eq!(msg.entries().len(), 2);
+    let firsts: Vec<&[u8]> = msg.entries().iter().map(|e| e.field_0()).collect();
+    assert_eq!(firsts, vec!(&[0xa][..], &[][..]));
+    assert_eq!(msg.entries().get(1).unwrap().field_1(), &Leaf(vec!(0xb)));
+    assert!(msg.entries().get(2).is_none());
+
+    let invalid = Inner(vec!(Leaf(vec!()), Leaf(vec!()), Inner(vec!())));
+    assert!(Message::new(&invalid).is_err());
+}
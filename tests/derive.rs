@@ -0,0 +1,140 @@
+#![cfg(feature = "derive")]
+
+use baum::{baum, baum_match, FromBaum, FromNodeError, Node, ToBaum};
+
+#[derive(Debug, PartialEq, ToBaum, FromBaum)]
+struct Header {
+    version: u8,
+    flags: [u8; 2],
+    sequence_number: u32,
+}
+
+#[derive(Debug, PartialEq, ToBaum, FromBaum)]
+struct Message<T> {
+    header: Header,
+    payload: Vec<T>,
+    checksum: Option<u16>,
+}
+
+#[derive(Debug, PartialEq, ToBaum, FromBaum)]
+struct Pair(i8, bool);
+
+#[derive(Debug, PartialEq, ToBaum, FromBaum)]
+struct Empty;
+
+#[test]
+fn derive_struct() {
+    let msg = Message {
+        header: Header { version: 1, flags: [0xa, 0xb], sequence_number: 0x0102 },
+        payload: vec!(Pair(-1, true), Pair(2, false)),
+        checksum: None,
+    };
+    let node = msg.to_node();
+    assert_eq!(node, baum!((
+        (0x01 0x0a_0b 0x02_01_00_00)
+        ((0xff 0x01) (0x02 0x00))
+        ()
+    )));
+    assert_eq!(Message::from_node(&node), Ok(msg));
+    assert_eq!(Empty.to_node(), Node::Inner(vec!()));
+    assert_eq!(Empty::from_node(&Node::Inner(vec!())), Ok(Empty));
+}
+
+#[test]
+fn derive_errors() {
+    let node = baum!((0x01 0x02));
+    assert_eq!(Pair::from_node(&node), Err(FromNodeError::InvalidValue(vec!(1))));
+    assert_eq!(Header::from_node(&node),
+        Err(FromNodeError::ChildCount { path: vec!(), expected: 3, actual: 2 }));
+    assert_eq!(Header::from_node(&Node::Leaf(vec!())), Err(FromNodeError::ExpectedInner(vec!())));
+
+    let node = baum!(((0x01 0x00 0x00_00_00_00) () ()));
+    assert_eq!(Message::<Pair>::from_node(&node),
+        Err(FromNodeError::LeafLength { path: vec!(0, 1), expected: 2, actual: 1 }));
+}
+
+#[derive(Debug, PartialEq, ToBaum, FromBaum)]
+enum Command {
+    Ping,
+    Move(i16, i16),
+    #[baum(tag = 10)]
+    Rename { id: u8, name: String },
+    Batch(Vec<Command>),
+}
+
+#[derive(Debug, PartialEq, ToBaum, FromBaum)]
+enum Never {}
+
+#[test]
+fn derive_enum() {
+    let cmd = Command::Batch(vec!(
+        Command::Ping,
+        Command::Move(1, -1),
+        Command::Rename { id: 7, name: "a".to_string() },
+    ));
+    let node = cmd.to_node();
+    assert_eq!(node, baum!((
+        0x03_00_00_00
+        (
+            (0x00_00_00_00)
+            (0x01_00_00_00 0x01_00 0xff_ff)
+            (0x0a_00_00_00 0x07 "a")
+        )
+    )));
+    assert_eq!(Command::from_node(&node), Ok(cmd));
+}
+
+#[test]
+fn derive_enum_errors() {
+    let tag = |t: u32| Node::Leaf(t.to_le_bytes().to_vec());
+    assert_eq!(Command::from_node(&Node::Inner(vec!())), Err(FromNodeError::MissingTag(vec!())));
+    assert_eq!(Command::from_node(&Node::Inner(vec!(tag(2)))),
+        Err(FromNodeError::UnknownVariant { path: vec!(), tag: 2 }));
+    assert_eq!(Command::from_node(&Node::Inner(vec!(Node::Leaf(vec!(0))))),
+        Err(FromNodeError::LeafLength { path: vec!(0), expected: 4, actual: 1 }));
+    assert_eq!(Command::from_node(&Node::Inner(vec!(tag(0), tag(0)))),
+        Err(FromNodeError::ChildCount { path: vec!(), expected: 1, actual: 2 }));
+    let node = Node::Inner(vec!(tag(3), Node::Inner(vec!(Node::Inner(vec!(tag(1), tag(0)))))));
+    assert_eq!(Command::from_node(&node),
+        Err(FromNodeError::ChildCount { path: vec!(1, 0), expected: 3, actual: 2 }));
+    assert_eq!(Never::from_node(&Node::Inner(vec!(tag(0)))),
+        Err(FromNodeError::UnknownVariant { path: vec!(), tag: 0 }));
+}
+
+#[test]
+fn baum_literal() {
+    assert_eq!(baum!(0x01_02), Node::Leaf(vec!(1, 2)));
+    assert_eq!(baum!(0x102), Node::Leaf(vec!(1, 2)));
+    assert_eq!(baum!(""), Node::Leaf(vec!()));
+    assert_eq!(baum!(()), Node::Inner(vec!()));
+    assert_eq!(baum!((0x01_02 ("abc" b"\x00\xff" 0x03))), Node::Inner(vec!(
+        Node::Leaf(vec!(1, 2)),
+        Node::Inner(vec!(Node::Leaf(b"abc".to_vec()), Node::Leaf(vec!(0, 0xff)), Node::Leaf(vec!(3)))),
+    )));
+    let text = "(0x01_02 (0x61_62_63 0x00_ff 0x03))";
+    assert_eq!(baum!((0x01_02 ("abc" b"\x00\xff" 0x03))).to_string(), text);
+}
+
+fn describe(node: &Node) -> String {
+    baum_match!(node,
+        "(0x01 $payload)" => format!("data {}", payload),
+        "(0x02 $a $a ...)" => format!("pair {}", a),
+        r#"("ping" ...)"# => "ping".to_string(),
+        _ => "unknown".to_string(),
+    )
+}
+
+#[test]
+fn baum_match() {
+    assert_eq!(describe(&baum!((0x01 (0x02 0x03)))), "data (0x02 0x03)");
+    assert_eq!(describe(&baum!((0x02 0x04 0x04 0x05))), "pair 0x04");
+    assert_eq!(describe(&baum!((0x02 0x04 0x05))), "unknown");
+    assert_eq!(describe(&baum!(("ping"))), "ping");
+
+    // without fallback, like `if` without `else`
+    let mut seen = vec!();
+    for node in [baum!((0x01 0x02)), baum!(0x03)] {
+        baum_match!(node, "(_ $x)" => seen.push(x.clone()));
+    }
+    assert_eq!(seen, vec!(baum!(0x02)));
+}
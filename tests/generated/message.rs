@@ -0,0 +1,170 @@
+// Generated by `baum::Schema::generate_rust`. Do not edit.
+
+#[derive(Clone, Copy, Debug)]
+pub struct Message<'a>(&'a ::baum::Node);
+
+impl<'a> Message<'a> {
+    pub fn schema() -> ::baum::Schema {
+        ::baum::Schema::Inner(vec![::baum::schema::Field { name: Some("header".to_string()), schema: ::baum::Schema::Inner(vec![::baum::schema::Field { name: Some("version".to_string()), schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 1, max_len: Some(1), utf8: false }) }, ::baum::schema::Field { name: Some("sequence_number".to_string()), schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 4, max_len: Some(4), utf8: false }) }]) }, ::baum::schema::Field { name: Some("name".to_string()), schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 0, max_len: None, utf8: true }) }, ::baum::schema::Field { name: Some("entries".to_string()), schema: ::baum::Schema::List { item: Box::new(::baum::Schema::Inner(vec![::baum::schema::Field { name: None, schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 0, max_len: None, utf8: false }) }, ::baum::schema::Field { name: None, schema: ::baum::Schema::Any }])), min: 0, max: None } }])
+    }
+
+    /// Validates `node` against the schema and wraps it.
+    pub fn new(node: &'a ::baum::Node) -> Result<Self, Vec<::baum::schema::Violation>> {
+        Self::schema().validate(node)?;
+        Ok(Message(node))
+    }
+
+    pub fn node(&self) -> &'a ::baum::Node {
+        self.0
+    }
+
+    pub fn header(&self) -> MessageHeader<'a> {
+        MessageHeader(&self.children()[0])
+    }
+
+    pub fn name(&self) -> &'a str {
+        ::std::str::from_utf8(Self::bytes(&self.children()[1])).unwrap()
+    }
+
+    pub fn entries(&self) -> MessageEntries<'a> {
+        MessageEntries(&self.children()[2])
+    }
+
+    fn children(&self) -> &'a [::baum::Node] {
+        match self.0 {
+            ::baum::Node::Inner(nodes) => nodes,
+            ::baum::Node::Leaf(_) => unreachable!(),
+        }
+    }
+
+    fn bytes(node: &'a ::baum::Node) -> &'a [u8] {
+        match node {
+            ::baum::Node::Leaf(bytes) => bytes,
+            ::baum::Node::Inner(_) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MessageHeader<'a>(&'a ::baum::Node);
+
+impl<'a> MessageHeader<'a> {
+    pub fn schema() -> ::baum::Schema {
+        ::baum::Schema::Inner(vec![::baum::schema::Field { name: Some("version".to_string()), schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 1, max_len: Some(1), utf8: false }) }, ::baum::schema::Field { name: Some("sequence_number".to_string()), schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 4, max_len: Some(4), utf8: false }) }])
+    }
+
+    /// Validates `node` against the schema and wraps it.
+    pub fn new(node: &'a ::baum::Node) -> Result<Self, Vec<::baum::schema::Violation>> {
+        Self::schema().validate(node)?;
+        Ok(MessageHeader(node))
+    }
+
+    pub fn node(&self) -> &'a ::baum::Node {
+        self.0
+    }
+
+    pub fn version(&self) -> &'a [u8; 1] {
+        ::std::convert::TryFrom::try_from(Self::bytes(&self.children()[0])).unwrap()
+    }
+
+    pub fn sequence_number(&self) -> &'a [u8; 4] {
+        ::std::convert::TryFrom::try_from(Self::bytes(&self.children()[1])).unwrap()
+    }
+
+    fn children(&self) -> &'a [::baum::Node] {
+        match self.0 {
+            ::baum::Node::Inner(nodes) => nodes,
+            ::baum::Node::Leaf(_) => unreachable!(),
+        }
+    }
+
+    fn bytes(node: &'a ::baum::Node) -> &'a [u8] {
+        match node {
+            ::baum::Node::Leaf(bytes) => bytes,
+            ::baum::Node::Inner(_) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MessageEntries<'a>(&'a ::baum::Node);
+
+impl<'a> MessageEntries<'a> {
+    pub fn schema() -> ::baum::Schema {
+        ::baum::Schema::List { item: Box::new(::baum::Schema::Inner(vec![::baum::schema::Field { name: None, schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 0, max_len: None, utf8: false }) }, ::baum::schema::Field { name: None, schema: ::baum::Schema::Any }])), min: 0, max: None }
+    }
+
+    /// Validates `node` against the schema and wraps it.
+    pub fn new(node: &'a ::baum::Node) -> Result<Self, Vec<::baum::schema::Violation>> {
+        Self::schema().validate(node)?;
+        Ok(MessageEntries(node))
+    }
+
+    pub fn node(&self) -> &'a ::baum::Node {
+        self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.children().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children().is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<MessageEntriesItem<'a>> {
+        self.children().get(idx).map(MessageEntriesItem)
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = MessageEntriesItem<'a>> {
+        self.children().iter().map(MessageEntriesItem)
+    }
+
+    fn children(&self) -> &'a [::baum::Node] {
+        match self.0 {
+            ::baum::Node::Inner(nodes) => nodes,
+            ::baum::Node::Leaf(_) => unreachable!(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MessageEntriesItem<'a>(&'a ::baum::Node);
+
+impl<'a> MessageEntriesItem<'a> {
+    pub fn schema() -> ::baum::Schema {
+        ::baum::Schema::Inner(vec![::baum::schema::Field { name: None, schema: ::baum::Schema::Leaf(::baum::schema::LeafSchema { min_len: 0, max_len: None, utf8: false }) }, ::baum::schema::Field { name: None, schema: ::baum::Schema::Any }])
+    }
+
+    /// Validates `node` against the schema and wraps it.
+    pub fn new(node: &'a ::baum::Node) -> Result<Self, Vec<::baum::schema::Violation>> {
+        Self::schema().validate(node)?;
+        Ok(MessageEntriesItem(node))
+    }
+
+    pub fn node(&self) -> &'a ::baum::Node {
+        self.0
+    }
+
+    pub fn field_0(&self) -> &'a [u8] {
+        Self::bytes(&self.children()[0])
+    }
+
+    pub fn field_1(&self) -> &'a ::baum::Node {
+        &self.children()[1]
+    }
+
+    fn children(&self) -> &'a [::baum::Node] {
+        match self.0 {
+            ::baum::Node::Inner(nodes) => nodes,
+            ::baum::Node::Leaf(_) => unreachable!(),
+        }
+    }
+
+    fn bytes(node: &'a ::baum::Node) -> &'a [u8] {
+        match node {
+            ::baum::Node::Leaf(bytes) => bytes,
+            ::baum::Node::Inner(_) => unreachable!(),
+        }
+    }
+}